@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
+use crate::validation::{validate_basis_points, validate_non_empty_string};
+
 /// Asset allocation record for a single asset within a portfolio
 #[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct AssetAllocation {
@@ -27,6 +29,19 @@ pub struct AssetAllocation {
     
     /// Last known price (in USD, scaled by 1e8 for precision)
     pub last_price: Option<u128>,
+    
+    /// Minimum allowed percentage (in basis points), if bounded
+    pub min_percentage: Option<u32>,
+    
+    /// Maximum allowed percentage (in basis points), if bounded
+    pub max_percentage: Option<u32>,
+    
+    /// Locked positions (e.g. staked or vesting) are never traded by rebalancing
+    pub locked: bool,
+    
+    /// Sector the asset belongs to (e.g. "Layer1", "Stablecoin", "DeFi")
+    #[serde(default)]
+    pub sector: Option<String>,
 }
 
 impl AssetAllocation {
@@ -39,7 +54,52 @@ impl AssetAllocation {
             last_modified: l1x_sdk::env::block_timestamp(),
             last_rebalance: 0,
             last_price: None,
+            min_percentage: None,
+            max_percentage: None,
+            locked: false,
+            sector: None,
+        }
+    }
+    
+    /// Sets the min/max bounds (in basis points), requiring min <= target <= max
+    pub fn set_bounds(&mut self, min_percentage: Option<u32>, max_percentage: Option<u32>) -> Result<(), &'static str> {
+        let min = min_percentage.unwrap_or(0);
+        let max = max_percentage.unwrap_or(10000);
+        
+        if min > max {
+            return Err("Minimum bound exceeds maximum bound");
+        }
+        
+        if max > 10000 {
+            return Err("Maximum bound exceeds 100%");
+        }
+        
+        if self.target_percentage < min || self.target_percentage > max {
+            return Err("Target percentage outside of bounds");
         }
+        
+        self.min_percentage = min_percentage;
+        self.max_percentage = max_percentage;
+        self.last_modified = l1x_sdk::env::block_timestamp();
+        Ok(())
+    }
+    
+    /// Checks whether a percentage (in basis points) lies within the bounds
+    pub fn is_within_bounds(&self, percentage: u32) -> bool {
+        percentage >= self.min_percentage.unwrap_or(0)
+            && percentage <= self.max_percentage.unwrap_or(10000)
+    }
+    
+    /// Clamps a percentage (in basis points) into the bounds
+    pub fn clamp_to_bounds(&self, percentage: u32) -> u32 {
+        percentage
+            .max(self.min_percentage.unwrap_or(0))
+            .min(self.max_percentage.unwrap_or(10000))
+    }
+    
+    /// Determines if the current percentage has breached a bound
+    pub fn breaches_bounds(&self) -> bool {
+        !self.is_within_bounds(self.current_percentage)
     }
     
     /// Updates the current percentage allocation
@@ -70,6 +130,11 @@ impl AssetAllocation {
         }
     }
     
+    /// Calculates signed drift from target (in basis points, positive = overweight)
+    pub fn drift_signed(&self) -> i32 {
+        self.current_percentage as i32 - self.target_percentage as i32
+    }
+    
     /// Calculates drift as a percentage of target (scaled by 100 for precision)
     /// Returns (drift_percentage * 100) for more precise calculations
     pub fn drift_percentage(&self) -> u32 {
@@ -99,11 +164,155 @@ impl AssetAllocation {
             current_percentage: self.current_percentage,
             target_percentage: self.target_percentage,
             drift_amount,
+            direction: match self.drift_signed() {
+                d if d > 0 => crate::events::DriftDirection::Over,
+                d if d < 0 => crate::events::DriftDirection::Under,
+                _ => crate::events::DriftDirection::Balanced,
+            },
+            severity: crate::events::DriftSeverity::from_drift(drift_amount, threshold),
             exceeds_threshold: drift_amount > threshold,
+            min_percentage: self.min_percentage,
+            max_percentage: self.max_percentage,
+            bound_breached: self.breaches_bounds(),
+            locked: self.locked,
+            buffer_breached: false,
+        }
+    }
+}
+
+/// How far rebalancing trades drifted assets back
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum RebalanceMode {
+    /// Trade every asset back to its exact target
+    ToTarget,
+    
+    /// Trade only back to the edge of a tolerance band around the target
+    ToBand {
+        /// Band half-width in basis points
+        band_bp: u32,
+    },
+}
+
+impl Default for RebalanceMode {
+    fn default() -> Self {
+        RebalanceMode::ToTarget
+    }
+}
+
+/// How the price impact of a trade is estimated
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum SlippageModel {
+    /// Same impact (in basis points) regardless of trade size
+    Constant(u32),
+    
+    /// Impact grows linearly with the trade's share of the market depth
+    LinearDepth {
+        /// Trade size that would move the price by 100%
+        depth: u128,
+    },
+}
+
+impl Default for SlippageModel {
+    fn default() -> Self {
+        SlippageModel::Constant(0)
+    }
+}
+
+impl SlippageModel {
+    /// Estimated price impact of trading `amount` (in basis points)
+    pub fn impact_bps(&self, amount: u128) -> u128 {
+        match self {
+            SlippageModel::Constant(bps) => *bps as u128,
+            SlippageModel::LinearDepth { depth } if *depth == 0 => u128::MAX,
+            SlippageModel::LinearDepth { depth } => amount.saturating_mul(10000) / depth,
+        }
+    }
+    
+    /// Number of equal parts `amount` must be split into so each stays within `max_impact_bps`
+    ///
+    /// A constant impact does not shrink with size, so such trades are never split.
+    pub fn parts_needed(&self, amount: u128, max_impact_bps: u32) -> u128 {
+        match self {
+            SlippageModel::LinearDepth { depth } if *depth > 0 && max_impact_bps > 0 => {
+                let max_part = depth.saturating_mul(max_impact_bps as u128) / 10000;
+                if max_part == 0 {
+                    return 1;
+                }
+                
+                ((amount + max_part - 1) / max_part).max(1)
+            },
+            _ => 1,
+        }
+    }
+}
+
+/// Lowest multiplier the adaptive drift threshold may apply (0.5x, in basis points of 1x)
+pub const MIN_ADAPTIVE_MULTIPLIER_BP: u32 = 5000;
+
+/// Highest multiplier the adaptive drift threshold may apply (3x, in basis points of 1x)
+pub const MAX_ADAPTIVE_MULTIPLIER_BP: u32 = 30000;
+
+/// Volatility-driven scaling of the drift threshold
+///
+/// The threshold is multiplied by `observed / reference_volatility_bp`, clamped
+/// to `[min_multiplier_bp, max_multiplier_bp]`, so it widens in volatile
+/// markets and tightens in calm ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AdaptiveThreshold {
+    /// Volatility (stddev of returns, in basis points) at which the threshold is unscaled
+    pub reference_volatility_bp: u32,
+    
+    /// Number of recent price records volatility is computed over
+    pub lookback_records: u32,
+    
+    /// Lowest multiplier applied (in basis points of 1x, at least 0.5x)
+    pub min_multiplier_bp: u32,
+    
+    /// Highest multiplier applied (in basis points of 1x, at most 3x)
+    pub max_multiplier_bp: u32,
+}
+
+impl AdaptiveThreshold {
+    /// Checks the multiplier bounds, reference volatility and lookback
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.reference_volatility_bp == 0 {
+            return Err("Reference volatility must be greater than zero");
         }
+        
+        if self.lookback_records < 3 {
+            return Err("Volatility lookback needs at least 3 price records");
+        }
+        
+        if self.min_multiplier_bp < MIN_ADAPTIVE_MULTIPLIER_BP
+            || self.max_multiplier_bp > MAX_ADAPTIVE_MULTIPLIER_BP
+            || self.min_multiplier_bp > self.max_multiplier_bp
+        {
+            return Err("Adaptive multiplier bounds must lie within 0.5x..3x");
+        }
+        
+        Ok(())
+    }
+    
+    /// Threshold multiplier (in basis points of 1x) for an observed volatility
+    pub fn multiplier_bp(&self, volatility_bp: u32) -> u32 {
+        let ratio = (volatility_bp as u64) * 10000 / (self.reference_volatility_bp as u64);
+        (ratio as u32).clamp(self.min_multiplier_bp, self.max_multiplier_bp)
     }
 }
 
+/// How the target weight freed by a removed allocation is handed out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum Redistribution {
+    /// Scale the remaining targets up pro-rata to fill the gap
+    Proportional,
+    
+    /// Add the freed weight to a single asset
+    ToAsset(String),
+    
+    /// Leave the gap; the set is marked invalid until targets sum to 100% again
+    None,
+}
+
 /// Set of asset allocations for a portfolio
 #[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct AllocationSet {
@@ -118,8 +327,105 @@ pub struct AllocationSet {
     
     /// Last rebalance timestamp
     pub last_rebalance: u64,
+    
+    /// How far rebalancing trades back towards the targets
+    #[serde(default)]
+    pub rebalance_mode: RebalanceMode,
+    
+    /// Optional base asset (e.g. "USDC") trades may be routed through
+    #[serde(default)]
+    pub base_asset: Option<String>,
+    
+    /// Audit trail of target changes, oldest first (capped at MAX_ALLOCATION_HISTORY)
+    #[serde(default)]
+    pub allocation_history: Vec<crate::events::AllocationChangedEvent>,
+    
+    /// Set when a removal left the targets short of 100%; cleared once they sum to 100% again
+    #[serde(default)]
+    pub invalid_targets: bool,
+    
+    /// Cash buffer asset (e.g. "USDC") kept at or above `buffer_bp`
+    #[serde(default)]
+    pub buffer_asset: Option<String>,
+    
+    /// Buffer floor in basis points of total value
+    #[serde(default)]
+    pub buffer_bp: u32,
+    
+    /// Correct drift by steering deposits to underweight assets instead of selling
+    #[serde(default)]
+    pub cashflow_rebalancing: bool,
+    
+    /// Maximum rebalances per 24-hour window (0 = unlimited)
+    #[serde(default)]
+    pub max_rebalances_per_day: u32,
+    
+    /// Rebalances recorded in the current window
+    #[serde(default)]
+    pub rebalances_in_window: u32,
+    
+    /// Start of the current rate-limit window
+    #[serde(default)]
+    pub window_start: u64,
+    
+    /// Minimum seconds between rebalances (0 = disabled)
+    #[serde(default)]
+    pub min_rebalance_interval_seconds: u64,
+    
+    /// Volatility-driven threshold scaling (`None` = fixed threshold)
+    #[serde(default)]
+    pub adaptive_threshold: Option<AdaptiveThreshold>,
+    
+    /// Target-weighted volatility of the set's assets last read from the price feed
+    #[serde(default)]
+    pub observed_volatility_bp: Option<u32>,
+    
+    /// Maximum estimated price impact of a single trade (in basis points, 0 = unlimited)
+    #[serde(default)]
+    pub max_trade_impact_bps: u32,
+    
+    /// Model used to estimate each trade's price impact
+    #[serde(default)]
+    pub slippage_model: SlippageModel,
+    
+    /// Maximum number of assets in the set (`None` = unlimited)
+    #[serde(default)]
+    pub max_assets: Option<u32>,
+    
+    /// Asset every trade must be routed through (e.g. "USDC" on chains with only stablecoin pairs)
+    #[serde(default)]
+    pub pivot_asset: Option<String>,
+    
+    /// Governance daily rebalance limit applied when `max_rebalances_per_day` is 0,
+    /// refreshed whenever the set is loaded and not serialized
+    #[serde(skip)]
+    #[borsh_skip]
+    pub governance_max_rebalances_per_day: u32,
+    
+    /// Asset ID -> position in `allocations`, built on first lookup and not serialized
+    #[serde(skip)]
+    #[borsh_skip]
+    index: std::cell::OnceCell<std::collections::HashMap<String, usize>>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of linear scans `AllocationSet::position` fell back to
+    static FALLBACK_SCANS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+    
+    /// Number of times an `AllocationSet` index was built
+    static INDEX_BUILDS: std::cell::Cell<usize> = std::cell::Cell::new(0);
 }
 
+/// Maximum number of target changes kept in an allocation set's history
+pub const MAX_ALLOCATION_HISTORY: usize = 100;
+
+/// Sector name used for assets that have not been assigned a sector
+pub const UNCLASSIFIED_SECTOR: &str = "Unclassified";
+
+/// Length of the rebalance rate-limit window in seconds
+pub const RATE_LIMIT_WINDOW_SECONDS: u64 = 24 * 60 * 60;
+
 impl AllocationSet {
     /// Creates a new allocation set with the specified drift threshold
     pub fn new(drift_threshold_bp: u32) -> Self {
@@ -128,461 +434,3323 @@ impl AllocationSet {
             rebalance_frequency_seconds: 0, // Default to manual rebalancing
             allocations: Vec::new(),
             last_rebalance: 0,
+            rebalance_mode: RebalanceMode::ToTarget,
+            base_asset: None,
+            allocation_history: Vec::new(),
+            invalid_targets: false,
+            buffer_asset: None,
+            buffer_bp: 0,
+            cashflow_rebalancing: false,
+            max_rebalances_per_day: 0,
+            rebalances_in_window: 0,
+            window_start: 0,
+            min_rebalance_interval_seconds: 0,
+            adaptive_threshold: None,
+            observed_volatility_bp: None,
+            max_trade_impact_bps: 0,
+            slippage_model: SlippageModel::default(),
+            max_assets: None,
+            pivot_asset: None,
+            governance_max_rebalances_per_day: 0,
+            index: std::cell::OnceCell::new(),
         }
     }
     
-    /// Sets rebalance frequency
-    pub fn set_rebalance_frequency(&mut self, frequency_seconds: u64) {
-        self.rebalance_frequency_seconds = frequency_seconds;
-    }
-    
-    /// Adds a new asset allocation to the set
-    pub fn add_allocation(&mut self, allocation: AssetAllocation) -> Result<(), &'static str> {
-        // Check if the asset already exists
-        if self.allocations.iter().any(|a| a.asset_id == allocation.asset_id) {
-            return Err("Asset already exists in allocation");
+    /// Finds the position of an asset in `allocations`
+    ///
+    /// Uses the asset index, falling back to a scan if `allocations` was
+    /// modified directly and the index no longer matches.
+    fn position(&self, asset_id: &str) -> Option<usize> {
+        let index = self.index.get_or_init(|| {
+            #[cfg(test)]
+            INDEX_BUILDS.with(|c| c.set(c.get() + 1));
+            
+            self.allocations.iter()
+                .enumerate()
+                .map(|(i, a)| (a.asset_id.clone(), i))
+                .collect()
+        });
+        
+        match index.get(asset_id) {
+            Some(&i) if self.allocations.get(i).map_or(false, |a| a.asset_id == asset_id) => return Some(i),
+            None if index.len() == self.allocations.len() => return None,
+            _ => {},
         }
         
+        #[cfg(test)]
+        FALLBACK_SCANS.with(|c| c.set(c.get() + 1));
+        
+        self.allocations.iter().position(|a| a.asset_id == asset_id)
+    }
+    
+    /// Appends an allocation, keeping the asset index in step
+    fn push_allocation(&mut self, allocation: AssetAllocation) {
+        let asset_id = allocation.asset_id.clone();
         self.allocations.push(allocation);
-        Ok(())
+        
+        let pos = self.allocations.len() - 1;
+        if let Some(index) = self.index.get_mut() {
+            index.insert(asset_id, pos);
+        }
     }
     
-    /// Updates an existing asset allocation
-    pub fn update_allocation(&mut self, asset_id: &str, target_percentage: u32) -> Result<(), &'static str> {
-        let allocation = self.allocations.iter_mut()
-            .find(|a| a.asset_id == asset_id)
-            .ok_or("Asset not found in allocation")?;
-            
-        allocation.update_target_percentage(target_percentage);
-        Ok(())
+    /// Drops the asset index after `allocations` is reshaped; it is rebuilt on next lookup
+    fn invalidate_index(&mut self) {
+        self.index = std::cell::OnceCell::new();
     }
     
-    /// Removes an asset allocation
-    pub fn remove_allocation(&mut self, asset_id: &str) -> Result<(), &'static str> {
-        let pos = self.allocations.iter()
-            .position(|a| a.asset_id == asset_id)
-            .ok_or("Asset not found in allocation")?;
-            
-        self.allocations.remove(pos);
+    /// Sets the cash buffer asset and its floor
+    pub fn set_buffer(&mut self, buffer_asset: Option<String>, buffer_bp: u32) -> Result<(), &'static str> {
+        if buffer_bp > 10000 {
+            return Err("Buffer cannot exceed 100%");
+        }
+        
+        self.buffer_asset = buffer_asset;
+        self.buffer_bp = buffer_bp;
         Ok(())
     }
     
-    /// Gets an asset allocation by ID
-    pub fn get_allocation(&self, asset_id: &str) -> Option<&AssetAllocation> {
-        self.allocations.iter().find(|a| a.asset_id == asset_id)
+    /// Checks whether an asset is the cash buffer
+    pub fn is_buffer(&self, asset_id: &str) -> bool {
+        self.buffer_asset.as_deref() == Some(asset_id)
     }
     
-    /// Checks if rebalancing is needed based on drift or time
-    pub fn needs_rebalancing(&self) -> bool {
-        // Check if time-based rebalancing is needed
-        if self.rebalance_frequency_seconds > 0 {
-            let current_time = l1x_sdk::env::block_timestamp();
-            let elapsed = current_time.saturating_sub(self.last_rebalance);
-            
-            if elapsed >= self.rebalance_frequency_seconds {
-                return true;
-            }
+    /// Checks whether the buffer's current weight has fallen below its floor
+    pub fn buffer_breached(&self) -> bool {
+        match &self.buffer_asset {
+            Some(buffer_asset) => self.get_allocation(buffer_asset)
+                .map_or(self.buffer_bp > 0, |a| a.current_percentage < self.buffer_bp),
+            None => false,
         }
-        
-        // Check if drift-based rebalancing is needed
-        for allocation in &self.allocations {
-            if allocation.drift() > self.drift_threshold_bp {
-                return true;
-            }
-        }
-        
-        false
     }
     
-    /// Checks if rebalancing is needed and emits appropriate events
-    pub fn check_and_emit_rebalance_events(&self, vault_id: &str) -> bool {
-        // Check if time-based rebalancing is needed
-        if self.rebalance_frequency_seconds > 0 {
-            let current_time = l1x_sdk::env::block_timestamp();
-            let elapsed = current_time.saturating_sub(self.last_rebalance);
+    /// Splits a deposit across assets, topping the buffer up to its floor first
+    ///
+    /// The remainder is distributed pro-rata to the other targets, or, with
+    /// cash-flow rebalancing enabled, towards underweight assets in proportion
+    /// to how far each is below target. Returns the `(asset, amount)` to buy
+    /// with the deposit.
+    pub fn deposit_and_allocate(&self, amount: u128, current_values: &[(String, u128)]) -> Vec<(String, u128)> {
+        let mut purchases = Vec::new();
+        let mut remaining = amount;
+        let total_after: u128 = current_values.iter().map(|(_, v)| *v).sum::<u128>() + amount;
+        
+        let current_value_map: std::collections::HashMap<&str, u128> = current_values
+            .iter()
+            .map(|(asset_id, value)| (asset_id.as_str(), *value))
+            .collect();
             
-            if elapsed >= self.rebalance_frequency_seconds {
-                // Emit scheduled rebalance event
-                let data = format!("{{\"elapsed_seconds\": {}, \"frequency\": {}}}", 
-                    elapsed, self.rebalance_frequency_seconds);
-                let event = crate::events::RebalanceEvent::new(
-                    crate::events::RebalanceEventType::ScheduledRebalance, 
-                    vault_id.to_string()
-                ).with_data(data);
-                event.emit();
+        if let Some(buffer_asset) = &self.buffer_asset {
+            let floor_value = total_after * (self.buffer_bp as u128) / 10000;
+            let buffer_value = *current_value_map.get(buffer_asset.as_str()).unwrap_or(&0);
                 
-                return true;
+            let top_up = floor_value.saturating_sub(buffer_value).min(remaining);
+            if top_up > 0 {
+                purchases.push((buffer_asset.clone(), top_up));
+                remaining -= top_up;
             }
         }
         
-        // Check if drift-based rebalancing is needed
-        let mut needs_rebalance = false;
-        let mut drift_results = Vec::new();
-        
-        for allocation in &self.allocations {
-            let drift = allocation.drift();
-            let drift_result = allocation.create_drift_result(self.drift_threshold_bp);
+        let others: Vec<&AssetAllocation> = self.allocations.iter()
+            .filter(|a| !self.is_buffer(&a.asset_id))
+            .collect();
             
-            if drift > self.drift_threshold_bp {
-                needs_rebalance = true;
-                drift_results.push(drift_result);
-            }
-        }
-        
-        // Emit drift exceeded event if needed
-        if needs_rebalance && !drift_results.is_empty() {
-            crate::events::emit_drift_exceeded_event(vault_id, drift_results);
+        if self.cashflow_rebalancing {
+            // Fill each asset's shortfall against its post-deposit target
+            let deficits: Vec<(String, u128)> = others.iter()
+                .map(|a| {
+                    let target_value = total_after * (a.target_percentage as u128) / 10000;
+                    let current_value = *current_value_map.get(a.asset_id.as_str()).unwrap_or(&0);
+                    (a.asset_id.clone(), target_value.saturating_sub(current_value))
+                })
+                .collect();
+            let total_deficit: u128 = deficits.iter().map(|(_, d)| *d).sum();
+            
+            let to_deficits = remaining.min(total_deficit);
+            purchases.extend(Self::split_pro_rata(to_deficits, &deficits));
+            remaining -= to_deficits;
         }
         
-        needs_rebalance
-    }
-    
-    /// Records a rebalance operation
-    pub fn record_rebalance(&mut self, prices: &[(String, u128)]) {
-        self.last_rebalance = l1x_sdk::env::block_timestamp();
-        
-        // Create a price map for lookup
-        let price_map: std::collections::HashMap<&str, u128> = prices
-            .iter()
-            .map(|(asset_id, price)| (asset_id.as_str(), *price))
+        // Anything left over follows the targets
+        let weights: Vec<(String, u128)> = others.iter()
+            .map(|a| (a.asset_id.clone(), a.target_percentage as u128))
             .collect();
-            
-        // Update each allocation
-        for allocation in &mut self.allocations {
-            let price = price_map.get(allocation.asset_id.as_str()).copied();
-            allocation.record_rebalance(price);
+        for (asset_id, share) in Self::split_pro_rata(remaining, &weights) {
+            match purchases.iter_mut().find(|(purchased, _)| *purchased == asset_id) {
+                Some((_, amount)) => *amount += share,
+                None => purchases.push((asset_id, share)),
+            }
         }
+        
+        purchases
     }
     
-    /// Performs auto-rebalancing calculation and returns transactions needed
-    pub fn calculate_rebalance_transactions(
-        &self,
-        current_values: &[(String, u128)],
-        total_value: u128
-    ) -> Vec<(String, String, u128)> {
-        if total_value == 0 || self.allocations.is_empty() {
+    /// Splits `amount` in proportion to `weights`; rounding dust goes to the last
+    /// weighted entry so nothing is left over
+    fn split_pro_rata(amount: u128, weights: &[(String, u128)]) -> Vec<(String, u128)> {
+        let weight_sum: u128 = weights.iter().map(|(_, w)| *w).sum();
+        if amount == 0 || weight_sum == 0 {
             return Vec::new();
         }
         
-        // Calculate target values based on allocations
-        let mut target_values = Vec::new();
+        let last_weighted = weights.iter().rposition(|(_, w)| *w > 0).unwrap_or(0);
+        let mut shares = Vec::new();
+        let mut distributed: u128 = 0;
         
-        for allocation in &self.allocations {
-            let target_value = total_value * (allocation.target_percentage as u128) / 10000;
-            target_values.push((allocation.asset_id.clone(), target_value));
+        for (i, (asset_id, weight)) in weights.iter().enumerate() {
+            let share = if i == last_weighted {
+                amount - distributed
+            } else {
+                amount * weight / weight_sum
+            };
+            
+            distributed += share;
+            if share > 0 {
+                shares.push((asset_id.clone(), share));
+            }
+            
+            if i == last_weighted {
+                break;
+            }
         }
         
-        // Convert current values to a map for easier lookup
-        let current_value_map: std::collections::HashMap<&str, u128> = current_values
+        shares
+    }
+    
+    /// Largest drift (in basis points) left after buying `purchases` on top of `current_values`
+    pub fn drift_after_purchases(&self, current_values: &[(String, u128)], purchases: &[(String, u128)]) -> u32 {
+        let mut values: std::collections::HashMap<&str, u128> = current_values
             .iter()
             .map(|(asset_id, value)| (asset_id.as_str(), *value))
             .collect();
             
-        // Find assets to sell (current > target) and buy (current < target)
-        let mut sellers = Vec::new();
-        let mut buyers = Vec::new();
+        for (asset_id, amount) in purchases {
+            *values.entry(asset_id.as_str()).or_insert(0) += *amount;
+        }
         
-        for (asset_id, target_value) in &target_values {
-            let current_value = *current_value_map.get(asset_id.as_str()).unwrap_or(&0);
-            
-            if current_value > *target_value {
-                // Need to sell some of this asset
-                sellers.push((asset_id.clone(), current_value - target_value));
-            } else if current_value < *target_value {
-                // Need to buy some of this asset
-                buyers.push((asset_id.clone(), target_value - current_value));
-            }
+        let total: u128 = values.values().sum();
+        if total == 0 {
+            return 0;
         }
         
-        // Match sellers with buyers to create transactions
-        let mut transactions = Vec::new();
-        let mut i = 0;
-        let mut j = 0;
+        self.allocations.iter()
+            .map(|a| {
+                let value = *values.get(a.asset_id.as_str()).unwrap_or(&0);
+                let current = (value * 10000 / total) as u32;
+                current.abs_diff(a.target_percentage)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+    
+    /// Enables or disables cash-flow rebalancing on deposits
+    pub fn set_cashflow_rebalancing(&mut self, enabled: bool) {
+        self.cashflow_rebalancing = enabled;
+    }
+    
+    /// Sets the base asset trades may be routed through
+    pub fn set_base_asset(&mut self, base_asset: Option<String>) {
+        self.base_asset = base_asset;
+    }
+    
+    /// Sets the asset every trade is routed through (`None` = direct pairs)
+    pub fn set_pivot_asset(&mut self, pivot_asset: Option<String>) {
+        self.pivot_asset = pivot_asset;
+    }
+    
+    /// Replaces each `A -> B` trade with `A -> pivot` and `pivot -> B`
+    ///
+    /// Trades already to or from the pivot are kept as they are. Legs of the
+    /// same pair are netted into one, so each seller sells into the pivot once
+    /// and each buyer is bought from it once. The pivot can only be spent as
+    /// far as `pivot_held` goes, since the sales into it settle asynchronously;
+    /// purchases beyond that wait for the next rebalance.
+    pub fn route_through_pivot(&self, transactions: Vec<(String, String, u128)>, pivot_held: u128) -> Vec<(String, String, u128)> {
+        let pivot = match &self.pivot_asset {
+            Some(pivot) => pivot,
+            None => return transactions,
+        };
         
-        while i < sellers.len() && j < buyers.len() {
-            let (sell_asset, mut sell_amount) = sellers[i].clone();
-            let (buy_asset, mut buy_amount) = buyers[j].clone();
-            
-            let amount_to_swap = sell_amount.min(buy_amount);
+        let mut routed: Vec<(String, String, u128)> = Vec::with_capacity(transactions.len() * 2);
+        let mut add = |source: String, target: String, amount: u128| {
+            match routed.iter_mut().find(|(s, t, _)| *s == source && *t == target) {
+                Some(leg) => leg.2 = leg.2.saturating_add(amount),
+                None => routed.push((source, target, amount)),
+            }
+        };
+        
+        for (source, target, amount) in transactions {
+            if &source == pivot || &target == pivot {
+                add(source, target, amount);
+                continue;
+            }
             
-            if amount_to_swap > 0 {
-                transactions.push((sell_asset.clone(), buy_asset.clone(), amount_to_swap));
-                
-                // Update remaining amounts
-                sell_amount -= amount_to_swap;
-                buy_amount -= amount_to_swap;
-                
-                sellers[i] = (sell_asset, sell_amount);
-                buyers[j] = (buy_asset, buy_amount);
-                
-                // Move to next seller or buyer if fully processed
-                if sell_amount == 0 {
-                    i += 1;
+            add(source, pivot.clone(), amount);
+            add(pivot.clone(), target, amount);
+        }
+        
+        let mut pivot_available = pivot_held;
+        routed.into_iter()
+            .filter_map(|(source, target, amount)| {
+                if &source != pivot {
+                    return Some((source, target, amount));
                 }
                 
-                if buy_amount == 0 {
-                    j += 1;
-                }
+                let funded = amount.min(pivot_available);
+                pivot_available -= funded;
+                (funded > 0).then_some((source, target, funded))
+            })
+            .collect()
+    }
+    
+    /// Sets the rebalance mode
+    pub fn set_rebalance_mode(&mut self, mode: RebalanceMode) {
+        self.rebalance_mode = mode;
+    }
+    
+    /// Sets rebalance frequency
+    pub fn set_rebalance_frequency(&mut self, frequency_seconds: u64) {
+        self.rebalance_frequency_seconds = frequency_seconds;
+    }
+    
+    /// Sets the maximum number of rebalances per 24-hour window (0 = unlimited)
+    pub fn set_max_rebalances_per_day(&mut self, max_rebalances: u32) {
+        self.max_rebalances_per_day = max_rebalances;
+    }
+    
+    /// Daily rebalance limit in force: the set's own, else the governance one (0 = unlimited)
+    pub fn daily_rebalance_limit(&self) -> u32 {
+        if self.max_rebalances_per_day == 0 {
+            self.governance_max_rebalances_per_day
+        } else {
+            self.max_rebalances_per_day
+        }
+    }
+    
+    /// Whether the daily rebalance limit has been reached in the window containing `now`
+    pub fn rate_limited_at(&self, now: u64) -> bool {
+        let limit = self.daily_rebalance_limit();
+        if limit == 0 {
+            return false;
+        }
+        
+        let window_end = self.window_start.saturating_add(RATE_LIMIT_WINDOW_SECONDS);
+        now < window_end && self.rebalances_in_window >= limit
+    }
+    
+    /// Opts into (or out of) the adaptive drift threshold
+    pub fn set_adaptive_threshold(&mut self, adaptive: Option<AdaptiveThreshold>) -> Result<(), &'static str> {
+        if let Some(config) = &adaptive {
+            config.validate()?;
+        }
+        
+        self.adaptive_threshold = adaptive;
+        self.observed_volatility_bp = None;
+        Ok(())
+    }
+    
+    /// Limits the estimated price impact of each trade (0 = unlimited)
+    pub fn set_trade_impact_limit(&mut self, max_trade_impact_bps: u32, slippage_model: SlippageModel) -> Result<(), &'static str> {
+        if max_trade_impact_bps > 10000 {
+            return Err("Trade impact limit cannot exceed 100%");
+        }
+        
+        self.max_trade_impact_bps = max_trade_impact_bps;
+        self.slippage_model = slippage_model;
+        Ok(())
+    }
+    
+    /// Splits trades whose estimated impact exceeds `max_trade_impact_bps` into equal child trades
+    ///
+    /// Children of one trade are consecutive and share its source and target;
+    /// the last child takes any remainder.
+    pub fn split_for_impact(&self, transactions: Vec<(String, String, u128)>) -> Vec<(String, String, u128)> {
+        if self.max_trade_impact_bps == 0 {
+            return transactions;
+        }
+        
+        let mut split = Vec::with_capacity(transactions.len());
+        for (source, target, amount) in transactions {
+            if self.slippage_model.impact_bps(amount) <= self.max_trade_impact_bps as u128 {
+                split.push((source, target, amount));
+                continue;
+            }
+            
+            let parts = self.slippage_model.parts_needed(amount, self.max_trade_impact_bps);
+            let part = amount / parts;
+            for i in 0..parts {
+                let child = if i + 1 == parts { amount - part * (parts - 1) } else { part };
+                split.push((source.clone(), target.clone(), child));
             }
         }
         
-        transactions
+        split
     }
     
-    /// Validates that allocation percentages sum to 100%
-    pub fn validate_percentages(&self) -> Result<(), &'static str> {
-        let total: u32 = self.allocations.iter().map(|a| a.target_percentage).sum();
+    /// Records per-asset volatilities as one target-weighted volatility
+    ///
+    /// Assets without a reading are left out of the weighting.
+    pub fn record_volatility(&mut self, volatilities: &[(String, u32)]) {
+        let mut weighted: u64 = 0;
+        let mut total_weight: u64 = 0;
+        
+        for (asset_id, volatility) in volatilities {
+            if let Some(allocation) = self.get_allocation(asset_id) {
+                weighted += (*volatility as u64) * (allocation.target_percentage as u64);
+                total_weight += allocation.target_percentage as u64;
+            }
+        }
+        
+        self.observed_volatility_bp = if total_weight == 0 {
+            None
+        } else {
+            Some((weighted / total_weight) as u32)
+        };
+    }
+    
+    /// Reads the set's asset volatilities from the price feed when adaptive mode is on
+    pub fn refresh_volatility(&mut self) {
+        let lookback = match &self.adaptive_threshold {
+            Some(config) => config.lookback_records as usize,
+            None => return,
+        };
+        
+        let volatilities: Vec<(String, u32)> = self.allocations.iter()
+            .filter_map(|a| {
+                crate::price_feed::asset_volatility_bp(&a.asset_id, lookback)
+                    .map(|volatility| (a.asset_id.clone(), volatility))
+            })
+            .collect();
+            
+        self.record_volatility(&volatilities);
+    }
+    
+    /// Drift threshold in effect, scaled by volatility in adaptive mode
+    ///
+    /// Falls back to `drift_threshold_bp` until a volatility has been observed.
+    pub fn effective_drift_threshold(&self) -> u32 {
+        match (&self.adaptive_threshold, self.observed_volatility_bp) {
+            (Some(config), Some(volatility)) => {
+                let scaled = (self.drift_threshold_bp as u64) * (config.multiplier_bp(volatility) as u64) / 10000;
+                scaled.min(u32::MAX as u64) as u32
+            },
+            _ => self.drift_threshold_bp,
+        }
+    }
+    
+    /// Sets the minimum number of seconds between rebalances (0 = disabled)
+    pub fn set_min_rebalance_interval(&mut self, interval_seconds: u64) {
+        self.min_rebalance_interval_seconds = interval_seconds;
+    }
+    
+    /// Seconds left before another rebalance is allowed at `now` (0 = none)
+    pub fn cooldown_remaining(&self, now: u64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_rebalance);
+        self.min_rebalance_interval_seconds.saturating_sub(elapsed)
+    }
+    
+    /// Whether the cooldown blocks a rebalance at `now`
+    ///
+    /// This applies to scheduled rebalances too: a schedule longer than the
+    /// cooldown is never due inside it, while a shorter one is held back.
+    fn blocked_by_cooldown(&self, now: u64) -> bool {
+        self.cooldown_remaining(now) > 0
+    }
+    
+    /// Whether a scheduled rebalance is due at `now`
+    fn scheduled_due(&self, now: u64) -> bool {
+        self.seconds_until_rebalance(now) == Some(0)
+    }
+    
+    /// Counts a rebalance at `now`, starting a new window once the current one has expired
+    pub fn count_rebalance(&mut self, now: u64) {
+        let window_end = self.window_start.saturating_add(RATE_LIMIT_WINDOW_SECONDS);
+        if self.rebalances_in_window == 0 || now >= window_end {
+            self.window_start = now;
+            self.rebalances_in_window = 0;
+        }
         
+        self.rebalances_in_window += 1;
+    }
+    
+    /// Adds a new asset allocation to the set
+    pub fn add_allocation(&mut self, allocation: AssetAllocation) -> Result<(), &'static str> {
+        // Check if the asset already exists
+        if self.position(&allocation.asset_id).is_some() {
+            return Err("Asset already exists in allocation");
+        }
+        
+        if !self.has_room_for(self.allocations.len() + 1) {
+            return Err("Maximum asset count reached");
+        }
+        
+        self.push_allocation(allocation);
+        self.refresh_validity();
+        Ok(())
+    }
+    
+    /// Whether the set may hold `asset_count` assets under `max_assets`
+    fn has_room_for(&self, asset_count: usize) -> bool {
+        self.max_assets.map_or(true, |max| asset_count <= max as usize)
+    }
+    
+    /// Limits the number of assets in the set (`None` = unlimited)
+    pub fn set_max_assets(&mut self, max_assets: Option<u32>) -> Result<(), &'static str> {
+        if max_assets == Some(0) {
+            return Err("Maximum asset count must be at least 1");
+        }
+        
+        if max_assets.map_or(false, |max| self.allocations.len() > max as usize) {
+            return Err("Allocation already holds more assets than the limit");
+        }
+        
+        self.max_assets = max_assets;
+        Ok(())
+    }
+    
+    /// Updates an existing asset allocation
+    pub fn update_allocation(&mut self, asset_id: &str, target_percentage: u32) -> Result<(), &'static str> {
+        let pos = self.position(asset_id).ok_or("Asset not found in allocation")?;
+        let allocation = &mut self.allocations[pos];
+            
+        if !allocation.is_within_bounds(target_percentage) {
+            return Err("Target percentage outside of bounds");
+        }
+        
+        allocation.update_target_percentage(target_percentage);
+        self.refresh_validity();
+        Ok(())
+    }
+    
+    /// Replaces the whole allocation policy with the given targets
+    ///
+    /// Held assets left out of `targets` are kept with a target of 0 so the
+    /// next rebalance sells out of them instead of orphaning their balance.
+    /// Existing assets keep their bounds, lock state and sector. Returns the
+    /// `(asset, old_target, new_target)` changes that were applied.
+    pub fn set_allocations(&mut self, targets: &[(String, u32)]) -> Result<Vec<(String, Option<u32>, Option<u32>)>, &'static str> {
+        self.replace_targets(targets)
+    }
+    
+    /// Replaces the allocations with a template's targets
+    ///
+    /// Held assets keep their current percentage and per-asset settings
+    /// (bounds, lock and sector), those not in the template are set to a
+    /// target of 0 so the next rebalance sells out of them, and new assets
+    /// start at 0 so it buys into them. Returns the target changes applied.
+    pub fn apply_template(&mut self, targets: &[(String, u32)]) -> Result<Vec<(String, Option<u32>, Option<u32>)>, &'static str> {
+        self.replace_targets(targets)
+    }
+    
+    /// Sets every asset's target to its entry in `targets`, or 0 when omitted
+    fn replace_targets(&mut self, targets: &[(String, u32)]) -> Result<Vec<(String, Option<u32>, Option<u32>)>, &'static str> {
+        let total: u32 = targets.iter().map(|(_, pct)| *pct).sum();
         if total != 10000 {
             return Err("Allocation percentages must sum to 100%");
         }
         
+        let mut new_assets = 0;
+        for (i, (asset_id, target_percentage)) in targets.iter().enumerate() {
+            if targets[..i].iter().any(|(other, _)| other == asset_id) {
+                return Err("Duplicate asset in allocation");
+            }
+            
+            if self.get_allocation(asset_id).is_none() {
+                new_assets += 1;
+            }
+        }
+        
+        if !self.has_room_for(self.allocations.len() + new_assets) {
+            return Err("Maximum asset count reached");
+        }
+        
+        let target_of = |asset_id: &str| targets.iter()
+            .find(|(id, _)| id == asset_id)
+            .map_or(0, |(_, pct)| *pct);
+            
+        if self.allocations.iter().any(|a| !a.is_within_bounds(target_of(&a.asset_id))) {
+            return Err("Target percentage outside of bounds");
+        }
+        
+        let mut changes = Vec::new();
+        
+        for allocation in &mut self.allocations {
+            let target_percentage = target_of(&allocation.asset_id);
+            if allocation.target_percentage != target_percentage {
+                changes.push((allocation.asset_id.clone(), Some(allocation.target_percentage), Some(target_percentage)));
+                allocation.update_target_percentage(target_percentage);
+            }
+        }
+        
+        for (asset_id, target_percentage) in targets {
+            if self.position(asset_id).is_none() {
+                let mut allocation = AssetAllocation::new(asset_id.clone(), *target_percentage);
+                allocation.update_current_percentage(0);
+                changes.push((asset_id.clone(), None, Some(*target_percentage)));
+                self.push_allocation(allocation);
+            }
+        }
+        
+        self.refresh_validity();
+        Ok(changes)
+    }
+    
+    /// Appends a target change to the audit trail, dropping the oldest entries past the cap
+    pub fn record_allocation_change(&mut self, event: crate::events::AllocationChangedEvent) {
+        self.allocation_history.push(event);
+        
+        if self.allocation_history.len() > MAX_ALLOCATION_HISTORY {
+            let excess = self.allocation_history.len() - MAX_ALLOCATION_HISTORY;
+            self.allocation_history.drain(..excess);
+        }
+    }
+    
+    /// Sets min/max bounds on an existing asset allocation
+    pub fn set_allocation_bounds(&mut self, asset_id: &str, min_percentage: Option<u32>, max_percentage: Option<u32>) -> Result<(), &'static str> {
+        let allocation = self.allocations.iter_mut()
+            .find(|a| a.asset_id == asset_id)
+            .ok_or("Asset not found in allocation")?;
+            
+        allocation.set_bounds(min_percentage, max_percentage)
+    }
+    
+    /// Computes the target percentages clamped to each asset's bounds,
+    /// or an error naming the assets whose bounds cannot be satisfied
+    pub fn bounded_targets(&self) -> Result<Vec<(String, u32)>, String> {
+        let min_sum: u32 = self.allocations.iter().map(|a| a.min_percentage.unwrap_or(0)).sum();
+        let max_sum: u32 = self.allocations.iter().map(|a| a.max_percentage.unwrap_or(10000)).sum();
+        
+        if min_sum > 10000 {
+            let conflicting: Vec<&str> = self.allocations.iter()
+                .filter(|a| a.min_percentage.is_some())
+                .map(|a| a.asset_id.as_str())
+                .collect();
+            return Err(format!("Minimum bounds exceed 100% for assets: {}", conflicting.join(", ")));
+        }
+        
+        if max_sum < 10000 {
+            let conflicting: Vec<&str> = self.allocations.iter()
+                .filter(|a| a.max_percentage.is_some())
+                .map(|a| a.asset_id.as_str())
+                .collect();
+            return Err(format!("Maximum bounds fall short of 100% for assets: {}", conflicting.join(", ")));
+        }
+        
+        let out_of_bounds: Vec<&str> = self.allocations.iter()
+            .filter(|a| !a.is_within_bounds(a.target_percentage))
+            .map(|a| a.asset_id.as_str())
+            .collect();
+            
+        if !out_of_bounds.is_empty() {
+            return Err(format!("Target percentages outside of bounds for assets: {}", out_of_bounds.join(", ")));
+        }
+        
+        Ok(self.allocations.iter()
+            .map(|a| (a.asset_id.clone(), a.clamp_to_bounds(a.target_percentage)))
+            .collect())
+    }
+    
+    /// Removes an asset allocation
+    ///
+    /// The freed target weight is handed out according to `redistribution`.
+    /// `balance` is the vault's actual holding of the asset; the current
+    /// percentage is seeded from the target, so it can't tell whether
+    /// anything is held. Removing an asset with a non-zero balance requires
+    /// `force`, acknowledging the residual will need to be sold. Returns the
+    /// `(asset, old_target, new_target)` changes, including the removal itself.
+    pub fn remove_allocation(
+        &mut self,
+        asset_id: &str,
+        redistribution: Redistribution,
+        balance: u128,
+        force: bool
+    ) -> Result<Vec<(String, Option<u32>, Option<u32>)>, &'static str> {
+        let pos = self.position(asset_id).ok_or("Asset not found in allocation")?;
+            
+        if balance > 0 && !force {
+            return Err("Asset still has a residual position; pass force to remove it");
+        }
+        
+        let freed = self.allocations[pos].target_percentage;
+        
+        // Work out the new targets of the remaining assets before mutating anything
+        let mut new_targets: Vec<(usize, u32)> = Vec::new();
+        
+        match &redistribution {
+            Redistribution::Proportional => {
+                let remaining: u32 = self.allocations.iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != pos)
+                    .map(|(_, a)| a.target_percentage)
+                    .sum();
+                    
+                if remaining == 0 {
+                    return Err("No remaining allocations to redistribute to");
+                }
+                
+                // Largest remainder so the scaled targets keep the original total
+                let total = (remaining + freed) as u64;
+                let mut assigned: u64 = 0;
+                let mut shares = Vec::new();
+                
+                for (i, allocation) in self.allocations.iter().enumerate() {
+                    if i == pos {
+                        continue;
+                    }
+                    
+                    let scaled = allocation.target_percentage as u64 * total;
+                    let floor = scaled / remaining as u64;
+                    assigned += floor;
+                    shares.push((i, floor, scaled % remaining as u64));
+                }
+                
+                let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+                by_remainder.sort_by(|a, b| shares[*b].2.cmp(&shares[*a].2).then_with(|| a.cmp(b)));
+                
+                for index in by_remainder.into_iter().take((total - assigned) as usize) {
+                    shares[index].1 += 1;
+                }
+                
+                new_targets = shares.into_iter().map(|(i, target, _)| (i, target as u32)).collect();
+            },
+            
+            Redistribution::ToAsset(target_asset) => {
+                let target_pos = self.position(target_asset)
+                    .ok_or("Redistribution asset not found in allocation")?;
+                    
+                if target_pos == pos {
+                    return Err("Cannot redistribute to the asset being removed");
+                }
+                
+                new_targets.push((target_pos, self.allocations[target_pos].target_percentage + freed));
+            },
+            
+            Redistribution::None => {},
+        }
+        
+        for (i, target) in &new_targets {
+            if !self.allocations[*i].is_within_bounds(*target) {
+                return Err("Redistributed target percentage outside of bounds");
+            }
+        }
+        
+        let mut changes = vec![(asset_id.to_string(), Some(freed), None)];
+        
+        for (i, target) in new_targets {
+            let allocation = &mut self.allocations[i];
+            
+            if allocation.target_percentage != target {
+                changes.push((allocation.asset_id.clone(), Some(allocation.target_percentage), Some(target)));
+                allocation.update_target_percentage(target);
+            }
+        }
+        
+        self.allocations.remove(pos);
+        self.invalidate_index();
+        
+        if redistribution == Redistribution::None && self.validate_percentages().is_err() {
+            self.invalid_targets = true;
+        }
+        
+        Ok(changes)
+    }
+    
+    /// Clears the invalid flag once the targets sum to 100% again
+    fn refresh_validity(&mut self) {
+        if self.invalid_targets && self.validate_percentages().is_ok() {
+            self.invalid_targets = false;
+        }
+    }
+    
+    /// Locks an asset allocation so rebalancing never trades it
+    pub fn lock_allocation(&mut self, asset_id: &str) -> Result<(), &'static str> {
+        let pos = self.position(asset_id).ok_or("Asset not found in allocation")?;
+        let allocation = &mut self.allocations[pos];
+            
+        allocation.locked = true;
+        allocation.last_modified = l1x_sdk::env::block_timestamp();
         Ok(())
     }
-}
-
-// Contract implementation with Borsh serialization
-const STORAGE_CONTRACT_KEY: &[u8] = b"ALLOCATION";
-
-#[derive(BorshSerialize, BorshDeserialize)]
-pub struct AllocationContract {
-    allocations: std::collections::HashMap<String, AllocationSet>, // Vault ID -> AllocationSet
-}
-
-#[l1x_sdk::contract]
-impl AllocationContract {
-    fn load() -> Self {
-        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
-            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
-            None => panic!("The contract isn't initialized"),
+    
+    /// Unlocks an asset allocation so it takes part in rebalancing again
+    pub fn unlock_allocation(&mut self, asset_id: &str) -> Result<(), &'static str> {
+        let pos = self.position(asset_id).ok_or("Asset not found in allocation")?;
+        let allocation = &mut self.allocations[pos];
+            
+        allocation.locked = false;
+        allocation.last_modified = l1x_sdk::env::block_timestamp();
+        Ok(())
+    }
+    
+    /// Assigns an asset allocation to a sector (or clears it with `None`)
+    pub fn set_allocation_sector(&mut self, asset_id: &str, sector: Option<String>) -> Result<(), &'static str> {
+        let pos = self.position(asset_id).ok_or("Asset not found in allocation")?;
+        let allocation = &mut self.allocations[pos];
+            
+        allocation.sector = sector;
+        allocation.last_modified = l1x_sdk::env::block_timestamp();
+        Ok(())
+    }
+    
+    /// Sums target percentages (in basis points) by sector; assets without a
+    /// sector are grouped under `UNCLASSIFIED_SECTOR`
+    pub fn sector_breakdown(&self) -> std::collections::HashMap<String, u32> {
+        let mut breakdown = std::collections::HashMap::new();
+        
+        for allocation in &self.allocations {
+            let sector = allocation.sector.clone()
+                .unwrap_or_else(|| UNCLASSIFIED_SECTOR.to_string());
+            *breakdown.entry(sector).or_insert(0) += allocation.target_percentage;
+        }
+        
+        breakdown
+    }
+    
+    /// Highest combined target percentage (in basis points) held in any one sector
+    pub fn max_sector_concentration(&self) -> u32 {
+        self.sector_breakdown().values().copied().max().unwrap_or(0)
+    }
+    
+    /// Checks whether an asset allocation is locked
+    pub fn is_locked(&self, asset_id: &str) -> bool {
+        self.get_allocation(asset_id).map_or(false, |a| a.locked)
+    }
+    
+    /// Gets an asset allocation by ID
+    pub fn get_allocation(&self, asset_id: &str) -> Option<&AssetAllocation> {
+        self.position(asset_id).map(|i| &self.allocations[i])
+    }
+    
+    /// Computes the USD value (scaled by 1e8) of each allocated asset from
+    /// `(asset, balance)` pairs and per-unit prices (scaled by 1e8)
+    pub fn market_values(
+        &self,
+        values: &[(String, u128)],
+        prices: &[(String, u128)]
+    ) -> Result<Vec<(String, u128)>, &'static str> {
+        let balance_map: std::collections::HashMap<&str, u128> = values
+            .iter()
+            .map(|(asset_id, balance)| (asset_id.as_str(), *balance))
+            .collect();
+        let price_map: std::collections::HashMap<&str, u128> = prices
+            .iter()
+            .map(|(asset_id, price)| (asset_id.as_str(), *price))
+            .collect();
+            
+        let mut market_values = Vec::new();
+        
+        for allocation in &self.allocations {
+            let balance = *balance_map.get(allocation.asset_id.as_str()).unwrap_or(&0);
+            
+            let value = if balance == 0 {
+                0
+            } else {
+                let price = price_map.get(allocation.asset_id.as_str())
+                    .ok_or("Missing price for allocated asset")?;
+                balance.checked_mul(*price).ok_or("Asset value overflow")?
+            };
+            
+            market_values.push((allocation.asset_id.clone(), value));
+        }
+        
+        Ok(market_values)
+    }
+    
+    /// Recomputes each allocation's current percentage from balances and prices
+    ///
+    /// Percentages are apportioned by largest remainder so the rounded values
+    /// always sum to exactly 10000. Returns the total USD value (scaled by 1e8).
+    pub fn update_current_from_values(
+        &mut self,
+        values: &[(String, u128)],
+        prices: &[(String, u128)]
+    ) -> Result<u128, &'static str> {
+        let market_values = self.market_values(values, prices)?;
+        
+        let mut total_value: u128 = 0;
+        for (_, value) in &market_values {
+            total_value = total_value.checked_add(*value).ok_or("Total value overflow")?;
+        }
+        
+        let values: Vec<u128> = market_values.iter().map(|(_, value)| *value).collect();
+        let percentages = Self::apportion_basis_points(&values)?;
+        
+        for (index, percentage) in percentages.into_iter().enumerate() {
+            self.allocations[index].update_current_percentage(percentage);
+        }
+        
+        Ok(total_value)
+    }
+    
+    /// Converts values into basis-point shares that sum to exactly 10000
+    fn apportion_basis_points(values: &[u128]) -> Result<Vec<u32>, &'static str> {
+        let mut total_value: u128 = 0;
+        for value in values {
+            total_value = total_value.checked_add(*value).ok_or("Total value overflow")?;
         }
+        
+        if total_value == 0 {
+            return Err("Total value is zero");
+        }
+        
+        // Floor each share, then hand the leftover basis points to the largest remainders
+        let mut shares = Vec::with_capacity(values.len());
+        let mut assigned: u128 = 0;
+        
+        for value in values {
+            let scaled = value.checked_mul(10000).ok_or("Asset value overflow")?;
+            assigned += scaled / total_value;
+            shares.push((scaled / total_value, scaled % total_value));
+        }
+        
+        let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+        by_remainder.sort_by(|a, b| shares[*b].1.cmp(&shares[*a].1).then_with(|| a.cmp(b)));
+        
+        for index in by_remainder.into_iter().take((10000 - assigned) as usize) {
+            shares[index].0 += 1;
+        }
+        
+        Ok(shares.into_iter().map(|(percentage, _)| percentage as u32).collect())
+    }
+    
+    /// Revalues current percentages by each asset's price move since its last
+    /// recorded price; assets without a new or recorded price keep their weight
+    pub fn reprice_current(&mut self, prices: &[(String, u128)]) -> Result<(), &'static str> {
+        let price_map: std::collections::HashMap<&str, u128> = prices
+            .iter()
+            .map(|(asset_id, price)| (asset_id.as_str(), *price))
+            .collect();
+            
+        let mut values = Vec::with_capacity(self.allocations.len());
+        
+        for allocation in &self.allocations {
+            let weight = (allocation.current_percentage as u128) * 100_000_000;
+            let value = match (price_map.get(allocation.asset_id.as_str()), allocation.last_price) {
+                (Some(price), Some(last_price)) if last_price > 0 => {
+                    weight.checked_mul(*price).ok_or("Asset value overflow")? / last_price
+                },
+                _ => weight,
+            };
+            values.push(value);
+        }
+        
+        let percentages = Self::apportion_basis_points(&values)?;
+        
+        for (allocation, percentage) in self.allocations.iter_mut().zip(percentages) {
+            allocation.update_current_percentage(percentage);
+        }
+        
+        Ok(())
+    }
+    
+    /// Value of a set worth `total_value` at its last recorded prices, marked to `prices`
+    ///
+    /// Each asset's share of `total_value` is scaled by its price move since
+    /// its last recorded price; assets without a new or recorded price keep
+    /// their value.
+    pub fn value_at_prices(&self, total_value: u128, prices: &[(String, u128)]) -> Result<u128, &'static str> {
+        let price_map: std::collections::HashMap<&str, u128> = prices
+            .iter()
+            .map(|(asset_id, price)| (asset_id.as_str(), *price))
+            .collect();
+            
+        let mut value: u128 = 0;
+        
+        for allocation in &self.allocations {
+            let held = total_value.checked_mul(allocation.current_percentage as u128)
+                .ok_or("Asset value overflow")? / 10000;
+            let marked = match (price_map.get(allocation.asset_id.as_str()), allocation.last_price) {
+                (Some(price), Some(last_price)) if last_price > 0 => {
+                    held.checked_mul(*price).ok_or("Asset value overflow")? / last_price
+                },
+                _ => held,
+            };
+            value = value.checked_add(marked).ok_or("Total value overflow")?;
+        }
+        
+        Ok(value)
+    }
+    
+    /// Projects current percentages after the given price moves, without changing the set
+    ///
+    /// `price_changes` are percentage moves per asset (50.0 = +50%, -20.0 =
+    /// -20%); assets not listed keep their price. Each asset's value is its
+    /// current weight scaled by its move, re-expressed as a share of the new
+    /// total. Moves below -100% are treated as the asset going to zero.
+    pub fn simulate_price_drift(&self, price_changes: &[(String, f64)]) -> Vec<(String, u32)> {
+        let values: Vec<u128> = self.allocations.iter()
+            .map(|allocation| {
+                let change = price_changes.iter()
+                    .find(|(asset_id, _)| asset_id == &allocation.asset_id)
+                    .map(|(_, change)| *change)
+                    .unwrap_or(0.0);
+                let factor = (1.0 + change / 100.0).max(0.0);
+                
+                (allocation.current_percentage as f64 * factor * 1_000_000.0) as u128
+            })
+            .collect();
+            
+        let percentages = Self::apportion_basis_points(&values)
+            .unwrap_or_else(|_| self.allocations.iter().map(|a| a.current_percentage).collect());
+            
+        self.allocations.iter()
+            .zip(percentages)
+            .map(|(allocation, percentage)| (allocation.asset_id.clone(), percentage))
+            .collect()
+    }
+    
+    /// Keccak-256 hash of the allocation targets, independent of their order
+    ///
+    /// Allocations are sorted by asset ID and each is encoded as the asset ID's
+    /// length (u32, little-endian), its bytes, then its target (u32,
+    /// little-endian), so equal configurations always hash the same.
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        let mut targets: Vec<(&str, u32)> = self.allocations.iter()
+            .map(|allocation| (allocation.asset_id.as_str(), allocation.target_percentage))
+            .collect();
+        targets.sort_by(|a, b| a.0.cmp(b.0));
+        
+        let mut encoded = Vec::new();
+        for (asset_id, target) in targets {
+            encoded.extend_from_slice(&(asset_id.len() as u32).to_le_bytes());
+            encoded.extend_from_slice(asset_id.as_bytes());
+            encoded.extend_from_slice(&target.to_le_bytes());
+        }
+        
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&l1x_sdk::env::keccak256(&encoded)[..]);
+        hash
+    }
+    
+    /// Seconds until the next scheduled rebalance at `now`, or `None` when
+    /// rebalancing is manual only
+    pub fn seconds_until_rebalance(&self, now: u64) -> Option<u64> {
+        if self.rebalance_frequency_seconds == 0 {
+            return None;
+        }
+        
+        let due = self.last_rebalance.saturating_add(self.rebalance_frequency_seconds);
+        Some(due.saturating_sub(now))
+    }
+    
+    /// Drift result for one allocation, flagging a buffer below its floor
+    fn drift_result_for(&self, allocation: &AssetAllocation) -> crate::events::DriftResult {
+        let mut drift_result = allocation.create_drift_result(self.effective_drift_threshold());
+        
+        // Flag a buffer below its floor so the UI can warn the user
+        if self.is_buffer(&allocation.asset_id) {
+            drift_result.buffer_breached = allocation.current_percentage < self.buffer_bp;
+        }
+        
+        drift_result
+    }
+    
+    /// Builds a drift report for every allocation at `now`
+    ///
+    /// When `prices` are supplied current percentages are revalued from them
+    /// first (see `reprice_current`); otherwise the stored percentages are used.
+    pub fn drift_report(&self, prices: Option<&[(String, u128)]>, now: u64) -> Result<DriftReport, &'static str> {
+        let mut set = self.clone();
+        if let Some(prices) = prices {
+            set.reprice_current(prices)?;
+        }
+        
+        let drift_results: Vec<crate::events::DriftResult> = set.allocations.iter()
+            .map(|allocation| set.drift_result_for(allocation))
+            .collect();
+            
+        let seconds_until_rebalance = set.seconds_until_rebalance(now);
+        let needs_rebalancing = !set.rate_limited_at(now)
+            && !set.blocked_by_cooldown(now)
+            && (set.scheduled_due(now) || drift_results.iter().any(|r| r.exceeds_threshold || r.bound_breached || r.buffer_breached));
+            
+        Ok(DriftReport {
+            drift_results,
+            drift_threshold_bp: set.drift_threshold_bp,
+            effective_threshold_bp: set.effective_drift_threshold(),
+            volatility_bp: set.observed_volatility_bp,
+            seconds_until_rebalance,
+            cooldown_remaining_seconds: set.cooldown_remaining(now),
+            needs_rebalancing,
+        })
+    }
+    
+    /// Checks if rebalancing is needed based on drift or time
+    pub fn needs_rebalancing(&self) -> bool {
+        self.needs_rebalancing_at(l1x_sdk::env::block_timestamp())
+    }
+    
+    /// Checks if rebalancing is needed at `now`, honouring the daily rebalance
+    /// limit and the minimum interval between rebalances
+    pub fn needs_rebalancing_at(&self, now: u64) -> bool {
+        if self.rate_limited_at(now) {
+            return false;
+        }
+        
+        if self.blocked_by_cooldown(now) {
+            return false;
+        }
+        
+        // Check if time-based rebalancing is needed
+        if self.scheduled_due(now) {
+            return true;
+        }
+        
+        // Check if drift-based rebalancing is needed
+        for allocation in &self.allocations {
+            if allocation.drift() > self.effective_drift_threshold() {
+                return true;
+            }
+            
+            // A breached min/max bound requires action regardless of drift
+            if allocation.breaches_bounds() {
+                return true;
+            }
+        }
+        
+        self.buffer_breached()
+    }
+    
+    /// Checks if rebalancing is needed and emits appropriate events
+    pub fn check_and_emit_rebalance_events(&self, vault_id: &str) -> bool {
+        let current_time = l1x_sdk::env::block_timestamp();
+        if self.rate_limited_at(current_time) || self.blocked_by_cooldown(current_time) {
+            return false;
+        }
+        
+        // Check if time-based rebalancing is needed
+        if self.rebalance_frequency_seconds > 0 {
+            let elapsed = current_time.saturating_sub(self.last_rebalance);
+            
+            if elapsed >= self.rebalance_frequency_seconds {
+                // Emit scheduled rebalance event
+                let data = format!("{{\"elapsed_seconds\": {}, \"frequency\": {}}}", 
+                    elapsed, self.rebalance_frequency_seconds);
+                let event = crate::events::RebalanceEvent::new(
+                    crate::events::RebalanceEventType::ScheduledRebalance, 
+                    vault_id.to_string()
+                ).with_data(data);
+                event.emit();
+                
+                return true;
+            }
+        }
+        
+        // Check if drift-based rebalancing is needed
+        let mut needs_rebalance = false;
+        let mut drift_results = Vec::new();
+        
+        for allocation in &self.allocations {
+            let drift_result = self.drift_result_for(allocation);
+            
+            if drift_result.exceeds_threshold || drift_result.bound_breached || drift_result.buffer_breached {
+                needs_rebalance = true;
+                drift_results.push(drift_result);
+            }
+        }
+        
+        // Emit drift exceeded event if needed
+        if needs_rebalance && !drift_results.is_empty() {
+            crate::events::emit_drift_exceeded_event(vault_id, drift_results);
+        }
+        
+        needs_rebalance
+    }
+    
+    /// Records a rebalance operation
+    pub fn record_rebalance(&mut self, prices: &[(String, u128)]) {
+        self.last_rebalance = l1x_sdk::env::block_timestamp();
+        self.count_rebalance(self.last_rebalance);
+        
+        // Create a price map for lookup
+        let price_map: std::collections::HashMap<&str, u128> = prices
+            .iter()
+            .map(|(asset_id, price)| (asset_id.as_str(), *price))
+            .collect();
+            
+        // Update each allocation
+        for allocation in &mut self.allocations {
+            let price = price_map.get(allocation.asset_id.as_str()).copied();
+            allocation.record_rebalance(price);
+        }
+    }
+    
+    /// Moves each `(source, target, amount)` leg's share of `total_value` from source to target
+    ///
+    /// Used for partial rebalances (such as gradual slices) where
+    /// `record_rebalance` would wrongly mark every asset as on target.
+    pub fn apply_transactions(&mut self, transactions: &[(String, String, u128)], total_value: u128) {
+        let reversed: Vec<(String, String, u128)> = transactions.iter()
+            .map(|(source, target, amount)| (target.clone(), source.clone(), *amount))
+            .collect();
+            
+        self.revert_transactions(&reversed, total_value);
+    }
+    
+    /// Undoes the effect of rebalance transactions that never executed
+    ///
+    /// `record_rebalance` assumes the whole plan went through; for each
+    /// `(source, target, amount)` leg that did not, its share of `total_value`
+    /// is moved back from the target's current percentage to the source's.
+    pub fn revert_transactions(&mut self, transactions: &[(String, String, u128)], total_value: u128) {
+        if total_value == 0 {
+            return;
+        }
+        
+        for (source, target, amount) in transactions {
+            let bp = (amount.saturating_mul(10000) / total_value).min(10000) as u32;
+            
+            if let Some(pos) = self.position(source) {
+                let allocation = &mut self.allocations[pos];
+                let percentage = allocation.current_percentage.saturating_add(bp).min(10000);
+                allocation.update_current_percentage(percentage);
+            }
+            
+            if let Some(pos) = self.position(target) {
+                let allocation = &mut self.allocations[pos];
+                let percentage = allocation.current_percentage.saturating_sub(bp);
+                allocation.update_current_percentage(percentage);
+            }
+        }
+    }
+    
+    /// Performs auto-rebalancing calculation and returns transactions needed
+    ///
+    /// Post-rebalance weights are clamped to each asset's min/max bounds; if the
+    /// bounds make the targets infeasible an error naming the conflicting assets
+    /// is returned instead of transactions.
+    ///
+    /// The plan is deterministic: sellers and buyers are ordered by amount
+    /// (largest first) and then asset ID before matching, so the same drift
+    /// state yields the same transactions regardless of the order of
+    /// `allocations` or `current_values`.
+    pub fn calculate_rebalance_transactions(
+        &self,
+        current_values: &[(String, u128)],
+        total_value: u128
+    ) -> Result<Vec<(String, String, u128)>, String> {
+        if self.invalid_targets {
+            return Err("Allocation targets no longer sum to 100%; fix them before rebalancing".to_string());
+        }
+        
+        if total_value == 0 || self.allocations.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        // Convert current values to a map for easier lookup
+        let current_value_map: std::collections::HashMap<&str, u128> = current_values
+            .iter()
+            .map(|(asset_id, value)| (asset_id.as_str(), *value))
+            .collect();
+            
+        let bounded_targets = self.bounded_targets()?;
+        
+        // Locked positions keep their current value; the free assets absorb the
+        // rebalance with their targets re-normalized over the unlocked portion
+        let locked_value: u128 = self.allocations.iter()
+            .filter(|a| a.locked)
+            .map(|a| *current_value_map.get(a.asset_id.as_str()).unwrap_or(&0))
+            .sum();
+        let free_value = total_value.saturating_sub(locked_value);
+        
+        let has_locked = self.allocations.iter().any(|a| a.locked);
+        let free_target_sum: u128 = if has_locked {
+            bounded_targets.iter()
+                .filter(|(asset_id, _)| !self.is_locked(asset_id))
+                .map(|(_, pct)| *pct as u128)
+                .sum()
+        } else {
+            10000
+        };
+        
+        // Calculate target values based on bounded allocations
+        let mut target_values = Vec::new();
+        
+        if free_target_sum > 0 {
+            for (asset_id, target_percentage) in bounded_targets {
+                if self.is_locked(&asset_id) {
+                    continue;
+                }
+                
+                let target_value = free_value * (target_percentage as u128) / free_target_sum;
+                target_values.push((asset_id, target_value));
+            }
+        }
+        
+        // In band mode, only trade back to the edge of the tolerance band
+        let band_value = match self.rebalance_mode {
+            RebalanceMode::ToTarget => 0,
+            RebalanceMode::ToBand { band_bp } => total_value * (band_bp as u128) / 10000,
+        };
+        
+        // Find assets to sell (current > target) and buy (current < target)
+        let mut sellers = Vec::new();
+        let mut buyers = Vec::new();
+        
+        for (asset_id, target_value) in &target_values {
+            let current_value = *current_value_map.get(asset_id.as_str()).unwrap_or(&0);
+            let upper = target_value.saturating_add(band_value);
+            let lower = target_value.saturating_sub(band_value);
+            
+            if current_value > upper {
+                // Need to sell some of this asset, but never draw the buffer below its floor
+                let mut sell_amount = current_value - upper;
+                if self.is_buffer(asset_id) {
+                    let floor_value = total_value * (self.buffer_bp as u128) / 10000;
+                    sell_amount = sell_amount.min(current_value.saturating_sub(floor_value));
+                }
+                
+                if sell_amount > 0 {
+                    sellers.push((asset_id.clone(), sell_amount));
+                }
+            } else if current_value < lower {
+                // Need to buy some of this asset
+                buyers.push((asset_id.clone(), lower - current_value));
+            }
+        }
+        
+        // Canonical order so every node pairs the same legs
+        sellers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        buyers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        
+        // Match sellers with buyers, keeping whichever plan needs the fewest swaps,
+        // route through the pivot asset if one is required, then break up
+        // trades too large for the market to absorb
+        let planned = Self::minimize_swap_count(sellers, buyers, self.base_asset.as_deref());
+        let pivot_held = self.pivot_asset.as_ref()
+            .and_then(|pivot| current_values.iter().find(|(asset_id, _)| asset_id == pivot))
+            .map_or(0, |(_, value)| *value);
+        Ok(self.split_for_impact(self.route_through_pivot(planned, pivot_held)))
+    }
+    
+    /// Calculates rebalance transactions that fit within the available swap liquidity
+    ///
+    /// Each swap draws on the liquidity of its target asset in `liquidity_map`
+    /// (assets missing from the map have none), in the same value units as
+    /// `current_values`. A swap larger than what is left is reduced to the
+    /// feasible part and swaps into an exhausted asset are dropped.
+    ///
+    /// The excess above the cap is dropped, not rerouted: every other buyer is
+    /// already fully funded by the plan, so the source asset simply stays
+    /// overweight and the next rebalance picks up the remaining drift.
+    pub fn calculate_rebalance_transactions_with_liquidity(
+        &self,
+        current_values: &[(String, u128)],
+        total_value: u128,
+        liquidity_map: &std::collections::HashMap<String, u128>,
+    ) -> Result<Vec<(String, String, u128)>, String> {
+        let planned = self.calculate_rebalance_transactions(current_values, total_value)?;
+        
+        let mut remaining = liquidity_map.clone();
+        let mut feasible = Vec::with_capacity(planned.len());
+        
+        for (source, target, amount) in planned {
+            let available = remaining.entry(target.clone()).or_insert(0);
+            let capped = amount.min(*available);
+            
+            if capped > 0 {
+                *available -= capped;
+                feasible.push((source, target, capped));
+            }
+        }
+        
+        Ok(feasible)
+    }
+    
+    /// Matches sellers with buyers in the order given, splitting amounts as needed
+    pub fn match_in_order(
+        mut sellers: Vec<(String, u128)>,
+        mut buyers: Vec<(String, u128)>,
+    ) -> Vec<(String, String, u128)> {
+        let mut transactions = Vec::new();
+        let mut i = 0;
+        let mut j = 0;
+        
+        while i < sellers.len() && j < buyers.len() {
+            let (sell_asset, mut sell_amount) = sellers[i].clone();
+            let (buy_asset, mut buy_amount) = buyers[j].clone();
+            
+            let amount_to_swap = sell_amount.min(buy_amount);
+            
+            if amount_to_swap > 0 {
+                transactions.push((sell_asset.clone(), buy_asset.clone(), amount_to_swap));
+                
+                // Update remaining amounts
+                sell_amount -= amount_to_swap;
+                buy_amount -= amount_to_swap;
+                
+                sellers[i] = (sell_asset, sell_amount);
+                buyers[j] = (buy_asset, buy_amount);
+            }
+            
+            // Move to next seller or buyer if fully processed
+            if sell_amount == 0 {
+                i += 1;
+            }
+            
+            if buy_amount == 0 {
+                j += 1;
+            }
+        }
+        
+        transactions
+    }
+    
+    /// Matches sellers with buyers, pairing exactly equal amounts first and then
+    /// the largest remaining seller with the largest remaining buyer
+    pub fn match_largest_first(
+        sellers: Vec<(String, u128)>,
+        buyers: Vec<(String, u128)>,
+    ) -> Vec<(String, String, u128)> {
+        let mut transactions = Vec::new();
+        
+        // Exact matches settle both legs in a single swap
+        let mut buyers_by_amount: std::collections::HashMap<u128, std::collections::VecDeque<usize>> =
+            std::collections::HashMap::new();
+        for (j, (_, amount)) in buyers.iter().enumerate() {
+            buyers_by_amount.entry(*amount).or_default().push_back(j);
+        }
+        
+        let mut matched_buyers = vec![false; buyers.len()];
+        let mut unmatched_sellers = Vec::new();
+        
+        for (sell_asset, amount) in sellers {
+            match buyers_by_amount.get_mut(&amount).and_then(|queue| queue.pop_front()) {
+                Some(j) => {
+                    matched_buyers[j] = true;
+                    transactions.push((sell_asset, buyers[j].0.clone(), amount));
+                },
+                None => unmatched_sellers.push((sell_asset, amount)),
+            }
+        }
+        
+        let mut sellers = unmatched_sellers;
+        let mut buyers: Vec<(String, u128)> = buyers.into_iter()
+            .zip(matched_buyers)
+            .filter(|(_, matched)| !matched)
+            .map(|(buyer, _)| buyer)
+            .collect();
+        
+        sellers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        buyers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        
+        transactions.extend(Self::match_in_order(sellers, buyers));
+        transactions
+    }
+    
+    /// Routes every trade through a base asset: sellers swap into it and
+    /// buyers are funded from it
+    pub fn route_through_base(
+        sellers: &[(String, u128)],
+        buyers: &[(String, u128)],
+        base_asset: &str,
+    ) -> Vec<(String, String, u128)> {
+        let mut transactions = Vec::new();
+        
+        for (asset_id, amount) in sellers {
+            if asset_id != base_asset && *amount > 0 {
+                transactions.push((asset_id.clone(), base_asset.to_string(), *amount));
+            }
+        }
+        
+        for (asset_id, amount) in buyers {
+            if asset_id != base_asset && *amount > 0 {
+                transactions.push((base_asset.to_string(), asset_id.clone(), *amount));
+            }
+        }
+        
+        transactions
+    }
+    
+    /// Builds the candidate plans and returns the one with the fewest swaps
+    /// (the in-order plan wins ties)
+    pub fn minimize_swap_count(
+        sellers: Vec<(String, u128)>,
+        buyers: Vec<(String, u128)>,
+        base_asset: Option<&str>,
+    ) -> Vec<(String, String, u128)> {
+        let mut best = Self::match_in_order(sellers.clone(), buyers.clone());
+        
+        let largest_first = Self::match_largest_first(sellers.clone(), buyers.clone());
+        if largest_first.len() < best.len() {
+            best = largest_first;
+        }
+        
+        if let Some(base) = base_asset {
+            let routed = Self::route_through_base(&sellers, &buyers, base);
+            if routed.len() < best.len() {
+                best = routed;
+            }
+        }
+        
+        best
+    }
+    
+    /// Validates that allocation percentages sum to 100%
+    pub fn validate_percentages(&self) -> Result<(), &'static str> {
+        let total: u32 = self.allocations.iter().map(|a| a.target_percentage).sum();
+        
+        if total != 10000 {
+            return Err("Allocation percentages must sum to 100%");
+        }
+        
+        Ok(())
+    }
+}
+
+/// Per-asset drift numbers and rebalance status for a vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftReport {
+    /// Drift result for every allocation
+    pub drift_results: Vec<crate::events::DriftResult>,
+    
+    /// Drift threshold (in basis points) that triggers rebalancing
+    pub drift_threshold_bp: u32,
+    
+    /// Threshold actually applied; differs from `drift_threshold_bp` in adaptive mode
+    pub effective_threshold_bp: u32,
+    
+    /// Volatility the effective threshold was derived from (adaptive mode only)
+    pub volatility_bp: Option<u32>,
+    
+    /// Seconds until the next scheduled rebalance (`None` = manual only)
+    pub seconds_until_rebalance: Option<u64>,
+    
+    /// Seconds left in the minimum interval between rebalances (0 = none)
+    pub cooldown_remaining_seconds: u64,
+    
+    /// Whether the vault needs rebalancing
+    pub needs_rebalancing: bool,
+}
+
+// Contract implementation with Borsh serialization
+/// Curated starter allocation users can apply to a vault in one call
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AllocationTemplate {
+    /// Template name (e.g. "Conservative 60/30/10")
+    pub name: String,
+    
+    /// Asset targets in basis points, summing to 10000
+    pub allocations: Vec<(String, u32)>,
+    
+    /// Timestamp when the template was created
+    pub created_at: u64,
+}
+
+const STORAGE_CONTRACT_KEY: &[u8] = b"ALLOCATION";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AllocationContract {
+    allocations: std::collections::HashMap<String, AllocationSet>, // Vault ID -> AllocationSet
+    templates: std::collections::HashMap<String, AllocationTemplate>, // Template name -> Template
+    admin: String,
+    vault_owners: std::collections::HashMap<String, String>, // Vault ID -> Controlling account
+}
+
+#[l1x_sdk::contract]
+impl AllocationContract {
+    fn load() -> Self {
+        let mut state = match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        };
+        
+        // Sets without a daily rebalance limit of their own follow the governance one
+        let max_rebalances_per_day = crate::governance::max_rebalances_per_day();
+        for allocation_set in state.allocations.values_mut() {
+            allocation_set.governance_max_rebalances_per_day = max_rebalances_per_day;
+        }
+        
+        state
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(admin: String) {
+        let mut state = Self {
+            allocations: std::collections::HashMap::new(),
+            templates: std::collections::HashMap::new(),
+            admin,
+            vault_owners: std::collections::HashMap::new(),
+        };
+
+        state.save()
+    }
+    
+    /// Checks if the caller is an admin
+    fn is_admin() -> bool {
+        let state = Self::load();
+        let caller = l1x_sdk::env::caller();
+        
+        state.admin == caller
+    }
+    
+    /// Checks that `caller` controls the allocation set of a vault
+    fn check_owner(&self, vault_id: &str, caller: &str) -> Result<(), String> {
+        match self.vault_owners.get(vault_id) {
+            Some(owner) if owner == caller => Ok(()),
+            Some(_) => Err(format!("Only the owner can modify allocations for vault {}", vault_id)),
+            None => Err(format!("No owner registered for vault {}", vault_id)),
+        }
+    }
+    
+    /// Panics unless every asset not yet in the set is allowed by the asset registry
+    fn assert_new_assets_allowed<'a>(allocation_set: &AllocationSet, asset_ids: impl IntoIterator<Item = &'a String>) {
+        crate::asset_registry::check_new_assets_allowed(allocation_set, asset_ids)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+    
+    /// Panics unless the caller controls the allocation set of a vault
+    fn assert_owner(&self, vault_id: &str) {
+        if let Err(err) = self.check_owner(vault_id, &l1x_sdk::env::caller()) {
+            panic!("{}", err);
+        }
+    }
+    
+    /// Creates (or replaces) an allocation template
+    pub fn create_template(name: String, allocations_json: String) -> String {
+        validate_non_empty_string(&name, "name")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        if !Self::is_admin() {
+            panic!("Only admin can create templates");
+        }
+        
+        let mut state = Self::load();
+        
+        let allocations: Vec<(String, u32)> = serde_json::from_str(&allocations_json)
+            .unwrap_or_else(|_| panic!("Failed to parse allocations"));
+            
+        for (asset_id, pct) in &allocations {
+            validate_non_empty_string(asset_id, "asset_id")
+                .unwrap_or_else(|err| panic!("{}", err));
+            validate_basis_points(*pct, "target_percentage")
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        
+        let total: u32 = allocations.iter().map(|(_, pct)| *pct).sum();
+        if total != 10000 {
+            panic!("Template percentages must sum to 100%");
+        }
+        
+        let template = AllocationTemplate {
+            name: name.clone(),
+            allocations,
+            created_at: l1x_sdk::env::block_timestamp(),
+        };
+        
+        state.templates.insert(name.clone(), template);
+        state.save();
+        
+        format!("Template {} created", name)
+    }
+    
+    /// Lists all allocation templates
+    pub fn list_templates() -> String {
+        let state = Self::load();
+        
+        let mut templates: Vec<&AllocationTemplate> = state.templates.values().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        
+        serde_json::to_string(&templates)
+            .unwrap_or_else(|_| "Failed to serialize templates".to_string())
+    }
+    
+    /// Replaces a vault's allocations with a template
+    pub fn apply_template(vault_id: String, template_name: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let template = state.templates.get(&template_name)
+            .cloned()
+            .unwrap_or_else(|| panic!("Template not found: {}", template_name));
+            
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        Self::assert_new_assets_allowed(allocation_set, template.allocations.iter().map(|(asset_id, _)| asset_id));
+        
+        let changes = allocation_set.apply_template(&template.allocations)
+            .unwrap_or_else(|err| panic!("Failed to apply template: {}", err));
+            
+        let signer = l1x_sdk::env::caller();
+        let events: Vec<crate::events::AllocationChangedEvent> = changes.into_iter()
+            .map(|(asset_id, old_target, new_target)| {
+                crate::events::AllocationChangedEvent::new(vault_id.clone(), asset_id, old_target, new_target, signer.clone())
+            })
+            .collect();
+            
+        for event in &events {
+            allocation_set.record_allocation_change(event.clone());
+        }
+        
+        state.save();
+        
+        for event in &events {
+            event.emit();
+        }
+        
+        format!("Template {} applied to vault {}", template_name, vault_id)
+    }
+    
+    /// Creates a new allocation set for a vault
+    pub fn create_allocation_set(vault_id: String, drift_threshold_bp: u32) -> String {
+        validate_non_empty_string(&vault_id, "vault_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_basis_points(drift_threshold_bp, "drift_threshold_bp")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let mut state = Self::load();
+        
+        if state.allocations.contains_key(&vault_id) {
+            panic!("Allocation set already exists for this vault");
+        }
+        
+        // A threshold of 0 means the protocol-wide default
+        let drift_threshold_bp = crate::governance::drift_threshold_or_global(drift_threshold_bp);
+        let allocation_set = AllocationSet::new(drift_threshold_bp);
+        state.allocations.insert(vault_id.clone(), allocation_set);
+        state.vault_owners.insert(vault_id.clone(), l1x_sdk::env::caller());
+        state.save();
+        
+        format!("Allocation set created for vault {}", vault_id)
+    }
+    
+    /// Hands control of a vault's allocations to another account (e.g. a vault contract)
+    ///
+    /// Callable by the current owner, or by the admin for sets created before
+    /// owners were recorded.
+    pub fn transfer_allocation_ownership(vault_id: String, new_owner: String) -> String {
+        let mut state = Self::load();
+        
+        if !state.allocations.contains_key(&vault_id) {
+            panic!("Allocation set not found for vault {}", vault_id);
+        }
+        
+        let caller = l1x_sdk::env::caller();
+        let is_unowned = !state.vault_owners.contains_key(&vault_id);
+        if !(is_unowned && caller == state.admin) {
+            state.assert_owner(&vault_id);
+        }
+        
+        state.vault_owners.insert(vault_id.clone(), new_owner.clone());
+        state.save();
+        
+        format!("Allocation ownership for vault {} transferred to {}", vault_id, new_owner)
+    }
+    
+    /// Gets the account controlling a vault's allocations
+    pub fn get_allocation_owner(vault_id: String) -> String {
+        let state = Self::load();
+        
+        state.vault_owners.get(&vault_id)
+            .cloned()
+            .unwrap_or_else(|| panic!("No owner registered for vault {}", vault_id))
+    }
+    
+    /// Sets rebalance frequency for a vault
+    pub fn set_rebalance_frequency(vault_id: String, frequency_seconds: u64) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_rebalance_frequency(frequency_seconds);
+        state.save();
+        
+        format!("Rebalance frequency set for vault {}", vault_id)
+    }
+    
+    /// Sets the maximum number of rebalances per 24 hours for a vault (0 = unlimited)
+    pub fn set_max_rebalances_per_day(vault_id: String, max_rebalances: u32) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_max_rebalances_per_day(max_rebalances);
+        state.save();
+        
+        format!("Daily rebalance limit set for vault {}", vault_id)
+    }
+    
+    /// Sets the maximum number of assets a vault may hold (`None` = unlimited)
+    pub fn set_max_assets(vault_id: String, max: Option<u32>) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_max_assets(max)
+            .unwrap_or_else(|err| panic!("Failed to set maximum asset count: {}", err));
+        state.save();
+        
+        format!("Maximum asset count updated for vault {}", vault_id)
+    }
+    
+    /// Sets the asset every rebalance trade of a vault is routed through
+    ///
+    /// An empty `asset_id` clears the pivot so trades use direct pairs again.
+    pub fn set_pivot_asset(vault_id: String, asset_id: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        let pivot_asset = if asset_id.is_empty() { None } else { Some(asset_id) };
+        allocation_set.set_pivot_asset(pivot_asset);
+        state.save();
+        
+        format!("Pivot asset updated for vault {}", vault_id)
+    }
+    
+    /// Sets the minimum number of seconds between rebalances for a vault (0 = disabled)
+    pub fn set_min_rebalance_interval(vault_id: String, interval_seconds: u64) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_min_rebalance_interval(interval_seconds);
+        state.save();
+        
+        format!("Rebalance cooldown set for vault {}", vault_id)
+    }
+    
+    /// Opts a vault into the volatility-driven drift threshold (`None` turns it off)
+    pub fn set_adaptive_threshold(vault_id: String, config_json: Option<String>) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let config: Option<AdaptiveThreshold> = config_json.map(|json| {
+            serde_json::from_str(&json).unwrap_or_else(|e| panic!("Failed to parse adaptive threshold: {}", e))
+        });
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_adaptive_threshold(config)
+            .unwrap_or_else(|err| panic!("Failed to set adaptive threshold: {}", err));
+        allocation_set.refresh_volatility();
+        state.save();
+        
+        format!("Adaptive threshold updated for vault {}", vault_id)
+    }
+    
+    /// Limits the estimated price impact of each rebalance trade for a vault
+    ///
+    /// `slippage_model_json` is a serialized `SlippageModel`, e.g.
+    /// `{"LinearDepth":{"depth":1000000}}` or `{"Constant":30}`.
+    pub fn set_trade_impact_limit(vault_id: String, max_trade_impact_bps: u32, slippage_model_json: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let slippage_model: SlippageModel = serde_json::from_str(&slippage_model_json)
+            .unwrap_or_else(|e| panic!("Failed to parse slippage model: {}", e));
+            
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_trade_impact_limit(max_trade_impact_bps, slippage_model)
+            .unwrap_or_else(|err| panic!("Failed to set trade impact limit: {}", err));
+        state.save();
+        
+        format!("Trade impact limit updated for vault {}", vault_id)
+    }
+    
+    /// Sets the rebalance mode for a vault ("target" or "band")
+    pub fn set_rebalance_mode(vault_id: String, mode: String, band_bp: Option<u32>) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        let rebalance_mode = match mode.as_str() {
+            "target" => RebalanceMode::ToTarget,
+            
+            "band" => {
+                let band_bp = band_bp
+                    .unwrap_or_else(|| panic!("Band width required for band rebalance mode"));
+                validate_basis_points(band_bp, "band_bp")
+                    .unwrap_or_else(|err| panic!("{}", err));
+                    
+                RebalanceMode::ToBand { band_bp }
+            },
+            
+            _ => panic!("Invalid rebalance mode: {}", mode),
+        };
+        
+        allocation_set.set_rebalance_mode(rebalance_mode);
+        state.save();
+        
+        format!("Rebalance mode set for vault {}", vault_id)
+    }
+    
+    /// Sets the cash buffer asset and floor for a vault
+    pub fn set_buffer(vault_id: String, buffer_asset: Option<String>, buffer_bp: u32) -> String {
+        validate_basis_points(buffer_bp, "buffer_bp")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_buffer(buffer_asset, buffer_bp)
+            .unwrap_or_else(|err| panic!("Failed to set buffer: {}", err));
+            
+        state.save();
+        
+        format!("Buffer set for vault {}", vault_id)
+    }
+    
+    /// Adds an asset allocation to a vault
+    pub fn add_allocation(vault_id: String, asset_id: String, target_percentage: u32) -> String {
+        validate_non_empty_string(&asset_id, "asset_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_basis_points(target_percentage, "target_percentage")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        Self::assert_new_assets_allowed(allocation_set, [&asset_id]);
+        
+        let allocation = AssetAllocation::new(asset_id.clone(), target_percentage);
+        allocation_set.add_allocation(allocation)
+            .unwrap_or_else(|err| panic!("Failed to add allocation: {}", err));
+            
+        let event = crate::events::AllocationChangedEvent::new(
+            vault_id.clone(),
+            asset_id.clone(),
+            None,
+            Some(target_percentage),
+            l1x_sdk::env::caller(),
+        );
+        allocation_set.record_allocation_change(event.clone());
+        
+        state.save();
+        
+        event.emit();
+        
+        format!("Allocation added for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Updates an asset allocation in a vault
+    pub fn update_allocation(vault_id: String, asset_id: String, target_percentage: u32) -> String {
+        validate_basis_points(target_percentage, "target_percentage")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        let old_target = allocation_set.get_allocation(&asset_id).map(|a| a.target_percentage);
+        
+        allocation_set.update_allocation(&asset_id, target_percentage)
+            .unwrap_or_else(|err| panic!("Failed to update allocation: {}", err));
+            
+        let event = crate::events::AllocationChangedEvent::new(
+            vault_id.clone(),
+            asset_id.clone(),
+            old_target,
+            Some(target_percentage),
+            l1x_sdk::env::caller(),
+        );
+        allocation_set.record_allocation_change(event.clone());
+        
+        state.save();
+        
+        event.emit();
+        
+        format!("Allocation updated for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Sets min/max percentage bounds for an asset allocation in a vault
+    pub fn set_allocation_bounds(vault_id: String, asset_id: String, min_percentage: Option<u32>, max_percentage: Option<u32>) -> String {
+        for (bound, field) in [(min_percentage, "min_percentage"), (max_percentage, "max_percentage")] {
+            if let Some(bound) = bound {
+                validate_basis_points(bound, field)
+                    .unwrap_or_else(|err| panic!("{}", err));
+            }
+        }
+        
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_allocation_bounds(&asset_id, min_percentage, max_percentage)
+            .unwrap_or_else(|err| panic!("Failed to set allocation bounds: {}", err));
+            
+        state.save();
+        
+        format!("Allocation bounds set for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Locks an asset allocation in a vault, excluding it from rebalancing
+    pub fn lock_allocation(vault_id: String, asset_id: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.lock_allocation(&asset_id)
+            .unwrap_or_else(|err| panic!("Failed to lock allocation: {}", err));
+            
+        state.save();
+        
+        format!("Allocation locked for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Unlocks an asset allocation in a vault
+    pub fn unlock_allocation(vault_id: String, asset_id: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.unlock_allocation(&asset_id)
+            .unwrap_or_else(|err| panic!("Failed to unlock allocation: {}", err));
+            
+        state.save();
+        
+        format!("Allocation unlocked for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Assigns an asset allocation in a vault to a sector
+    pub fn set_allocation_sector(vault_id: String, asset_id: String, sector: Option<String>) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        allocation_set.set_allocation_sector(&asset_id, sector)
+            .unwrap_or_else(|err| panic!("Failed to set allocation sector: {}", err));
+            
+        state.save();
+        
+        format!("Sector set for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Gets the target percentage (in basis points) held in each sector of a vault
+    pub fn get_sector_breakdown(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let allocation_set = state.allocations.get(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        serde_json::to_string(&allocation_set.sector_breakdown())
+            .unwrap_or_else(|_| "Failed to serialize sector breakdown".to_string())
+    }
+    
+    /// Removes an asset allocation from a vault
+    ///
+    /// `redistribution` is "proportional", "asset" (with `target_asset`) or "none".
+    /// `balance` is the vault's current holding of the asset.
+    pub fn remove_allocation(vault_id: String, asset_id: String, redistribution: String, target_asset: Option<String>, balance: u128, force: bool) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        let redistribution = match redistribution.as_str() {
+            "proportional" => Redistribution::Proportional,
+            
+            "asset" => {
+                let target_asset = target_asset
+                    .unwrap_or_else(|| panic!("Target asset required for asset redistribution"));
+                    
+                Redistribution::ToAsset(target_asset)
+            },
+            
+            "none" => Redistribution::None,
+            
+            _ => panic!("Invalid redistribution: {}", redistribution),
+        };
+        
+        let changes = allocation_set.remove_allocation(&asset_id, redistribution, balance, force)
+            .unwrap_or_else(|err| panic!("Failed to remove allocation: {}", err));
+            
+        let signer = l1x_sdk::env::caller();
+        let events: Vec<crate::events::AllocationChangedEvent> = changes.into_iter()
+            .map(|(changed_asset, old_target, new_target)| {
+                crate::events::AllocationChangedEvent::new(vault_id.clone(), changed_asset, old_target, new_target, signer.clone())
+            })
+            .collect();
+            
+        for event in &events {
+            allocation_set.record_allocation_change(event.clone());
+        }
+        
+        state.save();
+        
+        for event in &events {
+            event.emit();
+        }
+        
+        format!("Allocation removed for {} in vault {}", asset_id, vault_id)
+    }
+    
+    /// Replaces all asset allocations for a vault
+    pub fn set_allocations(vault_id: String, allocations_json: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        // Parse targets from JSON
+        let targets: Vec<(String, u32)> = serde_json::from_str(&allocations_json)
+            .unwrap_or_else(|_| panic!("Failed to parse allocations"));
+            
+        Self::assert_new_assets_allowed(allocation_set, targets.iter().map(|(asset_id, _)| asset_id));
+        
+        let changes = allocation_set.set_allocations(&targets)
+            .unwrap_or_else(|err| panic!("Failed to set allocations: {}", err));
+            
+        let signer = l1x_sdk::env::caller();
+        let events: Vec<crate::events::AllocationChangedEvent> = changes.into_iter()
+            .map(|(asset_id, old_target, new_target)| {
+                crate::events::AllocationChangedEvent::new(vault_id.clone(), asset_id, old_target, new_target, signer.clone())
+            })
+            .collect();
+            
+        for event in &events {
+            allocation_set.record_allocation_change(event.clone());
+        }
+        
+        state.save();
+        
+        for event in &events {
+            event.emit();
+        }
+        
+        format!("{} allocation changes applied to vault {}", events.len(), vault_id)
+    }
+    
+    /// Gets the allocation change history for a vault
+    pub fn get_allocation_history(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let allocation_set = state.allocations.get(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        serde_json::to_string(&allocation_set.allocation_history)
+            .unwrap_or_else(|_| "Failed to serialize allocation history".to_string())
+    }
+    
+    /// Gets all allocations for a vault
+    pub fn get_allocations(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let allocation_set = state.allocations.get(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        // Return allocations as JSON
+        serde_json::to_string(&allocation_set.allocations)
+            .unwrap_or_else(|_| "Failed to serialize allocations".to_string())
+    }
+    
+    /// Gets allocation set information for a vault
+    pub fn get_allocation_set(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let allocation_set = state.allocations.get(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        // Return allocation set as JSON
+        serde_json::to_string(allocation_set)
+            .unwrap_or_else(|_| "Failed to serialize allocation set".to_string())
+    }
+    
+    /// Gets the per-asset drift report for a vault, revalued at `prices_json` if supplied
+    pub fn get_drift_report(vault_id: String, prices_json: Option<String>) -> String {
+        let state = Self::load();
+        
+        let mut allocation_set = state.allocations.get(&vault_id)
+            .cloned()
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+        allocation_set.refresh_volatility();
+        
+        let prices: Option<Vec<(String, u128)>> = prices_json.map(|json| {
+            serde_json::from_str(&json).unwrap_or_else(|_| panic!("Failed to parse prices"))
+        });
+        
+        let report = allocation_set.drift_report(prices.as_deref(), l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("Failed to build drift report: {}", err));
+            
+        serde_json::to_string(&report)
+            .unwrap_or_else(|_| "Failed to serialize drift report".to_string())
+    }
+    
+    /// Checks if a vault needs rebalancing
+    pub fn needs_rebalancing(vault_id: String) -> bool {
+        let state = Self::load();
+        
+        let mut allocation_set = state.allocations.get(&vault_id)
+            .cloned()
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+        allocation_set.refresh_volatility();
+        
+        allocation_set.needs_rebalancing()
+    }
+    
+    /// Records a rebalance operation for a vault
+    pub fn record_rebalance(vault_id: String, prices_json: String) -> String {
+        let mut state = Self::load();
+        state.assert_owner(&vault_id);
+        
+        let allocation_set = state.allocations.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
+            
+        // Parse prices from JSON
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|_| panic!("Failed to parse prices"));
+            
+        allocation_set.record_rebalance(&prices);
+        state.save();
+        
+        format!("Rebalance recorded for vault {}", vault_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_asset_allocation() {
+        let mut allocation = AssetAllocation::new("BTC".to_string(), 6000);
+        
+        assert_eq!(allocation.asset_id, "BTC");
+        assert_eq!(allocation.target_percentage, 6000);
+        assert_eq!(allocation.current_percentage, 6000);
+        
+        // Update target percentage
+        allocation.update_target_percentage(7000);
+        assert_eq!(allocation.target_percentage, 7000);
+        assert_eq!(allocation.current_percentage, 6000);
+        
+        // Calculate drift
+        assert_eq!(allocation.drift(), 1000);
+        
+        // Record rebalance
+        allocation.record_rebalance(Some(50000));
+        assert_eq!(allocation.current_percentage, 7000);
+        assert_eq!(allocation.last_price, Some(50000));
+    }
+    
+    #[test]
+    fn test_allocation_set() {
+        let mut set = AllocationSet::new(300);
+        
+        // Add BTC allocation
+        let btc = AssetAllocation::new("BTC".to_string(), 6000);
+        set.add_allocation(btc).unwrap();
+        
+        // Add ETH allocation
+        let eth = AssetAllocation::new("ETH".to_string(), 4000);
+        set.add_allocation(eth).unwrap();
+        
+        // Validate percentages
+        assert!(set.validate_percentages().is_ok());
+        
+        // Try to add duplicate asset
+        let btc_dup = AssetAllocation::new("BTC".to_string(), 5000);
+        assert!(set.add_allocation(btc_dup).is_err());
+        
+        // Update an allocation
+        set.update_allocation("BTC", 5000).unwrap();
+        
+        // Validate that percentages no longer sum to 100%
+        assert!(set.validate_percentages().is_err());
+        
+        // Fix percentages
+        set.update_allocation("ETH", 5000).unwrap();
+        assert!(set.validate_percentages().is_ok());
+        
+        // Check if rebalancing is needed (no drift yet)
+        assert!(!set.needs_rebalancing());
+        
+        // Update current percentage to create drift
+        let btc_allocation = set.allocations.iter_mut()
+            .find(|a| a.asset_id == "BTC")
+            .unwrap();
+            
+        btc_allocation.update_current_percentage(6000);
+        
+        // Now we should need rebalancing (1000 bp drift > 300 bp threshold)
+        assert!(set.needs_rebalancing());
+        
+        // Record a rebalance
+        let prices = vec![
+            ("BTC".to_string(), 50000),
+            ("ETH".to_string(), 3000),
+        ];
+        
+        set.record_rebalance(&prices);
+        
+        // After rebalance, we shouldn't need another one
+        assert!(!set.needs_rebalancing());
+        
+        // Set a time-based rebalance frequency
+        set.set_rebalance_frequency(86400); // 1 day
+        
+        // Fast-forward 2 days
+        let current_time = l1x_sdk::env::block_timestamp();
+        l1x_sdk::env::set_block_timestamp(current_time + 172800);
+        
+        // Now we should need time-based rebalancing
+        assert!(set.needs_rebalancing());
+    }
+    
+    #[test]
+    fn test_allocation_bounds() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 8900)).unwrap();
+        set.add_allocation(AssetAllocation::new("USDC".to_string(), 1100)).unwrap();
+        
+        // Target must lie within the bounds
+        assert!(set.set_allocation_bounds("USDC", Some(2000), None).is_err());
+        assert!(set.set_allocation_bounds("BTC", Some(9500), Some(8000)).is_err());
+        
+        set.set_allocation_bounds("USDC", Some(1000), None).unwrap();
+        set.set_allocation_bounds("BTC", None, Some(9200)).unwrap();
+        
+        // Updating a target beyond its bound is rejected
+        assert!(set.update_allocation("BTC", 9500).is_err());
+        
+        // Dropping below the USDC floor triggers action even within the drift threshold
+        set.allocations[1].update_current_percentage(900);
+        set.allocations[0].update_current_percentage(9100);
+        assert!(set.needs_rebalancing());
+        
+        let result = set.allocations[1].create_drift_result(set.drift_threshold_bp);
+        assert!(result.bound_breached);
+        assert!(!result.exceeds_threshold);
+        assert_eq!(result.min_percentage, Some(1000));
+        
+        // Infeasible bounds produce an error naming the conflicting assets
+        set.allocations[0].min_percentage = Some(6000);
+        set.allocations[1].min_percentage = Some(5000);
+        let err = set.calculate_rebalance_transactions(
+            &[("BTC".to_string(), 5000), ("USDC".to_string(), 5000)],
+            10000,
+        ).unwrap_err();
+        assert!(err.contains("BTC"));
+        assert!(err.contains("USDC"));
+    }
+    
+    #[test]
+    fn test_locked_allocations() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 4000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+        set.add_allocation(AssetAllocation::new("SOL".to_string(), 3000)).unwrap();
+        
+        // SOL is staked and overweight (50% vs 30%)
+        set.lock_allocation("SOL").unwrap();
+        set.allocations[2].update_current_percentage(5000);
+        
+        let current_values = vec![
+            ("BTC".to_string(), 3000),
+            ("ETH".to_string(), 2000),
+            ("SOL".to_string(), 5000),
+        ];
+        
+        let transactions = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        
+        // SOL is never sold or bought; BTC/ETH split the free 5000 as 4:3 (2857/2142)
+        assert!(transactions.iter().all(|(s, t, _)| s != "SOL" && t != "SOL"));
+        assert_eq!(transactions, vec![("BTC".to_string(), "ETH".to_string(), 142)]);
+        
+        // Drift events still report the locked asset
+        let result = set.allocations[2].create_drift_result(set.drift_threshold_bp);
+        assert!(result.locked);
+        assert!(result.exceeds_threshold);
+        
+        set.unlock_allocation("SOL").unwrap();
+        assert!(!set.is_locked("SOL"));
+    }
+    
+    #[test]
+    fn test_band_rebalance_mode() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 4000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+        set.add_allocation(AssetAllocation::new("SOL".to_string(), 3000)).unwrap();
+        
+        let current_values = vec![
+            ("BTC".to_string(), 5000),
+            ("ETH".to_string(), 2200),
+            ("SOL".to_string(), 2800),
+        ];
+        
+        let to_target = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        let target_total: u128 = to_target.iter().map(|(_, _, amount)| *amount).sum();
+        
+        set.set_rebalance_mode(RebalanceMode::ToBand { band_bp: 500 });
+        let to_band = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        let band_total: u128 = to_band.iter().map(|(_, _, amount)| *amount).sum();
+        
+        // BTC only sold down to 45%, ETH only bought up to 25%, SOL left within its band
+        assert_eq!(target_total, 1000);
+        assert_eq!(to_target.len(), 2);
+        assert_eq!(to_band, vec![("BTC".to_string(), "ETH".to_string(), 300)]);
+        assert!(band_total < target_total);
+        
+        // The mode is part of the serialized allocation set
+        let json = serde_json::to_string(&set).unwrap();
+        assert!(json.contains("ToBand"));
+    }
+    
+    #[test]
+    fn test_swap_count_minimization() {
+        // 10-asset portfolio, 10% each
+        let assets = ["A", "B", "C", "D", "E", "F", "G", "H", "I", "J"];
+        let mut set = AllocationSet::new(100);
+        for asset in &assets {
+            set.add_allocation(AssetAllocation::new(asset.to_string(), 1000)).unwrap();
+        }
+        
+        // Overweight: A +100, B +300, C +200, D +400; underweight: E..J
+        let current_values: Vec<(String, u128)> = vec![
+            1100, 1300, 1200, 1400, 600, 900, 800, 700, 1000, 1000,
+        ].into_iter().zip(assets.iter()).map(|(v, a)| (a.to_string(), v)).collect();
+        
+        let sellers = vec![
+            ("A".to_string(), 100), ("B".to_string(), 300),
+            ("C".to_string(), 200), ("D".to_string(), 400),
+        ];
+        let buyers = vec![
+            ("E".to_string(), 400), ("F".to_string(), 100),
+            ("G".to_string(), 200), ("H".to_string(), 300),
+        ];
+        
+        let in_order = AllocationSet::match_in_order(sellers.clone(), buyers.clone());
+        let optimized = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        
+        assert_eq!(in_order.len(), 6);
+        assert_eq!(optimized.len(), 4);
+        
+        // Same total value moved either way
+        let in_order_total: u128 = in_order.iter().map(|(_, _, amount)| *amount).sum();
+        let optimized_total: u128 = optimized.iter().map(|(_, _, amount)| *amount).sum();
+        assert_eq!(in_order_total, optimized_total);
+        
+        // A routed plan that ties the direct plan is not preferred
+        let plan = AllocationSet::minimize_swap_count(
+            vec![("A".to_string(), 300)],
+            vec![("USDC".to_string(), 100), ("B".to_string(), 100), ("C".to_string(), 100)],
+            Some("USDC"),
+        );
+        assert_eq!(plan.len(), 3);
+        assert!(plan.iter().all(|(from, _, _)| from == "A"));
+    }
+    
+    #[test]
+    fn test_update_current_from_values() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 3334)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3333)).unwrap();
+        set.add_allocation(AssetAllocation::new("SOL".to_string(), 3333)).unwrap();
+        
+        // Three equal positions: 3333.33 bp each must still sum to 10000
+        let balances = vec![
+            ("BTC".to_string(), 1),
+            ("ETH".to_string(), 20),
+            ("SOL".to_string(), 400),
+        ];
+        let prices = vec![
+            ("BTC".to_string(), 60_000_00000000),
+            ("ETH".to_string(), 3_000_00000000),
+            ("SOL".to_string(), 150_00000000),
+        ];
+        
+        let total = set.update_current_from_values(&balances, &prices).unwrap();
+        assert_eq!(total, 180_000_00000000);
+        
+        let sum: u32 = set.allocations.iter().map(|a| a.current_percentage).sum();
+        assert_eq!(sum, 10000);
+        assert_eq!(set.allocations[0].current_percentage, 3334);
+        assert_eq!(set.allocations[1].current_percentage, 3333);
+        
+        // Uneven split with several fractional remainders
+        let balances = vec![
+            ("BTC".to_string(), 7),
+            ("ETH".to_string(), 11),
+            ("SOL".to_string(), 13),
+        ];
+        let prices = vec![
+            ("BTC".to_string(), 1),
+            ("ETH".to_string(), 1),
+            ("SOL".to_string(), 1),
+        ];
+        set.update_current_from_values(&balances, &prices).unwrap();
+        
+        let sum: u32 = set.allocations.iter().map(|a| a.current_percentage).sum();
+        assert_eq!(sum, 10000);
+        
+        // A held asset without a price is an error
+        let missing = vec![("BTC".to_string(), 1)];
+        assert!(set.update_current_from_values(&balances, &missing).is_err());
+    }
+    
+    #[test]
+    fn test_allocation_history() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        
+        let targets = vec![("BTC".to_string(), 5000), ("SOL".to_string(), 5000)];
+        let mut changes = set.set_allocations(&targets).unwrap();
+        changes.sort();
+        
+        assert_eq!(changes, vec![
+            ("BTC".to_string(), Some(6000), Some(5000)),
+            ("ETH".to_string(), Some(4000), Some(0)),
+            ("SOL".to_string(), None, Some(5000)),
+        ]);
+        
+        // The omitted asset is still held, now targeted for a full sale
+        let eth = set.get_allocation("ETH").unwrap();
+        assert_eq!(eth.target_percentage, 0);
+        assert_eq!(eth.current_percentage, 4000);
+        assert_eq!(set.get_allocation("SOL").unwrap().current_percentage, 0);
+        
+        // Targets must still sum to 100%
+        assert!(set.set_allocations(&[("BTC".to_string(), 9000)]).is_err());
+        
+        // History keeps only the most recent entries
+        for i in 0..(MAX_ALLOCATION_HISTORY + 5) {
+            let event = crate::events::AllocationChangedEvent::new(
+                "vault-1".to_string(),
+                "BTC".to_string(),
+                Some(i as u32),
+                Some(i as u32 + 1),
+                "owner-1".to_string(),
+            );
+            set.record_allocation_change(event);
+        }
+        
+        assert_eq!(set.allocation_history.len(), MAX_ALLOCATION_HISTORY);
+        assert_eq!(set.allocation_history[0].old_target_bps, Some(5));
+        assert_eq!(set.allocation_history[0].signer, "owner-1");
+        assert_eq!(
+            set.allocation_history[0].event_type,
+            crate::events::AllocationEventType::TargetChanged
+        );
+    }
+    
+    #[test]
+    fn test_remove_allocation_redistribution() {
+        let build = || {
+            let mut set = AllocationSet::new(300);
+            set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+            set.add_allocation(AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+            set.add_allocation(AssetAllocation::new("SOL".to_string(), 2000)).unwrap();
+            set
+        };
+        
+        // Proportional: 5000/3000 scale up to fill SOL's 2000
+        let mut set = build();
+        let changes = set.remove_allocation("SOL", Redistribution::Proportional, 0, false).unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(set.get_allocation("BTC").unwrap().target_percentage, 6250);
+        assert_eq!(set.get_allocation("ETH").unwrap().target_percentage, 3750);
+        assert!(set.validate_percentages().is_ok());
+        
+        // Rounded shares still sum to exactly 100%
+        let mut set = build();
+        set.remove_allocation("ETH", Redistribution::Proportional, 0, false).unwrap();
+        assert!(set.validate_percentages().is_ok());
+        
+        // ToAsset: all freed weight lands on one asset
+        let mut set = build();
+        set.remove_allocation("SOL", Redistribution::ToAsset("ETH".to_string()), 0, false).unwrap();
+        assert_eq!(set.get_allocation("ETH").unwrap().target_percentage, 5000);
+        assert_eq!(set.get_allocation("BTC").unwrap().target_percentage, 5000);
+        
+        // None: set is invalid until the targets are fixed
+        let mut set = build();
+        set.remove_allocation("SOL", Redistribution::None, 0, false).unwrap();
+        assert!(set.invalid_targets);
+        assert!(set.calculate_rebalance_transactions(&[], 10000).is_err());
+        set.update_allocation("ETH", 5000).unwrap();
+        assert!(!set.invalid_targets);
+        
+        // A residual position requires force
+        let mut set = build();
+        assert!(set.remove_allocation("SOL", Redistribution::Proportional, 5, false).is_err());
+        assert_eq!(set.allocations.len(), 3);
+        assert!(set.remove_allocation("SOL", Redistribution::Proportional, 5, true).is_ok());
+    }
+    
+    #[test]
+    fn test_apply_template() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("DOGE".to_string(), 5000)).unwrap();
+        set.allocations[0].update_current_percentage(5500);
+        set.allocations[1].update_current_percentage(4500);
+        set.allocations[0].set_bounds(Some(4000), Some(7000)).unwrap();
+        set.allocations[0].locked = true;
+        set.allocations[0].sector = Some("Layer1".to_string());
+        
+        let conservative = vec![
+            ("BTC".to_string(), 6000),
+            ("ETH".to_string(), 3000),
+            ("USDC".to_string(), 1000),
+        ];
+        
+        let mut changes = set.apply_template(&conservative).unwrap();
+        changes.sort();
+        assert_eq!(changes, vec![
+            ("BTC".to_string(), Some(5000), Some(6000)),
+            ("DOGE".to_string(), Some(5000), Some(0)),
+            ("ETH".to_string(), None, Some(3000)),
+            ("USDC".to_string(), None, Some(1000)),
+        ]);
+        
+        // Held assets keep their position and settings, new ones start empty
+        let btc = set.get_allocation("BTC").unwrap();
+        assert_eq!(btc.current_percentage, 5500);
+        assert_eq!((btc.min_percentage, btc.max_percentage), (Some(4000), Some(7000)));
+        assert!(btc.locked);
+        assert_eq!(btc.sector.as_deref(), Some("Layer1"));
+        assert_eq!(set.get_allocation("ETH").unwrap().current_percentage, 0);
+        assert_eq!(set.get_allocation("USDC").unwrap().current_percentage, 0);
+        
+        // Assets left out of the template are sold down rather than dropped
+        let doge = set.get_allocation("DOGE").unwrap();
+        assert_eq!(doge.target_percentage, 0);
+        assert_eq!(doge.current_percentage, 4500);
+        assert!(set.validate_percentages().is_ok());
+        
+        // Templates must respect the bounds of held assets
+        let too_little_btc = vec![("BTC".to_string(), 3000), ("ETH".to_string(), 7000)];
+        assert_eq!(set.apply_template(&too_little_btc), Err("Target percentage outside of bounds"));
+        let without_btc = vec![("ETH".to_string(), 10000)];
+        assert_eq!(set.apply_template(&without_btc), Err("Target percentage outside of bounds"));
+        
+        // Templates must sum to 100%
+        assert!(set.apply_template(&[("BTC".to_string(), 6000)]).is_err());
+    }
+    
+    #[test]
+    fn test_cash_buffer() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3500)).unwrap();
+        set.add_allocation(AssetAllocation::new("USDC".to_string(), 500)).unwrap();
+        set.set_buffer(Some("USDC".to_string()), 500).unwrap();
+        
+        // Deposits top the buffer up to its floor before anything else
+        let current_values = vec![
+            ("BTC".to_string(), 6000),
+            ("ETH".to_string(), 3500),
+            ("USDC".to_string(), 0),
+        ];
+        let purchases = set.deposit_and_allocate(500, &current_values);
+        assert_eq!(purchases, vec![("USDC".to_string(), 500)]);
+        
+        let purchases = set.deposit_and_allocate(1500, &current_values);
+        assert_eq!(purchases[0], ("USDC".to_string(), 550));
+        let total: u128 = purchases.iter().map(|(_, amount)| *amount).sum();
+        assert_eq!(total, 1500);
+        
+        // The buffer is never sold below its floor
+        set.allocations[0].update_current_percentage(5000);
+        set.allocations[2].update_current_percentage(1500);
+        let current_values = vec![
+            ("BTC".to_string(), 5000),
+            ("ETH".to_string(), 3500),
+            ("USDC".to_string(), 1500),
+        ];
+        let transactions = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        assert_eq!(transactions, vec![("USDC".to_string(), "BTC".to_string(), 1000)]);
+        
+        // A breached buffer triggers action even within the drift threshold
+        set.allocations[0].update_current_percentage(6100);
+        set.allocations[2].update_current_percentage(500);
+        assert!(!set.buffer_breached());
+        assert!(!set.needs_rebalancing());
+        
+        set.allocations[2].update_current_percentage(400);
+        assert!(set.buffer_breached());
+        assert!(set.needs_rebalancing());
+    }
+    
+    #[test]
+    fn test_indexed_lookup_scales() {
+        let mut set = AllocationSet::new(300);
+        for i in 0..100 {
+            set.add_allocation(AssetAllocation::new(format!("ASSET{}", i), 100)).unwrap();
+        }
+        
+        // Half the assets overweight, half underweight
+        let current_values: Vec<(String, u128)> = (0..100)
+            .map(|i| (format!("ASSET{}", i), if i % 2 == 0 { 120 } else { 80 }))
+            .collect();
+            
+        // Simulate a fresh load from storage, where the index is not serialized
+        let set: AllocationSet = serde_json::from_str(&serde_json::to_string(&set).unwrap()).unwrap();
+        
+        FALLBACK_SCANS.with(|c| c.set(0));
+        INDEX_BUILDS.with(|c| c.set(0));
+        
+        let transactions = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        assert_eq!(transactions.len(), 50);
+        
+        for i in 0..100 {
+            assert!(set.get_allocation(&format!("ASSET{}", i)).is_some());
+        }
+        assert!(set.get_allocation("MISSING").is_none());
+        
+        // Every lookup went through a single index build, never a linear scan
+        assert_eq!(INDEX_BUILDS.with(|c| c.get()), 1);
+        assert_eq!(FALLBACK_SCANS.with(|c| c.get()), 0);
+        
+        // Direct edits to `allocations` are still found via the fallback scan
+        let mut set = set;
+        set.allocations.push(AssetAllocation::new("EXTRA".to_string(), 0));
+        assert!(set.get_allocation("EXTRA").is_some());
+        assert_eq!(FALLBACK_SCANS.with(|c| c.get()), 1);
+    }
+    
+    #[test]
+    fn test_cashflow_rebalancing() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        
+        // BTC 70% / ETH 30%, 1000 bp of drift each
+        let current_values = vec![
+            ("BTC".to_string(), 7000),
+            ("ETH".to_string(), 3000),
+        ];
+        
+        // Pro-rata deposits leave the drift in place
+        let pro_rata = set.deposit_and_allocate(2500, &current_values);
+        assert_eq!(pro_rata, vec![("BTC".to_string(), 1500), ("ETH".to_string(), 1000)]);
+        assert!(set.drift_after_purchases(&current_values, &pro_rata) > set.drift_threshold_bp);
+        
+        // Cash-flow deposits go to the underweight asset and cure the drift
+        set.set_cashflow_rebalancing(true);
+        let purchases = set.deposit_and_allocate(2500, &current_values);
+        assert_eq!(purchases, vec![("BTC".to_string(), 500), ("ETH".to_string(), 2000)]);
+        assert_eq!(set.drift_after_purchases(&current_values, &purchases), 0);
+        
+        // No sell-side transactions are needed afterwards
+        let after = vec![
+            ("BTC".to_string(), 7500),
+            ("ETH".to_string(), 5000),
+        ];
+        assert!(set.calculate_rebalance_transactions(&after, 12500).unwrap().is_empty());
+        
+        // A small deposit is steered entirely to the underweight asset
+        let purchases = set.deposit_and_allocate(1000, &current_values);
+        assert_eq!(purchases, vec![("ETH".to_string(), 1000)]);
     }
-
-    fn save(&mut self) {
-        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    
+    #[test]
+    fn test_sector_breakdown() {
+        let mut set = AllocationSet::new(500);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 3000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 2500)).unwrap();
+        set.add_allocation(AssetAllocation::new("USDC".to_string(), 2000)).unwrap();
+        set.add_allocation(AssetAllocation::new("UNI".to_string(), 1500)).unwrap();
+        set.add_allocation(AssetAllocation::new("AAVE".to_string(), 1000)).unwrap();
+        
+        set.set_allocation_sector("BTC", Some("Layer1".to_string())).unwrap();
+        set.set_allocation_sector("ETH", Some("Layer1".to_string())).unwrap();
+        set.set_allocation_sector("USDC", Some("Stablecoin".to_string())).unwrap();
+        set.set_allocation_sector("UNI", Some("DeFi".to_string())).unwrap();
+        set.set_allocation_sector("AAVE", Some("DeFi".to_string())).unwrap();
+        
+        let breakdown = set.sector_breakdown();
+        assert_eq!(breakdown.len(), 3);
+        assert_eq!(breakdown["Layer1"], 5500);
+        assert_eq!(breakdown["Stablecoin"], 2000);
+        assert_eq!(breakdown["DeFi"], 2500);
+        assert_eq!(breakdown.values().sum::<u32>(), 10000);
+        assert_eq!(set.max_sector_concentration(), 5500);
+        
+        // Unassigned assets are grouped together
+        set.set_allocation_sector("USDC", None).unwrap();
+        assert_eq!(set.sector_breakdown()[UNCLASSIFIED_SECTOR], 2000);
+        assert!(set.set_allocation_sector("DOGE", None).is_err());
     }
-
-    pub fn new() {
-        let mut state = Self {
+    
+    #[test]
+    fn test_drift_report() {
+        let mut set = AllocationSet::new(500);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        set.set_rebalance_frequency(86_400);
+        set.record_rebalance(&[
+            ("BTC".to_string(), 50_000_00000000),
+            ("ETH".to_string(), 2_500_00000000),
+        ]);
+        set.last_rebalance = 1_000;
+        
+        // Stored percentages are on target
+        let report = set.drift_report(None, 2_000).unwrap();
+        assert_eq!(report.drift_results.len(), 2);
+        assert!(report.drift_results.iter().all(|r| r.drift_amount == 0));
+        assert_eq!(report.drift_threshold_bp, 500);
+        assert_eq!(report.seconds_until_rebalance, Some(85_400));
+        assert!(!report.needs_rebalancing);
+        
+        // BTC doubles: 2/3 vs 1/3 of the portfolio
+        let prices = vec![
+            ("BTC".to_string(), 100_000_00000000),
+            ("ETH".to_string(), 2_500_00000000),
+        ];
+        let report = set.drift_report(Some(&prices), 2_000).unwrap();
+        assert_eq!(report.drift_results[0].current_percentage, 6667);
+        assert_eq!(report.drift_results[1].current_percentage, 3333);
+        assert_eq!(report.drift_results[0].drift_amount, 1667);
+        assert!(report.drift_results[0].exceeds_threshold);
+        assert!(report.needs_rebalancing);
+        
+        // The stored percentages are left untouched
+        assert_eq!(set.allocations[0].current_percentage, 5000);
+        
+        // A due schedule alone triggers rebalancing
+        let report = set.drift_report(None, 90_000).unwrap();
+        assert_eq!(report.seconds_until_rebalance, Some(0));
+        assert!(report.needs_rebalancing);
+    }
+    
+    #[test]
+    fn test_allocation_owner_check() {
+        let mut state = AllocationContract {
             allocations: std::collections::HashMap::new(),
+            templates: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            vault_owners: std::collections::HashMap::new(),
         };
-
-        state.save()
+        state.allocations.insert("vault-1".to_string(), AllocationSet::new(500));
+        state.vault_owners.insert("vault-1".to_string(), "alice".to_string());
+        
+        assert!(state.check_owner("vault-1", "alice").is_ok());
+        
+        let err = state.check_owner("vault-1", "mallory").unwrap_err();
+        assert_eq!(err, "Only the owner can modify allocations for vault vault-1");
+        
+        // Unregistered vaults are rejected, even for the admin
+        assert!(state.check_owner("vault-2", "admin").is_err());
+        
+        // After a transfer only the new controller passes
+        state.vault_owners.insert("vault-1".to_string(), "custodial-vault".to_string());
+        assert!(state.check_owner("vault-1", "alice").is_err());
+        assert!(state.check_owner("vault-1", "custodial-vault").is_ok());
     }
     
-    /// Creates a new allocation set for a vault
-    pub fn create_allocation_set(vault_id: String, drift_threshold_bp: u32) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_rebalance_plan_is_deterministic() {
+        let assets = ["A", "B", "C", "D", "E", "F", "G", "H"];
+        let targets = [1500, 1500, 1000, 1000, 1500, 1000, 1500, 1000];
+        let values: [u128; 8] = [2000, 1200, 1300, 700, 1500, 600, 1900, 800];
         
-        if state.allocations.contains_key(&vault_id) {
-            panic!("Allocation set already exists for this vault");
-        }
+        let build = |order: &[usize]| {
+            let mut set = AllocationSet::new(100);
+            for &i in order {
+                set.add_allocation(AssetAllocation::new(assets[i].to_string(), targets[i])).unwrap();
+            }
+            let current_values: Vec<(String, u128)> = order.iter()
+                .map(|&i| (assets[i].to_string(), values[i]))
+                .collect();
+            let transactions = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+            serde_json::to_vec(&transactions).unwrap()
+        };
         
-        let allocation_set = AllocationSet::new(drift_threshold_bp);
-        state.allocations.insert(vault_id.clone(), allocation_set);
-        state.save();
+        let mut order: Vec<usize> = (0..assets.len()).collect();
+        let expected = build(&order);
+        assert!(expected.len() > 2);
         
-        format!("Allocation set created for vault {}", vault_id)
+        // Fisher-Yates shuffle driven by a fixed-seed LCG
+        let mut seed: u64 = 0x5eed;
+        for _ in 0..100 {
+            for i in (1..order.len()).rev() {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let j = ((seed >> 33) as usize) % (i + 1);
+                order.swap(i, j);
+            }
+            
+            assert_eq!(build(&order), expected, "plan changed for order {:?}", order);
+        }
     }
     
-    /// Sets rebalance frequency for a vault
-    pub fn set_rebalance_frequency(vault_id: String, frequency_seconds: u64) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_daily_rebalance_limit() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        set.set_max_rebalances_per_day(2);
         
-        let allocation_set = state.allocations.get_mut(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        allocation_set.set_rebalance_frequency(frequency_seconds);
-        state.save();
+        // Drift stays above the threshold throughout
+        set.allocations[0].update_current_percentage(7000);
+        set.allocations[1].update_current_percentage(3000);
         
-        format!("Rebalance frequency set for vault {}", vault_id)
+        let start = 1_000_000;
+        let mut completed = 0;
+        for attempt in 0..3u64 {
+            let now = start + attempt * 3600;
+            if set.needs_rebalancing_at(now) {
+                set.count_rebalance(now);
+                completed += 1;
+            }
+        }
+        
+        // First two succeed, the third in the same window is blocked
+        assert_eq!(completed, 2);
+        assert_eq!(set.rebalances_in_window, 2);
+        assert!(set.rate_limited_at(start + 3 * 3600));
+        assert!(!set.drift_report(None, start + 3 * 3600).unwrap().needs_rebalancing);
+        
+        // The counter resets once the window expires
+        let next_window = start + RATE_LIMIT_WINDOW_SECONDS;
+        assert!(set.needs_rebalancing_at(next_window));
+        set.count_rebalance(next_window);
+        assert_eq!(set.window_start, next_window);
+        assert_eq!(set.rebalances_in_window, 1);
+        
+        // No limit when unset
+        set.set_max_rebalances_per_day(0);
+        set.count_rebalance(next_window + 1);
+        set.count_rebalance(next_window + 2);
+        assert!(set.needs_rebalancing_at(next_window + 3));
+        
+        // ...unless governance sets a protocol-wide one
+        set.governance_max_rebalances_per_day = 3;
+        assert_eq!(set.daily_rebalance_limit(), 3);
+        assert!(set.rate_limited_at(next_window + 3));
+        
+        // The set's own limit takes precedence
+        set.set_max_rebalances_per_day(5);
+        assert!(!set.rate_limited_at(next_window + 3));
     }
     
-    /// Adds an asset allocation to a vault
-    pub fn add_allocation(vault_id: String, asset_id: String, target_percentage: u32) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_rebalance_cooldown() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        set.set_min_rebalance_interval(3600);
+        set.last_rebalance = 10_000;
         
-        let allocation_set = state.allocations.get_mut(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        let allocation = AssetAllocation::new(asset_id.clone(), target_percentage);
-        allocation_set.add_allocation(allocation)
-            .unwrap_or_else(|err| panic!("Failed to add allocation: {}", err));
-            
-        state.save();
+        // Drift exceeds the threshold but the cooldown has not elapsed
+        set.allocations[0].update_current_percentage(7000);
+        set.allocations[1].update_current_percentage(3000);
+        assert_eq!(set.cooldown_remaining(10_600), 3000);
+        assert!(!set.needs_rebalancing_at(10_600));
         
-        format!("Allocation added for {} in vault {}", asset_id, vault_id)
+        let report = set.drift_report(None, 10_600).unwrap();
+        assert_eq!(report.cooldown_remaining_seconds, 3000);
+        assert!(!report.needs_rebalancing);
+        
+        // Once it elapses drift triggers again
+        assert_eq!(set.cooldown_remaining(13_600), 0);
+        assert!(set.needs_rebalancing_at(13_600));
+        
+        // A schedule shorter than the cooldown is held back by it
+        set.allocations[0].update_current_percentage(6000);
+        set.allocations[1].update_current_percentage(4000);
+        set.set_rebalance_frequency(600);
+        assert!(!set.needs_rebalancing_at(10_600));
+        
+        // A schedule longer than the cooldown still fires when due
+        set.set_min_rebalance_interval(300);
+        set.set_rebalance_frequency(600);
+        assert!(set.needs_rebalancing_at(10_600));
+        
+        // Disabled cooldown never blocks
+        set.set_min_rebalance_interval(0);
+        assert_eq!(set.cooldown_remaining(10_001), 0);
     }
     
-    /// Updates an asset allocation in a vault
-    pub fn update_allocation(vault_id: String, asset_id: String, target_percentage: u32) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_adaptive_threshold() {
+        let mut set = AllocationSet::new(500);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
         
-        let allocation_set = state.allocations.get_mut(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        allocation_set.update_allocation(&asset_id, target_percentage)
-            .unwrap_or_else(|err| panic!("Failed to update allocation: {}", err));
-            
-        state.save();
+        // 700 bp of drift: above the 5% threshold
+        set.allocations[0].update_current_percentage(6700);
+        set.allocations[1].update_current_percentage(3300);
+        assert!(set.needs_rebalancing_at(0));
         
-        format!("Allocation updated for {} in vault {}", asset_id, vault_id)
+        let config = AdaptiveThreshold {
+            reference_volatility_bp: 200,
+            lookback_records: 24,
+            min_multiplier_bp: MIN_ADAPTIVE_MULTIPLIER_BP,
+            max_multiplier_bp: MAX_ADAPTIVE_MULTIPLIER_BP,
+        };
+        assert_eq!(
+            set.set_adaptive_threshold(Some(AdaptiveThreshold { max_multiplier_bp: 40000, ..config.clone() })),
+            Err("Adaptive multiplier bounds must lie within 0.5x..3x")
+        );
+        set.set_adaptive_threshold(Some(config)).unwrap();
+        
+        // Unscaled until volatility has been observed
+        assert_eq!(set.effective_drift_threshold(), 500);
+        
+        // Volatile market: BTC 600 bp, ETH 300 bp -> 480 bp weighted, 2.4x
+        set.record_volatility(&[("BTC".to_string(), 600), ("ETH".to_string(), 300)]);
+        assert_eq!(set.observed_volatility_bp, Some(480));
+        assert_eq!(set.effective_drift_threshold(), 1200);
+        assert!(!set.needs_rebalancing_at(0));
+        
+        let report = set.drift_report(None, 0).unwrap();
+        assert_eq!(report.drift_threshold_bp, 500);
+        assert_eq!(report.effective_threshold_bp, 1200);
+        assert_eq!(report.volatility_bp, Some(480));
+        assert!(!report.drift_results[0].exceeds_threshold);
+        assert!(!report.needs_rebalancing);
+        
+        // Extreme volatility is capped at 3x
+        set.record_volatility(&[("BTC".to_string(), 5000)]);
+        assert_eq!(set.effective_drift_threshold(), 1500);
+        
+        // Calm market tightens the threshold, floored at 0.5x
+        set.record_volatility(&[("BTC".to_string(), 10), ("ETH".to_string(), 10)]);
+        assert_eq!(set.effective_drift_threshold(), 250);
+        set.allocations[0].update_current_percentage(6300);
+        set.allocations[1].update_current_percentage(3700);
+        assert!(set.needs_rebalancing_at(0));
+        
+        // Opting out restores the fixed threshold
+        set.set_adaptive_threshold(None).unwrap();
+        assert_eq!(set.effective_drift_threshold(), 500);
+        assert!(!set.needs_rebalancing_at(0));
     }
     
-    /// Removes an asset allocation from a vault
-    pub fn remove_allocation(vault_id: String, asset_id: String) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_rebalance_transactions_with_liquidity() {
+        let mut set = AllocationSet::new(100);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+        set.add_allocation(AssetAllocation::new("SOL".to_string(), 2000)).unwrap();
         
-        let allocation_set = state.allocations.get_mut(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        allocation_set.remove_allocation(&asset_id)
-            .unwrap_or_else(|err| panic!("Failed to remove allocation: {}", err));
-            
-        state.save();
+        let current_values = vec![
+            ("BTC".to_string(), 8000),
+            ("ETH".to_string(), 1000),
+            ("SOL".to_string(), 1000),
+        ];
+        let unconstrained = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
+        assert_eq!(unconstrained, vec![
+            ("BTC".to_string(), "ETH".to_string(), 2000),
+            ("BTC".to_string(), "SOL".to_string(), 1000),
+        ]);
         
-        format!("Allocation removed for {} in vault {}", asset_id, vault_id)
+        // The ETH leg is overfunded for the pool and is capped to what it can absorb
+        let liquidity: std::collections::HashMap<String, u128> = vec![
+            ("ETH".to_string(), 1500),
+            ("SOL".to_string(), 5000),
+        ].into_iter().collect();
+        let capped = set.calculate_rebalance_transactions_with_liquidity(&current_values, 10000, &liquidity).unwrap();
+        assert_eq!(capped, vec![
+            ("BTC".to_string(), "ETH".to_string(), 1500),
+            ("BTC".to_string(), "SOL".to_string(), 1000),
+        ]);
+        
+        // The 500 ETH could not absorb is dropped, leaving BTC overweight for the next rebalance
+        let swapped: u128 = capped.iter().map(|(_, _, amount)| amount).sum();
+        assert_eq!(swapped, 2500);
+        
+        // No liquidity for a target drops its leg
+        let eth_only: std::collections::HashMap<String, u128> = vec![("ETH".to_string(), 5000)].into_iter().collect();
+        let capped = set.calculate_rebalance_transactions_with_liquidity(&current_values, 10000, &eth_only).unwrap();
+        assert_eq!(capped, vec![("BTC".to_string(), "ETH".to_string(), 2000)]);
+        
+        // Ample liquidity leaves the plan untouched
+        let ample: std::collections::HashMap<String, u128> = vec![
+            ("ETH".to_string(), u128::MAX),
+            ("SOL".to_string(), u128::MAX),
+        ].into_iter().collect();
+        assert_eq!(set.calculate_rebalance_transactions_with_liquidity(&current_values, 10000, &ample).unwrap(), unconstrained);
     }
     
-    /// Gets all allocations for a vault
-    pub fn get_allocations(vault_id: String) -> String {
-        let state = Self::load();
+    #[test]
+    fn test_split_trades_for_impact() {
+        let mut set = AllocationSet::new(100);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
         
-        let allocation_set = state.allocations.get(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        // Return allocations as JSON
-        serde_json::to_string(&allocation_set.allocations)
-            .unwrap_or_else(|_| "Failed to serialize allocations".to_string())
+        let current_values = vec![("BTC".to_string(), 65_000), ("ETH".to_string(), 35_000)];
+        assert_eq!(
+            set.calculate_rebalance_transactions(&current_values, 100_000).unwrap(),
+            vec![("BTC".to_string(), "ETH".to_string(), 15_000)]
+        );
+        
+        // 15000 against a depth of 1,000,000 moves the price 1.5%, over the 1% limit
+        set.set_trade_impact_limit(100, SlippageModel::LinearDepth { depth: 1_000_000 }).unwrap();
+        assert_eq!(set.slippage_model.impact_bps(15_000), 150);
+        assert_eq!(
+            set.calculate_rebalance_transactions(&current_values, 100_000).unwrap(),
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 7_500),
+                ("BTC".to_string(), "ETH".to_string(), 7_500),
+            ]
+        );
+        
+        // Uneven splits give the remainder to the last child
+        assert_eq!(
+            set.split_for_impact(vec![("BTC".to_string(), "ETH".to_string(), 25_001)]),
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 8_333),
+                ("BTC".to_string(), "ETH".to_string(), 8_333),
+                ("BTC".to_string(), "ETH".to_string(), 8_335),
+            ]
+        );
+        
+        // A constant impact cannot be reduced by splitting
+        set.set_trade_impact_limit(10, SlippageModel::Constant(30)).unwrap();
+        assert_eq!(set.calculate_rebalance_transactions(&current_values, 100_000).unwrap().len(), 1);
+        
+        assert!(set.set_trade_impact_limit(10001, SlippageModel::Constant(0)).is_err());
     }
     
-    /// Gets allocation set information for a vault
-    pub fn get_allocation_set(vault_id: String) -> String {
-        let state = Self::load();
+    #[test]
+    fn test_max_asset_count() {
+        let mut set = AllocationSet::new(300);
+        set.set_max_assets(Some(5)).unwrap();
         
-        let allocation_set = state.allocations.get(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        // Return allocation set as JSON
-        serde_json::to_string(allocation_set)
-            .unwrap_or_else(|_| "Failed to serialize allocation set".to_string())
+        for asset in ["BTC", "ETH", "SOL", "AVAX"] {
+            set.add_allocation(AssetAllocation::new(asset.to_string(), 2000)).unwrap();
+        }
+        
+        // The 5th asset fits, the 6th does not
+        assert_eq!(set.add_allocation(AssetAllocation::new("USDC".to_string(), 2000)), Ok(()));
+        assert_eq!(
+            set.add_allocation(AssetAllocation::new("MATIC".to_string(), 0)),
+            Err("Maximum asset count reached")
+        );
+        assert_eq!(set.allocations.len(), 5);
+        
+        // Replacing the whole policy is held to the same limit
+        let six: Vec<(String, u32)> = ["A", "B", "C", "D", "E", "F"].iter()
+            .enumerate()
+            .map(|(i, a)| (a.to_string(), if i == 0 { 5000 } else { 1000 }))
+            .collect();
+        assert_eq!(set.set_allocations(&six), Err("Maximum asset count reached"));
+        assert_eq!(set.apply_template(&six), Err("Maximum asset count reached"));
+        
+        // The limit cannot drop below the current asset count
+        assert!(set.set_max_assets(Some(4)).is_err());
+        assert!(set.set_max_assets(Some(0)).is_err());
+        set.set_max_assets(None).unwrap();
+        assert!(set.add_allocation(AssetAllocation::new("MATIC".to_string(), 0)).is_ok());
     }
     
-    /// Checks if a vault needs rebalancing
-    pub fn needs_rebalancing(vault_id: String) -> bool {
-        let state = Self::load();
+    #[test]
+    fn test_revert_unexecuted_transactions() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+        set.add_allocation(AssetAllocation::new("USDC".to_string(), 2000)).unwrap();
         
-        let allocation_set = state.allocations.get(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        allocation_set.needs_rebalancing()
+        // The plan sold 10% BTC into ETH and 5% into USDC; only the USDC leg executed
+        set.revert_transactions(&[("BTC".to_string(), "ETH".to_string(), 1000)], 10000);
+        
+        assert_eq!(set.get_allocation("BTC").unwrap().current_percentage, 6000);
+        assert_eq!(set.get_allocation("ETH").unwrap().current_percentage, 2000);
+        assert_eq!(set.get_allocation("USDC").unwrap().current_percentage, 2000);
+        assert!(set.needs_rebalancing_at(0));
     }
     
-    /// Records a rebalance operation for a vault
-    pub fn record_rebalance(vault_id: String, prices_json: String) -> String {
-        let mut state = Self::load();
+    #[test]
+    fn test_apply_partial_transactions() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        set.allocations[0].update_current_percentage(6000);
+        set.allocations[1].update_current_percentage(4000);
         
-        let allocation_set = state.allocations.get_mut(&vault_id)
-            .unwrap_or_else(|| panic!("Allocation set not found for vault {}", vault_id));
-            
-        // Parse prices from JSON
-        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
-            .unwrap_or_else(|_| panic!("Failed to parse prices"));
-            
-        allocation_set.record_rebalance(&prices);
-        state.save();
+        // Half of a 10% BTC -> ETH move executed
+        set.apply_transactions(&[("BTC".to_string(), "ETH".to_string(), 500)], 10000);
         
-        format!("Rebalance recorded for vault {}", vault_id)
+        assert_eq!(set.get_allocation("BTC").unwrap().current_percentage, 5500);
+        assert_eq!(set.get_allocation("ETH").unwrap().current_percentage, 4500);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
     
     #[test]
-    fn test_asset_allocation() {
-        let mut allocation = AssetAllocation::new("BTC".to_string(), 6000);
+    fn test_pivot_asset_routing() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        set.set_pivot_asset(Some("USDC".to_string()));
         
-        assert_eq!(allocation.asset_id, "BTC");
-        assert_eq!(allocation.target_percentage, 6000);
-        assert_eq!(allocation.current_percentage, 6000);
+        let current_values = vec![("BTC".to_string(), 6000), ("ETH".to_string(), 4000)];
+        let transactions = set.calculate_rebalance_transactions(&current_values, 10000).unwrap();
         
-        // Update target percentage
-        allocation.update_target_percentage(7000);
-        assert_eq!(allocation.target_percentage, 7000);
-        assert_eq!(allocation.current_percentage, 6000);
+        // Nothing held in the pivot yet: only the sale runs this time
+        assert_eq!(transactions, vec![
+            ("BTC".to_string(), "USDC".to_string(), 1000),
+        ]);
         
-        // Calculate drift
-        assert_eq!(allocation.drift(), 1000);
+        let mut with_pivot = current_values.clone();
+        with_pivot.push(("USDC".to_string(), 1000));
+        assert_eq!(set.calculate_rebalance_transactions(&with_pivot, 10000).unwrap(), vec![
+            ("BTC".to_string(), "USDC".to_string(), 1000),
+            ("USDC".to_string(), "ETH".to_string(), 1000),
+        ]);
         
-        // Record rebalance
-        allocation.record_rebalance(Some(50000));
-        assert_eq!(allocation.current_percentage, 7000);
-        assert_eq!(allocation.last_price, Some(50000));
+        // Trades into or out of the pivot are not split
+        let direct = set.route_through_pivot(vec![("BTC".to_string(), "USDC".to_string(), 500)], 0);
+        assert_eq!(direct, vec![("BTC".to_string(), "USDC".to_string(), 500)]);
+        
+        set.set_pivot_asset(None);
+        assert_eq!(set.calculate_rebalance_transactions(&current_values, 10000).unwrap().len(), 1);
     }
     
     #[test]
-    fn test_allocation_set() {
+    fn test_pivot_routing_passes_validation() {
+        use crate::rebalance::{RebalanceEngine, RebalanceStrategy};
+        
         let mut set = AllocationSet::new(300);
+        set.set_pivot_asset(Some("USDC".to_string()));
         
-        // Add BTC allocation
-        let btc = AssetAllocation::new("BTC".to_string(), 6000);
-        set.add_allocation(btc).unwrap();
+        let plan = vec![
+            ("BTC".to_string(), "SOL".to_string(), 600),
+            ("ETH".to_string(), "SOL".to_string(), 400),
+            ("BTC".to_string(), "ETH".to_string(), 200),
+        ];
+        let holdings: std::collections::HashMap<String, u128> = [
+            ("BTC".to_string(), 4000),
+            ("ETH".to_string(), 3000),
+            ("SOL".to_string(), 2300),
+            ("USDC".to_string(), 700),
+        ].into_iter().collect();
         
-        // Add ETH allocation
-        let eth = AssetAllocation::new("ETH".to_string(), 4000);
-        set.add_allocation(eth).unwrap();
+        // Legs are netted per pair and the purchases spend only the 700 USDC held
+        let routed = set.route_through_pivot(plan.clone(), 700);
+        assert_eq!(routed, vec![
+            ("BTC".to_string(), "USDC".to_string(), 800),
+            ("USDC".to_string(), "SOL".to_string(), 700),
+            ("ETH".to_string(), "USDC".to_string(), 400),
+        ]);
         
-        // Validate percentages
-        assert!(set.validate_percentages().is_ok());
+        let operation = RebalanceEngine::create_rebalance_operation("op-1".to_string(), RebalanceStrategy::Threshold, routed);
+        assert!(RebalanceEngine::validate_transactions(&operation, &holdings, 10000).is_empty());
         
-        // Try to add duplicate asset
-        let btc_dup = AssetAllocation::new("BTC".to_string(), 5000);
-        assert!(set.add_allocation(btc_dup).is_err());
+        // Routing each trade on its own repeats pairs and oversells the pivot
+        let unnetted: Vec<(String, String, u128)> = plan.into_iter()
+            .flat_map(|(source, target, amount)| vec![
+                (source, "USDC".to_string(), amount),
+                ("USDC".to_string(), target, amount),
+            ])
+            .collect();
+        let operation = RebalanceEngine::create_rebalance_operation("op-2".to_string(), RebalanceStrategy::Threshold, unnetted);
+        assert!(!RebalanceEngine::validate_transactions(&operation, &holdings, 10000).is_empty());
+    }
+    
+    #[test]
+    fn test_simulate_price_drift() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
         
-        // Update an allocation
-        set.update_allocation("BTC", 5000).unwrap();
+        // BTC +50%: 75 / (75 + 50) = 60%
+        let projected = set.simulate_price_drift(&[("BTC".to_string(), 50.0)]);
+        assert_eq!(projected, vec![("BTC".to_string(), 6000), ("ETH".to_string(), 4000)]);
         
-        // Validate that percentages no longer sum to 100%
-        assert!(set.validate_percentages().is_err());
+        // The set itself is untouched
+        assert_eq!(set.get_allocation("BTC").unwrap().current_percentage, 5000);
         
-        // Fix percentages
-        set.update_allocation("ETH", 5000).unwrap();
-        assert!(set.validate_percentages().is_ok());
+        // Both assets moving together leaves the weights alone
+        let projected = set.simulate_price_drift(&[("BTC".to_string(), -20.0), ("ETH".to_string(), -20.0)]);
+        assert_eq!(projected, vec![("BTC".to_string(), 5000), ("ETH".to_string(), 5000)]);
+    }
+    
+    #[test]
+    fn test_canonical_hash_ignores_order() {
+        let set = |targets: &[(&str, u32)]| {
+            let mut set = AllocationSet::new(500);
+            for (asset_id, target) in targets {
+                set.add_allocation(AssetAllocation::new(asset_id.to_string(), *target)).unwrap();
+            }
+            set
+        };
         
-        // Check if rebalancing is needed (no drift yet)
-        assert!(!set.needs_rebalancing());
+        let original = set(&[("BTC", 5000), ("ETH", 3000), ("USDC", 2000)]);
+        let reordered = set(&[("USDC", 2000), ("BTC", 5000), ("ETH", 3000)]);
+        assert_eq!(original.canonical_hash(), reordered.canonical_hash());
         
-        // Update current percentage to create drift
-        let btc_allocation = set.allocations.iter_mut()
-            .find(|a| a.asset_id == "BTC")
-            .unwrap();
-            
-        btc_allocation.update_current_percentage(6000);
+        // Changing any target changes the hash
+        let changed = set(&[("BTC", 5000), ("ETH", 2000), ("USDC", 3000)]);
+        assert_ne!(original.canonical_hash(), changed.canonical_hash());
         
-        // Now we should need rebalancing (1000 bp drift > 300 bp threshold)
-        assert!(set.needs_rebalancing());
+        // Asset IDs are length-prefixed, so they cannot run into each other
+        let split = set(&[("BT", 5000), ("CETH", 3000), ("USDC", 2000)]);
+        assert_ne!(original.canonical_hash(), split.canonical_hash());
+    }
+    
+    #[test]
+    fn test_value_at_prices() {
+        let mut set = AllocationSet::new(300);
+        set.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        set.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        set.record_rebalance(&[("BTC".to_string(), 100), ("ETH".to_string(), 200)]);
         
-        // Record a rebalance
-        let prices = vec![
-            ("BTC".to_string(), 50000),
-            ("ETH".to_string(), 3000),
-        ];
+        // BTC doubles, ETH halves: 500 * 2 + 500 / 2
+        let prices = vec![("BTC".to_string(), 200), ("ETH".to_string(), 100)];
+        assert_eq!(set.value_at_prices(1000, &prices), Ok(1250));
         
-        set.record_rebalance(&prices);
+        // Assets without a new price keep their value
+        assert_eq!(set.value_at_prices(1000, &prices[..1]), Ok(1500));
+    }
+    
+    #[test]
+    fn test_drift_direction_and_severity() {
+        use crate::events::{DriftDirection, DriftSeverity};
         
-        // After rebalance, we shouldn't need another one
-        assert!(!set.needs_rebalancing());
+        let mut allocation = AssetAllocation::new("BTC".to_string(), 5000);
+        allocation.update_current_percentage(6000);
+        assert_eq!(allocation.drift_signed(), 1000);
         
-        // Set a time-based rebalance frequency
-        set.set_rebalance_frequency(86400); // 1 day
+        // 10% drift is more than twice a 3% threshold
+        let result = allocation.create_drift_result(300);
+        assert_eq!(result.direction, DriftDirection::Over);
+        assert_eq!(result.severity, DriftSeverity::High);
+        assert!(result.exceeds_threshold);
         
-        // Fast-forward 2 days
-        let current_time = l1x_sdk::env::block_timestamp();
-        l1x_sdk::env::set_block_timestamp(current_time + 172800);
+        // Within twice the threshold is medium severity
+        assert_eq!(allocation.create_drift_result(600).severity, DriftSeverity::Medium);
         
-        // Now we should need time-based rebalancing
-        assert!(set.needs_rebalancing());
+        // At or within the threshold is low severity
+        let result = allocation.create_drift_result(1500);
+        assert_eq!(result.severity, DriftSeverity::Low);
+        assert!(!result.exceeds_threshold);
+        
+        // Underweight assets report a negative signed drift
+        allocation.update_current_percentage(4200);
+        assert_eq!(allocation.drift_signed(), -800);
+        assert_eq!(allocation.create_drift_result(300).direction, DriftDirection::Under);
+        
+        allocation.update_current_percentage(5000);
+        assert_eq!(allocation.create_drift_result(300).direction, DriftDirection::Balanced);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,219 @@
+//! Asset registry for One Capital Auto-Investing
+//!
+//! This module keeps the protocol-level whitelist and blacklist of assets that
+//! vault allocations may hold.
+
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+use crate::allocation::AllocationSet;
+
+/// Asset registry contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"ASSET_REGISTRY";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AssetRegistryContract {
+    whitelisted: std::collections::HashSet<String>,
+    blacklisted: std::collections::HashSet<String>,
+    admin: String,
+}
+
+#[l1x_sdk::contract]
+impl AssetRegistryContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(admin: String) {
+        let mut state = Self {
+            whitelisted: std::collections::HashSet::new(),
+            blacklisted: std::collections::HashSet::new(),
+            admin,
+        };
+
+        state.save()
+    }
+
+    /// Checks if the caller is an admin
+    fn is_admin() -> bool {
+        let state = Self::load();
+        let caller = l1x_sdk::env::caller();
+
+        state.admin == caller
+    }
+
+    /// Checks whether an asset may be added to allocations
+    fn check(&self, asset_id: &str) -> Result<(), &'static str> {
+        if self.blacklisted.contains(asset_id) {
+            return Err("Asset is blacklisted");
+        }
+
+        if !self.whitelisted.contains(asset_id) {
+            return Err("Asset not whitelisted");
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether an asset may be added to allocations
+    pub fn is_allowed(asset_id: String) -> bool {
+        let state = Self::load();
+
+        state.check(&asset_id).is_ok()
+    }
+
+    /// Adds an asset to the whitelist
+    pub fn whitelist_asset(asset_id: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can whitelist assets");
+        }
+
+        let mut state = Self::load();
+
+        state.whitelisted.insert(asset_id.clone());
+        state.save();
+
+        format!("Asset {} whitelisted", asset_id)
+    }
+
+    /// Removes an asset from the whitelist
+    pub fn remove_from_whitelist(asset_id: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can remove whitelisted assets");
+        }
+
+        let mut state = Self::load();
+
+        if !state.whitelisted.remove(&asset_id) {
+            panic!("Asset {} is not whitelisted", asset_id);
+        }
+
+        state.save();
+
+        format!("Asset {} removed from whitelist", asset_id)
+    }
+
+    /// Adds an asset to the blacklist; blacklisting overrides the whitelist
+    pub fn blacklist_asset(asset_id: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can blacklist assets");
+        }
+
+        let mut state = Self::load();
+
+        state.blacklisted.insert(asset_id.clone());
+        state.save();
+
+        format!("Asset {} blacklisted", asset_id)
+    }
+
+    /// Removes an asset from the blacklist
+    pub fn remove_from_blacklist(asset_id: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can remove blacklisted assets");
+        }
+
+        let mut state = Self::load();
+
+        if !state.blacklisted.remove(&asset_id) {
+            panic!("Asset {} is not blacklisted", asset_id);
+        }
+
+        state.save();
+
+        format!("Asset {} removed from blacklist", asset_id)
+    }
+
+    /// Lists whitelisted and blacklisted assets
+    pub fn list_assets() -> String {
+        let state = Self::load();
+
+        let mut whitelisted: Vec<&String> = state.whitelisted.iter().collect();
+        whitelisted.sort();
+        let mut blacklisted: Vec<&String> = state.blacklisted.iter().collect();
+        blacklisted.sort();
+
+        serde_json::json!({
+            "whitelisted": whitelisted,
+            "blacklisted": blacklisted,
+        }).to_string()
+    }
+}
+
+impl AssetRegistryContract {
+    /// Checks every asset not yet held by `allocation_set` against the registry
+    pub fn check_new_assets<'a>(
+        &self,
+        allocation_set: &AllocationSet,
+        asset_ids: impl IntoIterator<Item = &'a String>,
+    ) -> Result<(), String> {
+        for asset_id in asset_ids {
+            if allocation_set.get_allocation(asset_id).is_none() {
+                self.check(asset_id)
+                    .map_err(|err| format!("Failed to add allocation for {}: {}", asset_id, err))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks the registry before new assets are added to an allocation set
+pub fn check_new_assets_allowed<'a>(
+    allocation_set: &AllocationSet,
+    asset_ids: impl IntoIterator<Item = &'a String>,
+) -> Result<(), String> {
+    let state = AssetRegistryContract::load();
+
+    state.check_new_assets(allocation_set, asset_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::AssetAllocation;
+
+    fn registry() -> AssetRegistryContract {
+        let mut registry = AssetRegistryContract {
+            whitelisted: std::collections::HashSet::new(),
+            blacklisted: std::collections::HashSet::new(),
+            admin: "admin".to_string(),
+        };
+        registry.whitelisted.insert("BTC".to_string());
+        registry.whitelisted.insert("ETH".to_string());
+        registry.blacklisted.insert("ETH".to_string());
+        registry
+    }
+
+    #[test]
+    fn test_registry_gates_allocations() {
+        let mut registry = registry();
+        let mut set = AllocationSet::new(500);
+
+        let btc = "BTC".to_string();
+        let eth = "ETH".to_string();
+        let doge = "DOGE".to_string();
+
+        assert_eq!(registry.check_new_assets(&set, [&btc]), Ok(()));
+        assert_eq!(
+            registry.check_new_assets(&set, [&btc, &eth]),
+            Err("Failed to add allocation for ETH: Asset is blacklisted".to_string())
+        );
+        assert_eq!(
+            registry.check_new_assets(&set, [&doge]),
+            Err("Failed to add allocation for DOGE: Asset not whitelisted".to_string())
+        );
+
+        // Assets already held are not re-checked, even once blacklisted
+        set.add_allocation(AssetAllocation::new(btc.clone(), 6000)).unwrap();
+        registry.blacklisted.insert(btc.clone());
+        assert_eq!(registry.check_new_assets(&set, [&btc]), Ok(()));
+    }
+}
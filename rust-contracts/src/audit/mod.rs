@@ -0,0 +1,143 @@
+//! Audit log for One Capital Auto-Investing
+//!
+//! This module records admin-level state changes made anywhere in the
+//! protocol so they can be reviewed for compliance.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+/// One admin-level state change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AuditEntry {
+    /// Account that made the call
+    pub actor: String,
+
+    /// Contract the function belongs to (e.g. "PriceFeedContract")
+    pub contract_name: String,
+
+    /// Function that was called
+    pub function_name: String,
+
+    /// Keccak-256 hash of the JSON-encoded call parameters
+    pub params_hash: Vec<u8>,
+
+    /// Timestamp of the call
+    pub timestamp: u64,
+
+    /// Message returned by the call
+    pub result: String,
+}
+
+/// Audit log contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"AUDIT_LOG";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AuditLogContract {
+    entries: Vec<AuditEntry>, // Oldest first
+}
+
+#[l1x_sdk::contract]
+impl AuditLogContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            entries: Vec::new(),
+        };
+
+        state.save()
+    }
+
+    /// Entries in `[offset, offset + limit)`, oldest first
+    fn page(&self, limit: u32, offset: u32) -> &[AuditEntry] {
+        let start = (offset as usize).min(self.entries.len());
+        let end = start.saturating_add(limit as usize).min(self.entries.len());
+
+        &self.entries[start..end]
+    }
+
+    /// Gets a page of the audit log, oldest first
+    pub fn get_audit_log(limit: u32, offset: u32) -> String {
+        let state = Self::load();
+
+        serde_json::json!({
+            "entries": state.page(limit, offset),
+            "total": state.entries.len(),
+            "offset": offset,
+            "limit": limit,
+        }).to_string()
+    }
+}
+
+impl AuditLogContract {
+    /// Appends an entry for an admin call made by `actor`
+    ///
+    /// `params` is the JSON encoding of the call's parameters; only its hash is stored.
+    pub fn record(actor: &str, contract_name: &str, function_name: &str, params: &serde_json::Value, result: &str) {
+        let mut state = Self::load();
+
+        state.entries.push(AuditEntry {
+            actor: actor.to_string(),
+            contract_name: contract_name.to_string(),
+            function_name: function_name.to_string(),
+            params_hash: l1x_sdk::env::keccak256(params.to_string().as_bytes()).to_vec(),
+            timestamp: l1x_sdk::env::block_timestamp(),
+            result: result.to_string(),
+        });
+        state.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(function_name: &str, timestamp: u64) -> AuditEntry {
+        AuditEntry {
+            actor: "admin".to_string(),
+            contract_name: "PriceFeedContract".to_string(),
+            function_name: function_name.to_string(),
+            params_hash: vec![0u8; 32],
+            timestamp,
+            result: format!("{} ok", function_name),
+        }
+    }
+
+    #[test]
+    fn test_audit_log_pagination() {
+        let mut log = AuditLogContract {
+            entries: Vec::new(),
+        };
+
+        let calls = ["add_authority", "remove_authority", "update_vault", "set_take_profit", "register_validator"];
+        for (i, function_name) in calls.iter().enumerate() {
+            log.entries.push(entry(function_name, 100 + i as u64));
+        }
+
+        assert_eq!(log.entries.len(), 5);
+
+        let first: Vec<&str> = log.page(2, 0).iter().map(|e| e.function_name.as_str()).collect();
+        assert_eq!(first, vec!["add_authority", "remove_authority"]);
+
+        let second: Vec<&str> = log.page(2, 2).iter().map(|e| e.function_name.as_str()).collect();
+        assert_eq!(second, vec!["update_vault", "set_take_profit"]);
+
+        let last = log.page(2, 4);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0], entry("register_validator", 104));
+
+        assert!(log.page(2, 5).is_empty());
+        assert!(log.page(10, 99).is_empty());
+        assert_eq!(log.page(u32::MAX, 0).len(), 5);
+    }
+}
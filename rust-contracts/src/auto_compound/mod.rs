@@ -0,0 +1,184 @@
+//! Auto-compounding for One Capital Auto-Investing
+//!
+//! This module reinvests take-profit proceeds back into the vault instead of
+//! extracting them to the target asset.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+
+use crate::custodial_vault::{CustodialVault, RebalanceRequest, RebalanceRun};
+
+/// Per-vault auto-compound settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AutoCompoundConfig {
+    /// Reinvest take-profit proceeds instead of extracting them
+    pub enabled: bool,
+
+    /// Spread reinvested proceeds across the target allocations with a rebalance
+    pub compound_target_allocations: bool,
+}
+
+/// Outcome of reinvesting take-profit proceeds
+pub struct CompoundResult {
+    /// Amount of proceeds put back into the allocations
+    pub reinvested_amount: u128,
+
+    /// Rebalance cycle that spread the proceeds (`None` when not compounding into targets)
+    ///
+    /// A refused rebalance keeps the proceeds in the vault as they are.
+    pub rebalance: Option<Result<RebalanceRun, String>>,
+}
+
+impl AutoCompoundConfig {
+    /// Reinvests `profit_amount` into `vault`
+    ///
+    /// The proceeds were realized from the vault's own holdings and are still
+    /// part of its value, so nothing is deposited; they are only spread back
+    /// across the allocations, through the vault's regular rebalance pipeline
+    /// run with `request`. The caller finishes a submitted run once the vault
+    /// is saved. Returns `None` when auto-compounding is disabled or there is
+    /// no profit, in which case the proceeds are extracted as usual.
+    pub fn reinvest(
+        &self,
+        vault: &mut CustodialVault,
+        profit_amount: u128,
+        request: &RebalanceRequest,
+    ) -> Option<CompoundResult> {
+        if !self.enabled || profit_amount == 0 {
+            return None;
+        }
+
+        let rebalance = if self.compound_target_allocations {
+            Some(vault.try_rebalance(request))
+        } else {
+            None
+        };
+
+        Some(CompoundResult {
+            reinvested_amount: profit_amount,
+            rebalance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::AssetAllocation;
+    use crate::emergency::EmergencyPauseContract;
+    use crate::price_feed::{AssetMetadata, PriceFeedContract};
+    use crate::rebalance::RebalanceMode;
+    use crate::take_profit::{TakeProfitStrategy, TakeProfitType};
+    use std::collections::HashMap;
+
+    fn vault() -> CustodialVault {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.deposit(1000).unwrap();
+
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        strategy.set_baseline(1000);
+        vault.take_profit = Some(strategy);
+        vault
+    }
+
+    fn price_feed() -> PriceFeedContract {
+        let mut price_feed = PriceFeedContract::with_admin("admin".to_string());
+        for symbol in ["BTC", "ETH"] {
+            price_feed.register_asset_metadata(AssetMetadata {
+                symbol: symbol.to_string(),
+                decimals: 8,
+                display_name: symbol.to_string(),
+                addresses: HashMap::new(),
+                active: true,
+                registered_at: 0,
+            }).unwrap();
+        }
+        price_feed
+    }
+
+    #[test]
+    fn test_reinvest_take_profit() {
+        let mut vault = vault();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.allocations.min_rebalance_interval_seconds = 3600;
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        // Holdings worth 700/300, the 70/30 drift against 60/40 targets
+        let balances = vec![("BTC".to_string(), 7), ("ETH".to_string(), 30)];
+        let price_feed = price_feed();
+        let emergency = EmergencyPauseContract::unpaused();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "compound",
+            prices: &prices,
+            balances: Some(&balances),
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 0,
+            mode: RebalanceMode::Simulate,
+        };
+
+        // A 10% profit cycle
+        let profit = 1100 - vault.take_profit.as_ref().unwrap().baseline_value;
+
+        let config = AutoCompoundConfig { enabled: true, compound_target_allocations: true };
+        let result = config.reinvest(&mut vault, profit, &request).unwrap();
+
+        // The proceeds are already in the vault; reinvesting does not count them twice
+        assert_eq!(result.reinvested_amount, 100);
+        assert_eq!(vault.total_value, 1000);
+
+        // The proceeds were spread through the regular rebalance pipeline
+        match result.rebalance {
+            Some(Ok(RebalanceRun::Submitted { operation, .. })) => {
+                assert_eq!(operation.vault_id.as_deref(), Some("vault-1"));
+                assert!(!operation.transactions.is_empty());
+            },
+            _ => panic!("the proceeds should be rebalanced"),
+        }
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 6000);
+        assert_eq!(vault.allocations.allocations[1].current_percentage, 4000);
+
+        // ...which applies its checks: a second cycle is refused during the cooldown
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        let result = config.reinvest(&mut vault, profit, &request).unwrap();
+        assert!(matches!(result.rebalance, Some(Err(_))));
+        assert_eq!(vault.total_value, 1000);
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 7000);
+    }
+
+    #[test]
+    fn test_reinvest_disabled_or_without_rebalance() {
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        let price_feed = price_feed();
+        let emergency = EmergencyPauseContract::unpaused();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "compound",
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 0,
+            mode: RebalanceMode::Simulate,
+        };
+
+        // Disabled: proceeds are extracted as before
+        let mut vault = vault();
+        assert!(AutoCompoundConfig::default().reinvest(&mut vault, 100, &request).is_none());
+        assert_eq!(vault.total_value, 1000);
+
+        // No profit: nothing to reinvest
+        let config = AutoCompoundConfig { enabled: true, compound_target_allocations: false };
+        assert!(config.reinvest(&mut vault, 0, &request).is_none());
+
+        // Kept in the vault without a rebalance
+        let result = config.reinvest(&mut vault, 100, &request).unwrap();
+        assert_eq!(result.reinvested_amount, 100);
+        assert_eq!(vault.total_value, 1000);
+        assert!(result.rebalance.is_none());
+    }
+}
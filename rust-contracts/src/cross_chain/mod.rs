@@ -75,6 +75,86 @@ impl Blockchain {
             _ => true, // All others are EVM-compatible
         }
     }
+
+    /// Number of XTalk hops needed to reach the target chain
+    /// (swaps between two external chains are routed via L1X)
+    pub fn hop_count(&self, target: &Blockchain) -> u32 {
+        if self == target || *self == Blockchain::L1X || *target == Blockchain::L1X {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Estimated gas cost of settling a transaction on this chain (in USD, scaled by 1e8)
+    pub fn gas_cost_usd(&self) -> u128 {
+        match self {
+            Blockchain::L1X => 1_000_000,           // $0.01
+            Blockchain::Ethereum => 500_000_000,    // $5.00
+            Blockchain::Solana => 1_000_000,        // $0.01
+            Blockchain::Avalanche => 20_000_000,    // $0.20
+            Blockchain::Arbitrum => 10_000_000,     // $0.10
+            Blockchain::Optimism => 10_000_000,     // $0.10
+            Blockchain::Base => 5_000_000,          // $0.05
+            Blockchain::Polygon => 2_000_000,       // $0.02
+        }
+    }
+
+    /// Chain an asset is held on (assets without a native chain are held on L1X)
+    pub fn native_for_asset(asset: &str) -> Self {
+        match asset {
+            "ETH" => Blockchain::Ethereum,
+            "SOL" => Blockchain::Solana,
+            "AVAX" => Blockchain::Avalanche,
+            "ARB" => Blockchain::Arbitrum,
+            "OP" => Blockchain::Optimism,
+            "MATIC" => Blockchain::Polygon,
+            _ => Blockchain::L1X, // L1X, wrapped BTC and stablecoins
+        }
+    }
+}
+
+/// Swap fee for swaps within one chain (in basis points)
+pub const SAME_CHAIN_SWAP_FEE_BPS: u32 = 25;
+
+/// Swap fee for swaps across chains (in basis points)
+pub const CROSS_CHAIN_SWAP_FEE_BPS: u32 = 50;
+
+/// Swap fee quoted for swapping `source_asset` into `target_asset` (in basis points)
+pub fn swap_fee_bps(source_asset: &str, target_asset: &str) -> u32 {
+    if Blockchain::native_for_asset(source_asset) == Blockchain::native_for_asset(target_asset) {
+        SAME_CHAIN_SWAP_FEE_BPS
+    } else {
+        CROSS_CHAIN_SWAP_FEE_BPS
+    }
+}
+
+/// Mock exchange rate between two assets (1 source unit = X target units)
+fn mock_exchange_rate(source_asset: &str, target_asset: &str) -> f64 {
+    // This is a simplified example - in a real implementation,
+    // this would use actual exchange rates and market data
+    match (source_asset, target_asset) {
+        ("BTC", "ETH") => 16.5,     // 1 BTC = 16.5 ETH
+        ("ETH", "BTC") => 0.06,     // 1 ETH = 0.06 BTC
+        ("BTC", "L1X") => 2500.0,   // 1 BTC = 2500 L1X
+        ("ETH", "L1X") => 150.0,    // 1 ETH = 150 L1X
+        ("L1X", "BTC") => 0.0004,   // 1 L1X = 0.0004 BTC
+        ("L1X", "ETH") => 0.0066,   // 1 L1X = 0.0066 ETH
+        ("USDC", "USDT") => 1.001,  // 1 USDC = 1.001 USDT
+        ("USDT", "USDC") => 0.999,  // 1 USDT = 0.999 USDC
+        _ => 1.0,                   // Default 1:1 for unknown pairs
+    }
+}
+
+/// Mock USD price of one unit of an asset (scaled by 1e8)
+fn mock_usd_price(asset: &str) -> u128 {
+    match asset {
+        "BTC" => 65_000_00000000,
+        "ETH" => 3_500_00000000,
+        "SOL" => 140_00000000,
+        "L1X" => 26_00000000,
+        _ => 1_00000000, // Stablecoins and unknown assets
+    }
 }
 
 /// Cross-chain swap request
@@ -188,6 +268,65 @@ pub struct SwapRoute {
     pub liquidity: u128,
 }
 
+impl SwapRoute {
+    /// Cheapest route for moving `amount` from `source_chain` to `target_chain` over the
+    /// given route legs: the total fee is minimized, ties go to the faster route.
+    /// Legs without enough liquidity for `amount` are skipped; the returned route sums
+    /// fees and times across its legs and carries the smallest leg liquidity.
+    pub fn optimal(
+        legs: &[SwapRoute],
+        source_chain: Blockchain,
+        target_chain: Blockchain,
+        source_asset: &str,
+        target_asset: &str,
+        amount: u128,
+    ) -> Option<SwapRoute> {
+        // Cheapest (chain, fee, time, liquidity) found so far for each chain reached
+        let mut best: Vec<(Blockchain, u32, u64, u128)> = Vec::new();
+        let mut settled: Vec<Blockchain> = Vec::new();
+
+        // Start from the legs leaving the source, so same-chain swaps still take one leg
+        let mut frontier = (source_chain, 0u32, 0u64, u128::MAX);
+
+        loop {
+            let (from, fee, time, liquidity) = frontier;
+
+            for leg in legs.iter().filter(|leg| leg.source_chain == from && leg.liquidity >= amount) {
+                let candidate = (
+                    leg.target_chain,
+                    fee + leg.fee_bps,
+                    time + leg.estimated_time_seconds,
+                    liquidity.min(leg.liquidity),
+                );
+
+                match best.iter_mut().find(|entry| entry.0 == leg.target_chain) {
+                    Some(entry) if (candidate.1, candidate.2) < (entry.1, entry.2) => *entry = candidate,
+                    Some(_) => {}
+                    None => best.push(candidate),
+                }
+            }
+
+            frontier = *best.iter()
+                .filter(|entry| !settled.contains(&entry.0))
+                .min_by_key(|entry| (entry.1, entry.2))?;
+
+            if frontier.0 == target_chain {
+                return Some(SwapRoute {
+                    source_chain,
+                    target_chain,
+                    source_asset: source_asset.to_string(),
+                    target_asset: target_asset.to_string(),
+                    fee_bps: frontier.1,
+                    estimated_time_seconds: frontier.2,
+                    liquidity: frontier.3,
+                });
+            }
+
+            settled.push(frontier.0);
+        }
+    }
+}
+
 /// Cross-chain swap quote
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapQuote {
@@ -210,6 +349,68 @@ pub struct SwapQuote {
     pub max_slippage_bps: u32,
 }
 
+/// Fee estimate for a cross-chain swap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// Protocol fee (in target asset units)
+    pub protocol_fee: u128,
+
+    /// XTalk validator fee for all hops (in USD, scaled by 1e8)
+    pub validator_fee: u128,
+
+    /// Gas cost across all chains touched (in USD, scaled by 1e8)
+    pub gas_estimate: u128,
+
+    /// Total of all fees (in USD, scaled by 1e8)
+    pub total_fee_usd: u128,
+
+    /// Estimated time to complete (in seconds)
+    pub estimated_time_seconds: u64,
+}
+
+impl FeeEstimate {
+    /// XTalk validator fee charged per hop (in USD, scaled by 1e8)
+    pub const VALIDATOR_FEE_PER_HOP: u128 = 25_000_000; // $0.25
+
+    /// Estimated time per XTalk hop (in seconds)
+    pub const SECONDS_PER_HOP: u64 = 120;
+
+    /// Calculates the fee estimate for swapping `amount` of `source_asset`
+    pub fn calculate(
+        source_chain: Blockchain,
+        target_chain: Blockchain,
+        source_asset: &str,
+        target_asset: &str,
+        amount: u128,
+    ) -> Self {
+        let hops = source_chain.hop_count(&target_chain);
+
+        // Protocol fee uses the same rates as get_swap_quote
+        let exchange_rate = mock_exchange_rate(source_asset, target_asset);
+        let estimated_target_amount = (amount as f64 * exchange_rate) as u128;
+        let fee_bps: u128 = if source_chain == target_chain { 25 } else { 50 };
+        let protocol_fee = (estimated_target_amount * fee_bps) / 10000;
+
+        let validator_fee = Self::VALIDATOR_FEE_PER_HOP * hops as u128;
+
+        // Multi-hop routes also settle on L1X in between
+        let mut gas_estimate = source_chain.gas_cost_usd() + target_chain.gas_cost_usd();
+        if hops > 1 {
+            gas_estimate += Blockchain::L1X.gas_cost_usd();
+        }
+
+        let protocol_fee_usd = protocol_fee * mock_usd_price(target_asset) / 100_000_000;
+
+        Self {
+            protocol_fee,
+            validator_fee,
+            gas_estimate,
+            total_fee_usd: protocol_fee_usd + validator_fee + gas_estimate,
+            estimated_time_seconds: Self::SECONDS_PER_HOP * hops as u64,
+        }
+    }
+}
+
 /// Cross-chain contract storage
 const STORAGE_CONTRACT_KEY: &[u8] = b"CROSS_CHAIN";
 
@@ -255,33 +456,25 @@ impl CrossChainContract {
         state.save()
     }
     
-    /// Creates a new cross-chain swap request
-    pub fn create_swap_request(
+    /// Records a pending swap request, checking source liquidity first
+    fn insert_swap_request(
+        &mut self,
         user_id: String,
-        source_chain: String,
-        target_chain: String,
+        source_chain: Blockchain,
+        target_chain: Blockchain,
         source_asset: String,
         target_asset: String,
         amount: u128,
         max_slippage_bps: u32,
         target_address: String,
-    ) -> String {
-        let mut state = Self::load();
-        
-        // Parse blockchains
-        let source_chain_enum = Blockchain::from_string(&source_chain)
-            .unwrap_or_else(|_| panic!("Invalid source blockchain: {}", source_chain));
-            
-        let target_chain_enum = Blockchain::from_string(&target_chain)
-            .unwrap_or_else(|_| panic!("Invalid target blockchain: {}", target_chain));
-            
+    ) -> Result<String, String> {
         // Check if we have sufficient liquidity
-        let available_liquidity = state.liquidity.get(&source_asset)
+        let available_liquidity = self.liquidity.get(&source_asset)
             .cloned()
             .unwrap_or(0);
             
         if available_liquidity < amount {
-            panic!("Insufficient liquidity for {}", source_asset);
+            return Err(format!("Insufficient liquidity for {}", source_asset));
         }
         
         // Generate request ID
@@ -296,8 +489,8 @@ impl CrossChainContract {
         let swap_request = CrossChainSwapRequest {
             id: request_id.clone(),
             user_id: user_id.clone(),
-            source_chain: source_chain_enum,
-            target_chain: target_chain_enum,
+            source_chain,
+            target_chain,
             source_asset,
             target_asset,
             amount,
@@ -307,17 +500,57 @@ impl CrossChainContract {
             status: SwapStatus::Pending,
             source_tx_hash: None,
             target_tx_hash: None,
+            xtalk_message_id: None,
+            xtalk_status: None,
         };
         
         // Store the request
-        state.swap_requests.insert(request_id.clone(), swap_request);
+        self.swap_requests.insert(request_id.clone(), swap_request);
         
         // Add to user's swaps
-        let user_swaps = state.user_swaps.entry(user_id)
+        let user_swaps = self.user_swaps.entry(user_id)
             .or_insert_with(Vec::new);
             
         user_swaps.push(request_id.clone());
         
+        Ok(request_id)
+    }
+    
+    /// Creates a new cross-chain swap request
+    pub fn create_swap_request(
+        user_id: String,
+        source_chain: String,
+        target_chain: String,
+        source_asset: String,
+        target_asset: String,
+        amount: u128,
+        max_slippage_bps: u32,
+        target_address: String,
+    ) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        let mut state = Self::load();
+        
+        // Parse blockchains
+        let source_chain_enum = Blockchain::from_string(&source_chain)
+            .unwrap_or_else(|_| panic!("Invalid source blockchain: {}", source_chain));
+            
+        let target_chain_enum = Blockchain::from_string(&target_chain)
+            .unwrap_or_else(|_| panic!("Invalid target blockchain: {}", target_chain));
+            
+        let request_id = state.insert_swap_request(
+            user_id,
+            source_chain_enum,
+            target_chain_enum,
+            source_asset,
+            target_asset,
+            amount,
+            max_slippage_bps,
+            target_address,
+        ).unwrap_or_else(|e| panic!("{}", e));
+        
         state.save();
         
         request_id
@@ -462,6 +695,78 @@ impl CrossChainContract {
             .unwrap_or_else(|_| "Failed to serialize routes".to_string())
     }
     
+    /// Gets the cheapest route for swapping `amount` of `source_asset` into `target_asset`
+    pub fn get_optimal_route(
+        source_chain: String,
+        target_chain: String,
+        source_asset: String,
+        target_asset: String,
+        amount: u128,
+    ) -> String {
+        let source_chain_enum = Blockchain::from_string(&source_chain)
+            .unwrap_or_else(|_| panic!("Invalid source blockchain: {}", source_chain));
+
+        let target_chain_enum = Blockchain::from_string(&target_chain)
+            .unwrap_or_else(|_| panic!("Invalid target blockchain: {}", target_chain));
+
+        let state = Self::load();
+
+        let legs = state.route_legs(source_chain_enum, target_chain_enum, &source_asset, &target_asset);
+
+        let route = SwapRoute::optimal(
+            &legs,
+            source_chain_enum,
+            target_chain_enum,
+            &source_asset,
+            &target_asset,
+            amount,
+        ).unwrap_or_else(|| panic!("No route from {} to {} for amount {}", source_chain, target_chain, amount));
+
+        serde_json::to_string(&route)
+            .unwrap_or_else(|_| "Failed to serialize route".to_string())
+    }
+
+    /// Route graph for swapping between two chains: external chains connect through
+    /// L1X, and swaps within one chain settle directly
+    fn route_legs(
+        &self,
+        source_chain: Blockchain,
+        target_chain: Blockchain,
+        source_asset: &str,
+        target_asset: &str,
+    ) -> Vec<SwapRoute> {
+        let source_liquidity = self.liquidity.get(source_asset)
+            .unwrap_or_else(|| panic!("No liquidity for source asset {}", source_asset));
+
+        let target_liquidity = self.liquidity.get(target_asset)
+            .unwrap_or_else(|| panic!("No liquidity for target asset {}", target_asset));
+
+        let leg = |from: Blockchain, to: Blockchain, fee_bps: u32| SwapRoute {
+            source_chain: from,
+            target_chain: to,
+            source_asset: source_asset.to_string(),
+            target_asset: target_asset.to_string(),
+            fee_bps,
+            estimated_time_seconds: FeeEstimate::SECONDS_PER_HOP,
+            liquidity: std::cmp::min(*source_liquidity, *target_liquidity),
+        };
+
+        let mut legs = Vec::new();
+
+        if source_chain == target_chain {
+            legs.push(leg(source_chain, target_chain, SAME_CHAIN_SWAP_FEE_BPS));
+        }
+
+        for chain in [source_chain, target_chain] {
+            if chain != Blockchain::L1X {
+                legs.push(leg(chain, Blockchain::L1X, CROSS_CHAIN_SWAP_FEE_BPS));
+                legs.push(leg(Blockchain::L1X, chain, CROSS_CHAIN_SWAP_FEE_BPS));
+            }
+        }
+
+        legs
+    }
+
     /// Gets a quote for a cross-chain swap
     pub fn get_swap_quote(
         source_chain: String,
@@ -487,26 +792,12 @@ impl CrossChainContract {
             .unwrap_or_else(|| panic!("No liquidity for target asset {}", target_asset));
             
         // Calculate quote
-        // This is a simplified example - in a real implementation,
-        // this would use actual exchange rates and market data
-        
-        // Mock exchange rates
-        let exchange_rate = match (source_asset.as_str(), target_asset.as_str()) {
-            ("BTC", "ETH") => 16.5,     // 1 BTC = 16.5 ETH
-            ("ETH", "BTC") => 0.06,     // 1 ETH = 0.06 BTC
-            ("BTC", "L1X") => 2500.0,   // 1 BTC = 2500 L1X
-            ("ETH", "L1X") => 150.0,    // 1 ETH = 150 L1X
-            ("L1X", "BTC") => 0.0004,   // 1 L1X = 0.0004 BTC
-            ("L1X", "ETH") => 0.0066,   // 1 L1X = 0.0066 ETH
-            ("USDC", "USDT") => 1.001,  // 1 USDC = 1.001 USDT
-            ("USDT", "USDC") => 0.999,  // 1 USDT = 0.999 USDC
-            _ => 1.0,                   // Default 1:1 for unknown pairs
-        };
-        
+        let exchange_rate = mock_exchange_rate(&source_asset, &target_asset);
+
         let estimated_target_amount = (amount as f64 * exchange_rate) as u128;
         
         // Calculate fee
-        let fee_bps = if source_chain == target_chain { 25 } else { 50 };
+        let fee_bps = if source_chain == target_chain { SAME_CHAIN_SWAP_FEE_BPS } else { CROSS_CHAIN_SWAP_FEE_BPS };
         let fee_amount = (estimated_target_amount * fee_bps as u128) / 10000;
         
         // Final amount after fees
@@ -525,7 +816,43 @@ impl CrossChainContract {
         serde_json::to_string(&quote)
             .unwrap_or_else(|_| "Failed to serialize quote".to_string())
     }
-    
+
+    /// Estimates the total fees for a cross-chain swap before it is initiated
+    pub fn estimate_swap_fee(
+        source_chain: String,
+        target_chain: String,
+        source_asset: String,
+        target_asset: String,
+        amount: u128,
+    ) -> String {
+        // Parse blockchains
+        let source_chain_enum = Blockchain::from_string(&source_chain)
+            .unwrap_or_else(|_| panic!("Invalid source blockchain: {}", source_chain));
+
+        let target_chain_enum = Blockchain::from_string(&target_chain)
+            .unwrap_or_else(|_| panic!("Invalid target blockchain: {}", target_chain));
+
+        // Get liquidity
+        let state = Self::load();
+
+        let _ = state.liquidity.get(&source_asset)
+            .unwrap_or_else(|| panic!("No liquidity for source asset {}", source_asset));
+
+        let _ = state.liquidity.get(&target_asset)
+            .unwrap_or_else(|| panic!("No liquidity for target asset {}", target_asset));
+
+        let estimate = FeeEstimate::calculate(
+            source_chain_enum,
+            target_chain_enum,
+            &source_asset,
+            &target_asset,
+            amount,
+        );
+
+        serde_json::to_string(&estimate)
+            .unwrap_or_else(|_| "Failed to serialize fee estimate".to_string())
+    }
+
     /// Adds liquidity to the contract (for testing purposes)
     pub fn add_liquidity(asset: String, amount: u128) -> String {
         let mut state = Self::load();
@@ -542,6 +869,34 @@ impl CrossChainContract {
     }
 }
 
+/// Submits a same-chain swap for another contract, returning the swap request ID
+pub fn submit_swap_request(
+    user_id: &str,
+    chain: Blockchain,
+    source_asset: &str,
+    target_asset: &str,
+    amount: u128,
+    max_slippage_bps: u32,
+    target_address: &str,
+) -> Result<String, String> {
+    let mut state = CrossChainContract::load();
+    
+    let request_id = state.insert_swap_request(
+        user_id.to_string(),
+        chain,
+        chain,
+        source_asset.to_string(),
+        target_asset.to_string(),
+        amount,
+        max_slippage_bps,
+        target_address.to_string(),
+    )?;
+    
+    state.save();
+    
+    Ok(request_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -597,4 +952,77 @@ mod tests {
         swap.status = SwapStatus::Completed;
         assert_eq!(swap.status, SwapStatus::Completed);
     }
+
+    #[test]
+    fn test_fee_estimate_multi_hop() {
+        // ETH -> SOL is routed via L1X (two hops)
+        let multi_hop = FeeEstimate::calculate(
+            Blockchain::Ethereum,
+            Blockchain::Solana,
+            "ETH",
+            "USDC",
+            1_000_000,
+        );
+
+        // L1X -> ETH is a single hop
+        let single_hop = FeeEstimate::calculate(
+            Blockchain::L1X,
+            Blockchain::Ethereum,
+            "ETH",
+            "USDC",
+            1_000_000,
+        );
+
+        assert_eq!(Blockchain::Ethereum.hop_count(&Blockchain::Solana), 2);
+        assert_eq!(Blockchain::L1X.hop_count(&Blockchain::Ethereum), 1);
+
+        assert_eq!(multi_hop.validator_fee, 2 * FeeEstimate::VALIDATOR_FEE_PER_HOP);
+        assert_eq!(single_hop.validator_fee, FeeEstimate::VALIDATOR_FEE_PER_HOP);
+        assert_eq!(multi_hop.protocol_fee, single_hop.protocol_fee);
+        assert!(multi_hop.gas_estimate > single_hop.gas_estimate);
+        assert!(multi_hop.total_fee_usd > single_hop.total_fee_usd);
+        assert!(multi_hop.estimated_time_seconds > single_hop.estimated_time_seconds);
+    }
+
+    #[test]
+    fn test_optimal_route_picks_cheapest() {
+        let leg = |from: Blockchain, to: Blockchain, fee_bps: u32, time: u64, liquidity: u128| SwapRoute {
+            source_chain: from,
+            target_chain: to,
+            source_asset: "ETH".to_string(),
+            target_asset: "USDC".to_string(),
+            fee_bps,
+            estimated_time_seconds: time,
+            liquidity,
+        };
+
+        let legs = vec![
+            // Direct bridge: 90 bps
+            leg(Blockchain::Ethereum, Blockchain::Solana, 90, 600, 1_000_000),
+            // Via L1X: 30 + 30 = 60 bps in 240 seconds
+            leg(Blockchain::Ethereum, Blockchain::L1X, 30, 120, 1_000_000),
+            leg(Blockchain::L1X, Blockchain::Solana, 30, 120, 1_000_000),
+            // Via Arbitrum: 20 + 40 = 60 bps, but slower
+            leg(Blockchain::Ethereum, Blockchain::Arbitrum, 20, 300, 1_000_000),
+            leg(Blockchain::Arbitrum, Blockchain::Solana, 40, 300, 1_000_000),
+        ];
+
+        let route = SwapRoute::optimal(
+            &legs, Blockchain::Ethereum, Blockchain::Solana, "ETH", "USDC", 500_000,
+        ).unwrap();
+        assert_eq!(route.fee_bps, 60);
+        assert_eq!(route.estimated_time_seconds, 240);
+        assert_eq!(route.source_chain, Blockchain::Ethereum);
+        assert_eq!(route.target_chain, Blockchain::Solana);
+
+        // Legs too shallow for the amount are skipped
+        let mut shallow = legs.clone();
+        shallow[2].liquidity = 100;
+        let route = SwapRoute::optimal(
+            &shallow, Blockchain::Ethereum, Blockchain::Solana, "ETH", "USDC", 500_000,
+        ).unwrap();
+        assert_eq!((route.fee_bps, route.estimated_time_seconds), (60, 600));
+
+        assert!(SwapRoute::optimal(&legs, Blockchain::Solana, Blockchain::Ethereum, "ETH", "USDC", 1).is_none());
+    }
 }
\ No newline at end of file
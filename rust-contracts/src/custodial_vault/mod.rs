@@ -9,7 +9,13 @@ use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
 use crate::allocation::{AllocationSet, AssetAllocation};
-use crate::take_profit::{TakeProfitStrategy, TakeProfitType};
+use crate::take_profit::{ProfitDestination, TakeProfitStrategy, TakeProfitType};
+use crate::auto_compound::AutoCompoundConfig;
+use crate::rebalance::SuppressedTrade;
+use crate::validation::{validate_amount_nonzero, validate_basis_points, validate_non_empty_string};
+
+/// Highest performance fee a vault may charge (in basis points, 30%)
+pub const MAX_PERFORMANCE_FEE_BPS: u32 = 3000;
 
 /// Status of a vault
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
@@ -22,6 +28,9 @@ pub enum VaultStatus {
     
     /// Vault is closed (no operations possible)
     Closed,
+    
+    /// Vault is being handed over to a non-custodial vault (no operations possible)
+    Migrating,
 }
 
 /// X-Talk swap request for cross-chain operations
@@ -66,6 +75,282 @@ pub struct CustodialVault {
     
     /// Timestamp of the last rebalance
     pub last_rebalance: u64,
+    
+    /// Multi-sig wallet whose signers must approve rebalances (if any)
+    #[serde(default)]
+    pub multisig_wallet: Option<String>,
+    
+    /// Maximum total amount traded per rebalance (absolute)
+    #[serde(default)]
+    pub max_rebalance_value: Option<u128>,
+    
+    /// Maximum total amount traded per rebalance (in basis points of total value)
+    #[serde(default)]
+    pub max_rebalance_bp: Option<u32>,
+    
+    /// Reinvestment of take-profit proceeds
+    #[serde(default)]
+    pub auto_compound: AutoCompoundConfig,
+    
+    /// Maximum swap fee plus gas of a trade relative to its value (in basis points)
+    #[serde(default)]
+    pub max_trade_cost_bp: Option<u32>,
+    
+    /// Highest portfolio value a performance fee has been charged up to
+    #[serde(default)]
+    pub high_water_mark: u128,
+    
+    /// Performance fee on gains above the high-water mark (in basis points)
+    #[serde(default)]
+    pub performance_fee_bps: u32,
+    
+    /// Account credited with performance fees
+    #[serde(default)]
+    pub fee_recipient: Option<String>,
+    
+    /// Rebalance operation whose swaps are still in flight (if any)
+    #[serde(default)]
+    pub in_progress_rebalance: Option<String>,
+    
+    /// Take-profit proceeds held in the vault
+    #[serde(default)]
+    pub realized_profits: u128,
+    
+    /// Take-profit proceeds queued for the owner to claim
+    #[serde(default)]
+    pub pending_profit_withdrawals: Vec<ProfitWithdrawal>,
+    
+    /// Signature of the migration payload issued for the vault (while migrating)
+    #[serde(default)]
+    pub migration_signature: Option<String>,
+}
+
+/// Configuration of a custodial vault handed over to a non-custodial vault
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct MigrationPayload {
+    /// Custodial vault being migrated
+    pub source_vault_id: String,
+    
+    /// Owner of the vault
+    pub owner: String,
+    
+    /// Allocations of the vault
+    pub allocations: AllocationSet,
+    
+    /// Take profit strategy (if any)
+    pub take_profit: Option<TakeProfitStrategy>,
+    
+    /// Total value of the vault when the migration started
+    pub total_value: u128,
+    
+    /// Timestamp when the payload was issued
+    pub issued_at: u64,
+    
+    /// Hex keccak256 of the fields above, recorded on the source vault
+    pub signature: String,
+}
+
+impl MigrationPayload {
+    /// Hex keccak256 of the payload's contents, excluding `signature`
+    pub fn digest(&self) -> String {
+        let contents = (
+            &self.source_vault_id,
+            &self.owner,
+            &self.allocations,
+            &self.take_profit,
+            self.total_value,
+            self.issued_at,
+        );
+        let encoded = contents.try_to_vec().unwrap_or_default();
+        
+        l1x_sdk::env::keccak256(&encoded).iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Take-profit proceeds queued for withdrawal to the vault owner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct ProfitWithdrawal {
+    /// Amount queued (scaled)
+    pub amount: u128,
+    
+    /// Asset the profit was taken into
+    pub asset: String,
+    
+    /// Timestamp when the withdrawal was queued
+    pub queued_at: u64,
+}
+
+/// A page of a user's vaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustodialVaultPage {
+    /// Vaults on this page
+    pub vaults: Vec<CustodialVault>,
+    
+    /// Total number of vaults owned by the user
+    pub total: u32,
+    
+    /// Index of the first vault on this page
+    pub offset: u32,
+    
+    /// Maximum number of vaults per page
+    pub limit: u32,
+}
+
+/// Outcome of one vault in a batch rebalance
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BatchRebalanceStatus {
+    /// Swaps were generated and the allocations updated
+    Rebalanced,
+    
+    /// Nothing to do: inactive, within its drift threshold or rate limited
+    Skipped,
+    
+    /// The rebalance failed; the vault was left unchanged
+    Failed,
+}
+
+/// Result of one vault in a batch rebalance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRebalanceResult {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Outcome
+    pub status: BatchRebalanceStatus,
+    
+    /// Why the vault was skipped or failed, or a summary of the rebalance
+    pub message: String,
+    
+    /// ID of the rebalance operation (set once swaps were planned)
+    pub rebalance_id: Option<String>,
+    
+    /// Number of swaps in the operation
+    pub transaction_count: usize,
+}
+
+/// Inputs of one run of the shared rebalance pipeline
+pub struct RebalanceRequest<'a> {
+    /// Operation ID; derived from the vault ID and block time when `None`
+    pub rebalance_id: Option<String>,
+    
+    /// What triggered the rebalance ("manual", "drift", "scheduled", "batch" or "compound")
+    pub trigger: &'static str,
+    
+    /// Asset prices
+    pub prices: &'a [(String, u128)],
+    
+    /// Vault holdings to value at `prices`, when known
+    pub balances: Option<&'a [(String, u128)]>,
+    
+    /// Price feed holding the registered asset decimals
+    pub price_feed: &'a crate::price_feed::PriceFeedContract,
+    
+    /// Protocol pause switch, checked before the vault is touched
+    pub emergency: &'a crate::emergency::EmergencyPauseContract,
+    
+    /// Protocol fee rate charged on the value traded (in basis points)
+    pub protocol_fee_bps: u32,
+    
+    /// Whether the swaps are executed or only simulated
+    pub mode: crate::rebalance::RebalanceMode,
+}
+
+/// Outcome of one run of the shared rebalance pipeline
+pub enum RebalanceRun {
+    /// Nothing to trade; the check was recorded on the vault
+    Idle(String),
+    
+    /// Swaps were submitted
+    Submitted {
+        message: String,
+        operation: crate::rebalance::RebalanceOperation,
+        snapshot: crate::portfolio::PortfolioSnapshot,
+    },
+    
+    /// Submitting the swaps failed; the vault is left unchanged
+    SubmitFailed {
+        message: String,
+        operation: crate::rebalance::RebalanceOperation,
+    },
+}
+
+impl RebalanceRun {
+    /// Records the run in the other contracts once the vault state is saved
+    pub fn finish(self, vault_id: &str) -> String {
+        match self {
+            RebalanceRun::Idle(message) => message,
+            RebalanceRun::Submitted { message, operation, snapshot } => {
+                crate::fee_collector::FeeCollectorContract::record_fee(
+                    crate::fee_collector::VAULT_VALUE_ASSET,
+                    operation.protocol_fee,
+                );
+                
+                crate::rebalance::history::record_operation(operation);
+                crate::portfolio::snapshot_store::store_snapshot(vault_id, snapshot);
+                message
+            },
+            RebalanceRun::SubmitFailed { message, operation } => {
+                // Kept in the history so the failed submission can be inspected
+                crate::rebalance::history::record_operation(operation);
+                message
+            },
+        }
+    }
+}
+
+/// Before/after view of one asset in a simulated rebalance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedAllocation {
+    /// Asset ID
+    pub asset_id: String,
+    
+    /// Target percentage (in basis points)
+    pub target_percentage: u32,
+    
+    /// Percentage before the planned transactions (in basis points)
+    pub before_percentage: u32,
+    
+    /// Percentage after the planned transactions (in basis points)
+    pub after_percentage: u32,
+}
+
+/// Result of a dry-run rebalance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalanceSimulation {
+    /// Whether the vault would be rebalanced now
+    pub needs_rebalancing: bool,
+    
+    /// Planned `(source, target, amount)` swaps
+    pub transactions: Vec<(String, String, u128)>,
+    
+    /// Per-asset before/after percentages
+    pub allocations: Vec<SimulatedAllocation>,
+    
+    /// Estimated gas cost of executing the plan
+    pub estimated_cost: u128,
+    
+    /// Planned swaps dropped because their cost outweighed the drift they corrected
+    pub suppressed_trades: Vec<SuppressedTrade>,
+}
+
+/// Activity of a registered keeper
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct KeeperStats {
+    /// Rebalances that executed transactions on the keeper's call
+    pub executions: u64,
+    
+    /// Rewards credited to the keeper (internal accounting)
+    pub reward_balance: u128,
+}
+
+impl KeeperStats {
+    /// Records an executed rebalance and credits `reward`
+    pub fn record_execution(&mut self, reward: u128) {
+        self.executions += 1;
+        self.reward_balance = self.reward_balance.saturating_add(reward);
+    }
 }
 
 /// Custodial Vault contract
@@ -75,15 +360,29 @@ const STORAGE_CONTRACT_KEY: &[u8] = b"CUSTODIAL_VAULT";
 pub struct CustodialVaultContract {
     vaults: std::collections::HashMap<String, CustodialVault>, // Vault ID -> Vault
     user_vaults: std::collections::HashMap<String, Vec<String>>, // User ID -> Vault IDs
+    reentrancy_lock: bool, // Set while a fund-flow operation is running
+    accrued_fees: std::collections::HashMap<String, u128>, // Fee recipient -> Performance fees credited
+    admin: String, // Manages the keeper registry
+    keepers: std::collections::HashMap<String, KeeperStats>, // Keeper -> Activity
+    keeper_reward: u128, // Credited to a keeper per executed rebalance
+    processed_rebalance_ids: std::collections::HashSet<(String, String)>, // (Vault ID, rebalance ID) pairs passed to rebalance_idempotent
 }
 
 #[l1x_sdk::contract]
 impl CustodialVaultContract {
     fn load() -> Self {
-        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+        let mut state = match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
             Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
             None => panic!("The contract isn't initialized"),
+        };
+        
+        // Sets without a daily rebalance limit of their own follow the governance one
+        let max_rebalances_per_day = crate::governance::max_rebalances_per_day();
+        for vault in state.vaults.values_mut() {
+            vault.allocations.governance_max_rebalances_per_day = max_rebalances_per_day;
         }
+        
+        state
     }
 
     fn save(&mut self) {
@@ -94,18 +393,47 @@ impl CustodialVaultContract {
         let mut state = Self {
             vaults: std::collections::HashMap::new(),
             user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: l1x_sdk::env::caller(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 0,
+            processed_rebalance_ids: std::collections::HashSet::new(),
         };
 
         state.save()
     }
     
+    /// Whether a fund-flow operation is currently running
+    fn reentrancy_locked() -> bool {
+        Self::load().reentrancy_lock
+    }
+    
+    /// Persists the reentrancy lock so nested calls see it
+    fn set_reentrancy_lock(locked: bool) {
+        let mut state = Self::load();
+        state.reentrancy_lock = locked;
+        state.save();
+    }
+    
+    /// Takes the reentrancy lock for the rest of the calling operation
+    fn enter_non_reentrant() -> ReentrancyGuard {
+        ReentrancyGuard::acquire(Self::reentrancy_locked, Self::set_reentrancy_lock)
+    }
+    
     /// Creates a new vault for a user
     pub fn create_vault(owner: String, vault_id: String, name: String, description: String, drift_threshold_bp: u32) -> String {
+        validate_non_empty_string(&owner, "owner")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_non_empty_string(&vault_id, "vault_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_basis_points(drift_threshold_bp, "drift_threshold_bp")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
         let mut state = Self::load();
         
-        if state.vaults.contains_key(&vault_id) {
-            panic!("Vault with this ID already exists");
-        }
+        // A threshold of 0 means the protocol-wide default
+        let drift_threshold_bp = crate::governance::drift_threshold_or_global(drift_threshold_bp);
         
         // Create a new vault
         let vault = CustodialVault {
@@ -117,18 +445,102 @@ impl CustodialVaultContract {
             total_value: 0,
             created_at: l1x_sdk::env::block_timestamp(),
             last_rebalance: 0,
+            multisig_wallet: None,
+            max_rebalance_value: None,
+            max_rebalance_bp: None,
+            auto_compound: AutoCompoundConfig::default(),
+            max_trade_cost_bp: None,
+            high_water_mark: 0,
+            performance_fee_bps: 0,
+            fee_recipient: None,
+            in_progress_rebalance: None,
+            realized_profits: 0,
+            pending_profit_withdrawals: Vec::new(),
+            migration_signature: None,
         };
         
-        // Add vault to contract state
-        state.vaults.insert(vault_id.clone(), vault);
+        state.insert_vault(vault)
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        state.save();
+        
+        crate::events::emit_vault_status_changed_event(&vault_id, None, VaultStatus::Active, &owner);
         
-        // Add vault to user's vault list
-        let user_vaults = state.user_vaults.entry(owner.clone()).or_insert_with(Vec::new);
-        user_vaults.push(vault_id.clone());
+        format!("Vault {} created for user {}", vault_id, owner)
+    }
+    
+    /// Creates a vault for `new_owner` with the allocations and strategy of an existing one
+    ///
+    /// The copy starts empty, with current allocations at their targets and
+    /// no rebalance or take-profit history.
+    pub fn clone_vault(source_vault_id: String, new_vault_id: String, new_owner: String) -> String {
+        validate_non_empty_string(&new_vault_id, "new_vault_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_non_empty_string(&new_owner, "new_owner")
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        let mut state = Self::load();
+        
+        let source = state.vaults.get(&source_vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", source_vault_id));
+            
+        if source.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can clone a vault");
+        }
         
+        let vault = source.clone_as(new_vault_id.clone(), new_owner.clone(), l1x_sdk::env::block_timestamp());
+        
+        state.insert_vault(vault)
+            .unwrap_or_else(|err| panic!("{}", err));
+            
         state.save();
         
-        format!("Vault {} created for user {}", vault_id, owner)
+        crate::events::emit_vault_status_changed_event(&new_vault_id, None, VaultStatus::Active, &new_owner);
+        crate::events::emit_vault_cloned_event(&source_vault_id, &new_vault_id, &new_owner);
+        
+        format!("Vault {} cloned from {} for user {}", new_vault_id, source_vault_id, new_owner)
+    }
+    
+    /// Starts handing a vault over to a non-custodial vault
+    ///
+    /// The vault stops operating and the returned payload, carrying its
+    /// allocations and take profit strategy, completes the migration with
+    /// `NonCustodialVaultContract::complete_migration_from_custodial`.
+    pub fn initiate_migration_to_non_custodial(vault_id: String) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can migrate a vault");
+        }
+        
+        let old_status = vault.status;
+        let payload = vault.begin_migration(l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("Cannot migrate vault {}: {}", vault_id, err));
+        let owner = vault.owner.clone();
+        
+        state.save();
+        
+        crate::events::emit_vault_status_changed_event(&vault_id, Some(old_status), VaultStatus::Migrating, &owner);
+        crate::events::emit_vault_migration_initiated_event(&vault_id, &owner, &payload.signature);
+        
+        serde_json::to_string(&payload)
+            .unwrap_or_else(|_| "Failed to serialize migration payload".to_string())
+    }
+    
+    /// Adds a vault to the contract state and its owner's vault list
+    fn insert_vault(&mut self, vault: CustodialVault) -> Result<(), &'static str> {
+        if self.vaults.contains_key(&vault.id) {
+            return Err("Vault with this ID already exists");
+        }
+        
+        let user_vaults = self.user_vaults.entry(vault.owner.clone()).or_insert_with(Vec::new);
+        user_vaults.push(vault.id.clone());
+        
+        self.vaults.insert(vault.id.clone(), vault);
+        Ok(())
     }
     
     /// Gets a vault by ID
@@ -138,10 +550,59 @@ impl CustodialVaultContract {
         let vault = state.vaults.get(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        serde_json::to_string(vault)
+        let mut vault_json = serde_json::to_value(vault)
+            .unwrap_or_else(|_| panic!("Failed to serialize vault"));
+        vault_json["pending_profit_balance"] = serde_json::json!(vault.pending_profit_balance());
+        
+        serde_json::to_string(&vault_json)
             .unwrap_or_else(|_| "Failed to serialize vault".to_string())
     }
     
+    /// Gets the maximum drawdown (in percent) over a vault's recorded snapshots
+    pub fn get_vault_max_drawdown(vault_id: String) -> String {
+        let state = Self::load();
+        
+        if !state.vaults.contains_key(&vault_id) {
+            panic!("Vault not found: {}", vault_id);
+        }
+        
+        let snapshots = crate::portfolio::snapshot_store::vault_snapshots(&vault_id);
+        let max_drawdown = crate::portfolio::Portfolio::max_drawdown(&snapshots);
+        
+        serde_json::to_string(&max_drawdown)
+            .unwrap_or_else(|_| "Failed to serialize drawdown".to_string())
+    }
+    
+    /// Gets each asset's contribution to a vault's return between `from_ts` and `to_ts`
+    pub fn get_vault_attribution(vault_id: String, from_ts: u64, to_ts: u64) -> String {
+        let state = Self::load();
+        
+        if !state.vaults.contains_key(&vault_id) {
+            panic!("Vault not found: {}", vault_id);
+        }
+        
+        let snapshots: Vec<crate::portfolio::PortfolioSnapshot> = crate::portfolio::snapshot_store::vault_snapshots(&vault_id)
+            .into_iter()
+            .filter(|snapshot| snapshot.timestamp >= from_ts && snapshot.timestamp <= to_ts)
+            .collect();
+        let attribution = crate::portfolio::Portfolio::compute_attribution(&snapshots);
+        
+        serde_json::to_string(&attribution)
+            .unwrap_or_else(|_| "Failed to serialize attribution".to_string())
+    }
+    
+    /// Gets the 0-100 composite health score of a vault
+    pub fn get_vault_health_score(vault_id: String) -> u32 {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let snapshots = crate::portfolio::snapshot_store::vault_snapshots(&vault_id);
+        
+        crate::portfolio::Portfolio::health_score(&vault.allocations, &snapshots)
+    }
+    
     /// Gets all vaults for a user
     pub fn get_user_vaults(owner: String) -> String {
         let state = Self::load();
@@ -158,417 +619,1356 @@ impl CustodialVaultContract {
             .unwrap_or_else(|_| "Failed to serialize vaults".to_string())
     }
     
-    /// Updates vault settings
-    pub fn update_vault(vault_id: String, drift_threshold_bp: Option<u32>, status: Option<String>) -> String {
+    /// Gets the combined value and allocations of all of a user's vaults
+    pub fn get_aggregated_portfolio(owner: String) -> String {
+        let state = Self::load();
+        
+        let user_vault_ids = state.user_vaults.get(&owner)
+            .cloned()
+            .unwrap_or_default();
+            
+        let vaults: Vec<&CustodialVault> = user_vault_ids.iter()
+            .filter_map(|id| state.vaults.get(id))
+            .collect();
+            
+        let view = crate::portfolio::Portfolio::aggregate_vaults(user_vault_ids.clone(), &vaults);
+        
+        serde_json::to_string(&view)
+            .unwrap_or_else(|_| "Failed to serialize portfolio".to_string())
+    }
+    
+    /// Gets one page of a user's vaults
+    pub fn get_user_vaults_paginated(owner: String, offset: u32, limit: u32) -> String {
+        let state = Self::load();
+        
+        let page = state.user_vaults_page(&owner, offset, limit);
+        
+        serde_json::to_string(&page)
+            .unwrap_or_else(|_| "Failed to serialize vaults".to_string())
+    }
+    
+    /// Collects the vaults in `[offset, offset + limit)` of a user's vault list
+    fn user_vaults_page(&self, owner: &str, offset: u32, limit: u32) -> CustodialVaultPage {
+        let user_vault_ids = self.user_vaults.get(owner)
+            .cloned()
+            .unwrap_or_default();
+            
+        let vaults = user_vault_ids.iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .filter_map(|id| self.vaults.get(id).cloned())
+            .collect();
+            
+        CustodialVaultPage {
+            vaults,
+            total: user_vault_ids.len() as u32,
+            offset,
+            limit,
+        }
+    }
+    
+    /// Requires rebalances of a vault to be approved by a multi-sig wallet
+    pub fn set_multisig_wallet(vault_id: String, wallet_id: Option<String>) -> String {
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        // Update drift threshold if provided
-        if let Some(threshold) = drift_threshold_bp {
-            vault.allocations.drift_threshold_bp = threshold;
-        }
-        
-        // Update status if provided
-        if let Some(status_str) = status {
-            vault.status = match status_str.as_str() {
-                "active" => VaultStatus::Active,
-                "paused" => VaultStatus::Paused,
-                "closed" => VaultStatus::Closed,
-                _ => panic!("Invalid vault status: {}", status_str),
-            };
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can change the multi-sig wallet");
         }
         
+        vault.multisig_wallet = wallet_id;
         state.save();
         
-        format!("Vault {} updated", vault_id)
+        format!("Multi-sig wallet updated for vault {}", vault_id)
     }
     
-    /// Deposits funds into a vault
-    pub fn deposit(vault_id: String, amount: u128) -> String {
+    /// Sets the per-rebalance trading budget for a vault
+    ///
+    /// `max_value` caps the total traded amount outright and `max_bp` caps it
+    /// relative to the vault's total value; either may be cleared with `None`.
+    pub fn set_rebalance_budget(vault_id: String, max_value: Option<u128>, max_bp: Option<u32>) -> String {
+        if let Some(bp) = max_bp {
+            validate_basis_points(bp, "max_rebalance_bp")
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active {
-            panic!("Cannot deposit into a non-active vault");
-        }
-        
-        vault.total_value = vault.total_value.checked_add(amount)
-            .unwrap_or_else(|| panic!("Overflow when adding deposit"));
-            
+        vault.max_rebalance_value = max_value;
+        vault.max_rebalance_bp = max_bp;
         state.save();
         
-        format!("Deposited {} into vault {}", amount, vault_id)
+        format!("Rebalance budget updated for vault {}", vault_id)
     }
     
-    /// Withdraws funds from a vault
-    pub fn withdraw(vault_id: String, amount: u128) -> String {
+    /// Releases a vault's rebalance lock left behind by an operation that never settled
+    pub fn force_clear_rebalance_lock(vault_id: String) -> String {
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active {
-            panic!("Cannot withdraw from a non-active vault");
+        let caller = l1x_sdk::env::caller();
+        if vault.owner != caller {
+            panic!("Only the vault owner can clear its rebalance lock");
         }
         
-        if vault.total_value < amount {
-            panic!("Insufficient funds in vault");
-        }
-        
-        vault.total_value = vault.total_value.checked_sub(amount)
-            .unwrap_or_else(|| panic!("Underflow when subtracting withdrawal"));
-            
+        let rebalance_id = vault.in_progress_rebalance.take()
+            .unwrap_or_else(|| panic!("No rebalance in progress for vault {}", vault_id));
         state.save();
         
-        format!("Withdrew {} from vault {}", amount, vault_id)
+        crate::events::emit_rebalance_lock_cleared_event(&vault_id, &rebalance_id, &caller);
+        
+        format!("Cleared rebalance lock of {} on vault {}", rebalance_id, vault_id)
     }
     
-    /// Sets up take profit strategy for a vault
-    pub fn set_take_profit(vault_id: String, strategy_type: String, target_percentage: Option<u32>, interval_seconds: Option<u64>) -> String {
+    /// Configures the vault's performance fee
+    ///
+    /// The high-water mark starts at the vault's current value so gains made
+    /// before the fee was configured are never charged.
+    pub fn set_performance_fee(vault_id: String, performance_fee_bps: u32, fee_recipient: String) -> String {
+        validate_basis_points(performance_fee_bps, "performance_fee_bps")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_non_empty_string(&fee_recipient, "fee_recipient")
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        if performance_fee_bps > MAX_PERFORMANCE_FEE_BPS {
+            panic!("Performance fee cannot exceed {} bps", MAX_PERFORMANCE_FEE_BPS);
+        }
+        
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active {
-            panic!("Cannot set take profit for a non-active vault");
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can set the performance fee");
         }
         
-        // Create appropriate strategy based on type
-        let take_profit_type = match strategy_type.as_str() {
-            "manual" => TakeProfitType::Manual,
+        vault.performance_fee_bps = performance_fee_bps;
+        vault.fee_recipient = Some(fee_recipient.clone());
+        vault.high_water_mark = vault.high_water_mark.max(vault.total_value);
+        state.save();
+        
+        format!("Performance fee of {} bps set for vault {}, paid to {}", performance_fee_bps, vault_id, fee_recipient)
+    }
+    
+    /// Gets the performance fees credited to a recipient
+    pub fn get_accrued_fees(fee_recipient: String) -> u128 {
+        let state = Self::load();
+        
+        state.accrued_fees.get(&fee_recipient).copied().unwrap_or(0)
+    }
+    
+    /// Panics unless the caller is the contract admin
+    fn assert_admin(&self) {
+        if self.admin != l1x_sdk::env::caller() {
+            panic!("Only admin can manage keepers");
+        }
+    }
+    
+    /// Whether `caller` may trigger an automatic rebalance of `vault_id`
+    fn can_auto_rebalance(&self, vault_id: &str, caller: &str) -> bool {
+        self.keepers.contains_key(caller)
+            || self.vaults.get(vault_id).is_some_and(|vault| vault.owner == caller)
+    }
+    
+    /// Registers a keeper allowed to run automatic rebalances
+    pub fn add_keeper(keeper: String) -> String {
+        validate_non_empty_string(&keeper, "keeper")
+            .unwrap_or_else(|err| panic!("{}", err));
             
-            "percentage" => {
-                let percentage = target_percentage
-                    .unwrap_or_else(|| panic!("Percentage required for percentage-based take profit"));
-                    
-                TakeProfitType::Percentage { percentage }
-            },
-            
-            "time" => {
-                let interval = interval_seconds
-                    .unwrap_or_else(|| panic!("Interval required for time-based take profit"));
-                    
-                TakeProfitType::Time { interval_seconds: interval }
-            },
-            
-            _ => panic!("Invalid take profit strategy type: {}", strategy_type),
-        };
+        let mut state = Self::load();
+        state.assert_admin();
         
-        let mut strategy = TakeProfitStrategy::new(take_profit_type);
-        strategy.set_baseline(vault.total_value);
-        vault.take_profit = Some(strategy);
+        if state.keepers.contains_key(&keeper) {
+            panic!("Keeper already registered: {}", keeper);
+        }
         
+        state.keepers.insert(keeper.clone(), KeeperStats::default());
         state.save();
         
-        format!("Take profit strategy set for vault {}", vault_id)
+        format!("Keeper {} registered", keeper)
     }
     
-    /// Gets take profit strategy for a vault
-    pub fn get_take_profit(vault_id: String) -> String {
+    /// Removes a keeper; its accrued rewards are dropped with it
+    pub fn remove_keeper(keeper: String) -> String {
+        let mut state = Self::load();
+        state.assert_admin();
+        
+        if state.keepers.remove(&keeper).is_none() {
+            panic!("Keeper not found: {}", keeper);
+        }
+        state.save();
+        
+        format!("Keeper {} removed", keeper)
+    }
+    
+    /// Sets the reward credited to a keeper for each rebalance that executes transactions
+    pub fn set_keeper_reward(reward: u128) -> String {
+        let mut state = Self::load();
+        state.assert_admin();
+        
+        state.keeper_reward = reward;
+        state.save();
+        
+        format!("Keeper reward set to {}", reward)
+    }
+    
+    /// Gets the registered keepers and their activity
+    pub fn get_keeper_stats() -> String {
         let state = Self::load();
         
-        let vault = state.vaults.get(&vault_id)
+        serde_json::to_string(&state.keepers)
+            .unwrap_or_else(|_| "Failed to serialize keeper stats".to_string())
+    }
+    
+    /// Sets the maximum cost of a rebalance trade relative to its value
+    ///
+    /// Trades whose swap fee plus gas exceeds `max_cost_bp` of their amount are
+    /// skipped; `None` executes every planned trade.
+    pub fn set_max_trade_cost(vault_id: String, max_cost_bp: Option<u32>) -> String {
+        if let Some(bp) = max_cost_bp {
+            validate_basis_points(bp, "max_trade_cost_bp")
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        match &vault.take_profit {
-            Some(strategy) => serde_json::to_string(strategy)
-                .unwrap_or_else(|_| "Failed to serialize take profit strategy".to_string()),
-                
-            None => "No take profit strategy configured".to_string(),
-        }
+        vault.max_trade_cost_bp = max_cost_bp;
+        state.save();
+        
+        format!("Maximum trade cost updated for vault {}", vault_id)
     }
     
-    /// Checks if a vault needs rebalancing
-    pub fn needs_rebalancing(vault_id: String) -> bool {
-        let state = Self::load();
+    /// Configures reinvestment of take-profit proceeds
+    ///
+    /// With `compound_target_allocations` the proceeds are spread across the
+    /// target allocations by an immediate rebalance; otherwise they are only deposited.
+    pub fn set_auto_compound(vault_id: String, enabled: bool, compound_target_allocations: bool) -> String {
+        let mut state = Self::load();
         
-        let vault = state.vaults.get(&vault_id)
+        let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active {
-            return false;
-        }
+        vault.auto_compound = AutoCompoundConfig { enabled, compound_target_allocations };
+        state.save();
         
-        vault.allocations.needs_rebalancing()
+        format!("Auto-compound {} for vault {}", if enabled { "enabled" } else { "disabled" }, vault_id)
     }
     
-    /// Executes rebalancing for a vault
-    pub fn rebalance(vault_id: String, prices_json: String) -> String {
+    /// Updates vault settings
+    pub fn update_vault(vault_id: String, drift_threshold_bp: Option<u32>, status: Option<String>) -> String {
+        let params = serde_json::json!({ "vault_id": vault_id, "drift_threshold_bp": drift_threshold_bp, "status": status });
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active {
-            let error_msg = format!("Cannot rebalance a non-active vault: status is {:?}", vault.status);
-            crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
-            panic!("{}", error_msg);
+        // Update drift threshold if provided
+        if let Some(threshold) = drift_threshold_bp {
+            validate_basis_points(threshold, "drift_threshold_bp")
+                .unwrap_or_else(|err| panic!("{}", err));
+            vault.allocations.drift_threshold_bp = threshold;
         }
         
-        // Parse prices and current values from JSON
-        let prices: Vec<(String, u128)> = match serde_json::from_str(&prices_json) {
-            Ok(p) => p,
-            Err(e) => {
-                let error_msg = format!("Failed to parse prices: {}", e);
-                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
-                panic!("{}", error_msg);
+        // Update status if provided
+        let mut status_change = None;
+        if let Some(status_str) = status {
+            if vault.status == VaultStatus::Migrating {
+                panic!("Cannot change the status of a migrating vault");
             }
-        };
+            
+            let new_status = match status_str.as_str() {
+                "active" => VaultStatus::Active,
+                "paused" => VaultStatus::Paused,
+                "closed" => VaultStatus::Closed,
+                _ => panic!("Invalid vault status: {}", status_str),
+            };
+            
+            status_change = vault.transition_status(new_status, &l1x_sdk::env::caller());
+        }
         
-        // Emit rebalance initiated event
-        crate::events::emit_rebalance_initiated_event(&vault_id, "manual");
+        state.save();
         
-        // First, check if we actually need to rebalance
-        if !vault.allocations.check_and_emit_rebalance_events(&vault_id) {
-            // No rebalancing needed, but still record the check
-            vault.last_rebalance = l1x_sdk::env::block_timestamp();
-            state.save();
-            return format!("No rebalancing needed for vault {}", vault_id);
+        if let Some(event) = status_change {
+            event.emit();
         }
         
-        // Calculate the rebalance transactions
-        let current_values = prices.clone(); // We're using prices as current values for simplicity
-        let transactions = vault.allocations.calculate_rebalance_transactions(
-            &current_values, 
-            vault.total_value
+        let result = format!("Vault {} updated", vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "CustodialVaultContract",
+            "update_vault",
+            &params,
+            &result,
         );
         
-        if transactions.is_empty() {
-            vault.allocations.record_rebalance(&prices);
-            vault.last_rebalance = l1x_sdk::env::block_timestamp();
-            state.save();
-            
-            // Emit completed event with no transactions
-            crate::events::emit_rebalance_completed_event(&vault_id, 0, None);
-            
-            return format!("No rebalance transactions needed for vault {}", vault_id);
+        result
+    }
+    
+    /// Deposits funds into a vault
+    pub fn deposit(vault_id: String, amount: u128) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
         }
         
-        // Create a rebalance operation
-        let rebalance_id = format!("rebalance-{}-{}", vault_id, l1x_sdk::env::block_timestamp());
-        let strategy = crate::rebalance::RebalanceStrategy::Threshold;
+        validate_amount_nonzero(amount, "amount")
+            .unwrap_or_else(|err| panic!("{}", err));
         
-        let mut operation = crate::rebalance::RebalanceEngine::create_rebalance_operation(
-            rebalance_id, 
-            strategy, 
-            transactions.clone()
-        );
+        deposit_into_vault(&vault_id, amount)
+            .unwrap_or_else(|err| panic!("{}", err));
         
-        // Execute the rebalance
-        match operation.execute() {
-            Ok(_) => {
-                // Record the rebalance
-                vault.allocations.record_rebalance(&prices);
-                vault.last_rebalance = l1x_sdk::env::block_timestamp();
-                
-                // Calculate total cost
-                let total_cost = operation.total_cost;
-                
-                // Emit completed event
-                crate::events::emit_rebalance_completed_event(
-                    &vault_id, 
-                    transactions.len(),
-                    total_cost
-                );
-                
-                state.save();
-                format!("Rebalanced vault {} with {} transactions", vault_id, transactions.len())
-            },
-            Err(e) => {
-                let error_msg = format!("Rebalance failed: {:?}", e);
-                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
-                panic!("{}", error_msg);
-            }
-        }
+        format!("Deposited {} into vault {}", amount, vault_id)
     }
     
-    /// Auto-rebalance a vault based on its settings
-    pub fn auto_rebalance(vault_id: String, prices_json: String) -> String {
+    /// Deposits funds and returns how they should be allocated across assets
+    ///
+    /// The vault's cash buffer is topped up to its floor before other targets.
+    pub fn deposit_and_allocate(vault_id: String, amount: u128, current_values_json: String) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        validate_amount_nonzero(amount, "amount")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let _guard = Self::enter_non_reentrant();
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
         if vault.status != VaultStatus::Active {
-            return format!("Cannot auto-rebalance inactive vault {}", vault_id);
+            panic!("Cannot deposit into a non-active vault");
         }
         
-        // Parse prices from JSON
-        let prices: Vec<(String, u128)> = match serde_json::from_str(&prices_json) {
-            Ok(p) => p,
-            Err(e) => {
-                return format!("Failed to parse prices: {}", e);
-            }
-        };
+        // Parse current asset values from JSON
+        let current_values: Vec<(String, u128)> = serde_json::from_str(&current_values_json)
+            .unwrap_or_else(|_| panic!("Failed to parse current values"));
+            
+        let purchases = vault.allocations.deposit_and_allocate(amount, &current_values);
         
-        // Check if rebalancing is needed and emit events
-        if !vault.allocations.check_and_emit_rebalance_events(&vault_id) {
-            return format!("No rebalancing needed for vault {}", vault_id);
-        }
+        // Sell-side rebalancing is still needed if the deposit didn't cure the drift
+        let remaining_drift_bp = vault.allocations.drift_after_purchases(&current_values, &purchases);
+        let needs_sell_rebalance = remaining_drift_bp > vault.allocations.drift_threshold_bp;
         
-        // Determine trigger type
-        let trigger = if vault.allocations.rebalance_frequency_seconds > 0 {
-            let current_time = l1x_sdk::env::block_timestamp();
-            let elapsed = current_time.saturating_sub(vault.last_rebalance);
+        vault.total_value = vault.total_value.checked_add(amount)
+            .unwrap_or_else(|| panic!("Overflow when adding deposit"));
             
-            if elapsed >= vault.allocations.rebalance_frequency_seconds {
-                "scheduled"
-            } else {
-                "drift"
-            }
-        } else {
-            "drift"
-        };
+        let new_balance = vault.total_value;
+        let cashflow_rebalancing = vault.allocations.cashflow_rebalancing;
+        state.save();
         
-        // Emit rebalance initiated event
-        crate::events::emit_rebalance_initiated_event(&vault_id, trigger);
+        crate::events::emit_deposit_event(&vault_id, amount, &l1x_sdk::env::caller(), new_balance);
         
-        // Calculate the rebalance transactions
-        let current_values = prices.clone(); // We're using prices as current values for simplicity
-        let transactions = vault.allocations.calculate_rebalance_transactions(
-            &current_values, 
-            vault.total_value
-        );
+        serde_json::json!({
+            "purchases": purchases,
+            "cashflow_rebalancing": cashflow_rebalancing,
+            "remaining_drift_bp": remaining_drift_bp,
+            "needs_sell_rebalance": needs_sell_rebalance,
+        }).to_string()
+    }
+    
+    /// Enables or disables cash-flow rebalancing of deposits for a vault
+    pub fn set_cashflow_rebalancing(vault_id: String, enabled: bool) -> String {
+        let mut state = Self::load();
         
-        if transactions.is_empty() {
-            vault.allocations.record_rebalance(&prices);
-            vault.last_rebalance = l1x_sdk::env::block_timestamp();
-            state.save();
-            
-            // Emit completed event with no transactions
-            crate::events::emit_rebalance_completed_event(&vault_id, 0, None);
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-            return format!("No rebalance transactions needed for vault {}", vault_id);
-        }
-        
-        // Create a rebalance operation
-        let rebalance_id = format!("rebalance-{}-{}", vault_id, l1x_sdk::env::block_timestamp());
-        let strategy = match trigger {
-            "scheduled" => crate::rebalance::RebalanceStrategy::Scheduled,
-            _ => crate::rebalance::RebalanceStrategy::Threshold,
-        };
-        
-        let mut operation = crate::rebalance::RebalanceEngine::create_rebalance_operation(
-            rebalance_id, 
-            strategy, 
-            transactions.clone()
-        );
+        vault.allocations.set_cashflow_rebalancing(enabled);
+        state.save();
         
-        // Execute the rebalance
-        match operation.execute() {
-            Ok(_) => {
-                // Record the rebalance
-                vault.allocations.record_rebalance(&prices);
-                vault.last_rebalance = l1x_sdk::env::block_timestamp();
-                
-                // Calculate total cost
-                let total_cost = operation.total_cost;
-                
-                // Emit completed event
-                crate::events::emit_rebalance_completed_event(
-                    &vault_id, 
-                    transactions.len(),
-                    total_cost
-                );
-                
-                state.save();
-                format!("Auto-rebalanced vault {} with {} transactions", vault_id, transactions.len())
-            },
-            Err(e) => {
-                let error_msg = format!("Auto-rebalance failed: {:?}", e);
-                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
-                format!("{}", error_msg)
-            }
-        }
+        format!("Cash-flow rebalancing {} for vault {}", if enabled { "enabled" } else { "disabled" }, vault_id)
     }
     
-    /// Checks if take profit should be executed
-    pub fn should_take_profit(vault_id: String, current_value: u128) -> bool {
-        let state = Self::load();
+    /// Withdraws funds from a vault
+    pub fn withdraw(vault_id: String, amount: u128) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
         
-        let vault = state.vaults.get(&vault_id)
+        validate_amount_nonzero(amount, "amount")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active || vault.take_profit.is_none() {
-            return false;
+        if vault.status != VaultStatus::Active {
+            panic!("Cannot withdraw from a non-active vault");
         }
         
-        let strategy = vault.take_profit.as_ref().unwrap();
+        if vault.total_value < amount {
+            panic!("Insufficient funds in vault");
+        }
         
-        match &strategy.strategy_type {
-            TakeProfitType::Manual => false, // Manual requires explicit trigger
+        // Enforce the caller's wallet spending limit
+        let wallet_id = crate::wallet::WalletManager::wallet_id(&l1x_sdk::env::caller());
+        crate::wallet::WalletManager::record_spend(&wallet_id, amount)
+            .unwrap_or_else(|err| panic!("Withdrawal rejected: {}", err));
             
-            TakeProfitType::Percentage { percentage } => {
-                let baseline = strategy.baseline_value;
-                if baseline == 0 || current_value <= baseline {
-                    return false;
-                }
-                
-                let gain = current_value - baseline;
-                let gain_percentage = (gain * 10000) / baseline;
-                
-                gain_percentage >= (*percentage as u128)
-            },
+        vault.total_value = vault.total_value.checked_sub(amount)
+            .unwrap_or_else(|| panic!("Underflow when subtracting withdrawal"));
             
-            TakeProfitType::Time { interval_seconds } => {
-                let now = l1x_sdk::env::block_timestamp();
-                let elapsed = now.saturating_sub(strategy.last_execution);
-                
-                elapsed >= *interval_seconds
-            },
-        }
+        let new_balance = vault.total_value;
+        state.save();
+        
+        crate::events::emit_withdrawal_event(&vault_id, amount, &l1x_sdk::env::caller(), new_balance);
+        
+        format!("Withdrew {} from vault {}", amount, vault_id)
     }
     
-    /// Executes take profit for a vault
-    pub fn execute_take_profit(vault_id: String, current_value: u128, target_asset: String) -> String {
+    /// Sets up take profit strategy for a vault
+    ///
+    /// `profit_portion_bp` is the share of the gain realized per execution
+    /// (1 to 10000 basis points, the whole gain when omitted).
+    pub fn set_take_profit(
+        vault_id: String,
+        strategy_type: String,
+        target_percentage: Option<u32>,
+        interval_seconds: Option<u64>,
+        profit_portion_bp: Option<u32>,
+    ) -> String {
+        let params = serde_json::json!({
+            "vault_id": vault_id,
+            "strategy_type": strategy_type,
+            "target_percentage": target_percentage,
+            "interval_seconds": interval_seconds,
+            "profit_portion_bp": profit_portion_bp,
+        });
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
         if vault.status != VaultStatus::Active {
-            panic!("Cannot execute take profit for a non-active vault");
-        }
-        
-        if vault.take_profit.is_none() {
-            panic!("No take profit strategy configured for vault");
+            panic!("Cannot set take profit for a non-active vault");
         }
         
-        let strategy = vault.take_profit.as_mut().unwrap();
-        
-        // Update strategy execution
-        let baseline = strategy.baseline_value;
-        strategy.record_execution();
-        
-        // Calculate profit amount
-        let profit_amount = if current_value > baseline {
-            current_value - baseline
-        } else {
-            0 // No profit
+        // Create appropriate strategy based on type
+        let take_profit_type = match strategy_type.as_str() {
+            "manual" => TakeProfitType::Manual,
+            
+            "percentage" => {
+                let percentage = target_percentage
+                    .unwrap_or_else(|| panic!("Percentage required for percentage-based take profit"));
+                    
+                TakeProfitType::Percentage { percentage }
+            },
+            
+            "time" => {
+                let interval = interval_seconds
+                    .unwrap_or_else(|| panic!("Interval required for time-based take profit"));
+                    
+                TakeProfitType::Time { interval_seconds: interval }
+            },
+            
+            "trailing_stop" => {
+                let trail_bp = target_percentage
+                    .unwrap_or_else(|| panic!("Trail required for trailing-stop take profit"));
+                    
+                TakeProfitType::TrailingStop { trail_bp }
+            },
+            
+            _ => panic!("Invalid take profit strategy type: {}", strategy_type),
         };
         
-        // Set new baseline
-        strategy.set_baseline(current_value);
+        let mut strategy = TakeProfitStrategy::new(take_profit_type);
+        if let Some(portion) = profit_portion_bp {
+            strategy.set_profit_portion(portion)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        strategy.set_baseline(vault.total_value);
+        vault.take_profit = Some(strategy);
         
         state.save();
         
-        format!("Take profit executed for vault {}, profit: {}, new baseline: {}", vault_id, profit_amount, current_value)
+        let result = format!("Take profit strategy set for vault {}", vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "CustodialVaultContract",
+            "set_take_profit",
+            &params,
+            &result,
+        );
+        
+        result
     }
     
-    /// Manually triggers take profit for a vault
-    pub fn manual_take_profit(vault_id: String, current_value: u128, target_asset: String) -> String {
+    /// Sets up a laddered take profit strategy for a vault from a JSON array of tiers
+    ///
+    /// Each tier is `{"trigger_bp": .., "portion_bp": ..}`: when the gain over
+    /// the baseline reaches `trigger_bp`, `portion_bp` of the gain is realized.
+    pub fn set_take_profit_ladder(vault_id: String, tiers_json: String) -> String {
+        let tiers: Vec<crate::take_profit::LadderTier> = serde_json::from_str(&tiers_json)
+            .unwrap_or_else(|e| panic!("Failed to parse ladder tiers: {}", e));
+        crate::take_profit::validate_ladder(&tiers)
+            .unwrap_or_else(|err| panic!("{}", err));
+            
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can set take profit");
+        }
+        
         if vault.status != VaultStatus::Active {
-            panic!("Cannot execute take profit for a non-active vault");
+            panic!("Cannot set take profit for a non-active vault");
+        }
+        
+        let tier_count = tiers.len();
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Ladder { tiers });
+        strategy.set_baseline(vault.total_value);
+        vault.take_profit = Some(strategy);
+        
+        state.save();
+        
+        format!("Take profit ladder with {} tiers set for vault {}", tier_count, vault_id)
+    }
+    
+    /// Sets up a take profit strategy for a vault from a JSON `TakeProfitConfig`
+    ///
+    /// Unlike `set_take_profit`, this can express composite triggers, e.g.
+    /// `{"strategy_type": {"Composite": {"conditions": [{"Percentage": {"percentage": 500}},
+    /// {"Time": {"interval_seconds": 2592000}}], "mode": "All"}}}`.
+    pub fn set_take_profit_config(vault_id: String, config_json: String) -> String {
+        let params = serde_json::json!({
+            "vault_id": vault_id,
+            "config": config_json,
+        });
+        let config: crate::take_profit::TakeProfitConfig = serde_json::from_str(&config_json)
+            .unwrap_or_else(|e| panic!("Failed to parse take profit config: {}", e));
+        let mut strategy = config.into_strategy()
+            .unwrap_or_else(|err| panic!("Invalid take profit config: {}", err));
+            
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            panic!("Cannot set take profit for a non-active vault");
+        }
+        
+        let strategy_name = strategy.strategy_type.name();
+        strategy.set_baseline(vault.total_value);
+        vault.take_profit = Some(strategy);
+        
+        state.save();
+        
+        let result = format!("Take profit strategy ({}) set for vault {}", strategy_name, vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "CustodialVaultContract",
+            "set_take_profit_config",
+            &params,
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Removes a vault's take profit strategy
+    pub fn clear_take_profit(vault_id: String) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can clear take profit");
+        }
+        
+        if vault.take_profit.take().is_none() {
+            return format!("No take profit strategy configured for vault {}", vault_id);
+        }
+        
+        state.save();
+        
+        crate::events::emit_take_profit_changed_event(
+            &vault_id,
+            crate::events::TakeProfitChangeType::Cleared,
+            &l1x_sdk::env::caller(),
+        );
+        
+        format!("Take profit strategy cleared for vault {}", vault_id)
+    }
+    
+    /// Suspends a vault's take profit strategy without losing its configuration
+    pub fn pause_take_profit(vault_id: String) -> String {
+        Self::set_take_profit_paused(vault_id, true)
+    }
+    
+    /// Re-enables a vault's paused take profit strategy
+    pub fn resume_take_profit(vault_id: String) -> String {
+        Self::set_take_profit_paused(vault_id, false)
+    }
+    
+    fn set_take_profit_paused(vault_id: String, paused: bool) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can pause or resume take profit");
+        }
+        
+        let strategy = vault.take_profit.as_mut()
+            .unwrap_or_else(|| panic!("No take profit strategy configured for vault {}", vault_id));
+            
+        let (change_type, action) = if paused {
+            strategy.pause();
+            (crate::events::TakeProfitChangeType::Paused, "paused")
+        } else {
+            strategy.resume();
+            (crate::events::TakeProfitChangeType::Resumed, "resumed")
+        };
+        
+        state.save();
+        
+        crate::events::emit_take_profit_changed_event(&vault_id, change_type, &l1x_sdk::env::caller());
+        
+        format!("Take profit strategy {} for vault {}", action, vault_id)
+    }
+    
+    /// Gets take profit strategy for a vault
+    pub fn get_take_profit(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        match &vault.take_profit {
+            Some(strategy) => {
+                let mut strategy_json = serde_json::to_value(strategy)
+                    .unwrap_or_else(|_| panic!("Failed to serialize take profit strategy"));
+                strategy_json["remaining_executions"] = serde_json::json!(
+                    strategy.remaining_executions(l1x_sdk::env::block_timestamp())
+                );
+                
+                serde_json::to_string(&strategy_json)
+                    .unwrap_or_else(|_| "Failed to serialize take profit strategy".to_string())
+            },
+                
+            None => "No take profit strategy configured".to_string(),
+        }
+    }
+    
+    /// Checks if a vault needs rebalancing
+    pub fn needs_rebalancing(vault_id: String) -> bool {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            return false;
+        }
+        
+        let mut allocations = vault.allocations.clone();
+        allocations.refresh_volatility();
+        
+        allocations.needs_rebalancing()
+    }
+    
+    /// Gets the per-asset drift report for a vault, revalued at `prices_json` if supplied
+    pub fn get_drift_report(vault_id: String, prices_json: Option<String>) -> String {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let prices: Option<Vec<(String, u128)>> = prices_json.map(|json| {
+            serde_json::from_str(&json).unwrap_or_else(|_| panic!("Failed to parse prices"))
+        });
+        
+        let mut allocations = vault.allocations.clone();
+        allocations.refresh_volatility();
+        
+        let mut report = allocations.drift_report(prices.as_deref(), l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("Failed to build drift report: {}", err));
+            
+        // Inactive vaults are never rebalanced
+        if vault.status != VaultStatus::Active {
+            report.needs_rebalancing = false;
+        }
+        
+        serde_json::to_string(&report)
+            .unwrap_or_else(|_| "Failed to serialize drift report".to_string())
+    }
+    
+    /// Executes rebalancing for a vault
+    ///
+    /// When `balances_json` is provided, current percentages and values are
+    /// recomputed from the vault's holdings at the given prices.
+    pub fn rebalance(vault_id: String, prices_json: String, balances_json: Option<String>) -> String {
+        Self::execute_rebalance(vault_id, None, prices_json, balances_json)
+    }
+    
+    /// Executes rebalancing for a vault at most once per `rebalance_id`
+    ///
+    /// Preferred over `rebalance`: a retried submission with the same ID
+    /// returns "Already processed" instead of swapping twice. IDs are scoped
+    /// to the vault, and the operation is recorded as
+    /// `idempotent-<vault_id>-<rebalance_id>`.
+    pub fn rebalance_idempotent(vault_id: String, rebalance_id: String, prices_json: String) -> String {
+        validate_non_empty_string(&rebalance_id, "rebalance_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        Self::execute_rebalance(vault_id, Some(rebalance_id), prices_json, None)
+    }
+    
+    /// Rebalances several vaults at once, returning a JSON array of per-vault results
+    ///
+    /// Each vault goes through the same pipeline as `rebalance`. Vaults that
+    /// fail are reported (and logged as failed rebalances) without aborting
+    /// the rest of the batch. `balances_json` maps vault IDs to their
+    /// `(asset, balance)` holdings; vaults without an entry are valued from
+    /// their recorded percentages.
+    pub fn batch_rebalance(vault_ids: Vec<String>, prices_json: String, balances_json: Option<String>) -> String {
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|e| panic!("Failed to parse prices: {}", e));
+            
+        let balances: std::collections::HashMap<String, Vec<(String, u128)>> = balances_json
+            .map(|balances_json| serde_json::from_str(&balances_json)
+                .unwrap_or_else(|e| panic!("Failed to parse balances: {}", e)))
+            .unwrap_or_default();
+            
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        let price_feed = crate::price_feed::load_price_feed();
+        let emergency = crate::emergency::load_emergency_state();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "batch",
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: crate::governance::parameters().protocol_fee_bps,
+            mode: crate::rebalance::RebalanceMode::Live,
+        };
+        
+        let (results, runs) = state.rebalance_batch(&vault_ids, &balances, &request);
+        state.save();
+        
+        for (vault_id, run) in runs {
+            run.finish(&vault_id);
+        }
+        
+        serde_json::to_string(&results)
+            .unwrap_or_else(|_| "Failed to serialize batch results".to_string())
+    }
+    
+    /// Runs `request` on each of `vault_ids` that needs it, isolating failures
+    ///
+    /// Each vault gets its own rebalance ID and is valued at its own entry in
+    /// `balances`, when there is one. Returns the per-vault results and the
+    /// runs still to be finished once the state is saved.
+    fn rebalance_batch(
+        &mut self,
+        vault_ids: &[String],
+        balances: &std::collections::HashMap<String, Vec<(String, u128)>>,
+        request: &RebalanceRequest
+    ) -> (Vec<BatchRebalanceResult>, Vec<(String, RebalanceRun)>) {
+        let mut results = Vec::with_capacity(vault_ids.len());
+        let mut runs = Vec::new();
+        
+        for vault_id in vault_ids {
+            let vault_balances = balances.get(vault_id).map(|balances| balances.as_slice());
+            let result = |status, message: String, operation: Option<&crate::rebalance::RebalanceOperation>| BatchRebalanceResult {
+                vault_id: vault_id.clone(),
+                status,
+                message,
+                rebalance_id: operation.map(|operation| operation.id.clone()),
+                transaction_count: operation.map_or(0, |operation| operation.transactions.len()),
+            };
+            
+            match self.vaults.get(vault_id) {
+                None => {
+                    results.push(result(BatchRebalanceStatus::Failed, "Vault not found".to_string(), None));
+                    continue;
+                },
+                Some(vault) if vault.status != VaultStatus::Active => {
+                    results.push(result(BatchRebalanceStatus::Skipped, format!("Vault is {:?}", vault.status), None));
+                    continue;
+                },
+                // Recorded percentages only say something when there are no balances to value
+                Some(vault) if vault_balances.is_none() && !vault.needs_rebalancing() => {
+                    results.push(result(BatchRebalanceStatus::Skipped, "No rebalancing needed".to_string(), None));
+                    continue;
+                },
+                Some(_) => {},
+            }
+            
+            let request = RebalanceRequest {
+                rebalance_id: None,
+                balances: vault_balances,
+                ..*request
+            };
+            
+            match self.rebalance_vault(vault_id, &request) {
+                Ok(RebalanceRun::Idle(message)) => {
+                    results.push(result(BatchRebalanceStatus::Skipped, message, None));
+                },
+                Ok(run) => {
+                    let (status, message, operation) = match &run {
+                        RebalanceRun::Submitted { message, operation, .. } => (BatchRebalanceStatus::Rebalanced, message, operation),
+                        RebalanceRun::SubmitFailed { message, operation } => (BatchRebalanceStatus::Failed, message, operation),
+                        RebalanceRun::Idle(_) => unreachable!(),
+                    };
+                    results.push(result(status, message.clone(), Some(operation)));
+                    runs.push((vault_id.clone(), run));
+                },
+                Err(err) => results.push(result(BatchRebalanceStatus::Failed, err, None)),
+            }
+        }
+        
+        (results, runs)
+    }
+    
+    /// Runs the shared rebalance pipeline on one vault
+    ///
+    /// Every rebalance path goes through here, so all of them stop while the
+    /// protocol is paused. The vault is restored to its prior state when the rebalance fails,
+    /// including when its swaps could not be submitted.
+    fn rebalance_vault(&mut self, vault_id: &str, request: &RebalanceRequest) -> Result<RebalanceRun, String> {
+        request.emergency.check_active()?;
+        
+        let vault = self.vaults.get_mut(vault_id)
+            .ok_or_else(|| format!("Vault not found: {}", vault_id))?;
+            
+        vault.try_rebalance(request)
+    }
+    
+    /// Records `rebalance_id` as processed for `vault_id`; false if it already was
+    fn mark_processed(&mut self, vault_id: &str, rebalance_id: &str) -> bool {
+        self.processed_rebalance_ids.insert((vault_id.to_string(), rebalance_id.to_string()))
+    }
+    
+    /// Runs `request` on the vault at most once per `rebalance_id`
+    ///
+    /// Returns `None` when the vault already processed that ID. A rebalance
+    /// that fails leaves the ID unprocessed so it can be retried.
+    fn rebalance_once(&mut self, vault_id: &str, rebalance_id: &str, request: &RebalanceRequest) -> Result<Option<RebalanceRun>, String> {
+        if !self.mark_processed(vault_id, rebalance_id) {
+            return Ok(None);
+        }
+        
+        // Prefixed so a caller's ID never matches a timestamp-derived operation ID
+        let request = RebalanceRequest {
+            rebalance_id: Some(format!("idempotent-{}-{}", vault_id, rebalance_id)),
+            ..*request
+        };
+        
+        match self.rebalance_vault(vault_id, &request) {
+            Ok(run) => Ok(Some(run)),
+            Err(err) => {
+                self.processed_rebalance_ids.remove(&(vault_id.to_string(), rebalance_id.to_string()));
+                Err(err)
+            },
+        }
+    }
+    
+    /// Runs a rebalance once per `rebalance_id`, or under a timestamp-derived ID when `None`
+    fn execute_rebalance(vault_id: String, rebalance_id: Option<String>, prices_json: String, balances_json: Option<String>) -> String {
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        if !state.vaults.contains_key(&vault_id) {
+            panic!("Vault not found: {}", vault_id);
+        }
+        
+        // Parse prices and current values from JSON
+        let prices: Vec<(String, u128)> = match serde_json::from_str(&prices_json) {
+            Ok(p) => p,
+            Err(e) => {
+                let error_msg = format!("Failed to parse prices: {}", e);
+                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                panic!("{}", error_msg);
+            }
+        };
+        
+        let balances: Option<Vec<(String, u128)>> = match balances_json {
+            Some(balances_json) => match serde_json::from_str(&balances_json) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    let error_msg = format!("Failed to parse balances: {}", e);
+                    crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                    panic!("{}", error_msg);
+                }
+            },
+            None => None,
+        };
+        
+        let price_feed = crate::price_feed::load_price_feed();
+        let emergency = crate::emergency::load_emergency_state();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "manual",
+            prices: &prices,
+            balances: balances.as_deref(),
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: crate::governance::parameters().protocol_fee_bps,
+            mode: crate::rebalance::RebalanceMode::Live,
+        };
+        
+        let run = match rebalance_id {
+            Some(rebalance_id) => match state.rebalance_once(&vault_id, &rebalance_id, &request) {
+                Ok(Some(run)) => run,
+                Ok(None) => return "Already processed".to_string(),
+                Err(err) => panic!("{}", err),
+            },
+            None => state.rebalance_vault(&vault_id, &request)
+                .unwrap_or_else(|err| panic!("{}", err)),
+        };
+        
+        // Return rather than panic on a failed submission so the operation stays in the history
+        if !matches!(run, RebalanceRun::SubmitFailed { .. }) {
+            state.save();
+        }
+        
+        run.finish(&vault_id)
+    }
+    
+    /// Spreads a vault's rebalance over `total_duration_seconds` in `slices` equal parts
+    ///
+    /// The slices are queued and executed one per interval by the
+    /// `process_gradual_rebalances` job; the first one is due immediately.
+    pub fn start_gradual_rebalance(vault_id: String, prices_json: String, total_duration_seconds: u64, slices: u32) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        if slices == 0 {
+            panic!("A gradual rebalance needs at least one slice");
+        }
+        
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can start a gradual rebalance");
+        }
+        
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|e| panic!("Failed to parse prices: {}", e));
+            
+        let transactions = vault.plan_gradual_rebalance(&prices, l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("{}", err));
+        
+        if transactions.is_empty() {
+            return format!("No rebalance transactions needed for vault {}", vault_id);
+        }
+        
+        let rebalance_id = format!("gradual-{}-{}", vault_id, l1x_sdk::env::block_timestamp());
+        let config_hash = vault.allocations.canonical_hash();
+        
+        // The protocol fee on the value traded is charged slice by slice as they execute
+        let protocol_fee_bps = crate::governance::parameters().protocol_fee_bps;
+        let total_fee = transactions.iter()
+            .map(|(_, _, value)| crate::fee_collector::fee_at_rate(*value, protocol_fee_bps))
+            .fold(0u128, |total, fee| total.saturating_add(fee));
+        
+        // Validate and convert the whole plan to token units, then slice the units
+        let plan = vault.plan_operation(rebalance_id.clone(), crate::rebalance::RebalanceStrategy::Manual, transactions, &prices, vault.total_value, &crate::price_feed::load_price_feed())
+            .unwrap_or_else(|err| panic!("{}", err));
+        let unit_transactions = plan.transactions.into_iter()
+            .map(|transaction| (transaction.source_asset, transaction.target_asset, transaction.amount))
+            .collect();
+            
+        let mut operations: Vec<crate::rebalance::RebalanceOperation> = crate::rebalance::RebalanceEngine::create_gradual_operation(
+            rebalance_id,
+            unit_transactions,
+            total_duration_seconds,
+            slices,
+        ).into_iter()
+            .map(|operation| operation.with_vault_id(vault_id.clone()).with_allocation_config_hash(config_hash))
+            .collect();
+            
+        // Split like the trades: equal shares, with the remainder on the last slice
+        let slice_fee = total_fee / operations.len() as u128;
+        let last_slice = operations.len() - 1;
+        for (index, operation) in operations.iter_mut().enumerate() {
+            operation.protocol_fee = if index == last_slice {
+                total_fee - slice_fee * last_slice as u128
+            } else {
+                slice_fee
+            };
+        }
+        
+        state.save();
+        
+        crate::events::emit_rebalance_initiated_event(&vault_id, "gradual");
+        crate::rebalance::gradual::queue_slices(&vault_id, operations);
+        
+        format!("Queued gradual rebalance of vault {} in {} slices", vault_id, slices)
+    }
+    
+    /// Previews a rebalance without emitting events or changing any vault state
+    ///
+    /// Works on paused vaults too so operators can inspect them.
+    pub fn simulate_rebalance(vault_id: String, prices_json: String) -> String {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|e| panic!("Failed to parse prices: {}", e));
+            
+        let simulation = vault.simulate_rebalance(&prices)
+            .unwrap_or_else(|e| panic!("Failed to simulate rebalance: {}", e));
+            
+        serde_json::to_string(&simulation)
+            .unwrap_or_else(|_| "Failed to serialize simulation".to_string())
+    }
+    
+    /// Rehearses a rebalance on production state without moving funds
+    ///
+    /// Runs the full rebalance pipeline in `RebalanceMode::Simulate`: swaps are
+    /// logged but never executed, and nothing is saved. Returns the simulated
+    /// operation as JSON, or the reason no operation was planned. Only
+    /// registered keepers and the vault owner may rehearse.
+    pub fn rehearse_rebalance(vault_id: String, prices_json: String, balances_json: Option<String>) -> String {
+        let state = Self::load();
+        
+        let caller = l1x_sdk::env::caller();
+        if !state.can_auto_rebalance(&vault_id, &caller) {
+            panic!("Only registered keepers or the vault owner can rehearse a rebalance");
+        }
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|e| panic!("Failed to parse prices: {}", e));
+            
+        let balances: Option<Vec<(String, u128)>> = balances_json.map(|balances_json| {
+            serde_json::from_str(&balances_json)
+                .unwrap_or_else(|e| panic!("Failed to parse balances: {}", e))
+        });
+        
+        let price_feed = crate::price_feed::load_price_feed();
+        let emergency = crate::emergency::load_emergency_state();
+        emergency.check_active()
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "simulate",
+            prices: &prices,
+            balances: balances.as_deref(),
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: crate::governance::parameters().protocol_fee_bps,
+            mode: crate::rebalance::RebalanceMode::Simulate,
+        };
+        
+        match vault.rehearse_rebalance(&request) {
+            Ok(RebalanceRun::Submitted { operation, .. }) => serde_json::to_string(&operation)
+                .unwrap_or_else(|_| "Failed to serialize rehearsal".to_string()),
+            Ok(RebalanceRun::Idle(message)) | Ok(RebalanceRun::SubmitFailed { message, .. }) => message,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    
+    /// Auto-rebalance a vault based on its settings
+    ///
+    /// Only registered keepers and the vault owner may trigger it; keepers are
+    /// credited when the rebalance executes transactions.
+    pub fn auto_rebalance(vault_id: String, prices_json: String) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        let caller = l1x_sdk::env::caller();
+        if !state.can_auto_rebalance(&vault_id, &caller) {
+            panic!("Only registered keepers or the vault owner can auto-rebalance");
+        }
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            return format!("Cannot auto-rebalance inactive vault {}", vault_id);
+        }
+        
+        if vault.multisig_wallet.is_some() {
+            return format!("Vault {} requires multi-sig approval to rebalance", vault_id);
+        }
+        
+        // Determine trigger type
+        let trigger = if vault.allocations.rebalance_frequency_seconds > 0 {
+            let current_time = l1x_sdk::env::block_timestamp();
+            let elapsed = current_time.saturating_sub(vault.last_rebalance);
+            
+            if elapsed >= vault.allocations.rebalance_frequency_seconds {
+                "scheduled"
+            } else {
+                "drift"
+            }
+        } else {
+            "drift"
+        };
+        
+        // Parse prices from JSON
+        let prices: Vec<(String, u128)> = match serde_json::from_str(&prices_json) {
+            Ok(p) => p,
+            Err(e) => {
+                return format!("Failed to parse prices: {}", e);
+            }
+        };
+        
+        let price_feed = crate::price_feed::load_price_feed();
+        let emergency = crate::emergency::load_emergency_state();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger,
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: crate::governance::parameters().protocol_fee_bps,
+            mode: crate::rebalance::RebalanceMode::Live,
+        };
+        
+        let run = match state.rebalance_vault(&vault_id, &request) {
+            Ok(run) => run,
+            Err(err) => return err,
+        };
+        
+        match run {
+            RebalanceRun::SubmitFailed { .. } => return run.finish(&vault_id),
+            RebalanceRun::Submitted { .. } => {
+                if let Some(stats) = state.keepers.get_mut(&caller) {
+                    stats.record_execution(state.keeper_reward);
+                }
+            },
+            RebalanceRun::Idle(_) => {},
+        }
+        
+        state.save();
+        run.finish(&vault_id)
+    }
+    
+    /// Checks if take profit should be executed
+    ///
+    /// Read-only: the strategy's peak is only raised from price-feed values
+    /// in `execute_take_profit`, never from a caller-supplied value.
+    pub fn should_take_profit(vault_id: String, current_value: u128) -> bool {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        vault.should_take_profit(current_value)
+    }
+    
+    /// Executes take profit for a vault, valuing it at fresh price-feed prices
+    pub fn execute_take_profit(vault_id: String, target_asset: String) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            panic!("Cannot execute take profit for a non-active vault");
+        }
+        
+        if vault.take_profit.is_none() {
+            panic!("No take profit strategy configured for vault");
+        }
+        
+        if vault.take_profit.as_ref().is_some_and(|strategy| strategy.paused) {
+            panic!("Take profit strategy is paused for vault {}", vault_id);
+        }
+        
+        let now = l1x_sdk::env::block_timestamp();
+        if vault.take_profit.as_ref().is_some_and(|strategy| strategy.remaining_executions(now) == Some(0)) {
+            panic!("Take profit execution cap reached for vault {}", vault_id);
+        }
+        
+        // The vault is valued from the price feed, never from the caller
+        let current_value = vault.market_value()
+            .unwrap_or_else(|err| panic!("Cannot value vault {}: {}", vault_id, err));
+        
+        // Charge the performance fee on gains above the high-water mark
+        let previous_high_water_mark = vault.high_water_mark;
+        let performance_fee = vault.charge_performance_fee(current_value);
+        let fee_recipient = vault.fee_recipient.clone().unwrap_or_default();
+        if performance_fee > 0 {
+            *state.accrued_fees.entry(fee_recipient.clone()).or_insert(0) += performance_fee;
+        }
+        
+        let strategy = vault.take_profit.as_mut().unwrap();
+        
+        // Only price-feed valuations move the peak trailing stops are measured from
+        strategy.update_high_water_mark(current_value);
+        
+        // Update strategy execution
+        let baseline = strategy.baseline_value;
+        strategy.record_execution();
+        
+        // Calculate profit amount, net of the performance fee; ladders realize only the fired tier's portion
+        let realized = strategy.realize_gain(current_value);
+        let profit_amount = realized.saturating_sub(performance_fee);
+        
+        // The protocol fee is charged on the profit in the target asset
+        let protocol_fee = crate::fee_collector::protocol_fee(profit_amount);
+        let profit_amount = profit_amount - protocol_fee;
+        
+        // Set new baseline, keeping any gain left unrealized
+        strategy.advance_baseline(current_value, realized);
+        let new_baseline = strategy.baseline_value;
+        let destination = strategy.profit_destination;
+        
+        // Reinvest the profit when the strategy says so, or when auto-compounding into the default destination
+        let mut auto_compound = vault.auto_compound.clone();
+        auto_compound.enabled = match destination {
+            ProfitDestination::ReinvestAsAllocation => true,
+            ProfitDestination::HoldInVault => auto_compound.enabled,
+            ProfitDestination::QueueWithdrawal => false,
+        };
+        let prices = if auto_compound.enabled && auto_compound.compound_target_allocations {
+            let asset_ids: Vec<String> = vault.allocations.allocations.iter()
+                .map(|a| a.asset_id.clone())
+                .collect();
+            
+            // Never rebalance the proceeds in at stale prices
+            crate::price_feed::fresh_prices(&asset_ids)
+                .unwrap_or_else(|err| panic!("Cannot compound take profit: {}", err))
+        } else {
+            Vec::new()
+        };
+        let price_feed = crate::price_feed::load_price_feed();
+        let emergency = crate::emergency::load_emergency_state();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "compound",
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: crate::governance::parameters().protocol_fee_bps,
+            mode: crate::rebalance::RebalanceMode::Live,
+        };
+        let compounded = auto_compound.reinvest(vault, profit_amount, &request);
+        if compounded.is_none() {
+            vault.set_aside_profit(destination, profit_amount, &target_asset, now);
+        }
+        
+        let snapshot = vault.snapshot_at(current_value);
+        
+        state.save();
+        
+        crate::portfolio::snapshot_store::store_snapshot(&vault_id, snapshot);
+        crate::fee_collector::FeeCollectorContract::record_fee(&target_asset, protocol_fee);
+        
+        if performance_fee > 0 {
+            crate::events::emit_performance_fee_event(
+                &vault_id,
+                performance_fee,
+                &fee_recipient,
+                previous_high_water_mark,
+                current_value,
+            );
+        }
+        
+        if let Some(result) = compounded {
+            crate::events::emit_auto_compound_event(&vault_id, result.reinvested_amount);
+            
+            let rebalance = match result.rebalance {
+                Some(Ok(run)) => run.finish(&vault_id),
+                Some(Err(err)) => format!("rebalance skipped: {}", err),
+                None => "not rebalanced".to_string(),
+            };
+            
+            return format!(
+                "Take profit compounded for vault {}, reinvested: {}, new baseline: {} ({})",
+                vault_id, result.reinvested_amount, new_baseline, rebalance
+            );
+        }
+        
+        let strategy = state.vaults[&vault_id].take_profit.as_ref().unwrap();
+        crate::events::emit_take_profit_executed_event(
+            &vault_id,
+            strategy,
+            baseline,
+            current_value,
+            profit_amount,
+            &target_asset,
+        );
+        
+        format!("Take profit executed for vault {}, profit: {}, new baseline: {}", vault_id, profit_amount, new_baseline)
+    }
+    
+    /// Pays out a vault's take-profit proceeds queued for withdrawal
+    pub fn claim_profits(vault_id: String) -> String {
+        if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+            panic!("{}", crate::emergency::PROTOCOL_PAUSED_ERROR);
+        }
+        
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can claim profits");
+        }
+        
+        let claimed = vault.claim_profits()
+            .unwrap_or_else(|err| panic!("{}", err));
+        if claimed == 0 {
+            panic!("No profits pending withdrawal for vault {}", vault_id);
+        }
+        
+        let new_balance = vault.total_value;
+        state.save();
+        
+        crate::events::emit_withdrawal_event(&vault_id, claimed, &l1x_sdk::env::caller(), new_balance);
+        
+        format!("Claimed {} in profits from vault {}", claimed, vault_id)
+    }
+    
+    /// Manually triggers take profit for a vault
+    pub fn manual_take_profit(vault_id: String, current_value: u128, target_asset: String) -> String {
+        let _guard = Self::enter_non_reentrant();
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            panic!("Cannot execute take profit for a non-active vault");
         }
         
         if vault.take_profit.is_none() {
@@ -585,31 +1985,767 @@ impl CustodialVaultContract {
         let profit_amount = if current_value > baseline {
             current_value - baseline
         } else {
-            0 // No profit
+            0 // No profit
+        };
+        
+        // Set new baseline, as far as the baseline policy allows
+        strategy.rebase(current_value);
+        let new_baseline = strategy.baseline_value;
+        
+        let snapshot = vault.snapshot_at(current_value);
+        
+        state.save();
+        
+        crate::portfolio::snapshot_store::store_snapshot(&vault_id, snapshot);
+        
+        let strategy = state.vaults[&vault_id].take_profit.as_ref().unwrap();
+        crate::events::emit_take_profit_executed_event(
+            &vault_id,
+            strategy,
+            baseline,
+            current_value,
+            profit_amount,
+            &target_asset,
+        );
+        
+        format!("Manual take profit executed for vault {}, profit: {}, new baseline: {}", vault_id, profit_amount, new_baseline)
+    }
+}
+
+/// RAII guard holding a reentrancy lock; the lock is released when the guard is dropped
+pub struct ReentrancyGuard {
+    set_lock: fn(bool),
+}
+
+impl ReentrancyGuard {
+    /// Takes the lock, panicking if it is already held
+    pub fn acquire(is_locked: fn() -> bool, set_lock: fn(bool)) -> Self {
+        if is_locked() {
+            panic!("Reentrant call detected");
+        }
+        
+        set_lock(true);
+        Self { set_lock }
+    }
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        (self.set_lock)(false);
+    }
+}
+
+/// Stores a fully configured vault (e.g. one built by the vault factory)
+pub fn register_vault(vault: CustodialVault) -> Result<(), &'static str> {
+    let mut state = CustodialVaultContract::load();
+    
+    let vault_id = vault.id.clone();
+    let owner = vault.owner.clone();
+    let status = vault.status;
+    
+    state.insert_vault(vault)?;
+    state.save();
+    
+    crate::events::emit_vault_status_changed_event(&vault_id, None, status, &owner);
+    
+    Ok(())
+}
+
+/// Closes the custodial vault a migration payload was issued for, after checking its signature
+pub fn complete_migration(payload: &MigrationPayload) -> Result<(), &'static str> {
+    let mut state = CustodialVaultContract::load();
+    
+    let vault = state.vaults.get_mut(&payload.source_vault_id)
+        .ok_or("Source vault not found")?;
+        
+    vault.finish_migration(payload)?;
+    let owner = vault.owner.clone();
+    state.save();
+    
+    crate::events::emit_vault_status_changed_event(&payload.source_vault_id, Some(VaultStatus::Migrating), VaultStatus::Closed, &owner);
+    
+    Ok(())
+}
+
+/// Releases a vault's rebalance lock once the operation holding it has settled
+pub fn release_rebalance_lock(vault_id: &str, rebalance_id: &str) {
+    let mut state = CustodialVaultContract::load();
+    
+    if let Some(vault) = state.vaults.get_mut(vault_id) {
+        if vault.finish_rebalance(rebalance_id) {
+            state.save();
+        }
+    }
+}
+
+/// Locks a vault again for `rebalance_id` while its resubmitted swaps settle
+///
+/// Succeeds when the lock is free or already held by `rebalance_id`.
+pub fn acquire_rebalance_lock(vault_id: &str, rebalance_id: &str) -> Result<(), String> {
+    let mut state = CustodialVaultContract::load();
+    
+    let vault = state.vaults.get_mut(vault_id)
+        .ok_or_else(|| format!("Vault not found: {}", vault_id))?;
+        
+    if vault.in_progress_rebalance.as_deref() == Some(rebalance_id) {
+        return Ok(());
+    }
+    
+    vault.begin_rebalance(rebalance_id)?;
+    state.save();
+    
+    Ok(())
+}
+
+/// Rolls back the drift state of a cancelled rebalance's unexecuted legs
+///
+/// Releases the rebalance lock when `resolved`, i.e. no leg is still in flight.
+pub fn revert_cancelled_rebalance(vault_id: &str, rebalance_id: &str, cancelled: &[(String, String, u128)], resolved: bool) {
+    let mut state = CustodialVaultContract::load();
+    
+    if let Some(vault) = state.vaults.get_mut(vault_id) {
+        let total_value = vault.total_value;
+        vault.allocations.revert_transactions(cancelled, total_value);
+        
+        // Legs still in flight release the lock from their swap callback
+        if resolved {
+            vault.finish_rebalance(rebalance_id);
+        }
+        state.save();
+    }
+}
+
+/// Submits one slice of a gradual rebalance
+///
+/// Returns `Err` when the vault can't take the slice yet (protocol paused,
+/// vault inactive, or still waiting on a previous rebalance), in which case
+/// it stays queued. The vault's current percentages move by the slice's
+/// share only.
+pub fn execute_rebalance_slice(mut operation: crate::rebalance::RebalanceOperation) -> Result<String, String> {
+    if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+        return Err(crate::emergency::PROTOCOL_PAUSED_ERROR.to_string());
+    }
+    
+    let mut state = CustodialVaultContract::load();
+    
+    let vault_id = operation.vault_id.clone().unwrap_or_default();
+    let vault = state.vaults.get_mut(&vault_id)
+        .ok_or_else(|| format!("Vault not found: {}", vault_id))?;
+        
+    if vault.status != VaultStatus::Active {
+        return Err(format!("Cannot rebalance a non-active vault: status is {:?}", vault.status));
+    }
+    
+    vault.begin_rebalance(&operation.id)?;
+    
+    let transactions: Vec<(String, String, u128)> = operation.transactions.iter()
+        .map(|t| (t.source_asset.clone(), t.target_asset.clone(), t.amount))
+        .collect();
+        
+    match operation.submit() {
+        Ok(_) => {
+            let total_value = vault.total_value;
+            vault.allocations.apply_transactions(&transactions, total_value);
+            vault.last_rebalance = l1x_sdk::env::block_timestamp();
+            
+            // The slice's share of the protocol fee, fixed when the rebalance was queued
+            operation.protocol_fee = operation.protocol_fee.min(vault.total_value);
+            vault.total_value -= operation.protocol_fee;
+            
+            if operation.is_resolved() {
+                vault.finish_rebalance(&operation.id);
+                crate::events::emit_rebalance_completed_event(&vault_id, transactions.len(), operation.total_cost, false);
+            }
+            
+            state.save();
+            crate::fee_collector::FeeCollectorContract::record_fee(crate::fee_collector::VAULT_VALUE_ASSET, operation.protocol_fee);
+            
+            let message = format!("Executed slice {} of vault {} with {} transactions", operation.id, vault_id, transactions.len());
+            crate::rebalance::history::record_operation(operation);
+            Ok(message)
+        },
+        Err(e) => {
+            // The slice is consumed; it stays in the history as failed
+            let error_msg = format!("Rebalance slice failed: {:?}", e);
+            crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+            crate::rebalance::history::record_operation(operation);
+            Ok(error_msg)
+        }
+    }
+}
+
+/// Target percentage of each asset of a custodial vault, if it exists
+pub fn vault_target_allocations(vault_id: &str) -> Option<Vec<(String, u32)>> {
+    let state = CustodialVaultContract::load();
+    
+    state.vaults.get(vault_id).map(|vault| vault.target_allocations())
+}
+
+/// Whether `account` is a registered keeper
+pub fn is_keeper(account: &str) -> bool {
+    CustodialVaultContract::load().keepers.contains_key(account)
+}
+
+/// Owner of a custodial vault, if it exists
+pub fn vault_owner(vault_id: &str) -> Option<String> {
+    let state = CustodialVaultContract::load();
+    
+    state.vaults.get(vault_id).map(|vault| vault.owner.clone())
+}
+
+/// Deposits `amount` into a vault, returning its new balance
+///
+/// Fails instead of panicking so callers depositing into several vaults can
+/// skip the ones that refuse the deposit.
+pub fn deposit_into_vault(vault_id: &str, amount: u128) -> Result<u128, String> {
+    if crate::emergency::EmergencyPauseContract::is_protocol_paused() {
+        return Err(crate::emergency::PROTOCOL_PAUSED_ERROR.to_string());
+    }
+    
+    let _guard = CustodialVaultContract::enter_non_reentrant();
+    let mut state = CustodialVaultContract::load();
+    
+    let vault = state.vaults.get_mut(vault_id)
+        .ok_or_else(|| format!("Vault not found: {}", vault_id))?;
+        
+    if vault.status != VaultStatus::Active {
+        return Err("Cannot deposit into a non-active vault".to_string());
+    }
+    
+    // The protocol fee is taken off the top of the deposit
+    let fee = crate::fee_collector::protocol_fee(amount);
+    vault.deposit(amount - fee)?;
+        
+    let new_balance = vault.total_value;
+    state.save();
+    
+    crate::fee_collector::FeeCollectorContract::record_fee(crate::fee_collector::VAULT_VALUE_ASSET, fee);
+    crate::events::emit_deposit_event(vault_id, amount, &l1x_sdk::env::caller(), new_balance);
+    
+    Ok(new_balance)
+}
+
+impl CustodialVault {
+    /// Creates a new custodial vault
+    pub fn new(id: String, owner: String, drift_threshold_bp: u32) -> Self {
+        Self {
+            id,
+            owner,
+            status: VaultStatus::Active,
+            allocations: AllocationSet::new(drift_threshold_bp),
+            take_profit: None,
+            total_value: 0,
+            created_at: l1x_sdk::env::block_timestamp(),
+            last_rebalance: 0,
+            multisig_wallet: None,
+            max_rebalance_value: None,
+            max_rebalance_bp: None,
+            auto_compound: AutoCompoundConfig::default(),
+            max_trade_cost_bp: None,
+            high_water_mark: 0,
+            performance_fee_bps: 0,
+            fee_recipient: None,
+            in_progress_rebalance: None,
+            realized_profits: 0,
+            pending_profit_withdrawals: Vec::new(),
+            migration_signature: None,
+        }
+    }
+    
+    /// Current percentage of each asset (in basis points)
+    pub fn current_allocations(&self) -> Vec<(String, u32)> {
+        self.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), a.current_percentage))
+            .collect()
+    }
+    
+    /// Builds the rebalance operation for a plan of `(source, target, value)` trades
+    ///
+    /// The plan is checked against the vault's holdings at `total_value` with
+    /// `RebalanceEngine::validate_transactions`, then every trade is converted
+    /// from USD value to source-asset units using `prices` and the decimals
+    /// registered in `price_feed`.
+    pub fn plan_operation(
+        &self,
+        id: String,
+        strategy: crate::rebalance::RebalanceStrategy,
+        transactions: Vec<(String, String, u128)>,
+        prices: &[(String, u128)],
+        total_value: u128,
+        price_feed: &crate::price_feed::PriceFeedContract,
+    ) -> Result<crate::rebalance::RebalanceOperation, String> {
+        let value_plan = crate::rebalance::RebalanceEngine::create_rebalance_operation(id.clone(), strategy, transactions.clone());
+        
+        // Refuse plans that oversell an asset, move too much at once or repeat a pair
+        let validation_errors = crate::rebalance::RebalanceEngine::validate_transactions(
+            &value_plan,
+            &self.holdings_value(total_value),
+            total_value,
+        );
+        if !validation_errors.is_empty() {
+            return Err(format!("Rebalance validation failed: {:?}", validation_errors));
+        }
+        
+        let prices: std::collections::HashMap<String, u128> = prices.iter().cloned().collect();
+        let operation = crate::rebalance::RebalanceEngine::create_from_registered_assets(
+            id,
+            strategy,
+            transactions,
+            &prices,
+            price_feed,
+        )?;
+        
+        Ok(operation.with_vault_id(self.id.clone())
+            .with_allocation_config_hash(self.allocations.canonical_hash()))
+    }
+    
+    /// Runs one rebalance of the vault, leaving it unchanged unless swaps were submitted
+    ///
+    /// Cross-contract writes are deferred to `RebalanceRun::finish`, which the
+    /// caller runs once the vault state is saved.
+    pub fn try_rebalance(&mut self, request: &RebalanceRequest) -> Result<RebalanceRun, String> {
+        let backup = self.clone();
+        let run = self.run_rebalance(request);
+        if matches!(run, Err(_) | Ok(RebalanceRun::SubmitFailed { .. })) {
+            *self = backup;
+        }
+        
+        run
+    }
+    
+    /// Runs `request` in `RebalanceMode::Simulate` on a copy of the vault, leaving the vault untouched
+    ///
+    /// Multi-sig vaults are refused so a rehearsal never consumes the signers' approval.
+    pub fn rehearse_rebalance(&self, request: &RebalanceRequest) -> Result<RebalanceRun, String> {
+        if self.multisig_wallet.is_some() {
+            return Err(format!("Vault {} requires multi-sig approval to rebalance", self.id));
+        }
+        
+        let request = RebalanceRequest {
+            rebalance_id: request.rebalance_id.clone(),
+            mode: crate::rebalance::RebalanceMode::Simulate,
+            ..*request
+        };
+        
+        self.clone().try_rebalance(&request)
+    }
+    
+    /// Runs one rebalance of the vault: checks, planning and swap submission
+    ///
+    /// Failures before any swap is submitted are returned as errors; the
+    /// caller restores the vault (see `try_rebalance`).
+    fn run_rebalance(&mut self, request: &RebalanceRequest) -> Result<RebalanceRun, String> {
+        let vault_id = self.id.clone();
+        let now = l1x_sdk::env::block_timestamp();
+        
+        if self.status != VaultStatus::Active {
+            let error_msg = format!("Cannot rebalance a non-active vault: status is {:?}", self.status);
+            crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+            return Err(error_msg);
+        }
+        
+        let cooldown = self.allocations.cooldown_remaining(now);
+        if cooldown > 0 {
+            return Err(format!("Rebalance cooldown active for vault {}: {} seconds remaining", vault_id, cooldown));
+        }
+        
+        self.check_rebalance_lock()?;
+        
+        let prices = request.prices;
+        
+        // Refresh current percentages from market values when holdings are known
+        let (current_values, total_value) = match request.balances {
+            Some(balances) => {
+                let values = self.allocations.market_values(balances, prices)
+                    .and_then(|values| {
+                        self.allocations.update_current_from_values(balances, prices)
+                            .map(|total| (values, total))
+                    });
+                    
+                match values {
+                    Ok(v) => v,
+                    Err(e) => {
+                        let error_msg = format!("Failed to value holdings: {}", e);
+                        crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                        return Err(error_msg);
+                    }
+                }
+            },
+            None => (prices.to_vec(), self.total_value), // We're using prices as current values for simplicity
+        };
+        
+        crate::events::emit_rebalance_initiated_event(&vault_id, request.trigger);
+        
+        // First, check if we actually need to rebalance
+        self.allocations.refresh_volatility();
+        if !self.allocations.check_and_emit_rebalance_events(&vault_id) {
+            // No rebalancing needed, but still record the check
+            self.last_rebalance = now;
+            return Ok(RebalanceRun::Idle(format!("No rebalancing needed for vault {}", vault_id)));
+        }
+        
+        let transactions = match self.allocations.calculate_rebalance_transactions(&current_values, total_value) {
+            Ok(t) => t,
+            Err(e) => {
+                let error_msg = format!("Failed to calculate rebalance transactions: {}", e);
+                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                return Err(error_msg);
+            }
+        };
+        
+        // Keep the plan within the vault's rebalance budget, then skip trades not worth their cost
+        let (transactions, truncated_by_cap) = self.apply_rebalance_budget(transactions, total_value);
+        let (transactions, suppressed_trades) = self.apply_trade_costs(transactions);
+        
+        if transactions.is_empty() {
+            self.allocations.record_rebalance(prices);
+            self.last_rebalance = now;
+            
+            // Emit completed event with no transactions
+            crate::events::emit_rebalance_completed_event(&vault_id, 0, None, false);
+            
+            return Ok(RebalanceRun::Idle(format!("No rebalance transactions needed for vault {}", vault_id)));
+        }
+        
+        // Multi-sig vaults only rebalance once their signers have approved
+        if let Some(wallet_id) = self.multisig_wallet.clone() {
+            if !crate::wallet::WalletContract::consume_approval(wallet_id, self.rebalance_action_hash(prices, &transactions)) {
+                let error_msg = format!("Rebalance of vault {} requires multi-sig approval", vault_id);
+                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                return Err(error_msg);
+            }
+        }
+        
+        let rebalance_id = request.rebalance_id.clone()
+            .unwrap_or_else(|| format!("rebalance-{}-{}", vault_id, now));
+        let strategy = if request.trigger == "scheduled" {
+            crate::rebalance::RebalanceStrategy::Scheduled
+        } else if self.allocations.adaptive_threshold.is_some() {
+            crate::rebalance::RebalanceStrategy::Adaptive
+        } else {
+            crate::rebalance::RebalanceStrategy::Threshold
+        };
+        
+        let mut operation = match self.plan_operation(rebalance_id, strategy, transactions.clone(), prices, total_value, request.price_feed) {
+            Ok(operation) => operation.with_mode(request.mode),
+            Err(error_msg) => {
+                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                return Err(error_msg);
+            }
+        };
+        operation.truncated_by_cap = truncated_by_cap;
+        let suppressed_count = suppressed_trades.len();
+        operation.suppressed_trades = suppressed_trades;
+        operation.pre_allocations = self.current_allocations();
+        
+        // Hold the vault until every swap of the operation has settled
+        self.begin_rebalance(&operation.id)?;
+        
+        // Submit the swaps; legs still in flight are finalized by their swap callbacks
+        if let Err(e) = operation.submit() {
+            let error_msg = format!("Rebalance failed: {:?}", e);
+            crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+            return Ok(RebalanceRun::SubmitFailed { message: error_msg, operation });
+        }
+        
+        self.allocations.record_rebalance(prices);
+        self.last_rebalance = now;
+        operation.post_allocations = self.current_allocations();
+        
+        // Emit completed event once no swap is awaiting its callback
+        if operation.is_resolved() {
+            self.finish_rebalance(&operation.id);
+            crate::events::emit_rebalance_completed_event(
+                &vault_id,
+                transactions.len(),
+                operation.total_cost,
+                truncated_by_cap
+            );
+        }
+        
+        // The protocol fee is charged on the value traded; simulated swaps are free
+        if !operation.simulated {
+            operation.protocol_fee = self.charge_rebalance_fee(&transactions, request.protocol_fee_bps);
+        }
+        
+        Ok(RebalanceRun::Submitted {
+            message: format!(
+                "Rebalanced vault {} with {} transactions ({} suppressed)",
+                vault_id, transactions.len(), suppressed_count
+            ),
+            snapshot: self.snapshot_at(total_value.saturating_sub(operation.protocol_fee)),
+            operation,
+        })
+    }
+    
+    /// Checks a gradual rebalance may start and returns its `(source, target, value)` trades
+    ///
+    /// Applies the same status, cooldown, lock and multi-sig checks as
+    /// `run_rebalance`. A non-empty plan counts as a rebalance at `now` for
+    /// the cooldown and daily limit; its slices are not held to either.
+    pub fn plan_gradual_rebalance(&mut self, prices: &[(String, u128)], now: u64) -> Result<Vec<(String, String, u128)>, String> {
+        if self.status != VaultStatus::Active {
+            return Err(format!("Cannot rebalance a non-active vault: status is {:?}", self.status));
+        }
+        
+        let cooldown = self.allocations.cooldown_remaining(now);
+        if cooldown > 0 {
+            return Err(format!("Rebalance cooldown active for vault {}: {} seconds remaining", self.id, cooldown));
+        }
+        
+        if self.allocations.rate_limited_at(now) {
+            return Err(format!("Daily rebalance limit reached for vault {}", self.id));
+        }
+        
+        self.check_rebalance_lock()?;
+        
+        // We're using prices as current values for simplicity, as auto_rebalance does
+        let transactions = self.allocations.calculate_rebalance_transactions(prices, self.total_value)
+            .map_err(|e| format!("Failed to calculate rebalance transactions: {}", e))?;
+        let (transactions, _) = self.apply_rebalance_budget(transactions, self.total_value);
+        let (transactions, _) = self.apply_trade_costs(transactions);
+        
+        if transactions.is_empty() {
+            return Ok(transactions);
+        }
+        
+        // Multi-sig vaults only rebalance once their signers have approved
+        if let Some(wallet_id) = self.multisig_wallet.clone() {
+            if !crate::wallet::WalletContract::consume_approval(wallet_id, self.rebalance_action_hash(prices, &transactions)) {
+                return Err(format!("Rebalance of vault {} requires multi-sig approval", self.id));
+            }
+        }
+        
+        self.allocations.last_rebalance = now;
+        self.allocations.count_rebalance(now);
+        
+        Ok(transactions)
+    }
+    
+    /// Takes the protocol fee on a plan of `(source, target, value)` trades out of the vault
+    ///
+    /// Returns the fee charged, which never exceeds the vault's total value.
+    pub fn charge_rebalance_fee(&mut self, transactions: &[(String, String, u128)], fee_bps: u32) -> u128 {
+        let fee = transactions.iter()
+            .map(|(_, _, value)| crate::fee_collector::fee_at_rate(*value, fee_bps))
+            .fold(0u128, |total, fee| total.saturating_add(fee))
+            .min(self.total_value);
+            
+        self.total_value -= fee;
+        fee
+    }
+    
+    /// Value held in each asset when the vault is worth `total_value`
+    pub fn holdings_value(&self, total_value: u128) -> std::collections::HashMap<String, u128> {
+        self.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), total_value.saturating_mul(a.current_percentage as u128) / 10000))
+            .collect()
+    }
+    
+    /// Value of the vault at fresh price-feed prices
+    pub fn market_value(&self) -> Result<u128, String> {
+        let asset_ids: Vec<String> = self.allocations.allocations.iter()
+            .map(|a| a.asset_id.clone())
+            .collect();
+        let prices = crate::price_feed::fresh_prices(&asset_ids)?;
+        
+        self.allocations.value_at_prices(self.total_value, &prices)
+            .map_err(|err| err.to_string())
+    }
+    
+    /// Target percentage of each asset (in basis points)
+    pub fn target_allocations(&self) -> Vec<(String, u32)> {
+        self.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), a.target_percentage))
+            .collect()
+    }
+    
+    /// Snapshot of the vault at `total_value`, splitting it by current percentages
+    pub fn snapshot_at(&self, total_value: u128) -> crate::portfolio::PortfolioSnapshot {
+        let asset_values = self.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), total_value * (a.current_percentage as u128) / 10000))
+            .collect();
+            
+        let mut snapshot = crate::portfolio::Portfolio::create_snapshot(asset_values, &self.allocations);
+        snapshot.total_value = total_value;
+        snapshot
+    }
+    
+    /// Plans a rebalance at `prices` without touching the vault
+    ///
+    /// Current percentages are revalued from `prices` and turned into values
+    /// against `total_value`; the planned swaps are then applied to those
+    /// values to produce the after percentages.
+    pub fn simulate_rebalance(&self, prices: &[(String, u128)]) -> Result<RebalanceSimulation, String> {
+        let mut allocations = self.allocations.clone();
+        if self.total_value > 0 {
+            allocations.reprice_current(prices)?;
+        }
+        
+        let current_values: Vec<(String, u128)> = allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), self.total_value * (a.current_percentage as u128) / 10000))
+            .collect();
+            
+        let needs_rebalancing = allocations.needs_rebalancing();
+        let (transactions, suppressed_trades) = if needs_rebalancing {
+            let planned = allocations.calculate_rebalance_transactions(&current_values, self.total_value)?;
+            self.apply_trade_costs(self.apply_rebalance_budget(planned, self.total_value).0)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        
+        let mut after_values: std::collections::HashMap<&str, u128> = current_values.iter()
+            .map(|(asset_id, value)| (asset_id.as_str(), *value))
+            .collect();
+        for (source, target, amount) in &transactions {
+            if let Some(value) = after_values.get_mut(source.as_str()) {
+                *value = value.saturating_sub(*amount);
+            }
+            *after_values.entry(target.as_str()).or_insert(0) += *amount;
+        }
+        
+        let percentage_of = |value: u128| -> u32 {
+            if self.total_value == 0 { 0 } else { (value * 10000 / self.total_value) as u32 }
+        };
+        
+        let simulated = allocations.allocations.iter()
+            .map(|a| SimulatedAllocation {
+                asset_id: a.asset_id.clone(),
+                target_percentage: a.target_percentage,
+                before_percentage: a.current_percentage,
+                after_percentage: percentage_of(*after_values.get(a.asset_id.as_str()).unwrap_or(&0)),
+            })
+            .collect();
+            
+        let operation = crate::rebalance::RebalanceEngine::create_rebalance_operation(
+            format!("simulation-{}", self.id),
+            crate::rebalance::RebalanceStrategy::Manual,
+            transactions.clone(),
+        );
+        let estimated_cost = if transactions.is_empty() {
+            0
+        } else {
+            crate::rebalance::RebalanceEngine::estimate_gas_costs(&operation)
+        };
+        
+        Ok(RebalanceSimulation {
+            needs_rebalancing,
+            transactions,
+            allocations: simulated,
+            estimated_cost,
+            suppressed_trades,
+        })
+    }
+    
+    /// Maximum total amount a rebalance of `total_value` may trade, if capped
+    ///
+    /// When both an absolute and a relative cap are set the smaller applies.
+    pub fn rebalance_budget(&self, total_value: u128) -> Option<u128> {
+        let relative = self.max_rebalance_bp
+            .map(|bp| total_value * (bp as u128) / 10000);
+            
+        match (self.max_rebalance_value, relative) {
+            (Some(absolute), Some(relative)) => Some(absolute.min(relative)),
+            (absolute, relative) => absolute.or(relative),
+        }
+    }
+    
+    /// Caps planned transactions to the rebalance budget, most drifted assets first
+    ///
+    /// Returns the capped plan and whether the cap cut it short.
+    pub fn apply_rebalance_budget(
+        &self,
+        transactions: Vec<(String, String, u128)>,
+        total_value: u128,
+    ) -> (Vec<(String, String, u128)>, bool) {
+        let cap = match self.rebalance_budget(total_value) {
+            Some(cap) => cap,
+            None => return (transactions, false),
         };
         
-        // Set new baseline
-        strategy.set_baseline(current_value);
+        let drifts: std::collections::HashMap<String, u32> = self.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), a.drift()))
+            .collect();
+            
+        crate::rebalance::RebalanceEngine::apply_budget_cap(transactions, &drifts, cap)
+    }
+    
+    /// Charges the performance fee on `current_value`'s gain above the high-water mark
+    ///
+    /// The fee is deducted from the vault's total value and the high-water mark
+    /// moves up to `current_value`. Returns the fee, which is zero when there is
+    /// no new profit above the mark or no fee is configured.
+    pub fn charge_performance_fee(&mut self, current_value: u128) -> u128 {
+        if current_value <= self.high_water_mark {
+            return 0;
+        }
         
-        state.save();
+        let gain = current_value - self.high_water_mark;
+        self.high_water_mark = current_value;
         
-        format!("Manual take profit executed for vault {}, profit: {}, new baseline: {}", vault_id, profit_amount, current_value)
+        if self.fee_recipient.is_none() {
+            return 0;
+        }
+        
+        let fee = (gain * self.performance_fee_bps as u128 / 10000).min(self.total_value);
+        self.total_value -= fee;
+        
+        fee
     }
-}
-
-impl CustodialVault {
-    /// Creates a new custodial vault
-    pub fn new(id: String, owner: String, drift_threshold_bp: u32) -> Self {
-        Self {
-            id,
-            owner,
-            status: VaultStatus::Active,
-            allocations: AllocationSet::new(drift_threshold_bp),
-            take_profit: None,
-            total_value: 0,
-            created_at: l1x_sdk::env::block_timestamp(),
-            last_rebalance: 0,
+    
+    /// Splits planned transactions into those worth executing and those suppressed by `max_trade_cost_bp`
+    pub fn apply_trade_costs(
+        &self,
+        transactions: Vec<(String, String, u128)>,
+    ) -> (Vec<(String, String, u128)>, Vec<SuppressedTrade>) {
+        match self.max_trade_cost_bp {
+            Some(max_cost_bp) => crate::rebalance::RebalanceEngine::plan_with_costs(
+                transactions,
+                crate::cross_chain::swap_fee_bps,
+                crate::rebalance::GAS_COST_PER_TX,
+                max_cost_bp,
+            ),
+            None => (transactions, Vec::new()),
+        }
+    }
+    
+    /// Fails while another rebalance of this vault still has swaps in flight
+    pub fn check_rebalance_lock(&self) -> Result<(), String> {
+        match &self.in_progress_rebalance {
+            Some(rebalance_id) => Err(format!(
+                "Rebalance {} is still in progress for vault {}", rebalance_id, self.id
+            )),
+            None => Ok(()),
+        }
+    }
+    
+    /// Locks the vault for `rebalance_id` until its swaps have settled
+    pub fn begin_rebalance(&mut self, rebalance_id: &str) -> Result<(), String> {
+        self.check_rebalance_lock()?;
+        self.in_progress_rebalance = Some(rebalance_id.to_string());
+        Ok(())
+    }
+    
+    /// Releases the lock if `rebalance_id` holds it, returning whether it did
+    pub fn finish_rebalance(&mut self, rebalance_id: &str) -> bool {
+        if self.in_progress_rebalance.as_deref() != Some(rebalance_id) {
+            return false;
         }
+        
+        self.in_progress_rebalance = None;
+        true
+    }
+    
+    /// Action hash multi-sig signers approve to authorize one rebalance plan of this vault
+    ///
+    /// Keccak256 of the borsh encoding of `(vault_id, prices, transactions)`,
+    /// so an approval covers exactly the prices and trades it was given for.
+    pub fn rebalance_action_hash(&self, prices: &[(String, u128)], transactions: &[(String, String, u128)]) -> Vec<u8> {
+        let encoded = (self.id.clone(), prices.to_vec(), transactions.to_vec())
+            .try_to_vec()
+            .expect("rebalance plan serializes");
+            
+        l1x_sdk::env::keccak256(&encoded).to_vec()
     }
     
     /// Checks if the vault needs rebalancing
@@ -640,9 +2776,57 @@ impl CustodialVault {
         self.total_value = self.total_value.checked_add(amount)
             .ok_or("Overflow in deposit calculation")?;
             
+        // A strategy without a baseline (new or cloned) measures gains from the first deposit
+        if let Some(strategy) = self.take_profit.as_mut() {
+            if strategy.baseline_value == 0 {
+                strategy.set_baseline(self.total_value);
+            }
+        }
+        
         Ok(())
     }
     
+    /// Holds or queues take-profit proceeds that were not reinvested
+    pub fn set_aside_profit(&mut self, destination: ProfitDestination, amount: u128, asset: &str, now: u64) {
+        if amount == 0 {
+            return;
+        }
+        
+        match destination {
+            ProfitDestination::ReinvestAsAllocation => {},
+            ProfitDestination::HoldInVault => {
+                self.realized_profits = self.realized_profits.saturating_add(amount);
+            },
+            ProfitDestination::QueueWithdrawal => {
+                self.pending_profit_withdrawals.push(ProfitWithdrawal {
+                    amount,
+                    asset: asset.to_string(),
+                    queued_at: now,
+                });
+            },
+        }
+    }
+    
+    /// Total take-profit proceeds queued for withdrawal
+    pub fn pending_profit_balance(&self) -> u128 {
+        self.pending_profit_withdrawals.iter()
+            .fold(0u128, |total, withdrawal| total.saturating_add(withdrawal.amount))
+    }
+    
+    /// Pays out the queued take-profit withdrawals, returning the amount claimed
+    ///
+    /// The proceeds were realized from the vault's holdings, so they leave
+    /// its total value when claimed.
+    pub fn claim_profits(&mut self) -> Result<u128, &'static str> {
+        let claimed = self.pending_profit_balance();
+        
+        self.total_value = self.total_value.checked_sub(claimed)
+            .ok_or("Insufficient funds to pay out profits")?;
+        self.pending_profit_withdrawals.clear();
+        
+        Ok(claimed)
+    }
+    
     /// Withdraws funds from the vault
     pub fn withdraw(&mut self, amount: u128) -> Result<(), &'static str> {
         if self.status != VaultStatus::Active {
@@ -772,22 +2956,132 @@ impl CustodialVault {
         Ok(swap_requests)
     }
     
-    /// Checks if take profit conditions are met
-    pub fn should_take_profit(&self, current_prices: &[(String, u128)]) -> bool {
-        if self.status != VaultStatus::Active || self.take_profit.is_none() {
+    /// Checks if take profit conditions are met at the vault's `current_value`
+    pub fn should_take_profit(&self, current_value: u128) -> bool {
+        if self.status != VaultStatus::Active {
             return false;
         }
         
         match &self.take_profit {
-            Some(strategy) => strategy.should_execute(current_prices),
+            Some(strategy) => strategy.should_execute(current_value),
             None => false,
         }
     }
     
+    /// Copy of the vault's configuration as a new, empty vault `id` owned by `owner`
+    ///
+    /// Allocations start at their targets and the take profit strategy keeps
+    /// its configuration but restarts from a zero baseline. Balances, fees
+    /// owed, locks and the multi-sig wallet are not carried over.
+    pub fn clone_as(&self, id: String, owner: String, now: u64) -> Self {
+        let mut allocations = self.allocations.clone();
+        allocations.last_rebalance = 0;
+        allocations.rebalances_in_window = 0;
+        allocations.window_start = 0;
+        allocations.allocation_history.clear();
+        for allocation in allocations.allocations.iter_mut() {
+            allocation.current_percentage = allocation.target_percentage;
+            allocation.last_rebalance = 0;
+            allocation.last_price = None;
+        }
+        
+        // The baseline is set again by the clone's first deposit
+        let take_profit = self.take_profit.clone().map(|mut strategy| {
+            strategy.set_baseline(0);
+            strategy.last_execution = 0;
+            strategy.recent_executions.clear();
+            strategy
+        });
+        
+        Self {
+            id,
+            owner,
+            status: VaultStatus::Active,
+            allocations,
+            take_profit,
+            total_value: 0,
+            created_at: now,
+            last_rebalance: 0,
+            multisig_wallet: None,
+            max_rebalance_value: self.max_rebalance_value,
+            max_rebalance_bp: self.max_rebalance_bp,
+            auto_compound: self.auto_compound.clone(),
+            max_trade_cost_bp: self.max_trade_cost_bp,
+            high_water_mark: 0,
+            performance_fee_bps: self.performance_fee_bps,
+            fee_recipient: self.fee_recipient.clone(),
+            in_progress_rebalance: None,
+            realized_profits: 0,
+            pending_profit_withdrawals: Vec::new(),
+            migration_signature: None,
+        }
+    }
+    
+    /// Stops the vault and issues the payload migrating it at `now`
+    pub fn begin_migration(&mut self, now: u64) -> Result<MigrationPayload, &'static str> {
+        if self.status != VaultStatus::Active && self.status != VaultStatus::Paused {
+            return Err("Only active or paused vaults can be migrated");
+        }
+        
+        if self.in_progress_rebalance.is_some() {
+            return Err("A rebalance is still in progress");
+        }
+        
+        let mut payload = MigrationPayload {
+            source_vault_id: self.id.clone(),
+            owner: self.owner.clone(),
+            allocations: self.allocations.clone(),
+            take_profit: self.take_profit.clone(),
+            total_value: self.total_value,
+            issued_at: now,
+            signature: String::new(),
+        };
+        payload.signature = payload.digest();
+        
+        self.status = VaultStatus::Migrating;
+        self.migration_signature = Some(payload.signature.clone());
+        
+        Ok(payload)
+    }
+    
+    /// Closes the vault once `payload` has been checked against the one it issued
+    pub fn finish_migration(&mut self, payload: &MigrationPayload) -> Result<(), &'static str> {
+        if self.status != VaultStatus::Migrating {
+            return Err("Vault is not migrating");
+        }
+        
+        if self.migration_signature.as_deref() != Some(payload.signature.as_str()) || payload.digest() != payload.signature {
+            return Err("Invalid migration payload signature");
+        }
+        
+        self.status = VaultStatus::Closed;
+        self.migration_signature = None;
+        
+        Ok(())
+    }
+    
     /// Changes the vault status
     pub fn change_status(&mut self, new_status: VaultStatus) {
         self.status = new_status;
     }
+    
+    /// Changes the vault status, returning the event to emit if it actually changed
+    pub fn transition_status(&mut self, new_status: VaultStatus, changed_by: &str) -> Option<crate::events::VaultStatusChangedEvent> {
+        let old_status = self.status;
+        
+        if old_status == new_status {
+            return None;
+        }
+        
+        self.status = new_status;
+        
+        Some(crate::events::VaultStatusChangedEvent::new(
+            self.id.clone(),
+            Some(old_status),
+            new_status,
+            changed_by.to_string(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -855,8 +3149,742 @@ mod tests {
         
         assert!(vault.take_profit.is_some());
         
-        // Paused vault cannot change strategy
+        // Gains are measured on the vault's value against the baseline
+        vault.take_profit.as_mut().unwrap().set_baseline(1000);
+        assert!(!vault.should_take_profit(1050));
+        assert!(vault.should_take_profit(1100));
+        
+        // Paused vault cannot change strategy or take profit
         vault.change_status(VaultStatus::Paused);
         assert!(vault.set_take_profit_strategy(TakeProfitType::Manual).is_err());
+        assert!(!vault.should_take_profit(1100));
+    }
+    
+    #[test]
+    fn test_status_transition_events() {
+        let mut vault = CustodialVault::new(
+            "vault-1".to_string(),
+            "owner-1".to_string(),
+            300,
+        );
+        
+        let paused = vault.transition_status(VaultStatus::Paused, "owner-1").unwrap();
+        let resumed = vault.transition_status(VaultStatus::Active, "owner-1").unwrap();
+        
+        assert_eq!(paused.old_status, Some(VaultStatus::Active));
+        assert_eq!(paused.new_status, VaultStatus::Paused);
+        assert_eq!(resumed.old_status, Some(VaultStatus::Paused));
+        assert_eq!(resumed.new_status, VaultStatus::Active);
+        
+        // No event when the status does not change
+        assert!(vault.transition_status(VaultStatus::Active, "owner-1").is_none());
+    }
+    
+    #[test]
+    fn test_user_vaults_pagination() {
+        let mut state = CustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 0,
+            processed_rebalance_ids: std::collections::HashSet::new(),
+        };
+        
+        for i in 0..5 {
+            let vault_id = format!("vault-{}", i);
+            state.vaults.insert(vault_id.clone(), CustodialVault::new(vault_id.clone(), "owner-1".to_string(), 300));
+            state.user_vaults.entry("owner-1".to_string()).or_insert_with(Vec::new).push(vault_id);
+        }
+        
+        let first = state.user_vaults_page("owner-1", 0, 3);
+        assert_eq!(first.total, 5);
+        assert_eq!(first.vaults.len(), 3);
+        assert_eq!(first.vaults[0].id, "vault-0");
+        assert_eq!(first.vaults[2].id, "vault-2");
+        
+        let second = state.user_vaults_page("owner-1", 3, 3);
+        assert_eq!(second.total, 5);
+        assert_eq!(second.offset, 3);
+        assert_eq!(second.vaults.len(), 2);
+        assert_eq!(second.vaults[0].id, "vault-3");
+        assert_eq!(second.vaults[1].id, "vault-4");
+        
+        // Past the end yields an empty page
+        assert!(state.user_vaults_page("owner-1", 5, 3).vaults.is_empty());
+    }
+    
+    #[test]
+    fn test_snapshot_at() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        
+        let snapshot = vault.snapshot_at(25_000);
+        assert_eq!(snapshot.total_value, 25_000);
+        assert_eq!(snapshot.asset_values, vec![("BTC".to_string(), 15_000), ("ETH".to_string(), 10_000)]);
+        assert_eq!(snapshot.asset_allocations, vec![("BTC".to_string(), 6000), ("ETH".to_string(), 4000)]);
+    }
+    
+    #[test]
+    fn test_simulate_rebalance() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.deposit(10000).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.change_status(VaultStatus::Paused);
+        
+        let before = serde_json::to_string(&vault).unwrap();
+        let simulation = vault.simulate_rebalance(&[]).unwrap();
+        
+        assert!(simulation.needs_rebalancing);
+        assert_eq!(simulation.transactions, vec![("BTC".to_string(), "ETH".to_string(), 1000)]);
+        assert_eq!(simulation.allocations[0].before_percentage, 7000);
+        assert_eq!(simulation.allocations[0].after_percentage, 6000);
+        assert_eq!(simulation.allocations[1].after_percentage, 4000);
+        assert_eq!(simulation.estimated_cost, 3_500_000);
+        
+        // Nothing about the vault changed
+        assert_eq!(serde_json::to_string(&vault).unwrap(), before);
+    }
+    
+    #[test]
+    fn test_rebalance_budget() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.deposit(10000).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        
+        let planned = vec![("BTC".to_string(), "ETH".to_string(), 1000)];
+        
+        // No budget configured
+        assert_eq!(vault.rebalance_budget(10000), None);
+        assert_eq!(vault.apply_rebalance_budget(planned.clone(), 10000), (planned.clone(), false));
+        
+        // 5% of the vault per run
+        vault.max_rebalance_bp = Some(500);
+        assert_eq!(vault.rebalance_budget(10000), Some(500));
+        assert_eq!(
+            vault.apply_rebalance_budget(planned.clone(), 10000),
+            (vec![("BTC".to_string(), "ETH".to_string(), 500)], true)
+        );
+        
+        // The smaller of the absolute and relative caps applies
+        vault.max_rebalance_value = Some(300);
+        assert_eq!(vault.rebalance_budget(10000), Some(300));
+        vault.max_rebalance_value = Some(2000);
+        assert_eq!(vault.rebalance_budget(10000), Some(500));
+        
+        // The dry run previews the capped plan
+        let simulation = vault.simulate_rebalance(&[]).unwrap();
+        assert_eq!(simulation.transactions, vec![("BTC".to_string(), "ETH".to_string(), 500)]);
+        assert_eq!(simulation.allocations[0].after_percentage, 6500);
+    }
+    
+    #[test]
+    fn test_trade_cost_suppression() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 50);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.deposit(100_000_000).unwrap(); // $1
+        vault.allocations.allocations[0].update_current_percentage(6100);
+        vault.allocations.allocations[1].update_current_percentage(3900);
+        
+        // Without a limit every planned trade runs
+        let simulation = vault.simulate_rebalance(&[]).unwrap();
+        assert_eq!(simulation.transactions, vec![("BTC".to_string(), "ETH".to_string(), 1_000_000)]);
+        assert!(simulation.suppressed_trades.is_empty());
+        
+        // Gas alone dwarfs the 1% drift correction
+        vault.max_trade_cost_bp = Some(crate::rebalance::DEFAULT_MAX_TRADE_COST_BP);
+        let simulation = vault.simulate_rebalance(&[]).unwrap();
+        assert!(simulation.transactions.is_empty());
+        assert_eq!(simulation.suppressed_trades.len(), 1);
+        assert_eq!(simulation.suppressed_trades[0].estimated_cost, 5_000 + crate::rebalance::GAS_COST_PER_TX);
+        assert_eq!(simulation.suppressed_trades[0].cost_ratio_bp, 25050);
+        assert_eq!(simulation.estimated_cost, 0);
+    }
+    
+    #[test]
+    fn test_performance_fee_high_water_mark() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.deposit(10000).unwrap();
+        vault.performance_fee_bps = 2000; // 20%
+        vault.fee_recipient = Some("manager".to_string());
+        vault.high_water_mark = 10000;
+        
+        // No new profit above the mark
+        assert_eq!(vault.charge_performance_fee(9000), 0);
+        assert_eq!(vault.high_water_mark, 10000);
+        
+        // 20% of the 2000 gain
+        assert_eq!(vault.charge_performance_fee(12000), 400);
+        assert_eq!(vault.high_water_mark, 12000);
+        assert_eq!(vault.total_value, 9600);
+        
+        // Dropping and recovering to the same level charges nothing again
+        assert_eq!(vault.charge_performance_fee(11000), 0);
+        assert_eq!(vault.charge_performance_fee(12000), 0);
+        assert_eq!(vault.total_value, 9600);
+        
+        // Only the gain above the mark is charged
+        assert_eq!(vault.charge_performance_fee(13000), 200);
+        assert_eq!(vault.high_water_mark, 13000);
+        
+        // Without a recipient the mark still tracks gains but nothing is charged
+        vault.fee_recipient = None;
+        assert_eq!(vault.charge_performance_fee(14000), 0);
+        assert_eq!(vault.high_water_mark, 14000);
+        assert_eq!(vault.total_value, 9400);
+    }
+    
+    #[test]
+    fn test_rebalance_lock() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        assert!(vault.check_rebalance_lock().is_ok());
+        
+        vault.begin_rebalance("rebalance-1").unwrap();
+        assert_eq!(vault.in_progress_rebalance.as_deref(), Some("rebalance-1"));
+        
+        // A second rebalance is rejected with the blocking operation's ID
+        let err = vault.begin_rebalance("rebalance-2").unwrap_err();
+        assert!(err.contains("rebalance-1"));
+        assert_eq!(vault.check_rebalance_lock().unwrap_err(), err);
+        
+        // Only the holder releases the lock
+        assert!(!vault.finish_rebalance("rebalance-2"));
+        assert!(vault.in_progress_rebalance.is_some());
+        assert!(vault.finish_rebalance("rebalance-1"));
+        assert!(vault.in_progress_rebalance.is_none());
+        
+        vault.begin_rebalance("rebalance-2").unwrap();
+    }
+    
+    thread_local! {
+        static TEST_LOCK: std::cell::Cell<bool> = std::cell::Cell::new(false);
+    }
+    
+    fn test_locked() -> bool {
+        TEST_LOCK.with(|lock| lock.get())
+    }
+    
+    fn set_test_lock(locked: bool) {
+        TEST_LOCK.with(|lock| lock.set(locked));
+    }
+    
+    /// Stand-in for `withdraw` whose transfer hands control to `on_transfer`
+    fn guarded_withdraw(vault: &std::cell::RefCell<CustodialVault>, amount: u128, on_transfer: &dyn Fn()) {
+        let _guard = ReentrancyGuard::acquire(test_locked, set_test_lock);
+        
+        if vault.borrow().total_value < amount {
+            panic!("Insufficient funds in vault");
+        }
+        
+        on_transfer();
+        vault.borrow_mut().withdraw(amount).unwrap();
+    }
+    
+    #[test]
+    fn test_reentrant_withdraw_rejected() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.deposit(1000).unwrap();
+        let vault = std::cell::RefCell::new(vault);
+        
+        // A malicious transfer target calls back into withdraw
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            guarded_withdraw(&vault, 600, &|| guarded_withdraw(&vault, 600, &|| {}));
+        }));
+        
+        let err = result.unwrap_err();
+        assert_eq!(err.downcast_ref::<&str>(), Some(&"Reentrant call detected"));
+        assert_eq!(vault.borrow().total_value, 1000);
+        
+        // The lock is released once the outer call unwinds
+        assert!(!test_locked());
+        guarded_withdraw(&vault, 600, &|| {});
+        assert_eq!(vault.borrow().total_value, 400);
+        assert!(!test_locked());
+    }
+    
+    #[test]
+    fn test_keeper_authorization_and_rewards() {
+        let mut state = CustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 5,
+            processed_rebalance_ids: std::collections::HashSet::new(),
+        };
+        state.vaults.insert("vault-1".to_string(), CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300));
+        state.keepers.insert("keeper-1".to_string(), KeeperStats::default());
+        
+        assert!(state.can_auto_rebalance("vault-1", "owner-1"));
+        assert!(state.can_auto_rebalance("vault-1", "keeper-1"));
+        assert!(!state.can_auto_rebalance("vault-1", "stranger"));
+        assert!(!state.can_auto_rebalance("vault-2", "owner-1"));
+        
+        let stats = state.keepers.get_mut("keeper-1").unwrap();
+        stats.record_execution(state.keeper_reward);
+        stats.record_execution(state.keeper_reward);
+        assert_eq!(*stats, KeeperStats { executions: 2, reward_balance: 10 });
+    }
+    
+    #[test]
+    fn test_rebalance_report() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.total_value = 1000;
+        let prices = vec![("BTC".to_string(), 700), ("ETH".to_string(), 300)];
+        
+        let transactions = vault.allocations.calculate_rebalance_transactions(&prices, vault.total_value).unwrap();
+        let mut operation = crate::rebalance::RebalanceEngine::create_rebalance_operation(
+            "rebalance-1".to_string(),
+            crate::rebalance::RebalanceStrategy::Threshold,
+            transactions,
+        ).with_vault_id("vault-1".to_string());
+        operation.pre_allocations = vault.current_allocations();
+        
+        operation.execute().unwrap();
+        vault.allocations.record_rebalance(&prices);
+        operation.post_allocations = vault.current_allocations();
+        
+        let report = operation.report(&vault.target_allocations());
+        
+        assert!(operation.transactions.iter().all(|t| t.status == crate::rebalance::RebalanceStatus::Completed));
+        assert_eq!(report.drift_before_bp, 2000);
+        assert_eq!(report.drift_reduction_bp, report.drift_before_bp as i64 - report.drift_after_bp as i64);
+        for (asset_id, percentage) in &report.post_allocations {
+            let target = vault.allocations.get_allocation(asset_id).unwrap().target_percentage;
+            assert!(percentage.abs_diff(target) <= vault.allocations.drift_threshold_bp);
+        }
+    }
+    
+    #[test]
+    fn test_rebalance_ids_processed_once() {
+        let drifted = |id: &str| {
+            let mut vault = CustodialVault::new(id.to_string(), "owner-1".to_string(), 300);
+            vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+            vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+            vault.allocations.allocations[0].update_current_percentage(7000);
+            vault.allocations.allocations[1].update_current_percentage(3000);
+            vault.deposit(1000).unwrap();
+            vault
+        };
+        
+        let mut state = CustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 0,
+            processed_rebalance_ids: std::collections::HashSet::new(),
+        };
+        state.insert_vault(drifted("vault-1")).unwrap();
+        state.insert_vault(drifted("vault-2")).unwrap();
+        
+        let mut price_feed = crate::price_feed::PriceFeedContract::with_admin("admin".to_string());
+        for symbol in ["BTC", "ETH"] {
+            price_feed.register_asset_metadata(crate::price_feed::AssetMetadata {
+                symbol: symbol.to_string(),
+                decimals: 8,
+                display_name: symbol.to_string(),
+                addresses: std::collections::HashMap::new(),
+                active: true,
+                registered_at: 0,
+            }).unwrap();
+        }
+        let emergency = crate::emergency::EmergencyPauseContract::unpaused();
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        // Holdings worth 700/300, the 70/30 drift against 60/40 targets
+        let balances = vec![("BTC".to_string(), 7), ("ETH".to_string(), 30)];
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "manual",
+            prices: &prices,
+            balances: Some(&balances),
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 0,
+            mode: crate::rebalance::RebalanceMode::Simulate,
+        };
+        
+        let first = match state.rebalance_once("vault-1", "rebalance-1", &request) {
+            Ok(Some(RebalanceRun::Submitted { operation, .. })) => operation,
+            _ => panic!("the first submission should rebalance"),
+        };
+        assert_eq!(first.id, "idempotent-vault-1-rebalance-1");
+        
+        // A retried submission is a no-op
+        assert!(matches!(state.rebalance_once("vault-1", "rebalance-1", &request), Ok(None)));
+        assert_eq!(state.processed_rebalance_ids.len(), 1);
+        
+        // The same ID on another vault is its own rebalance, recorded under its own operation ID
+        let other = match state.rebalance_once("vault-2", "rebalance-1", &request) {
+            Ok(Some(RebalanceRun::Submitted { operation, .. })) => operation,
+            _ => panic!("another vault's submission should rebalance"),
+        };
+        assert_eq!(other.id, "idempotent-vault-2-rebalance-1");
+        
+        // A failed attempt can be retried
+        assert!(state.rebalance_once("vault-3", "rebalance-1", &request).is_err());
+        assert!(!state.processed_rebalance_ids.contains(&("vault-3".to_string(), "rebalance-1".to_string())));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_profit_destinations() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.deposit(1000).unwrap();
+        
+        // Reinvested proceeds stay in the allocations, not set aside
+        vault.set_aside_profit(ProfitDestination::ReinvestAsAllocation, 100, "USDC", 10);
+        assert_eq!(vault.realized_profits, 0);
+        assert_eq!(vault.pending_profit_balance(), 0);
+        
+        vault.set_aside_profit(ProfitDestination::HoldInVault, 100, "USDC", 10);
+        assert_eq!(vault.realized_profits, 100);
+        assert_eq!(vault.pending_profit_balance(), 0);
+        
+        vault.set_aside_profit(ProfitDestination::QueueWithdrawal, 50, "USDC", 20);
+        vault.set_aside_profit(ProfitDestination::QueueWithdrawal, 0, "USDC", 25);
+        vault.set_aside_profit(ProfitDestination::QueueWithdrawal, 70, "USDT", 30);
+        assert_eq!(vault.pending_profit_withdrawals.len(), 2);
+        assert_eq!(vault.pending_profit_balance(), 120);
+        
+        // Claiming pays the queue out of the vault once, leaving held profits untouched
+        assert_eq!(vault.claim_profits(), Ok(120));
+        assert_eq!(vault.total_value, 880);
+        assert_eq!(vault.claim_profits(), Ok(0));
+        assert_eq!(vault.total_value, 880);
+        assert_eq!(vault.realized_profits, 100);
+        
+        // A queue larger than the vault is refused without clearing it
+        vault.set_aside_profit(ProfitDestination::QueueWithdrawal, 1000, "USDC", 40);
+        assert!(vault.claim_profits().is_err());
+        assert_eq!(vault.pending_profit_balance(), 1000);
+        assert_eq!(vault.total_value, 880);
+    }
+    
+    #[test]
+    fn test_clone_vault_copies_configuration() {
+        let mut source = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        source.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        source.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        source.allocations.allocations[0].update_current_percentage(7000);
+        source.allocations.allocations[1].update_current_percentage(3000);
+        source.allocations.last_rebalance = 500;
+        source.deposit(1000).unwrap();
+        
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        strategy.set_baseline(1000);
+        strategy.set_profit_portion(5000).unwrap();
+        source.take_profit = Some(strategy);
+        
+        let mut clone = source.clone_as("vault-2".to_string(), "owner-2".to_string(), 900);
+        
+        assert_eq!(clone.id, "vault-2");
+        assert_eq!(clone.owner, "owner-2");
+        assert_eq!(clone.total_value, 0);
+        assert_eq!(clone.created_at, 900);
+        assert_eq!(clone.allocations.last_rebalance, 0);
+        
+        // Same targets, with current allocations reset to them
+        assert_eq!(clone.target_allocations(), source.target_allocations());
+        assert_eq!(clone.current_allocations(), vec![("BTC".to_string(), 6000), ("ETH".to_string(), 4000)]);
+        
+        // Same take profit configuration, restarted from a zero baseline
+        let cloned_strategy = clone.take_profit.as_ref().unwrap();
+        assert_eq!(cloned_strategy.strategy_type, TakeProfitType::Percentage { percentage: 1000 });
+        assert_eq!(cloned_strategy.profit_portion_bp, 5000);
+        assert_eq!(cloned_strategy.baseline_value, 0);
+        
+        // A separate object: changing the clone leaves the source alone
+        clone.deposit(250).unwrap();
+        clone.allocations.allocations[0].target_percentage = 5000;
+        assert_eq!(source.total_value, 1000);
+        assert_eq!(source.allocations.allocations[0].target_percentage, 6000);
+        assert_eq!(source.allocations.allocations[0].current_percentage, 7000);
+        
+        // The first deposit restarts take profit from the clone's own value
+        let cloned_strategy = clone.take_profit.as_ref().unwrap();
+        assert_eq!(cloned_strategy.baseline_value, 250);
+        assert!(!cloned_strategy.should_execute(260));
+        assert!(cloned_strategy.should_execute(275));
+    }
+    
+    #[test]
+    fn test_batch_rebalance_skips_paused_vault() {
+        let drifted = |id: &str| {
+            let mut vault = CustodialVault::new(id.to_string(), "owner-1".to_string(), 300);
+            vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+            vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+            vault.allocations.allocations[0].update_current_percentage(7000);
+            vault.allocations.allocations[1].update_current_percentage(3000);
+            vault.deposit(1000).unwrap();
+            vault
+        };
+        
+        let mut state = CustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 0,
+            processed_rebalance_ids: std::collections::HashSet::new(),
+        };
+        for id in ["vault-1", "vault-2", "vault-3"] {
+            state.insert_vault(drifted(id)).unwrap();
+        }
+        state.vaults.get_mut("vault-2").unwrap().change_status(VaultStatus::Paused);
+        
+        let vault_ids: Vec<String> = ["vault-1", "vault-2", "vault-3", "vault-4"].iter().map(|id| id.to_string()).collect();
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        
+        let mut price_feed = crate::price_feed::PriceFeedContract::with_admin("admin".to_string());
+        for symbol in ["BTC", "ETH"] {
+            price_feed.register_asset_metadata(crate::price_feed::AssetMetadata {
+                symbol: symbol.to_string(),
+                decimals: 8,
+                display_name: symbol.to_string(),
+                addresses: std::collections::HashMap::new(),
+                active: true,
+                registered_at: 0,
+            }).unwrap();
+        }
+        
+        let emergency = crate::emergency::EmergencyPauseContract::unpaused();
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "batch",
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 100,
+            mode: crate::rebalance::RebalanceMode::Simulate,
+        };
+        // Holdings worth 700/300, the 70/30 drift against 60/40 targets
+        let balances: std::collections::HashMap<String, Vec<(String, u128)>> = ["vault-1", "vault-2", "vault-3"].iter()
+            .map(|id| (id.to_string(), vec![("BTC".to_string(), 7), ("ETH".to_string(), 30)]))
+            .collect();
+        let (results, runs) = state.rebalance_batch(&vault_ids, &balances, &request);
+        
+        let statuses: Vec<BatchRebalanceStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![
+            BatchRebalanceStatus::Rebalanced,
+            BatchRebalanceStatus::Skipped,
+            BatchRebalanceStatus::Rebalanced,
+            BatchRebalanceStatus::Failed,
+        ]);
+        assert_eq!(results[3].message, "Vault not found");
+        
+        // Rebalanced vaults went through the full pipeline and settled their operation
+        assert_eq!(runs.len(), 2);
+        for (result, id) in [(&results[0], "vault-1"), (&results[2], "vault-3")] {
+            assert!(result.rebalance_id.is_some());
+            assert!(result.transaction_count > 0);
+            assert!(state.vaults[id].in_progress_rebalance.is_none());
+            
+            // Simulated swaps are not charged the protocol fee
+            assert_eq!(state.vaults[id].total_value, 1000);
+        }
+        
+        // The paused vault is untouched
+        assert_eq!(state.vaults["vault-2"].allocations.allocations[0].current_percentage, 7000);
+        assert_eq!(state.vaults["vault-2"].last_rebalance, 0);
+    }
+    
+    #[test]
+    fn test_rebalance_action_hash_covers_plan() {
+        let vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        let prices = vec![("BTC".to_string(), 50_000), ("ETH".to_string(), 2_500)];
+        let plan = vec![("BTC".to_string(), "ETH".to_string(), 100)];
+        
+        let hash = vault.rebalance_action_hash(&prices, &plan);
+        assert_eq!(hash, vault.rebalance_action_hash(&prices, &plan));
+        
+        // An approval of one plan does not authorize another
+        let bigger_plan = vec![("BTC".to_string(), "ETH".to_string(), 1_000)];
+        assert_ne!(hash, vault.rebalance_action_hash(&prices, &bigger_plan));
+        
+        let moved_prices = vec![("BTC".to_string(), 40_000), ("ETH".to_string(), 2_500)];
+        assert_ne!(hash, vault.rebalance_action_hash(&moved_prices, &plan));
+        
+        let other = CustodialVault::new("vault-2".to_string(), "owner-1".to_string(), 300);
+        assert_ne!(hash, other.rebalance_action_hash(&prices, &plan));
+    }
+    
+    #[test]
+    fn test_rebalance_blocked_while_protocol_paused() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.deposit(1000).unwrap();
+        
+        let mut state = CustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+            reentrancy_lock: false,
+            accrued_fees: std::collections::HashMap::new(),
+            admin: "admin".to_string(),
+            keepers: std::collections::HashMap::new(),
+            keeper_reward: 0,
+            processed_rebalance_ids: std::collections::HashSet::new(),
+        };
+        state.insert_vault(vault).unwrap();
+        
+        let mut emergency = crate::emergency::EmergencyPauseContract::unpaused();
+        emergency.set_paused(crate::emergency::EMERGENCY_ADMINS[0], true, "Incident".to_string()).unwrap();
+        
+        let price_feed = crate::price_feed::PriceFeedContract::with_admin("admin".to_string());
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "manual",
+            prices: &prices,
+            balances: None,
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 100,
+            mode: crate::rebalance::RebalanceMode::Simulate,
+        };
+        
+        // The shared pipeline behind rebalance, auto_rebalance and batch_rebalance refuses to run
+        assert_eq!(
+            state.rebalance_vault("vault-1", &request).err(),
+            Some(crate::emergency::PROTOCOL_PAUSED_ERROR.to_string())
+        );
+        
+        let vault_ids = vec!["vault-1".to_string()];
+        let (results, runs) = state.rebalance_batch(&vault_ids, &std::collections::HashMap::new(), &request);
+        assert_eq!(results[0].status, BatchRebalanceStatus::Failed);
+        assert_eq!(results[0].message, crate::emergency::PROTOCOL_PAUSED_ERROR);
+        assert!(runs.is_empty());
+        
+        let vault = &state.vaults["vault-1"];
+        assert_eq!(vault.last_rebalance, 0);
+        assert!(vault.in_progress_rebalance.is_none());
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 7000);
+    }
+    
+    #[test]
+    fn test_charge_rebalance_fee() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.deposit(10_000).unwrap();
+        
+        // 1% of the 3_000 traded comes out of the vault
+        let plan = vec![
+            ("BTC".to_string(), "ETH".to_string(), 2_000),
+            ("BTC".to_string(), "USDC".to_string(), 1_000),
+        ];
+        assert_eq!(vault.charge_rebalance_fee(&plan, 100), 30);
+        assert_eq!(vault.total_value, 9_970);
+        
+        assert_eq!(vault.charge_rebalance_fee(&plan, 0), 0);
+        assert_eq!(vault.total_value, 9_970);
+        
+        // Never more than the vault holds
+        vault.total_value = 10;
+        assert_eq!(vault.charge_rebalance_fee(&plan, 100), 10);
+        assert_eq!(vault.total_value, 0);
+    }
+    
+    #[test]
+    fn test_gradual_rebalance_respects_cooldown() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.set_min_rebalance_interval(3600);
+        vault.deposit(1000).unwrap();
+        let prices = vec![("BTC".to_string(), 700), ("ETH".to_string(), 300)];
+        
+        let transactions = vault.plan_gradual_rebalance(&prices, 10_000).unwrap();
+        assert!(!transactions.is_empty());
+        assert_eq!(vault.allocations.last_rebalance, 10_000);
+        assert_eq!(vault.allocations.rebalances_in_window, 1);
+        
+        // A second gradual rebalance waits out the cooldown like any other
+        let err = vault.plan_gradual_rebalance(&prices, 10_000 + 1800).unwrap_err();
+        assert!(err.contains("cooldown"));
+        assert!(vault.plan_gradual_rebalance(&prices, 10_000 + 3600).is_ok());
+        
+        // ...and is refused while another rebalance holds the vault
+        vault.allocations.last_rebalance = 0;
+        vault.begin_rebalance("rebalance-1").unwrap();
+        assert!(vault.plan_gradual_rebalance(&prices, 20_000).is_err());
+    }
+    
+    #[test]
+    fn test_rehearse_rebalance_leaves_vault_untouched() {
+        let mut vault = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.deposit(1000).unwrap();
+        
+        let mut price_feed = crate::price_feed::PriceFeedContract::with_admin("admin".to_string());
+        for symbol in ["BTC", "ETH"] {
+            price_feed.register_asset_metadata(crate::price_feed::AssetMetadata {
+                symbol: symbol.to_string(),
+                decimals: 8,
+                display_name: symbol.to_string(),
+                addresses: std::collections::HashMap::new(),
+                active: true,
+                registered_at: 0,
+            }).unwrap();
+        }
+        let emergency = crate::emergency::EmergencyPauseContract::unpaused();
+        let prices = vec![("BTC".to_string(), 100), ("ETH".to_string(), 10)];
+        
+        // Holdings worth 700/300, the 70/30 drift against 60/40 targets
+        let balances = vec![("BTC".to_string(), 7), ("ETH".to_string(), 30)];
+        
+        // A live request is still rehearsed in simulate mode
+        let request = RebalanceRequest {
+            rebalance_id: None,
+            trigger: "simulate",
+            prices: &prices,
+            balances: Some(&balances),
+            price_feed: &price_feed,
+            emergency: &emergency,
+            protocol_fee_bps: 100,
+            mode: crate::rebalance::RebalanceMode::Live,
+        };
+        
+        let operation = match vault.rehearse_rebalance(&request) {
+            Ok(RebalanceRun::Submitted { operation, .. }) => operation,
+            _ => panic!("a drifted vault should rehearse a rebalance"),
+        };
+        assert!(operation.simulated);
+        assert_eq!(operation.total_cost, Some(0));
+        assert_eq!(operation.protocol_fee, 0);
+        
+        // Neither the cooldown, the lock nor the allocations moved
+        assert_eq!(vault.last_rebalance, 0);
+        assert!(vault.in_progress_rebalance.is_none());
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 7000);
+        assert_eq!(vault.total_value, 1000);
+        
+        // Multi-sig vaults are never rehearsed, so no approval is consumed
+        vault.multisig_wallet = Some("wallet-1".to_string());
+        assert!(vault.rehearse_rebalance(&request).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+//! Dollar-cost averaging for One Capital Auto-Investing
+//!
+//! This module splits a deposit into equal installments that are paid into a
+//! custodial vault at a fixed interval.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+use std::collections::HashMap;
+
+use crate::validation::{validate_amount_nonzero, validate_non_empty_string};
+
+/// Recurring deposit into a custodial vault
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct DcaOrder {
+    /// Unique identifier
+    pub id: String,
+
+    /// Vault receiving the deposits
+    pub vault_id: String,
+
+    /// Owner of the vault who created the order
+    pub owner: String,
+
+    /// Total amount of the source asset to invest
+    pub total_amount: u128,
+
+    /// Amount invested per interval (the last installment takes the remainder)
+    pub per_interval_amount: u128,
+
+    /// Seconds between installments
+    pub interval_seconds: u64,
+
+    /// Installments left to execute
+    pub intervals_remaining: u32,
+
+    /// Timestamp the next installment is due
+    pub next_execution: u64,
+
+    /// Asset the installments are paid in
+    pub source_asset: String,
+}
+
+impl DcaOrder {
+    /// Creates an order whose first installment is due at `start`
+    pub fn new(
+        vault_id: String,
+        owner: String,
+        total_amount: u128,
+        per_interval_amount: u128,
+        interval_seconds: u64,
+        source_asset: String,
+        start: u64,
+    ) -> Result<Self, &'static str> {
+        if per_interval_amount == 0 || interval_seconds == 0 {
+            return Err("Installment amount and interval must be greater than zero");
+        }
+
+        if total_amount < per_interval_amount {
+            return Err("Total amount must cover at least one installment");
+        }
+
+        let intervals = (total_amount + per_interval_amount - 1) / per_interval_amount;
+        if intervals > u32::MAX as u128 {
+            return Err("Too many installments");
+        }
+
+        Ok(Self {
+            id: String::new(),
+            vault_id,
+            owner,
+            total_amount,
+            per_interval_amount,
+            interval_seconds,
+            intervals_remaining: intervals as u32,
+            next_execution: start,
+            source_asset,
+        })
+    }
+
+    /// Sets the order ID
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Whether an installment is due at `now`
+    pub fn is_due(&self, now: u64) -> bool {
+        self.intervals_remaining > 0 && now >= self.next_execution
+    }
+
+    /// Amount of the next installment
+    pub fn installment(&self) -> u128 {
+        if self.intervals_remaining != 1 {
+            return self.per_interval_amount;
+        }
+
+        let intervals = (self.total_amount + self.per_interval_amount - 1) / self.per_interval_amount;
+        self.total_amount - self.per_interval_amount * (intervals - 1)
+    }
+
+    /// Marks the next installment as executed and schedules the following one
+    pub fn record_execution(&mut self) {
+        self.intervals_remaining = self.intervals_remaining.saturating_sub(1);
+        self.next_execution = self.next_execution.saturating_add(self.interval_seconds);
+    }
+}
+
+/// An installment ready to be deposited
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DcaExecution {
+    /// Order the installment belongs to
+    pub order_id: String,
+
+    /// Vault receiving the deposit
+    pub vault_id: String,
+
+    /// Installment in the source asset
+    pub amount: u128,
+
+    /// Installment valued in USD (scaled), the amount deposited
+    pub value: u128,
+}
+
+/// A due installment that was not executed and stays due
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DcaSkip {
+    /// Order the installment belongs to
+    pub order_id: String,
+
+    /// Why the installment was not executed
+    pub reason: String,
+}
+
+/// Outcome of executing due installments
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DcaRun {
+    /// Installments deposited into their vaults
+    pub executions: Vec<DcaExecution>,
+
+    /// Installments left due for a later run
+    pub skipped: Vec<DcaSkip>,
+}
+
+/// DCA contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"DCA";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DcaContract {
+    orders: HashMap<String, DcaOrder>, // Order ID -> Order
+    next_order_id: u64,
+}
+
+#[l1x_sdk::contract]
+impl DcaContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            orders: HashMap::new(),
+            next_order_id: 0,
+        };
+
+        state.save()
+    }
+
+    /// Executes every installment due at `now`, ordered by order ID
+    ///
+    /// Installments are valued at the source asset's price (scaled by 1e8) and
+    /// handed to `deposit`. Orders without a price or whose deposit fails are
+    /// skipped and stay due; finished orders are removed.
+    fn take_due<F>(&mut self, now: u64, prices: &HashMap<String, u128>, mut deposit: F) -> DcaRun
+    where
+        F: FnMut(&DcaExecution) -> Result<(), String>,
+    {
+        let mut due: Vec<&mut DcaOrder> = self.orders.values_mut()
+            .filter(|order| order.is_due(now))
+            .collect();
+        due.sort_by_key(|order| order.id.clone());
+
+        let mut run = DcaRun::default();
+        for order in due {
+            let price = match prices.get(&order.source_asset) {
+                Some(price) => *price,
+                None => {
+                    run.skipped.push(DcaSkip {
+                        order_id: order.id.clone(),
+                        reason: format!("No fresh price for {}", order.source_asset),
+                    });
+                    continue;
+                }
+            };
+
+            let amount = order.installment();
+            let execution = DcaExecution {
+                order_id: order.id.clone(),
+                vault_id: order.vault_id.clone(),
+                amount,
+                value: amount.saturating_mul(price) / 100_000_000,
+            };
+
+            if execution.value > 0 {
+                if let Err(reason) = deposit(&execution) {
+                    run.skipped.push(DcaSkip { order_id: order.id.clone(), reason });
+                    continue;
+                }
+            }
+
+            order.record_execution();
+            run.executions.push(execution);
+        }
+
+        self.orders.retain(|_, order| order.intervals_remaining > 0);
+
+        run
+    }
+
+    /// Creates a DCA order into one of the caller's vaults, starting now
+    pub fn create_dca_order(
+        vault_id: String,
+        total_amount: u128,
+        per_interval_amount: u128,
+        interval_seconds: u64,
+        source_asset: String,
+    ) -> String {
+        validate_non_empty_string(&source_asset, "source_asset")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_amount_nonzero(total_amount, "total_amount")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let owner = l1x_sdk::env::caller();
+        let vault_owner = crate::custodial_vault::vault_owner(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+        if vault_owner != owner {
+            panic!("Only the vault owner can create DCA orders");
+        }
+
+        let mut state = Self::load();
+
+        let order_id = format!("dca-{}", state.next_order_id);
+        let order = DcaOrder::new(
+            vault_id,
+            owner,
+            total_amount,
+            per_interval_amount,
+            interval_seconds,
+            source_asset,
+            l1x_sdk::env::block_timestamp(),
+        ).unwrap_or_else(|err| panic!("{}", err))
+            .with_id(order_id.clone());
+
+        state.next_order_id += 1;
+        state.orders.insert(order_id.clone(), order);
+        state.save();
+
+        order_id
+    }
+
+    /// Cancels one of the caller's DCA orders
+    pub fn cancel_dca_order(order_id: String) -> String {
+        let mut state = Self::load();
+
+        let order = state.orders.get(&order_id)
+            .unwrap_or_else(|| panic!("DCA order not found: {}", order_id));
+        if order.owner != l1x_sdk::env::caller() {
+            panic!("Only the order owner can cancel it");
+        }
+
+        state.orders.remove(&order_id);
+        state.save();
+
+        format!("DCA order {} cancelled", order_id)
+    }
+
+    /// Gets an owner's active DCA orders
+    pub fn get_dca_orders(owner: String) -> String {
+        let state = Self::load();
+
+        let mut orders: Vec<&DcaOrder> = state.orders.values()
+            .filter(|order| order.owner == owner)
+            .collect();
+        orders.sort_by_key(|order| order.id.clone());
+
+        serde_json::to_string(&orders)
+            .unwrap_or_else(|_| "Failed to serialize DCA orders".to_string())
+    }
+
+    /// Deposits every due installment into its vault
+    ///
+    /// Installments are valued at the price feed's fresh prices. An order that
+    /// cannot be priced or deposited is logged and skipped without blocking
+    /// the others.
+    pub fn execute_dca_orders() -> String {
+        let now = l1x_sdk::env::block_timestamp();
+        let mut state = Self::load();
+
+        let feed = crate::price_feed::load_price_feed();
+        let prices: HashMap<String, u128> = state.orders.values()
+            .filter(|order| order.is_due(now))
+            .filter_map(|order| feed.checked_price(&order.source_asset, now).ok()
+                .map(|checked| (order.source_asset.clone(), checked.price)))
+            .collect();
+
+        let run = state.take_due(now, &prices, |execution| {
+            crate::custodial_vault::deposit_into_vault(&execution.vault_id, execution.value).map(|_| ())
+        });
+        state.save();
+
+        for skip in &run.skipped {
+            l1x_sdk::env::log(&format!("Skipped DCA order {}: {}", skip.order_id, skip.reason));
+        }
+
+        serde_json::to_string(&run)
+            .unwrap_or_else(|_| "Failed to serialize DCA executions".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3600;
+
+    fn contract(orders: Vec<DcaOrder>) -> DcaContract {
+        DcaContract {
+            orders: orders.into_iter().map(|order| (order.id.clone(), order)).collect(),
+            next_order_id: 0,
+        }
+    }
+
+    fn deposit_ok(_: &DcaExecution) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn order(id: &str, total_amount: u128, per_interval_amount: u128) -> DcaOrder {
+        DcaOrder::new(
+            "vault-1".to_string(),
+            "owner-1".to_string(),
+            total_amount,
+            per_interval_amount,
+            HOUR,
+            "USDC".to_string(),
+            1000,
+        ).unwrap().with_id(id.to_string())
+    }
+
+    #[test]
+    fn test_dca_lifecycle() {
+        let mut state = contract(vec![order("dca-0", 300, 100)]);
+        let prices: HashMap<String, u128> = vec![("USDC".to_string(), 100_000_000)].into_iter().collect();
+
+        // First installment is due at creation
+        let run = state.take_due(1000, &prices, deposit_ok);
+        assert_eq!(run.executions, vec![DcaExecution {
+            order_id: "dca-0".to_string(),
+            vault_id: "vault-1".to_string(),
+            amount: 100,
+            value: 100,
+        }]);
+        assert_eq!(state.orders["dca-0"].intervals_remaining, 2);
+
+        // Nothing due before the interval elapses
+        assert!(state.take_due(1000 + HOUR - 1, &prices, deposit_ok).executions.is_empty());
+
+        // Second installment
+        assert_eq!(state.take_due(1000 + HOUR, &prices, deposit_ok).executions.len(), 1);
+        assert_eq!(state.orders["dca-0"].intervals_remaining, 1);
+        assert_eq!(state.orders["dca-0"].next_execution, 1000 + 2 * HOUR);
+
+        // Third and last installment removes the order
+        assert_eq!(state.take_due(1000 + 2 * HOUR, &prices, deposit_ok).executions.len(), 1);
+        assert!(state.orders.is_empty());
+        assert!(state.take_due(1000 + 3 * HOUR, &prices, deposit_ok).executions.is_empty());
+    }
+
+    #[test]
+    fn test_dca_installments_and_pricing() {
+        // The last installment takes the remainder
+        let mut uneven = order("dca-0", 250, 100);
+        assert_eq!(uneven.intervals_remaining, 3);
+        uneven.record_execution();
+        uneven.record_execution();
+        assert_eq!(uneven.installment(), 50);
+
+        assert!(DcaOrder::new("vault-1".to_string(), "owner-1".to_string(), 50, 100, HOUR, "USDC".to_string(), 0).is_err());
+        assert!(DcaOrder::new("vault-1".to_string(), "owner-1".to_string(), 100, 100, 0, "USDC".to_string(), 0).is_err());
+
+        // Installments are valued at the source asset's price and wait for one if missing
+        let mut state = contract(vec![order("dca-0", 300, 100)]);
+        let run = state.take_due(1000, &HashMap::new(), deposit_ok);
+        assert!(run.executions.is_empty());
+        assert_eq!(run.skipped[0].reason, "No fresh price for USDC");
+        assert_eq!(state.orders["dca-0"].intervals_remaining, 3);
+
+        let prices: HashMap<String, u128> = vec![("USDC".to_string(), 99_000_000)].into_iter().collect();
+        assert_eq!(state.take_due(1000, &prices, deposit_ok).executions[0].value, 99);
+    }
+
+    #[test]
+    fn test_dca_failed_deposit_is_skipped() {
+        let mut state = contract(vec![order("dca-0", 300, 100), order("dca-1", 300, 100)]);
+        let prices: HashMap<String, u128> = vec![("USDC".to_string(), 100_000_000)].into_iter().collect();
+
+        // A refused deposit skips its order without blocking the others
+        let run = state.take_due(1000, &prices, |execution| {
+            if execution.order_id == "dca-0" {
+                Err("Cannot deposit into a non-active vault".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(run.executions.len(), 1);
+        assert_eq!(run.executions[0].order_id, "dca-1");
+        assert_eq!(run.skipped, vec![DcaSkip {
+            order_id: "dca-0".to_string(),
+            reason: "Cannot deposit into a non-active vault".to_string(),
+        }]);
+
+        // The skipped installment stays due
+        assert_eq!(state.orders["dca-0"].intervals_remaining, 3);
+        assert_eq!(state.orders["dca-1"].intervals_remaining, 2);
+        assert_eq!(state.take_due(1000, &prices, deposit_ok).executions[0].order_id, "dca-0");
+    }
+}
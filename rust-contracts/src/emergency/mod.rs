@@ -0,0 +1,207 @@
+//! Emergency pause for One Capital Auto-Investing
+//!
+//! This module provides a protocol-level switch that halts fund-moving
+//! operations across all contracts at once. Only the protocol's multisig
+//! signers can flip it.
+
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+/// Signers of the protocol multisig allowed to pause and unpause
+pub const EMERGENCY_ADMINS: [&str; 3] = [
+    "0x1d7a2c4e9b3f5a8c6e0d2b4f7a9c1e3d5b7f9a2c",
+    "0x4f8b2d6a0c3e5f7b9d1a3c5e7f9b2d4a6c8e0f1b",
+    "0x9c3e5a7f1b4d6c8e0a2f4b6d8c1e3a5f7b9d2c4e",
+];
+
+/// Error returned by guarded operations while the protocol is paused
+pub const PROTOCOL_PAUSED_ERROR: &str = "Protocol is paused";
+
+/// Pause reason reported while only the governance parameter pauses the protocol
+pub const GOVERNANCE_PAUSE_REASON: &str = "Paused by governance";
+
+/// Emergency pause contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"EMERGENCY_PAUSE";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct EmergencyPauseContract {
+    is_paused: bool,
+    pause_reason: String,
+}
+
+#[l1x_sdk::contract]
+impl EmergencyPauseContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            is_paused: false,
+            pause_reason: String::new(),
+        };
+
+        state.save()
+    }
+
+    /// Halts deposits, withdrawals, rebalances, take-profits and swaps protocol-wide
+    pub fn pause(reason: String) -> String {
+        let mut state = Self::load();
+
+        state.set_paused(&l1x_sdk::env::caller(), true, reason.clone())
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+
+        l1x_sdk::env::log(&format!("Protocol paused: {}", reason));
+
+        format!("Protocol paused: {}", reason)
+    }
+
+    /// Resumes normal operation
+    pub fn unpause() -> String {
+        let mut state = Self::load();
+
+        state.set_paused(&l1x_sdk::env::caller(), false, String::new())
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+
+        l1x_sdk::env::log("Protocol unpaused");
+
+        "Protocol unpaused".to_string()
+    }
+
+    /// Checks if the protocol is paused
+    ///
+    /// A protocol whose pause switch was never initialized is not paused.
+    pub fn is_protocol_paused() -> bool {
+        load_emergency_state().is_paused
+    }
+
+    /// Gets the pause state and reason
+    pub fn get_pause_status() -> String {
+        let state = load_emergency_state();
+
+        serde_json::json!({
+            "is_paused": state.is_paused,
+            "pause_reason": state.pause_reason,
+        }).to_string()
+    }
+}
+
+impl EmergencyPauseContract {
+    /// Pause state of a protocol that has never been paused
+    pub fn unpaused() -> Self {
+        Self {
+            is_paused: false,
+            pause_reason: String::new(),
+        }
+    }
+
+    /// Checks if an account is one of the multisig signers
+    fn is_emergency_admin(account: &str) -> bool {
+        EMERGENCY_ADMINS.contains(&account)
+    }
+
+    /// Pauses or unpauses the protocol on behalf of `caller`
+    pub fn set_paused(&mut self, caller: &str, paused: bool, reason: String) -> Result<(), &'static str> {
+        if !Self::is_emergency_admin(caller) {
+            return Err("Only emergency admins can change the pause state");
+        }
+
+        if paused && reason.trim().is_empty() {
+            return Err("A pause reason is required");
+        }
+
+        self.is_paused = paused;
+        self.pause_reason = if paused { reason } else { String::new() };
+
+        Ok(())
+    }
+
+    /// Pause state with the governance `emergency_pause` parameter folded in
+    ///
+    /// Either switch pauses the protocol; lifting the multisig pause leaves a
+    /// governance pause in place.
+    pub fn with_governance_pause(mut self, governance_paused: bool) -> Self {
+        if governance_paused && !self.is_paused {
+            self.is_paused = true;
+            self.pause_reason = GOVERNANCE_PAUSE_REASON.to_string();
+        }
+
+        self
+    }
+
+    /// Fails with `PROTOCOL_PAUSED_ERROR` while the protocol is paused
+    pub fn check_active(&self) -> Result<(), &'static str> {
+        if self.is_paused {
+            return Err(PROTOCOL_PAUSED_ERROR);
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the pause state, treating an uninitialized contract as unpaused
+///
+/// The governance `emergency_pause` parameter pauses the protocol as well.
+pub fn load_emergency_state() -> EmergencyPauseContract {
+    let state = match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+        Some(bytes) => EmergencyPauseContract::try_from_slice(&bytes).unwrap(),
+        None => EmergencyPauseContract::unpaused(),
+    };
+
+    state.with_governance_pause(crate::governance::parameters().emergency_pause)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract() -> EmergencyPauseContract {
+        EmergencyPauseContract::unpaused()
+    }
+
+    #[test]
+    fn test_pause_requires_emergency_admin() {
+        let mut state = contract();
+
+        assert_eq!(
+            state.set_paused("0x0000000000000000000000000000000000000001", true, "exploit".to_string()),
+            Err("Only emergency admins can change the pause state")
+        );
+        assert_eq!(state.set_paused(EMERGENCY_ADMINS[0], true, " ".to_string()), Err("A pause reason is required"));
+        assert!(!state.is_paused);
+
+        assert_eq!(state.set_paused(EMERGENCY_ADMINS[1], true, "Oracle exploit".to_string()), Ok(()));
+        assert!(state.is_paused);
+        assert_eq!(state.pause_reason, "Oracle exploit");
+
+        // Any signer can lift the pause
+        assert_eq!(state.set_paused(EMERGENCY_ADMINS[2], false, String::new()), Ok(()));
+        assert!(!state.is_paused);
+        assert!(state.pause_reason.is_empty());
+    }
+
+    #[test]
+    fn test_governance_pause() {
+        // The governance parameter pauses an unpaused protocol
+        let state = contract().with_governance_pause(true);
+        assert_eq!(state.check_active(), Err(PROTOCOL_PAUSED_ERROR));
+        assert_eq!(state.pause_reason, GOVERNANCE_PAUSE_REASON);
+
+        // ...and leaves a multisig pause and its reason alone
+        let mut state = contract();
+        state.set_paused(EMERGENCY_ADMINS[0], true, "Oracle exploit".to_string()).unwrap();
+        let state = state.with_governance_pause(true);
+        assert_eq!(state.pause_reason, "Oracle exploit");
+
+        assert_eq!(contract().with_governance_pause(false).check_active(), Ok(()));
+    }
+}
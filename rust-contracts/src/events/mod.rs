@@ -4,6 +4,7 @@
 //! that can be captured by the UI or external systems.
 
 use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
 /// Event types for rebalancing
@@ -23,6 +24,24 @@ pub enum RebalanceEventType {
     
     /// Scheduled rebalance triggered
     ScheduledRebalance,
+    
+    /// A failed rebalance transaction succeeded on retry
+    TransactionRetrySucceeded,
+    
+    /// A rebalance transaction failed and has no retries left
+    TransactionFailedPermanently,
+    
+    /// A rebalance transaction was executed, submitted or settled
+    TransactionExecuted,
+    
+    /// A rebalance transaction failed
+    TransactionFailed,
+    
+    /// A vault's rebalance lock was cleared by hand
+    RebalanceLockCleared,
+    
+    /// A rebalance was cancelled before all of its transactions executed
+    RebalanceCancelled,
 }
 
 /// Event for rebalancing operations
@@ -65,6 +84,46 @@ impl RebalanceEvent {
     }
 }
 
+/// Which side of its target an asset has drifted to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftDirection {
+    /// Current weight above target
+    Over,
+    
+    /// Current weight below target
+    Under,
+    
+    /// Current weight exactly on target
+    Balanced,
+}
+
+/// How far drift is past the rebalance threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DriftSeverity {
+    /// Drift at or within the threshold
+    Low,
+    
+    /// Drift up to twice the threshold
+    Medium,
+    
+    /// Drift more than twice the threshold
+    High,
+}
+
+impl DriftSeverity {
+    /// Classifies a drift magnitude relative to a threshold (both in basis points)
+    pub fn from_drift(drift: u32, threshold: u32) -> Self {
+        if drift <= threshold {
+            DriftSeverity::Low
+        } else if drift <= threshold.saturating_mul(2) {
+            DriftSeverity::Medium
+        } else {
+            DriftSeverity::High
+        }
+    }
+}
+
 /// Drift calculation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DriftResult {
@@ -80,8 +139,29 @@ pub struct DriftResult {
     /// Drift amount in basis points
     pub drift_amount: u32,
     
+    /// Whether the asset is over or under its target
+    pub direction: DriftDirection,
+    
+    /// Drift severity relative to the threshold
+    pub severity: DriftSeverity,
+    
     /// Whether the drift exceeds the threshold
     pub exceeds_threshold: bool,
+    
+    /// Minimum allowed percentage, if bounded
+    pub min_percentage: Option<u32>,
+    
+    /// Maximum allowed percentage, if bounded
+    pub max_percentage: Option<u32>,
+    
+    /// Whether a min/max bound (rather than the drift threshold) was breached
+    pub bound_breached: bool,
+    
+    /// Whether the asset is locked (no rebalance action is generated for it)
+    pub locked: bool,
+    
+    /// Whether this is the cash buffer and it has fallen below its floor
+    pub buffer_breached: bool,
 }
 
 /// Helper to emit a drift exceeded event
@@ -101,11 +181,14 @@ pub fn emit_rebalance_initiated_event(vault_id: &str, trigger: &str) {
 }
 
 /// Helper to emit a rebalance completed event
-pub fn emit_rebalance_completed_event(vault_id: &str, tx_count: usize, total_cost: Option<u128>) {
+///
+/// `truncated_by_cap` tells the keeper a budget cap cut the plan short and a
+/// follow-up rebalance is needed.
+pub fn emit_rebalance_completed_event(vault_id: &str, tx_count: usize, total_cost: Option<u128>, truncated_by_cap: bool) {
     let data = if let Some(cost) = total_cost {
-        format!("{{\"transaction_count\": {}, \"total_cost\": {}}}", tx_count, cost)
+        format!("{{\"transaction_count\": {}, \"total_cost\": {}, \"truncated_by_cap\": {}}}", tx_count, cost, truncated_by_cap)
     } else {
-        format!("{{\"transaction_count\": {}}}", tx_count)
+        format!("{{\"transaction_count\": {}, \"truncated_by_cap\": {}}}", tx_count, truncated_by_cap)
     };
     
     let event = RebalanceEvent::new(RebalanceEventType::RebalanceCompleted, vault_id.to_string())
@@ -119,4 +202,851 @@ pub fn emit_rebalance_failed_event(vault_id: &str, error: &str) {
     let event = RebalanceEvent::new(RebalanceEventType::RebalanceFailed, vault_id.to_string())
         .with_data(data);
     event.emit();
-}
\ No newline at end of file
+}
+
+/// Helper to emit an event for a rebalance transaction that succeeded on retry
+pub fn emit_transaction_retry_succeeded_event(vault_id: &str, rebalance_id: &str, transaction_index: usize, retry_count: u8) {
+    let data = format!(
+        "{{\"rebalance_id\": \"{}\", \"transaction_index\": {}, \"retry_count\": {}}}",
+        rebalance_id, transaction_index, retry_count
+    );
+    let event = RebalanceEvent::new(RebalanceEventType::TransactionRetrySucceeded, vault_id.to_string())
+        .with_data(data);
+    event.emit();
+}
+
+/// Helper to emit an event for a rebalance transaction that has exhausted its retries
+pub fn emit_transaction_failed_permanently_event(vault_id: &str, rebalance_id: &str, transaction_index: usize, error: &str) {
+    let data = format!(
+        "{{\"rebalance_id\": \"{}\", \"transaction_index\": {}, \"error\": \"{}\"}}",
+        rebalance_id, transaction_index, error
+    );
+    let event = RebalanceEvent::new(RebalanceEventType::TransactionFailedPermanently, vault_id.to_string())
+        .with_data(data);
+    event.emit();
+}
+
+/// Helper to emit a rebalance cancelled event
+pub fn emit_rebalance_cancelled_event(vault_id: &str, rebalance_id: &str, cancelled_count: usize, cancelled_by: &str) {
+    let data = format!(
+        "{{\"rebalance_id\": \"{}\", \"cancelled_transactions\": {}, \"cancelled_by\": \"{}\"}}",
+        rebalance_id, cancelled_count, cancelled_by
+    );
+    let event = RebalanceEvent::new(RebalanceEventType::RebalanceCancelled, vault_id.to_string())
+        .with_data(data);
+    event.emit();
+}
+
+/// Helper to emit an event for a rebalance lock cleared by the vault owner
+pub fn emit_rebalance_lock_cleared_event(vault_id: &str, rebalance_id: &str, cleared_by: &str) {
+    let data = format!(
+        "{{\"rebalance_id\": \"{}\", \"cleared_by\": \"{}\"}}",
+        rebalance_id, cleared_by
+    );
+    let event = RebalanceEvent::new(RebalanceEventType::RebalanceLockCleared, vault_id.to_string())
+        .with_data(data);
+    event.emit();
+}
+
+/// State of one rebalance transaction, carried by transaction events
+///
+/// Carries everything needed to rebuild the transaction without the stored history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProgress {
+    /// Rebalance operation ID
+    pub rebalance_id: String,
+    
+    /// Index of the transaction within the operation
+    pub transaction_index: usize,
+    
+    /// Source asset ID
+    pub source_asset: String,
+    
+    /// Target asset ID
+    pub target_asset: String,
+    
+    /// Amount swapped
+    pub amount: u128,
+    
+    /// Transaction status after this step
+    pub status: crate::rebalance::RebalanceStatus,
+    
+    /// Gas cost, once executed
+    pub gas_cost: Option<u128>,
+    
+    /// Swap message or transaction ID, once submitted
+    pub tx_hash: Option<String>,
+    
+    /// Amount of the target asset received, once settled
+    pub received_amount: Option<u128>,
+    
+    /// Error message if failed
+    pub error: Option<String>,
+}
+
+impl TransactionProgress {
+    /// Captures the current state of a rebalance transaction
+    pub fn new(rebalance_id: &str, transaction_index: usize, transaction: &crate::rebalance::RebalanceTransaction) -> Self {
+        Self {
+            rebalance_id: rebalance_id.to_string(),
+            transaction_index,
+            source_asset: transaction.source_asset.clone(),
+            target_asset: transaction.target_asset.clone(),
+            amount: transaction.amount,
+            status: transaction.status,
+            gas_cost: transaction.gas_cost,
+            tx_hash: transaction.tx_hash.clone(),
+            received_amount: transaction.received_amount,
+            error: transaction.error.clone(),
+        }
+    }
+}
+
+/// Helper to emit the progress of one rebalance transaction
+///
+/// Failed transactions are reported as `TransactionFailed`, everything else as
+/// `TransactionExecuted`.
+pub fn emit_transaction_progress_event(
+    vault_id: &str,
+    rebalance_id: &str,
+    transaction_index: usize,
+    transaction: &crate::rebalance::RebalanceTransaction,
+) {
+    let event_type = if transaction.status == crate::rebalance::RebalanceStatus::Failed {
+        RebalanceEventType::TransactionFailed
+    } else {
+        RebalanceEventType::TransactionExecuted
+    };
+    
+    let data = serde_json::to_string(&TransactionProgress::new(rebalance_id, transaction_index, transaction))
+        .unwrap_or_default();
+    let event = RebalanceEvent::new(event_type, vault_id.to_string())
+        .with_data(data);
+    event.emit();
+}
+
+/// Event types for vault fund flows
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FundFlowEventType {
+    /// Funds deposited into a vault
+    Deposit,
+    
+    /// Funds withdrawn from a vault
+    Withdrawal,
+    
+    /// Funds withdrawn under emergency conditions
+    EmergencyWithdrawal,
+}
+
+/// Event for deposits and withdrawals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FundFlowEvent {
+    /// Event type
+    pub event_type: FundFlowEventType,
+    
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Amount deposited or withdrawn
+    pub amount: u128,
+    
+    /// Account that moved the funds
+    pub actor: String,
+    
+    /// Timestamp
+    pub timestamp: u64,
+    
+    /// Vault balance after the operation
+    pub new_balance: u128,
+}
+
+impl FundFlowEvent {
+    /// Creates a new fund flow event
+    pub fn new(event_type: FundFlowEventType, vault_id: String, amount: u128, actor: String, new_balance: u128) -> Self {
+        Self {
+            event_type,
+            vault_id,
+            amount,
+            actor,
+            timestamp: l1x_sdk::env::block_timestamp(),
+            new_balance,
+        }
+    }
+    
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("FUND_FLOW_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a deposit event
+pub fn emit_deposit_event(vault_id: &str, amount: u128, actor: &str, new_balance: u128) {
+    let event = FundFlowEvent::new(FundFlowEventType::Deposit, vault_id.to_string(), amount, actor.to_string(), new_balance);
+    event.emit();
+}
+
+/// Helper to emit a withdrawal event
+pub fn emit_withdrawal_event(vault_id: &str, amount: u128, actor: &str, new_balance: u128) {
+    let event = FundFlowEvent::new(FundFlowEventType::Withdrawal, vault_id.to_string(), amount, actor.to_string(), new_balance);
+    event.emit();
+}
+
+/// Event emitted on every take-profit execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeProfitEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Strategy type ("manual", "percentage", or "time")
+    pub strategy_type_str: String,
+    
+    /// Baseline value before execution
+    pub baseline_value: u128,
+    
+    /// Portfolio value at execution
+    pub current_value: u128,
+    
+    /// Profit realized
+    pub profit_amount: u128,
+    
+    /// Baseline value after execution
+    pub new_baseline: u128,
+    
+    /// Execution timestamp
+    pub execution_time: u64,
+    
+    /// Asset the profit is converted to
+    pub target_asset: String,
+}
+
+impl TakeProfitEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("TAKE_PROFIT_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a take profit executed event for a strategy that has just executed
+pub fn emit_take_profit_executed_event(
+    vault_id: &str,
+    strategy: &crate::take_profit::TakeProfitStrategy,
+    baseline_value: u128,
+    current_value: u128,
+    profit_amount: u128,
+    target_asset: &str,
+) {
+    write_take_profit_executed_event(l1x_sdk::env::log, vault_id, strategy, baseline_value, current_value, profit_amount, target_asset);
+}
+
+/// Writes the take profit executed event to `log`, reading the type, new baseline and execution time from `strategy`
+fn write_take_profit_executed_event(
+    log: fn(&str),
+    vault_id: &str,
+    strategy: &crate::take_profit::TakeProfitStrategy,
+    baseline_value: u128,
+    current_value: u128,
+    profit_amount: u128,
+    target_asset: &str,
+) {
+    let event = TakeProfitEvent {
+        vault_id: vault_id.to_string(),
+        strategy_type_str: strategy.strategy_type.name().to_string(),
+        baseline_value,
+        current_value,
+        profit_amount,
+        new_baseline: strategy.baseline_value,
+        execution_time: strategy.last_execution,
+        target_asset: target_asset.to_string(),
+    };
+    log(&event.log_line());
+}
+
+/// Kind of change made to a vault's take profit strategy
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TakeProfitChangeType {
+    /// Strategy removed from the vault
+    Cleared,
+    
+    /// Strategy suspended, keeping its configuration
+    Paused,
+    
+    /// Suspended strategy re-enabled
+    Resumed,
+}
+
+/// Event emitted when a take profit strategy is cleared, paused or resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeProfitChangedEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Kind of change
+    pub change_type: TakeProfitChangeType,
+    
+    /// Account that made the change
+    pub changed_by: String,
+    
+    /// Timestamp
+    pub timestamp: u64,
+}
+
+impl TakeProfitChangedEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("TAKE_PROFIT_CHANGED_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a take profit changed event
+pub fn emit_take_profit_changed_event(vault_id: &str, change_type: TakeProfitChangeType, changed_by: &str) {
+    let event = TakeProfitChangedEvent {
+        vault_id: vault_id.to_string(),
+        change_type,
+        changed_by: changed_by.to_string(),
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when a performance fee is charged on gains above the high-water mark
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceFeeEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Fee deducted from the vault
+    pub fee_amount: u128,
+    
+    /// Account credited with the fee
+    pub fee_recipient: String,
+    
+    /// High-water mark before the fee
+    pub previous_high_water_mark: u128,
+    
+    /// High-water mark after the fee
+    pub high_water_mark: u128,
+    
+    /// Execution timestamp
+    pub timestamp: u64,
+}
+
+impl PerformanceFeeEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("PERFORMANCE_FEE_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a performance fee event
+pub fn emit_performance_fee_event(
+    vault_id: &str,
+    fee_amount: u128,
+    fee_recipient: &str,
+    previous_high_water_mark: u128,
+    high_water_mark: u128,
+) {
+    let event = PerformanceFeeEvent {
+        vault_id: vault_id.to_string(),
+        fee_amount,
+        fee_recipient: fee_recipient.to_string(),
+        previous_high_water_mark,
+        high_water_mark,
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when take-profit proceeds are reinvested into the vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoCompoundEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Profit deposited back into the vault
+    pub reinvested_amount: u128,
+    
+    /// Execution timestamp
+    pub timestamp: u64,
+}
+
+impl AutoCompoundEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("AUTO_COMPOUND_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit an auto-compound event
+pub fn emit_auto_compound_event(vault_id: &str, reinvested_amount: u128) {
+    let event = AutoCompoundEvent {
+        vault_id: vault_id.to_string(),
+        reinvested_amount,
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when a user confirms a proposed non-custodial rebalance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalanceConfirmedEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Proposal the user confirmed
+    pub proposal_id: String,
+    
+    /// Account whose wallet signed the confirmation
+    pub confirmed_by: String,
+    
+    /// Confirmation timestamp
+    pub timestamp: u64,
+}
+
+impl RebalanceConfirmedEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("REBALANCE_CONFIRMED_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a rebalance confirmed event
+pub fn emit_rebalance_confirmed_event(vault_id: &str, proposal_id: &str, confirmed_by: &str) {
+    let event = RebalanceConfirmedEvent {
+        vault_id: vault_id.to_string(),
+        proposal_id: proposal_id.to_string(),
+        confirmed_by: confirmed_by.to_string(),
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when a price update deviating too far from the stored price is quarantined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceDeviationRejectedEvent {
+    /// Asset symbol
+    pub symbol: String,
+    
+    /// Stored price the update was compared against
+    pub previous_price: u128,
+    
+    /// Price that was quarantined
+    pub rejected_price: u128,
+    
+    /// Deviation of the update from the stored price (in basis points)
+    pub deviation_bp: u32,
+    
+    /// Deviation limit of the symbol (in basis points)
+    pub max_deviation_bp: u32,
+    
+    /// Authority that submitted the update
+    pub provider: String,
+    
+    /// Rejection timestamp
+    pub timestamp: u64,
+}
+
+impl PriceDeviationRejectedEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("PRICE_DEVIATION_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a price deviation rejected event
+pub fn emit_price_deviation_rejected_event(
+    symbol: &str,
+    previous_price: u128,
+    rejected_price: u128,
+    deviation_bp: u32,
+    max_deviation_bp: u32,
+    provider: &str,
+) {
+    let event = PriceDeviationRejectedEvent {
+        symbol: symbol.to_string(),
+        previous_price,
+        rejected_price,
+        deviation_bp,
+        max_deviation_bp,
+        provider: provider.to_string(),
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when a vault is created as a copy of another
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultClonedEvent {
+    /// Vault whose configuration was copied
+    pub source_vault_id: String,
+    
+    /// Vault created from the copy
+    pub new_vault_id: String,
+    
+    /// Owner of the new vault
+    pub new_owner: String,
+    
+    /// Clone timestamp
+    pub timestamp: u64,
+}
+
+impl VaultClonedEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("VAULT_CLONED_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a vault cloned event
+pub fn emit_vault_cloned_event(source_vault_id: &str, new_vault_id: &str, new_owner: &str) {
+    let event = VaultClonedEvent {
+        source_vault_id: source_vault_id.to_string(),
+        new_vault_id: new_vault_id.to_string(),
+        new_owner: new_owner.to_string(),
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted when a custodial vault starts migrating to a non-custodial vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultMigrationInitiatedEvent {
+    /// Custodial vault being migrated
+    pub vault_id: String,
+    
+    /// Owner of the vault
+    pub owner: String,
+    
+    /// Signature of the migration payload
+    pub payload_signature: String,
+    
+    /// Initiation timestamp
+    pub timestamp: u64,
+}
+
+impl VaultMigrationInitiatedEvent {
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("VAULT_MIGRATION_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a vault migration initiated event
+pub fn emit_vault_migration_initiated_event(vault_id: &str, owner: &str, payload_signature: &str) {
+    let event = VaultMigrationInitiatedEvent {
+        vault_id: vault_id.to_string(),
+        owner: owner.to_string(),
+        payload_signature: payload_signature.to_string(),
+        timestamp: l1x_sdk::env::block_timestamp(),
+    };
+    event.emit();
+}
+
+/// Event emitted every time a vault status transitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultStatusChangedEvent {
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Previous status (None when the vault was just created)
+    pub old_status: Option<crate::custodial_vault::VaultStatus>,
+    
+    /// New status
+    pub new_status: crate::custodial_vault::VaultStatus,
+    
+    /// Account that changed the status
+    pub changed_by: String,
+    
+    /// Timestamp
+    pub timestamp: u64,
+}
+
+impl VaultStatusChangedEvent {
+    /// Creates a new vault status changed event
+    pub fn new(
+        vault_id: String,
+        old_status: Option<crate::custodial_vault::VaultStatus>,
+        new_status: crate::custodial_vault::VaultStatus,
+        changed_by: String,
+    ) -> Self {
+        Self {
+            vault_id,
+            old_status,
+            new_status,
+            changed_by,
+            timestamp: l1x_sdk::env::block_timestamp(),
+        }
+    }
+    
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("VAULT_STATUS_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit a vault status changed event
+pub fn emit_vault_status_changed_event(
+    vault_id: &str,
+    old_status: Option<crate::custodial_vault::VaultStatus>,
+    new_status: crate::custodial_vault::VaultStatus,
+    changed_by: &str,
+) {
+    let event = VaultStatusChangedEvent::new(vault_id.to_string(), old_status, new_status, changed_by.to_string());
+    event.emit();
+}
+
+/// Event types for allocation policy changes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum AllocationEventType {
+    /// An asset's target percentage changed
+    TargetChanged,
+}
+
+/// Kind of change made to an asset allocation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum AllocationChangeType {
+    /// Asset added to the allocation set
+    Added,
+    
+    /// Asset target updated
+    Updated,
+    
+    /// Asset removed from the allocation set
+    Removed,
+}
+
+/// Event recording the before/after state of an allocation mutation
+///
+/// The same record is kept in the allocation set's audit trail.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AllocationChangedEvent {
+    /// Event type
+    pub event_type: AllocationEventType,
+    
+    /// Vault ID
+    pub vault_id: String,
+    
+    /// Asset ID
+    pub asset_id: String,
+    
+    /// Kind of change
+    pub change_type: AllocationChangeType,
+    
+    /// Target before the change (None when added)
+    pub old_target_bps: Option<u32>,
+    
+    /// Target after the change (None when removed)
+    pub new_target_bps: Option<u32>,
+    
+    /// Account that signed the change
+    pub signer: String,
+    
+    /// Timestamp
+    pub timestamp: u64,
+}
+
+impl AllocationChangedEvent {
+    /// Creates a new allocation changed event
+    pub fn new(
+        vault_id: String,
+        asset_id: String,
+        old_target_bps: Option<u32>,
+        new_target_bps: Option<u32>,
+        signer: String,
+    ) -> Self {
+        let change_type = match (old_target_bps, new_target_bps) {
+            (None, _) => AllocationChangeType::Added,
+            (Some(_), None) => AllocationChangeType::Removed,
+            (Some(_), Some(_)) => AllocationChangeType::Updated,
+        };
+        
+        Self {
+            event_type: AllocationEventType::TargetChanged,
+            vault_id,
+            asset_id,
+            change_type,
+            old_target_bps,
+            new_target_bps,
+            signer,
+            timestamp: l1x_sdk::env::block_timestamp(),
+        }
+    }
+    
+    /// Formats the event as a log line
+    pub fn log_line(&self) -> String {
+        let event_json = serde_json::to_string(&self).unwrap_or_default();
+        format!("ALLOCATION_EVENT:{}", event_json)
+    }
+    
+    /// Emits the event
+    pub fn emit(&self) {
+        l1x_sdk::env::log(&self.log_line());
+    }
+}
+
+/// Helper to emit an allocation changed event
+pub fn emit_allocation_changed_event(vault_id: &str, asset_id: &str, old_target_bps: Option<u32>, new_target_bps: Option<u32>, signer: &str) {
+    let event = AllocationChangedEvent::new(vault_id.to_string(), asset_id.to_string(), old_target_bps, new_target_bps, signer.to_string());
+    event.emit();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_fund_flow_event_json() {
+        let deposit = FundFlowEvent::new(FundFlowEventType::Deposit, "vault-1".to_string(), 500, "user-1".to_string(), 1500);
+        let withdrawal = FundFlowEvent::new(FundFlowEventType::Withdrawal, "vault-1".to_string(), 200, "user-1".to_string(), 1300);
+        
+        for event in [deposit, withdrawal] {
+            let line = event.log_line();
+            let json = line.strip_prefix("FUND_FLOW_EVENT:").unwrap();
+            let parsed: FundFlowEvent = serde_json::from_str(json).unwrap();
+            
+            assert_eq!(parsed.event_type, event.event_type);
+            assert_eq!(parsed.amount, event.amount);
+            assert_eq!(parsed.new_balance, event.new_balance);
+            assert_eq!(parsed.actor, "user-1");
+        }
+    }
+    
+    #[test]
+    fn test_transaction_progress_json() {
+        let mut operation = crate::rebalance::RebalanceOperation::new("rebalance-1".to_string(), crate::rebalance::RebalanceStrategy::Threshold);
+        operation.add_transaction("BTC".to_string(), "ETH".to_string(), 500);
+        operation.transactions[0].status = crate::rebalance::RebalanceStatus::Failed;
+        operation.transactions[0].error = Some("No liquidity".to_string());
+        
+        let progress = TransactionProgress::new(&operation.id, 0, &operation.transactions[0]);
+        let json = serde_json::to_string(&progress).unwrap();
+        let parsed: TransactionProgress = serde_json::from_str(&json).unwrap();
+        
+        assert_eq!(parsed.rebalance_id, "rebalance-1");
+        assert_eq!(parsed.transaction_index, 0);
+        assert_eq!(parsed.source_asset, "BTC");
+        assert_eq!(parsed.target_asset, "ETH");
+        assert_eq!(parsed.amount, 500);
+        assert_eq!(parsed.status, crate::rebalance::RebalanceStatus::Failed);
+        assert_eq!(parsed.gas_cost, None);
+        assert_eq!(parsed.error.as_deref(), Some("No liquidity"));
+    }
+    
+    thread_local! {
+        static LOGGED: std::cell::RefCell<Vec<String>> = std::cell::RefCell::new(Vec::new());
+    }
+    
+    fn capture_log(line: &str) {
+        LOGGED.with(|logged| logged.borrow_mut().push(line.to_string()));
+    }
+    
+    #[test]
+    fn test_take_profit_event_json() {
+        let mut strategy = crate::take_profit::TakeProfitStrategy::new(
+            crate::take_profit::TakeProfitType::Percentage { percentage: 2000 },
+        );
+        strategy.set_baseline(1000);
+        strategy.record_execution_at(12345);
+        strategy.set_baseline(1200);
+        
+        write_take_profit_executed_event(capture_log, "vault-1", &strategy, 1000, 1200, 200, "USDC");
+        
+        let logged = LOGGED.with(|logged| logged.borrow().clone());
+        assert_eq!(logged.len(), 1);
+        let json = logged[0].strip_prefix("TAKE_PROFIT_EVENT:").unwrap();
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+        
+        for field in [
+            "vault_id", "strategy_type_str", "baseline_value", "current_value",
+            "profit_amount", "new_baseline", "execution_time", "target_asset",
+        ] {
+            assert!(value.get(field).is_some(), "missing field {}", field);
+        }
+        assert_eq!(value["vault_id"], "vault-1");
+        assert_eq!(value["strategy_type_str"], "percentage");
+        assert_eq!(value["baseline_value"], 1000);
+        assert_eq!(value["profit_amount"], 200);
+        assert_eq!(value["new_baseline"], 1200);
+        assert_eq!(value["execution_time"], 12345);
+        assert_eq!(value["target_asset"], "USDC");
+    }
+    
+    #[test]
+    fn test_allocation_changed_event_diffs() {
+        let added = AllocationChangedEvent::new("vault-1".to_string(), "BTC".to_string(), None, Some(5000), "owner-1".to_string());
+        assert_eq!(added.change_type, AllocationChangeType::Added);
+        assert_eq!(added.old_target_bps, None);
+        
+        let updated = AllocationChangedEvent::new("vault-1".to_string(), "BTC".to_string(), Some(5000), Some(6000), "owner-1".to_string());
+        assert_eq!(updated.change_type, AllocationChangeType::Updated);
+        assert_eq!(updated.old_target_bps, Some(5000));
+        assert_eq!(updated.new_target_bps, Some(6000));
+        
+        let removed = AllocationChangedEvent::new("vault-1".to_string(), "BTC".to_string(), Some(6000), None, "owner-1".to_string());
+        assert_eq!(removed.change_type, AllocationChangeType::Removed);
+        assert_eq!(removed.new_target_bps, None);
+        
+        let json = removed.log_line();
+        assert!(json.starts_with("ALLOCATION_EVENT:"));
+        assert!(json.contains("\"Removed\""));
+    }
+}
@@ -0,0 +1,203 @@
+//! Protocol fee collection for One Capital Auto-Investing
+//!
+//! This module accumulates the protocol fees charged by every contract in one
+//! place and splits them between recipients by weight. The fee rate is the
+//! governance `protocol_fee_bps` parameter.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+/// Asset ID fees charged on vault value (USD, scaled) are recorded under
+pub const VAULT_VALUE_ASSET: &str = "USD";
+
+/// Share of a distribution paid to one recipient
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeDistribution {
+    /// Account receiving the fees
+    pub recipient: String,
+
+    /// Asset of the fees
+    pub asset_id: String,
+
+    /// Amount paid
+    pub amount: u128,
+}
+
+/// Fee collector contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"FEE_COLLECTOR";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct FeeCollectorContract {
+    balances: std::collections::HashMap<String, u128>, // Asset ID -> Undistributed fees
+    total_collected: u128,
+    admin: String,
+}
+
+#[l1x_sdk::contract]
+impl FeeCollectorContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            balances: std::collections::HashMap::new(),
+            total_collected: 0,
+            admin: l1x_sdk::env::caller(),
+        };
+
+        state.save()
+    }
+
+    /// Splits the accumulated fees between recipients proportionally to their weights
+    ///
+    /// `recipients_json` is a list of `[recipient, weight]` pairs. Rounding
+    /// dust goes to the first recipient, and the balances are reset.
+    pub fn distribute_fees(recipients_json: String) -> String {
+        let mut state = Self::load();
+
+        if state.admin != l1x_sdk::env::caller() {
+            panic!("Only the fee collector admin can distribute fees");
+        }
+
+        let recipients: Vec<(String, u32)> = serde_json::from_str(&recipients_json)
+            .unwrap_or_else(|e| panic!("Failed to parse recipients: {}", e));
+
+        let distributions = state.distribute(&recipients)
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+
+        serde_json::to_string(&distributions)
+            .unwrap_or_else(|_| "Failed to serialize distributions".to_string())
+    }
+
+    /// Gets the undistributed fees per asset
+    pub fn get_fee_balances() -> String {
+        let state = Self::load();
+
+        serde_json::to_string(&state.balances)
+            .unwrap_or_else(|_| "Failed to serialize balances".to_string())
+    }
+
+    /// Gets the total fees ever collected, across assets
+    pub fn get_total_collected() -> u128 {
+        let state = Self::load();
+
+        state.total_collected
+    }
+}
+
+impl FeeCollectorContract {
+    /// Records a fee charged in `asset_id`
+    pub fn record_fee(asset_id: &str, amount: u128) {
+        if amount == 0 {
+            return;
+        }
+
+        let mut state = Self::load();
+        state.credit(asset_id, amount);
+        state.save();
+    }
+
+    /// Adds `amount` to the balance of `asset_id`
+    fn credit(&mut self, asset_id: &str, amount: u128) {
+        let balance = self.balances.entry(asset_id.to_string()).or_insert(0);
+        *balance = balance.saturating_add(amount);
+        self.total_collected = self.total_collected.saturating_add(amount);
+    }
+
+    /// Splits every balance between `recipients` by weight and resets the balances
+    fn distribute(&mut self, recipients: &[(String, u32)]) -> Result<Vec<FeeDistribution>, &'static str> {
+        let total_weight: u128 = recipients.iter().map(|(_, weight)| *weight as u128).sum();
+        if total_weight == 0 {
+            return Err("Recipients need a positive total weight");
+        }
+
+        let mut assets: Vec<(&String, &u128)> = self.balances.iter().collect();
+        assets.sort();
+
+        let mut distributions = Vec::new();
+        for (asset_id, balance) in assets {
+            let shares: Vec<u128> = recipients.iter()
+                .map(|(_, weight)| balance * *weight as u128 / total_weight)
+                .collect();
+            let dust = balance - shares.iter().sum::<u128>();
+
+            for (i, ((recipient, _), share)) in recipients.iter().zip(shares).enumerate() {
+                let amount = if i == 0 { share + dust } else { share };
+                if amount > 0 {
+                    distributions.push(FeeDistribution {
+                        recipient: recipient.clone(),
+                        asset_id: asset_id.clone(),
+                        amount,
+                    });
+                }
+            }
+        }
+
+        self.balances.clear();
+        Ok(distributions)
+    }
+}
+
+/// Protocol fee on `amount` at the governance `protocol_fee_bps` rate
+pub fn protocol_fee(amount: u128) -> u128 {
+    fee_at_rate(amount, crate::governance::parameters().protocol_fee_bps)
+}
+
+/// Fee on `amount` at `fee_bps` basis points
+pub fn fee_at_rate(amount: u128, fee_bps: u32) -> u128 {
+    amount.saturating_mul(fee_bps as u128) / 10000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fees_accumulate_and_split_evenly() {
+        let mut state = FeeCollectorContract {
+            balances: std::collections::HashMap::new(),
+            total_collected: 0,
+            admin: "admin".to_string(),
+        };
+
+        // Deposit, rebalance and take-profit fees from three vaults
+        state.credit(VAULT_VALUE_ASSET, 100); // vault-1 deposit
+        state.credit("BTC", 40); // vault-2 rebalance
+        state.credit(VAULT_VALUE_ASSET, 60); // vault-3 deposit
+        state.credit("USDC", 21); // vault-3 take profit
+        assert_eq!(state.balances[VAULT_VALUE_ASSET], 160);
+        assert_eq!(state.balances["BTC"], 40);
+        assert_eq!(state.total_collected, 221);
+
+        let recipients = vec![("treasury".to_string(), 1), ("stakers".to_string(), 1)];
+        let distributions = state.distribute(&recipients).unwrap();
+
+        let paid = |recipient: &str, asset_id: &str| distributions.iter()
+            .filter(|d| d.recipient == recipient && d.asset_id == asset_id)
+            .map(|d| d.amount)
+            .sum::<u128>();
+        assert_eq!(paid("treasury", VAULT_VALUE_ASSET), 80);
+        assert_eq!(paid("stakers", VAULT_VALUE_ASSET), 80);
+        assert_eq!(paid("treasury", "BTC"), 20);
+        assert_eq!(paid("stakers", "BTC"), 20);
+
+        // Odd amounts leave the rounding dust with the first recipient
+        assert_eq!(paid("treasury", "USDC"), 11);
+        assert_eq!(paid("stakers", "USDC"), 10);
+
+        // Balances reset, the lifetime total does not
+        assert!(state.balances.is_empty());
+        assert_eq!(state.total_collected, 221);
+        assert!(state.distribute(&[]).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Protocol governance for One Capital Auto-Investing
+//!
+//! This module holds the protocol-wide fee and threshold parameters that
+//! other contracts fall back to when they have no value of their own.
+//! Changes are proposed by the admin and can only be executed once the
+//! voting period has passed.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+/// Protocol-wide parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct GovernanceParameters {
+    /// Protocol fee (in basis points)
+    pub protocol_fee_bps: u32,
+
+    /// Drift threshold for vaults and allocation sets created without one (in basis points)
+    pub global_drift_threshold_bp: u32,
+
+    /// Maximum rebalances per vault per day (0 = unlimited)
+    pub max_rebalances_per_day: u32,
+
+    /// Whether fund-moving operations are halted
+    pub emergency_pause: bool,
+}
+
+impl Default for GovernanceParameters {
+    fn default() -> Self {
+        Self {
+            protocol_fee_bps: 0,
+            global_drift_threshold_bp: 500, // 5%
+            max_rebalances_per_day: 0,
+            emergency_pause: false,
+        }
+    }
+}
+
+/// Parameter a proposal changes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum GovernanceParameter {
+    ProtocolFeeBps,
+    GlobalDriftThresholdBp,
+    MaxRebalancesPerDay,
+    EmergencyPause,
+}
+
+impl GovernanceParameter {
+    /// Parses a parameter from its key (the field name in `GovernanceParameters`)
+    pub fn from_key(key: &str) -> Result<Self, &'static str> {
+        match key {
+            "protocol_fee_bps" => Ok(GovernanceParameter::ProtocolFeeBps),
+            "global_drift_threshold_bp" => Ok(GovernanceParameter::GlobalDriftThresholdBp),
+            "max_rebalances_per_day" => Ok(GovernanceParameter::MaxRebalancesPerDay),
+            "emergency_pause" => Ok(GovernanceParameter::EmergencyPause),
+            _ => Err("Unknown governance parameter"),
+        }
+    }
+
+    /// Checks that `value` is valid for the parameter
+    fn validate(&self, value: u32) -> Result<(), &'static str> {
+        match self {
+            GovernanceParameter::ProtocolFeeBps | GovernanceParameter::GlobalDriftThresholdBp if value > 10000 => {
+                Err("Basis point parameters cannot exceed 10000")
+            },
+            GovernanceParameter::EmergencyPause if value > 1 => {
+                Err("emergency_pause must be 0 or 1")
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A pending or executed parameter change
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct GovernanceProposal {
+    /// Unique identifier for the proposal
+    pub id: u64,
+
+    /// Parameter to change
+    pub parameter: GovernanceParameter,
+
+    /// New value (0 or 1 for `emergency_pause`)
+    pub value: u32,
+
+    /// Account that proposed the change
+    pub proposed_by: String,
+
+    /// Timestamp when the change was proposed
+    pub proposed_at: u64,
+
+    /// Earliest timestamp the change can be executed
+    pub executable_at: u64,
+
+    /// Whether the change has been applied
+    pub executed: bool,
+}
+
+/// Governance contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"GOVERNANCE";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GovernanceContract {
+    parameters: GovernanceParameters,
+    proposals: std::collections::HashMap<u64, GovernanceProposal>, // Proposal ID -> Proposal
+    next_proposal_id: u64,
+    voting_period_seconds: u64,
+    admin: String,
+}
+
+#[l1x_sdk::contract]
+impl GovernanceContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(voting_period_seconds: u64) {
+        let mut state = Self {
+            parameters: GovernanceParameters::default(),
+            proposals: std::collections::HashMap::new(),
+            next_proposal_id: 0,
+            voting_period_seconds,
+            admin: l1x_sdk::env::caller(),
+        };
+
+        state.save()
+    }
+
+    /// Records a proposal by `caller` to set `key` to `value`, executable after the voting period
+    fn propose(&mut self, caller: &str, key: &str, value: u32, now: u64) -> Result<u64, &'static str> {
+        if caller != self.admin {
+            return Err("Only the governance admin can propose changes");
+        }
+
+        let parameter = GovernanceParameter::from_key(key)?;
+        parameter.validate(value)?;
+
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+
+        self.proposals.insert(id, GovernanceProposal {
+            id,
+            parameter,
+            value,
+            proposed_by: caller.to_string(),
+            proposed_at: now,
+            executable_at: now.saturating_add(self.voting_period_seconds),
+            executed: false,
+        });
+
+        Ok(id)
+    }
+
+    /// Applies a proposal whose voting period has passed at `now`
+    fn execute(&mut self, proposal_id: u64, now: u64) -> Result<(), &'static str> {
+        let proposal = self.proposals.get_mut(&proposal_id)
+            .ok_or("Proposal not found")?;
+
+        if proposal.executed {
+            return Err("Proposal already executed");
+        }
+
+        if now < proposal.executable_at {
+            return Err("Voting period has not passed yet");
+        }
+
+        let value = proposal.value;
+        match proposal.parameter {
+            GovernanceParameter::ProtocolFeeBps => self.parameters.protocol_fee_bps = value,
+            GovernanceParameter::GlobalDriftThresholdBp => self.parameters.global_drift_threshold_bp = value,
+            GovernanceParameter::MaxRebalancesPerDay => self.parameters.max_rebalances_per_day = value,
+            GovernanceParameter::EmergencyPause => self.parameters.emergency_pause = value == 1,
+        }
+        proposal.executed = true;
+
+        Ok(())
+    }
+
+    /// Proposes setting parameter `key` to `value` (0 or 1 for `emergency_pause`)
+    pub fn propose_change(key: String, value: u32) -> String {
+        let mut state = Self::load();
+
+        let proposal_id = state.propose(&l1x_sdk::env::caller(), &key, value, l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("{}", err));
+        let executable_at = state.proposals[&proposal_id].executable_at;
+        state.save();
+
+        format!("Proposal {} to set {} to {} executable at {}", proposal_id, key, value, executable_at)
+    }
+
+    /// Applies a proposed change once its voting period has passed
+    pub fn execute_change(proposal_id: u64) -> String {
+        let mut state = Self::load();
+
+        state.execute(proposal_id, l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("Failed to execute proposal {}: {}", proposal_id, err));
+        state.save();
+
+        format!("Proposal {} executed", proposal_id)
+    }
+
+    /// Gets the current protocol parameters
+    pub fn get_parameters() -> String {
+        let state = Self::load();
+
+        serde_json::to_string(&state.parameters)
+            .unwrap_or_else(|_| "Failed to serialize parameters".to_string())
+    }
+
+    /// Gets a proposal
+    pub fn get_proposal(proposal_id: u64) -> String {
+        let state = Self::load();
+
+        let proposal = state.proposals.get(&proposal_id)
+            .unwrap_or_else(|| panic!("Proposal not found: {}", proposal_id));
+
+        serde_json::to_string(proposal)
+            .unwrap_or_else(|_| "Failed to serialize proposal".to_string())
+    }
+}
+
+/// Current protocol parameters, or the defaults if governance isn't initialized
+pub fn parameters() -> GovernanceParameters {
+    match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+        Some(bytes) => GovernanceContract::try_from_slice(&bytes).unwrap().parameters,
+        None => GovernanceParameters::default(),
+    }
+}
+
+/// `drift_threshold_bp`, or the protocol-wide threshold when it is 0
+pub fn drift_threshold_or_global(drift_threshold_bp: u32) -> u32 {
+    if drift_threshold_bp == 0 {
+        parameters().global_drift_threshold_bp
+    } else {
+        drift_threshold_bp
+    }
+}
+
+/// Protocol-wide daily rebalance limit for allocation sets without their own (0 = unlimited)
+pub fn max_rebalances_per_day() -> u32 {
+    parameters().max_rebalances_per_day
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract() -> GovernanceContract {
+        GovernanceContract {
+            parameters: GovernanceParameters::default(),
+            proposals: std::collections::HashMap::new(),
+            next_proposal_id: 0,
+            voting_period_seconds: 3 * 86400,
+            admin: "admin".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_change_applies_only_after_timelock() {
+        let mut state = contract();
+
+        assert!(state.propose("someone", "protocol_fee_bps", 30, 1000).is_err());
+        assert!(state.propose("admin", "unknown_key", 30, 1000).is_err());
+        assert!(state.propose("admin", "protocol_fee_bps", 10001, 1000).is_err());
+
+        let id = state.propose("admin", "protocol_fee_bps", 30, 1000).unwrap();
+
+        // Still within the voting period
+        assert_eq!(state.execute(id, 1000 + 3 * 86400 - 1), Err("Voting period has not passed yet"));
+        assert_eq!(state.parameters.protocol_fee_bps, 0);
+
+        assert_eq!(state.execute(id, 1000 + 3 * 86400), Ok(()));
+        assert_eq!(state.parameters.protocol_fee_bps, 30);
+
+        // A proposal only executes once
+        assert_eq!(state.execute(id, 1000 + 4 * 86400), Err("Proposal already executed"));
+
+        let pause = state.propose("admin", "emergency_pause", 1, 2000).unwrap();
+        state.execute(pause, 2000 + 3 * 86400).unwrap();
+        assert!(state.parameters.emergency_pause);
+    }
+}
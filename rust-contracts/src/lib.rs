@@ -17,18 +17,51 @@ pub mod custodial_vault;
 /// Non-custodial vault implementation where user retains control of funds
 pub mod non_custodial_vault;
 
+/// Vault factory for creating vaults from pre-configured templates
+pub mod vault_factory;
+
+/// Protocol-level asset whitelist and blacklist
+pub mod asset_registry;
+
 /// Take profit strategies for automated profit realization
 pub mod take_profit;
 
+/// Reinvestment of take-profit proceeds back into vaults
+pub mod auto_compound;
+
+/// Dollar-cost averaging into custodial vaults
+pub mod dca;
+
 /// Cross-chain swap functionality using L1X XTalk protocol
 pub mod cross_chain;
 
+/// Atomic and batched cross-chain swaps settled through X-Talk
+pub mod x_swap;
+
 /// Price feed oracle service for real-time asset pricing
 pub mod price_feed;
 
 /// Event system for contract event emission
 pub mod events;
 
+/// Common parameter checks for contract entry points
+pub mod validation;
+
+/// Protocol-wide emergency pause
+pub mod emergency;
+
+/// Timelocked governance of protocol-wide parameters
+pub mod governance;
+
+/// Collection and distribution of protocol fees
+pub mod fee_collector;
+
+/// Constant-product liquidity pool for on-chain swaps
+pub mod liquidity_pool;
+
+/// Audit log of admin-level state changes
+pub mod audit;
+
 /// Rebalance functionality for portfolio balancing
 pub mod rebalance;
 
@@ -51,9 +84,4 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const DESCRIPTION: &str = "One Capital Auto-Investing Smart Contracts";
 
 #[cfg(test)]
-mod tests {
-    #[test]
-    fn version_check() {
-        assert_eq!(super::VERSION, env!("CARGO_PKG_VERSION"));
-    }
-}
\ No newline at end of file
+mod tests;
@@ -0,0 +1,336 @@
+//! Liquidity pool for One Capital Auto-Investing
+//!
+//! This module keeps on-chain reserves per asset and swaps between them with
+//! a constant-product formula. Liquidity providers hold shares of each
+//! asset's reserve, which grow with the swap fees left in the pool.
+//!
+//! Reserves are bookkeeping only: no tokens are escrowed when liquidity is
+//! provided, so pool prices are not used to quote swaps elsewhere.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+use crate::validation::{validate_amount_nonzero, validate_basis_points, validate_non_empty_string};
+
+/// Scale of spot prices (1e8, like the price feed)
+pub const SPOT_PRICE_SCALE: u128 = 100_000_000;
+
+/// Outcome of a swap
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapResult {
+    /// Asset sold to the pool
+    pub from_asset: String,
+
+    /// Asset bought from the pool
+    pub to_asset: String,
+
+    /// Amount sold
+    pub amount_in: u128,
+
+    /// Amount bought, net of the fee
+    pub amount_out: u128,
+}
+
+/// Liquidity pool contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"LIQUIDITY_POOL";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct LiquidityPoolContract {
+    reserves: std::collections::HashMap<String, u128>, // Asset ID -> Reserve
+    lp_shares: std::collections::HashMap<String, std::collections::HashMap<String, u128>>, // Asset ID -> Provider -> Shares
+    fee_bps: u32,
+}
+
+#[l1x_sdk::contract]
+impl LiquidityPoolContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(fee_bps: u32) {
+        validate_basis_points(fee_bps, "fee_bps")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let mut state = Self::with_fee(fee_bps);
+
+        state.save()
+    }
+
+    /// Adds `amount` of `asset_id` to the pool, crediting `provider` with shares
+    pub fn provide_liquidity(asset_id: String, amount: u128, provider: String) -> String {
+        validate_non_empty_string(&asset_id, "asset_id")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_amount_nonzero(amount, "amount")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        if provider != l1x_sdk::env::caller() {
+            panic!("Only the provider can add their liquidity");
+        }
+
+        let mut state = Self::load();
+
+        let shares = state.deposit(&asset_id, amount, &provider)
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+
+        format!("Provided {} {} for {} shares", amount, asset_id, shares)
+    }
+
+    /// Redeems `provider`'s `shares` of `asset_id` for their part of the reserve
+    pub fn remove_liquidity(asset_id: String, shares: u128, provider: String) -> String {
+        validate_amount_nonzero(shares, "shares")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        if provider != l1x_sdk::env::caller() {
+            panic!("Only the provider can remove their liquidity");
+        }
+
+        let mut state = Self::load();
+
+        let amount = state.withdraw(&asset_id, shares, &provider)
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+
+        format!("Removed {} {} for {} shares", amount, asset_id, shares)
+    }
+
+    /// Swaps `amount_in` of `from_asset` into `to_asset`, failing if less than `min_out` would be received
+    pub fn swap(from_asset: String, to_asset: String, amount_in: u128, min_out: u128) -> String {
+        validate_amount_nonzero(amount_in, "amount_in")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let mut state = Self::load();
+
+        let result = state.execute_swap(&from_asset, &to_asset, amount_in, min_out)
+            .unwrap_or_else(|err| panic!("Swap failed: {}", err));
+        state.save();
+
+        serde_json::to_string(&result)
+            .unwrap_or_else(|_| "Failed to serialize swap result".to_string())
+    }
+
+    /// Gets the price of one unit of `base` in `quote` (scaled by 1e8)
+    pub fn get_spot_price(base: String, quote: String) -> u128 {
+        let state = Self::load();
+
+        state.spot_price(&base, &quote)
+            .unwrap_or_else(|| panic!("No liquidity for {}/{}", base, quote))
+    }
+
+    /// Gets the reserve of every asset
+    pub fn get_reserves() -> String {
+        let state = Self::load();
+
+        serde_json::to_string(&state.reserves)
+            .unwrap_or_else(|_| "Failed to serialize reserves".to_string())
+    }
+}
+
+impl LiquidityPoolContract {
+    /// Creates an empty pool charging `fee_bps` on swaps
+    pub fn with_fee(fee_bps: u32) -> Self {
+        Self {
+            reserves: std::collections::HashMap::new(),
+            lp_shares: std::collections::HashMap::new(),
+            fee_bps,
+        }
+    }
+
+    /// Reserve of `asset_id` (0 when it has no liquidity)
+    pub fn reserve(&self, asset_id: &str) -> u128 {
+        self.reserves.get(asset_id).copied().unwrap_or(0)
+    }
+
+    /// Total shares issued against the reserve of `asset_id`
+    fn total_shares(&self, asset_id: &str) -> u128 {
+        self.lp_shares.get(asset_id)
+            .map(|shares| shares.values().sum())
+            .unwrap_or(0)
+    }
+
+    /// Adds `amount` to the reserve of `asset_id`, returning the shares issued to `provider`
+    ///
+    /// The first provider gets one share per unit; later ones get shares in
+    /// proportion to the reserve they add to.
+    pub fn deposit(&mut self, asset_id: &str, amount: u128, provider: &str) -> Result<u128, &'static str> {
+        let reserve = self.reserve(asset_id);
+        let total_shares = self.total_shares(asset_id);
+
+        let shares = if total_shares == 0 || reserve == 0 {
+            amount
+        } else {
+            amount.checked_mul(total_shares).ok_or("Overflow in share calculation")? / reserve
+        };
+
+        if shares == 0 {
+            return Err("Amount too small to issue shares");
+        }
+
+        self.reserves.insert(asset_id.to_string(), reserve.checked_add(amount).ok_or("Overflow in reserve")?);
+        *self.lp_shares.entry(asset_id.to_string()).or_default()
+            .entry(provider.to_string()).or_insert(0) += shares;
+
+        Ok(shares)
+    }
+
+    /// Redeems `shares` of `asset_id` held by `provider`, returning the amount paid out
+    pub fn withdraw(&mut self, asset_id: &str, shares: u128, provider: &str) -> Result<u128, &'static str> {
+        let total_shares = self.total_shares(asset_id);
+        let held = self.lp_shares.get(asset_id)
+            .and_then(|holders| holders.get(provider))
+            .copied()
+            .unwrap_or(0);
+
+        if shares == 0 {
+            return Err("Shares must be greater than zero");
+        }
+
+        if shares > held {
+            return Err("Insufficient shares");
+        }
+
+        let reserve = self.reserve(asset_id);
+        let amount = reserve.checked_mul(shares).ok_or("Overflow in withdrawal calculation")? / total_shares;
+
+        self.reserves.insert(asset_id.to_string(), reserve - amount);
+        let holders = self.lp_shares.get_mut(asset_id).unwrap();
+        if held == shares {
+            holders.remove(provider);
+        } else {
+            holders.insert(provider.to_string(), held - shares);
+        }
+
+        Ok(amount)
+    }
+
+    /// Amount of `to_asset` received for `amount_in` of `from_asset`, net of the fee
+    ///
+    /// Constant product: `reserve_in * reserve_out` is preserved with the
+    /// fee-adjusted input, so larger swaps get a worse price.
+    pub fn quote_swap(&self, from_asset: &str, to_asset: &str, amount_in: u128) -> Result<u128, &'static str> {
+        if from_asset == to_asset {
+            return Err("Cannot swap an asset for itself");
+        }
+
+        let reserve_in = self.reserve(from_asset);
+        let reserve_out = self.reserve(to_asset);
+        if reserve_in == 0 || reserve_out == 0 {
+            return Err("No liquidity for this pair");
+        }
+
+        let amount_in_after_fee = amount_in.checked_mul(10000 - self.fee_bps as u128)
+            .ok_or("Overflow in swap calculation")? / 10000;
+        let numerator = reserve_out.checked_mul(amount_in_after_fee).ok_or("Overflow in swap calculation")?;
+        let denominator = reserve_in.checked_add(amount_in_after_fee).ok_or("Overflow in swap calculation")?;
+
+        Ok(numerator / denominator)
+    }
+
+    /// Swaps against the reserves, failing if less than `min_out` would be received
+    pub fn execute_swap(
+        &mut self,
+        from_asset: &str,
+        to_asset: &str,
+        amount_in: u128,
+        min_out: u128,
+    ) -> Result<SwapResult, &'static str> {
+        let amount_out = self.quote_swap(from_asset, to_asset, amount_in)?;
+
+        if amount_out == 0 {
+            return Err("Swap amount too small");
+        }
+
+        if amount_out < min_out {
+            return Err("Output below minimum (slippage exceeded)");
+        }
+
+        // The whole input, fee included, stays in the pool for the providers
+        let reserve_in = self.reserve(from_asset);
+        let reserve_out = self.reserve(to_asset);
+        self.reserves.insert(from_asset.to_string(), reserve_in + amount_in);
+        self.reserves.insert(to_asset.to_string(), reserve_out - amount_out);
+
+        Ok(SwapResult {
+            from_asset: from_asset.to_string(),
+            to_asset: to_asset.to_string(),
+            amount_in,
+            amount_out,
+        })
+    }
+
+    /// Price of one unit of `base` in `quote` from the reserves (scaled by 1e8)
+    pub fn spot_price(&self, base: &str, quote: &str) -> Option<u128> {
+        let reserve_base = self.reserve(base);
+        let reserve_quote = self.reserve(quote);
+
+        if reserve_base == 0 || reserve_quote == 0 {
+            return None;
+        }
+
+        reserve_quote.checked_mul(SPOT_PRICE_SCALE).map(|scaled| scaled / reserve_base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> LiquidityPoolContract {
+        let mut pool = LiquidityPoolContract::with_fee(30);
+        pool.deposit("ETH", 1_000_000, "alice").unwrap();
+        pool.deposit("USDC", 2_000_000, "alice").unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_swap_output_and_slippage() {
+        let mut pool = pool();
+        assert_eq!(pool.spot_price("ETH", "USDC"), Some(2 * SPOT_PRICE_SCALE));
+
+        // 9970 after the 0.3% fee: 2_000_000 * 9970 / 1_009_970
+        assert_eq!(pool.quote_swap("ETH", "USDC", 10_000), Ok(19_743));
+
+        // Below spot (20_000) because of the fee and price impact; bigger swaps move the price more
+        let large = pool.quote_swap("ETH", "USDC", 100_000).unwrap();
+        assert!(large < 10 * 19_743);
+
+        // A minimum above the output is rejected without touching the reserves
+        assert!(pool.execute_swap("ETH", "USDC", 10_000, 19_744).is_err());
+        assert_eq!(pool.reserve("ETH"), 1_000_000);
+
+        let result = pool.execute_swap("ETH", "USDC", 10_000, 19_700).unwrap();
+        assert_eq!(result.amount_out, 19_743);
+        assert_eq!(pool.reserve("ETH"), 1_010_000);
+        assert_eq!(pool.reserve("USDC"), 2_000_000 - 19_743);
+
+        // The product never shrinks
+        assert!(pool.reserve("ETH") * pool.reserve("USDC") >= 1_000_000 * 2_000_000);
+
+        assert!(pool.quote_swap("ETH", "BTC", 10_000).is_err());
+        assert!(pool.quote_swap("ETH", "ETH", 10_000).is_err());
+    }
+
+    #[test]
+    fn test_liquidity_shares() {
+        let mut pool = pool();
+
+        // A second provider doubling the reserve gets as many shares as the first
+        assert_eq!(pool.deposit("ETH", 1_000_000, "bob"), Ok(1_000_000));
+        assert!(pool.withdraw("ETH", 1_000_001, "bob").is_err());
+        assert_eq!(pool.withdraw("ETH", 1_000_000, "bob"), Ok(1_000_000));
+
+        // Swap input, fee included, grows the remaining provider's claim
+        pool.execute_swap("ETH", "USDC", 10_000, 0).unwrap();
+        assert_eq!(pool.withdraw("ETH", 1_000_000, "alice"), Ok(1_010_000));
+        assert_eq!(pool.reserve("ETH"), 0);
+        assert!(pool.withdraw("ETH", 1, "alice").is_err());
+    }
+}
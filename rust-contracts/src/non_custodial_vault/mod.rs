@@ -10,7 +10,17 @@ use l1x_sdk::prelude::*;
 
 use crate::allocation::{AllocationSet, AssetAllocation};
 use crate::take_profit::{TakeProfitStrategy, TakeProfitType};
-use crate::custodial_vault::VaultStatus;
+use crate::custodial_vault::{MigrationPayload, VaultStatus};
+
+/// How long rebalance recommendations stay valid unless configured otherwise (24 hours)
+pub const DEFAULT_RECOMMENDATION_TTL_SECONDS: u64 = 24 * 3600;
+
+fn default_recommendation_ttl_seconds() -> u64 {
+    DEFAULT_RECOMMENDATION_TTL_SECONDS
+}
+
+/// Maximum number of drift snapshots kept per vault
+pub const MAX_DRIFT_HISTORY: usize = 30;
 
 /// Non-custodial vault for user-controlled portfolio management
 #[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
@@ -41,6 +51,61 @@ pub struct NonCustodialVault {
     
     /// Last rebalance recommendations
     pub last_recommendations: Vec<RebalanceRecommendation>,
+    
+    /// Rebalance proposal awaiting the owner's signed confirmation
+    #[serde(default)]
+    pub pending_proposal: Option<RebalanceProposal>,
+    
+    /// Proposals the owner has confirmed, oldest first
+    #[serde(default)]
+    pub archived_proposals: Vec<RebalanceProposal>,
+    
+    /// How long rebalance recommendations stay valid (in seconds)
+    #[serde(default = "default_recommendation_ttl_seconds")]
+    pub recommendation_ttl_seconds: u64,
+    
+    /// Most recent drift snapshots, oldest first (at most `MAX_DRIFT_HISTORY`)
+    #[serde(default)]
+    pub drift_history: Vec<DriftSnapshot>,
+}
+
+/// Drift of a vault's allocations at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct DriftSnapshot {
+    /// Timestamp of the snapshot
+    pub timestamp: u64,
+    
+    /// Drift from target of each asset (in basis points)
+    pub per_asset_drift: Vec<(String, u32)>,
+    
+    /// Largest drift of any asset (in basis points)
+    pub max_drift: u32,
+}
+
+/// Status of a rebalance proposal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum ProposalStatus {
+    /// Waiting for the owner to confirm
+    Proposed,
+    
+    /// Confirmed by the owner; allocations were updated
+    Confirmed,
+}
+
+/// Rebalance recommendations proposed to the owner of a non-custodial vault
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct RebalanceProposal {
+    /// Unique identifier for the proposal
+    pub proposal_id: String,
+    
+    /// Recommended actions
+    pub recommendations: Vec<RebalanceRecommendation>,
+    
+    /// Current status
+    pub status: ProposalStatus,
+    
+    /// Timestamp when the proposal was made
+    pub created_at: u64,
 }
 
 /// Recommended rebalance action for a non-custodial vault
@@ -60,6 +125,32 @@ pub struct RebalanceRecommendation {
     
     /// Suggested amount to buy/sell in USD
     pub amount_usd: u128,
+    
+    /// Timestamp when the recommendation was made
+    #[serde(default)]
+    pub recommended_at: u64,
+    
+    /// Timestamp after which prices have likely moved too far to act on it
+    #[serde(default)]
+    pub expires_at: u64,
+    
+    /// Confidence (0-100) that the drift is worth acting on, lower for volatile assets
+    #[serde(default = "default_confidence_score")]
+    pub confidence_score: u8,
+}
+
+fn default_confidence_score() -> u8 {
+    crate::rebalance::NEUTRAL_CONFIDENCE
+}
+
+/// Rebalance recommendations that are still fresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecommendationsResponse {
+    /// Recommendations that have not expired
+    pub recommendations: Vec<RebalanceRecommendation>,
+    
+    /// Number of recommendations left out because they expired
+    pub stale_count: u32,
 }
 
 /// Type of rebalance action to take
@@ -75,6 +166,22 @@ pub enum RebalanceAction {
     NoAction,
 }
 
+/// A page of a user's vaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonCustodialVaultPage {
+    /// Vaults on this page
+    pub vaults: Vec<NonCustodialVault>,
+    
+    /// Total number of vaults owned by the user
+    pub total: u32,
+    
+    /// Index of the first vault on this page
+    pub offset: u32,
+    
+    /// Maximum number of vaults per page
+    pub limit: u32,
+}
+
 /// Non-custodial vault contract storage
 const STORAGE_CONTRACT_KEY: &[u8] = b"NON_CUSTODIAL_VAULT";
 
@@ -87,10 +194,18 @@ pub struct NonCustodialVaultContract {
 #[l1x_sdk::contract]
 impl NonCustodialVaultContract {
     fn load() -> Self {
-        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+        let mut state = match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
             Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
             None => panic!("The contract isn't initialized"),
+        };
+        
+        // Sets without a daily rebalance limit of their own follow the governance one
+        let max_rebalances_per_day = crate::governance::max_rebalances_per_day();
+        for vault in state.vaults.values_mut() {
+            vault.allocations.governance_max_rebalances_per_day = max_rebalances_per_day;
         }
+        
+        state
     }
 
     fn save(&mut self) {
@@ -115,28 +230,54 @@ impl NonCustodialVaultContract {
         }
         
         // Create a new vault
-        let vault = NonCustodialVault {
-            id: vault_id.clone(),
-            owner: owner.clone(),
-            status: VaultStatus::Active,
-            allocations: AllocationSet::new(drift_threshold_bp),
-            take_profit: None,
-            estimated_value: 0,
-            created_at: l1x_sdk::env::block_timestamp(),
-            last_rebalance: 0,
-            last_recommendations: Vec::new(),
-        };
+        let vault = NonCustodialVault::new(vault_id.clone(), owner.clone(), drift_threshold_bp);
+        state.insert_vault(vault);
         
-        // Add vault to contract state
-        state.vaults.insert(vault_id.clone(), vault);
+        state.save();
         
-        // Add vault to user's vault list
-        let user_vaults = state.user_vaults.entry(owner.clone()).or_insert_with(Vec::new);
-        user_vaults.push(vault_id.clone());
+        crate::events::emit_vault_status_changed_event(&vault_id, None, VaultStatus::Active, &owner);
+        
+        format!("Non-custodial vault {} created for user {}", vault_id, owner)
+    }
+    
+    /// Creates a non-custodial vault from the payload of a custodial vault's migration
+    ///
+    /// `payload_json` is the payload returned by
+    /// `CustodialVaultContract::initiate_migration_to_non_custodial`; the
+    /// custodial vault is closed once its signature checks out.
+    pub fn complete_migration_from_custodial(payload_json: String, new_vault_id: String, owner: String) -> String {
+        let payload: MigrationPayload = serde_json::from_str(&payload_json)
+            .unwrap_or_else(|e| panic!("Failed to parse migration payload: {}", e));
+            
+        if owner != payload.owner || owner != l1x_sdk::env::caller() {
+            panic!("Only the owner of the migrated vault can complete its migration");
+        }
+        
+        let mut state = Self::load();
+        
+        if state.vaults.contains_key(&new_vault_id) {
+            panic!("Vault with this ID already exists");
+        }
+        
+        crate::custodial_vault::complete_migration(&payload)
+            .unwrap_or_else(|err| panic!("Cannot complete migration: {}", err));
+            
+        let vault = NonCustodialVault::from_migration(&payload, new_vault_id.clone(), l1x_sdk::env::block_timestamp());
+        state.insert_vault(vault);
         
         state.save();
         
-        format!("Non-custodial vault {} created for user {}", vault_id, owner)
+        crate::events::emit_vault_status_changed_event(&new_vault_id, None, VaultStatus::Active, &owner);
+        
+        format!("Non-custodial vault {} created from custodial vault {}", new_vault_id, payload.source_vault_id)
+    }
+    
+    /// Adds a vault to the contract state and its owner's vault list
+    fn insert_vault(&mut self, vault: NonCustodialVault) {
+        let user_vaults = self.user_vaults.entry(vault.owner.clone()).or_insert_with(Vec::new);
+        user_vaults.push(vault.id.clone());
+        
+        self.vaults.insert(vault.id.clone(), vault);
     }
     
     /// Gets a vault by ID
@@ -166,8 +307,44 @@ impl NonCustodialVaultContract {
             .unwrap_or_else(|_| "Failed to serialize vaults".to_string())
     }
     
+    /// Gets one page of a user's vaults
+    pub fn get_user_vaults_paginated(owner: String, offset: u32, limit: u32) -> String {
+        let state = Self::load();
+        
+        let page = state.user_vaults_page(&owner, offset, limit);
+        
+        serde_json::to_string(&page)
+            .unwrap_or_else(|_| "Failed to serialize vaults".to_string())
+    }
+    
+    /// Collects the vaults in `[offset, offset + limit)` of a user's vault list
+    fn user_vaults_page(&self, owner: &str, offset: u32, limit: u32) -> NonCustodialVaultPage {
+        let user_vault_ids = self.user_vaults.get(owner)
+            .cloned()
+            .unwrap_or_default();
+            
+        let vaults = user_vault_ids.iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .filter_map(|id| self.vaults.get(id).cloned())
+            .collect();
+            
+        NonCustodialVaultPage {
+            vaults,
+            total: user_vault_ids.len() as u32,
+            offset,
+            limit,
+        }
+    }
+    
     /// Updates vault settings
     pub fn update_vault(vault_id: String, drift_threshold_bp: Option<u32>, status: Option<String>, estimated_value: Option<u128>) -> String {
+        let params = serde_json::json!({
+            "vault_id": vault_id,
+            "drift_threshold_bp": drift_threshold_bp,
+            "status": status,
+            "estimated_value": estimated_value,
+        });
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
@@ -179,13 +356,16 @@ impl NonCustodialVaultContract {
         }
         
         // Update status if provided
+        let mut status_change = None;
         if let Some(status_str) = status {
-            vault.status = match status_str.as_str() {
+            let new_status = match status_str.as_str() {
                 "active" => VaultStatus::Active,
                 "paused" => VaultStatus::Paused,
                 "closed" => VaultStatus::Closed,
                 _ => panic!("Invalid vault status: {}", status_str),
             };
+            
+            status_change = vault.transition_status(new_status, &l1x_sdk::env::caller());
         }
         
         // Update estimated value if provided
@@ -195,11 +375,40 @@ impl NonCustodialVaultContract {
         
         state.save();
         
-        format!("Vault {} updated", vault_id)
+        if let Some(event) = status_change {
+            event.emit();
+        }
+        
+        let result = format!("Vault {} updated", vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "NonCustodialVaultContract",
+            "update_vault",
+            &params,
+            &result,
+        );
+        
+        result
     }
     
     /// Sets up a take profit strategy for a vault
-    pub fn set_take_profit(vault_id: String, strategy_type: String, target_percentage: Option<u32>, interval_seconds: Option<u64>) -> String {
+    ///
+    /// `profit_portion_bp` is the share of the gain realized per execution
+    /// (1 to 10000 basis points, the whole gain when omitted).
+    pub fn set_take_profit(
+        vault_id: String,
+        strategy_type: String,
+        target_percentage: Option<u32>,
+        interval_seconds: Option<u64>,
+        profit_portion_bp: Option<u32>,
+    ) -> String {
+        let params = serde_json::json!({
+            "vault_id": vault_id,
+            "strategy_type": strategy_type,
+            "target_percentage": target_percentage,
+            "interval_seconds": interval_seconds,
+            "profit_portion_bp": profit_portion_bp,
+        });
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
@@ -227,16 +436,142 @@ impl NonCustodialVaultContract {
                 TakeProfitType::Time { interval_seconds: interval }
             },
             
+            "trailing_stop" => {
+                let trail_bp = target_percentage
+                    .unwrap_or_else(|| panic!("Trail required for trailing-stop take profit"));
+                    
+                TakeProfitType::TrailingStop { trail_bp }
+            },
+            
             _ => panic!("Invalid take profit strategy type: {}", strategy_type),
         };
         
         let mut strategy = TakeProfitStrategy::new(take_profit_type);
+        if let Some(portion) = profit_portion_bp {
+            strategy.set_profit_portion(portion)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        strategy.set_baseline(vault.estimated_value);
+        vault.take_profit = Some(strategy);
+        
+        state.save();
+        
+        let result = format!("Take profit strategy set for vault {}", vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "NonCustodialVaultContract",
+            "set_take_profit",
+            &params,
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Sets up a take profit strategy for a vault from a JSON `TakeProfitConfig`
+    ///
+    /// Unlike `set_take_profit`, this can express composite triggers, e.g.
+    /// `{"strategy_type": {"Composite": {"conditions": [{"Percentage": {"percentage": 500}},
+    /// {"Time": {"interval_seconds": 2592000}}], "mode": "All"}}}`.
+    pub fn set_take_profit_config(vault_id: String, config_json: String) -> String {
+        let params = serde_json::json!({
+            "vault_id": vault_id,
+            "config": config_json,
+        });
+        let config: crate::take_profit::TakeProfitConfig = serde_json::from_str(&config_json)
+            .unwrap_or_else(|e| panic!("Failed to parse take profit config: {}", e));
+        let mut strategy = config.into_strategy()
+            .unwrap_or_else(|err| panic!("Invalid take profit config: {}", err));
+            
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.status != VaultStatus::Active {
+            panic!("Cannot set take profit for a non-active vault");
+        }
+        
+        let strategy_name = strategy.strategy_type.name();
         strategy.set_baseline(vault.estimated_value);
         vault.take_profit = Some(strategy);
         
         state.save();
         
-        format!("Take profit strategy set for vault {}", vault_id)
+        let result = format!("Take profit strategy ({}) set for vault {}", strategy_name, vault_id);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "NonCustodialVaultContract",
+            "set_take_profit_config",
+            &params,
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Removes a vault's take profit strategy
+    pub fn clear_take_profit(vault_id: String) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can clear take profit");
+        }
+        
+        if vault.take_profit.take().is_none() {
+            return format!("No take profit strategy configured for vault {}", vault_id);
+        }
+        
+        state.save();
+        
+        crate::events::emit_take_profit_changed_event(
+            &vault_id,
+            crate::events::TakeProfitChangeType::Cleared,
+            &l1x_sdk::env::caller(),
+        );
+        
+        format!("Take profit strategy cleared for vault {}", vault_id)
+    }
+    
+    /// Suspends a vault's take profit strategy without losing its configuration
+    pub fn pause_take_profit(vault_id: String) -> String {
+        Self::set_take_profit_paused(vault_id, true)
+    }
+    
+    /// Re-enables a vault's paused take profit strategy
+    pub fn resume_take_profit(vault_id: String) -> String {
+        Self::set_take_profit_paused(vault_id, false)
+    }
+    
+    fn set_take_profit_paused(vault_id: String, paused: bool) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        if vault.owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can pause or resume take profit");
+        }
+        
+        let strategy = vault.take_profit.as_mut()
+            .unwrap_or_else(|| panic!("No take profit strategy configured for vault {}", vault_id));
+            
+        let (change_type, action) = if paused {
+            strategy.pause();
+            (crate::events::TakeProfitChangeType::Paused, "paused")
+        } else {
+            strategy.resume();
+            (crate::events::TakeProfitChangeType::Resumed, "resumed")
+        };
+        
+        state.save();
+        
+        crate::events::emit_take_profit_changed_event(&vault_id, change_type, &l1x_sdk::env::caller());
+        
+        format!("Take profit strategy {} for vault {}", action, vault_id)
     }
     
     /// Gets take profit strategy for a vault
@@ -247,8 +582,16 @@ impl NonCustodialVaultContract {
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
         match &vault.take_profit {
-            Some(strategy) => serde_json::to_string(strategy)
-                .unwrap_or_else(|_| "Failed to serialize take profit strategy".to_string()),
+            Some(strategy) => {
+                let mut strategy_json = serde_json::to_value(strategy)
+                    .unwrap_or_else(|_| panic!("Failed to serialize take profit strategy"));
+                strategy_json["remaining_executions"] = serde_json::json!(
+                    strategy.remaining_executions(l1x_sdk::env::block_timestamp())
+                );
+                
+                serde_json::to_string(&strategy_json)
+                    .unwrap_or_else(|_| "Failed to serialize take profit strategy".to_string())
+            },
                 
             None => "No take profit strategy configured".to_string(),
         }
@@ -311,18 +654,58 @@ impl NonCustodialVaultContract {
             .unwrap_or_else(|_| "Failed to serialize allocations".to_string())
     }
     
-    /// Checks if rebalancing is needed
+    /// Syncs a vault with the user's wallet holdings
+    ///
+    /// Recomputes current percentages from `(asset, balance)` pairs at the given
+    /// prices and stores the resulting total as the vault's estimated value.
+    pub fn sync(vault_id: String, balances_json: String, prices_json: String) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let balances: Vec<(String, u128)> = serde_json::from_str(&balances_json)
+            .unwrap_or_else(|_| panic!("Failed to parse balances"));
+        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+            .unwrap_or_else(|_| panic!("Failed to parse prices"));
+            
+        let total_value = vault.allocations.update_current_from_values(&balances, &prices)
+            .unwrap_or_else(|e| panic!("Failed to sync vault {}: {}", vault_id, e));
+            
+        vault.update_estimated_value(total_value);
+        
+        state.save();
+        
+        format!("Vault {} synced with estimated value {}", vault_id, total_value)
+    }
+    
+    /// Checks if rebalancing is needed, recording the vault's current drift
     pub fn needs_rebalancing(vault_id: String) -> bool {
-        let state = Self::load();
+        let mut state = Self::load();
         
-        let vault = state.vaults.get(&vault_id)
+        let vault = state.vaults.get_mut(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
         if vault.status != VaultStatus::Active {
             return false;
         }
         
-        vault.allocations.needs_rebalancing()
+        let needs_rebalancing = vault.needs_rebalancing();
+        
+        state.save();
+        
+        needs_rebalancing
+    }
+    
+    /// Gets a vault's recent drift snapshots, oldest first
+    pub fn get_drift_history(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        serde_json::to_string(&vault.drift_history)
+            .unwrap_or_else(|_| "Failed to serialize drift history".to_string())
     }
     
     /// Checks if rebalancing is needed and emits events
@@ -390,10 +773,17 @@ impl NonCustodialVaultContract {
         };
         
         // Calculate necessary transactions
-        let transactions = vault.allocations.calculate_rebalance_transactions(
+        let transactions = match vault.allocations.calculate_rebalance_transactions(
             &prices,
             vault.total_value
-        );
+        ) {
+            Ok(t) => t,
+            Err(e) => {
+                let error_msg = format!("Failed to calculate rebalance transactions: {}", e);
+                crate::events::emit_rebalance_failed_event(&vault_id, &error_msg);
+                panic!("{}", error_msg);
+            }
+        };
         
         if transactions.is_empty() {
             return format!("No rebalance transactions needed for vault {}", vault_id);
@@ -405,7 +795,7 @@ impl NonCustodialVaultContract {
             rebalance_id,
             crate::rebalance::RebalanceStrategy::Manual,
             transactions
-        );
+        ).with_allocation_config_hash(vault.allocations.canonical_hash());
         
         // Estimate gas costs
         let estimated_cost = crate::rebalance::RebalanceEngine::estimate_gas_costs(&operation);
@@ -454,8 +844,11 @@ impl NonCustodialVaultContract {
         format!("Rebalance authorized for vault {}", vault_id)
     }
     
-    /// Generates rebalancing recommendations
-    pub fn generate_rebalance_recommendations(vault_id: String, prices_json: String) -> String {
+    /// Proposes rebalancing recommendations for the owner to confirm
+    ///
+    /// Allocations are left untouched until the proposal is confirmed with
+    /// `confirm_rebalance`; a new proposal replaces any unconfirmed one.
+    pub fn propose_rebalance(vault_id: String, prices_json: String) -> String {
         let mut state = Self::load();
         
         let vault = state.vaults.get_mut(&vault_id)
@@ -466,74 +859,95 @@ impl NonCustodialVaultContract {
         }
         
         // Parse prices from JSON
-        let prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
+        let _prices: Vec<(String, u128)> = serde_json::from_str(&prices_json)
             .unwrap_or_else(|_| panic!("Failed to parse prices"));
             
-        let total_value = vault.estimated_value;
-        
-        if total_value == 0 {
+        if vault.estimated_value == 0 {
             panic!("Vault has no estimated value");
         }
         
-        // Generate recommendations
-        let mut recommendations = Vec::new();
+        let proposal_id = format!("proposal-{}-{}", vault_id, l1x_sdk::env::block_timestamp());
+        vault.propose_rebalance(proposal_id);
         
-        for allocation in &vault.allocations.allocations {
-            let current_value = total_value * (allocation.current_percentage as u128) / 10000;
-            let target_value = total_value * (allocation.target_percentage as u128) / 10000;
-            
-            let action = if current_value < target_value {
-                RebalanceAction::Buy
-            } else if current_value > target_value {
-                RebalanceAction::Sell
-            } else {
-                RebalanceAction::NoAction
-            };
-            
-            let amount_usd = if current_value < target_value {
-                target_value - current_value
-            } else if current_value > target_value {
-                current_value - target_value
-            } else {
-                0
-            };
-            
-            recommendations.push(RebalanceRecommendation {
-                asset_id: allocation.asset_id.clone(),
-                current_percentage: allocation.current_percentage,
-                target_percentage: allocation.target_percentage,
-                action,
-                amount_usd,
-            });
-        }
+        // Volatile assets get less confident recommendations
+        let price_feed = crate::price_feed::load_price_feed();
+        vault.score_recommendations(|asset_id| crate::rebalance::compute_confidence(asset_id, &price_feed));
+        let proposal = vault.pending_proposal.clone();
         
-        // Store recommendations
-        vault.last_recommendations = recommendations.clone();
-        vault.last_rebalance = l1x_sdk::env::block_timestamp();
+        state.save();
         
-        // Update allocation current percentages to match target
-        // (assumes user will follow recommendations)
-        for allocation in &mut vault.allocations.allocations {
-            allocation.update_current_percentage(allocation.target_percentage);
+        serde_json::to_string(&proposal)
+            .unwrap_or_else(|_| "Failed to serialize proposal".to_string())
+    }
+    
+    /// Confirms a rebalance proposal signed by the owner's wallet
+    ///
+    /// `user_signature` is the hex-encoded signature of `proposal_message`
+    /// by the wallet registered for the vault owner.
+    pub fn confirm_rebalance(vault_id: String, proposal_id: String, user_signature: String) -> String {
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        let message = proposal_message(&vault_id, &proposal_id);
+        if !crate::wallet::verify_wallet_signature(&vault.owner, &message, &user_signature) {
+            panic!("Invalid signature for proposal {}", proposal_id);
         }
         
+        vault.confirm_proposal(&proposal_id)
+            .unwrap_or_else(|err| panic!("Failed to confirm rebalance: {}", err));
+            
+        let owner = vault.owner.clone();
         state.save();
         
-        serde_json::to_string(&recommendations)
-            .unwrap_or_else(|_| "Failed to serialize recommendations".to_string())
+        crate::events::emit_rebalance_confirmed_event(&vault_id, &proposal_id, &owner);
+        
+        format!("Rebalance proposal {} confirmed for vault {}", proposal_id, vault_id)
+    }
+    
+    /// Gets the rebalance proposal awaiting confirmation, if any
+    pub fn get_rebalance_proposal(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let vault = state.vaults.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        serde_json::to_string(&vault.pending_proposal)
+            .unwrap_or_else(|_| "Failed to serialize proposal".to_string())
     }
     
-    /// Gets previous rebalancing recommendations
+    /// Gets previous rebalancing recommendations that have not expired yet
     pub fn get_rebalance_recommendations(vault_id: String) -> String {
         let state = Self::load();
         
         let vault = state.vaults.get(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        serde_json::to_string(&vault.last_recommendations)
+        let response = vault.fresh_recommendations(l1x_sdk::env::block_timestamp());
+        
+        serde_json::to_string(&response)
             .unwrap_or_else(|_| "Failed to serialize recommendations".to_string())
     }
     
+    /// Sets how long a vault's rebalance recommendations stay valid
+    pub fn set_recommendation_ttl_seconds(vault_id: String, ttl: u64) -> String {
+        if ttl == 0 {
+            panic!("Recommendation TTL must be positive");
+        }
+        
+        let mut state = Self::load();
+        
+        let vault = state.vaults.get_mut(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+            
+        vault.recommendation_ttl_seconds = ttl;
+        
+        state.save();
+        
+        format!("Recommendation TTL for vault {} set to {} seconds", vault_id, ttl)
+    }
+    
     /// Execute authorized rebalance for a non-custodial vault
     pub fn execute_rebalance(vault_id: String, plan_id: String) -> String {
         let mut state = Self::load();
@@ -585,7 +999,7 @@ impl NonCustodialVaultContract {
         state.save();
         
         // Emit completed event
-        crate::events::emit_rebalance_completed_event(&vault_id, 1, Some(2_500_000));
+        crate::events::emit_rebalance_completed_event(&vault_id, 1, Some(2_500_000), false);
         
         format!("Rebalance executed for vault {}", vault_id)
     }
@@ -616,40 +1030,15 @@ impl NonCustodialVaultContract {
     }
     
     /// Checks if take profit should be executed
+    ///
+    /// Read-only: a caller-supplied value never moves the strategy's peak.
     pub fn should_take_profit(vault_id: String, current_value: u128) -> bool {
         let state = Self::load();
         
         let vault = state.vaults.get(&vault_id)
             .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
             
-        if vault.status != VaultStatus::Active || vault.take_profit.is_none() {
-            return false;
-        }
-        
-        let strategy = vault.take_profit.as_ref().unwrap();
-        
-        match &strategy.strategy_type {
-            TakeProfitType::Manual => false, // Manual requires explicit trigger
-            
-            TakeProfitType::Percentage { percentage } => {
-                let baseline = strategy.baseline_value;
-                if baseline == 0 || current_value <= baseline {
-                    return false;
-                }
-                
-                let gain = current_value - baseline;
-                let gain_percentage = (gain * 10000) / baseline;
-                
-                gain_percentage >= (*percentage as u128)
-            },
-            
-            TakeProfitType::Time { interval_seconds } => {
-                let now = l1x_sdk::env::block_timestamp();
-                let elapsed = now.saturating_sub(strategy.last_execution);
-                
-                elapsed >= *interval_seconds
-            },
-        }
+        vault.should_take_profit(current_value)
     }
     
     /// Gets take profit recommendation
@@ -663,25 +1052,18 @@ impl NonCustodialVaultContract {
             return "No take profit strategy configured or vault not active".to_string();
         }
         
-        let should_take_profit = Self::should_take_profit(vault_id.clone(), current_value);
-        
-        if !should_take_profit {
+        if !vault.should_take_profit(current_value) {
             return "Take profit conditions not met".to_string();
         }
         
         let strategy = vault.take_profit.as_mut().unwrap();
         
-        // Calculate profit amount
-        let baseline = strategy.baseline_value;
-        let profit_amount = if current_value > baseline {
-            current_value - baseline
-        } else {
-            0 // No profit
-        };
+        // Calculate profit amount (only the configured portion of the gain)
+        let profit_amount = strategy.realize_gain(current_value);
         
         // Update strategy execution
         strategy.record_execution();
-        strategy.set_baseline(current_value);
+        strategy.advance_baseline(current_value, profit_amount);
         
         state.save();
         
@@ -702,25 +1084,166 @@ impl NonCustodialVault {
             created_at: l1x_sdk::env::block_timestamp(),
             last_rebalance: 0,
             last_recommendations: Vec::new(),
+            pending_proposal: None,
+            archived_proposals: Vec::new(),
+            recommendation_ttl_seconds: DEFAULT_RECOMMENDATION_TTL_SECONDS,
+            drift_history: Vec::new(),
         }
     }
     
+    /// Vault `id` with the allocations and take profit strategy of a migrated custodial vault
+    pub fn from_migration(payload: &MigrationPayload, id: String, now: u64) -> Self {
+        let mut vault = Self::new(id, payload.owner.clone(), payload.allocations.drift_threshold_bp);
+        vault.allocations = payload.allocations.clone();
+        vault.take_profit = payload.take_profit.clone();
+        vault.estimated_value = payload.total_value;
+        vault.created_at = now;
+        vault
+    }
+    
     /// Updates the estimated value
     pub fn update_estimated_value(&mut self, value: u128) {
         self.estimated_value = value;
     }
     
-    /// Checks if rebalancing is needed
-    pub fn needs_rebalancing(&self) -> bool {
+    /// Checks if take profit should be executed at `current_value`
+    pub fn should_take_profit(&self, current_value: u128) -> bool {
         if self.status != VaultStatus::Active {
             return false;
         }
         
+        match &self.take_profit {
+            Some(strategy) => strategy.should_execute(current_value),
+            None => false,
+        }
+    }
+    
+    /// Changes the vault status, returning the event to emit if it actually changed
+    pub fn transition_status(&mut self, new_status: VaultStatus, changed_by: &str) -> Option<crate::events::VaultStatusChangedEvent> {
+        let old_status = self.status;
+        
+        if old_status == new_status {
+            return None;
+        }
+        
+        self.status = new_status;
+        
+        Some(crate::events::VaultStatusChangedEvent::new(
+            self.id.clone(),
+            Some(old_status),
+            new_status,
+            changed_by.to_string(),
+        ))
+    }
+    
+    /// Checks if rebalancing is needed, recording the current drift
+    pub fn needs_rebalancing(&mut self) -> bool {
+        self.needs_rebalancing_at(l1x_sdk::env::block_timestamp())
+    }
+    
+    /// Checks if rebalancing is needed at `now`, recording the drift at that time
+    pub fn needs_rebalancing_at(&mut self, now: u64) -> bool {
+        if self.status != VaultStatus::Active {
+            return false;
+        }
+        
+        self.record_drift(now);
         self.allocations.needs_rebalancing()
     }
     
+    /// Appends a snapshot of the current drift, dropping the oldest beyond `MAX_DRIFT_HISTORY`
+    pub fn record_drift(&mut self, now: u64) {
+        let per_asset_drift: Vec<(String, u32)> = self.allocations.allocations.iter()
+            .map(|allocation| (allocation.asset_id.clone(), allocation.drift()))
+            .collect();
+        let max_drift = per_asset_drift.iter().map(|(_, drift)| *drift).max().unwrap_or(0);
+        
+        self.drift_history.push(DriftSnapshot {
+            timestamp: now,
+            per_asset_drift,
+            max_drift,
+        });
+        
+        if self.drift_history.len() > MAX_DRIFT_HISTORY {
+            let excess = self.drift_history.len() - MAX_DRIFT_HISTORY;
+            self.drift_history.drain(..excess);
+        }
+    }
+    
     /// Generates rebalancing recommendations
     pub fn generate_rebalance_recommendations(&mut self) -> Vec<RebalanceRecommendation> {
+        let now = l1x_sdk::env::block_timestamp();
+        self.record_drift(now);
+        let recommendations = self.build_recommendations(now);
+        
+        self.last_recommendations = recommendations.clone();
+        self.last_rebalance = l1x_sdk::env::block_timestamp();
+        
+        recommendations
+    }
+    
+    /// Stores the current recommendations as a proposal awaiting confirmation
+    pub fn propose_rebalance(&mut self, proposal_id: String) -> RebalanceProposal {
+        let recommendations = self.build_recommendations(l1x_sdk::env::block_timestamp());
+        self.last_recommendations = recommendations.clone();
+        
+        let proposal = RebalanceProposal {
+            proposal_id,
+            recommendations,
+            status: ProposalStatus::Proposed,
+            created_at: l1x_sdk::env::block_timestamp(),
+        };
+        self.pending_proposal = Some(proposal.clone());
+        
+        proposal
+    }
+    
+    /// Confirms the pending proposal: allocations are marked as rebalanced
+    /// and the proposal is archived
+    pub fn confirm_proposal(&mut self, proposal_id: &str) -> Result<RebalanceProposal, &'static str> {
+        match &self.pending_proposal {
+            Some(proposal) if proposal.proposal_id == proposal_id => {}
+            Some(_) => return Err("Proposal ID does not match the pending proposal"),
+            None => return Err("No rebalance proposal pending"),
+        }
+        
+        let mut proposal = self.pending_proposal.take().unwrap();
+        proposal.status = ProposalStatus::Confirmed;
+        
+        // The owner has agreed to follow the recommendations
+        for allocation in &mut self.allocations.allocations {
+            allocation.update_current_percentage(allocation.target_percentage);
+        }
+        self.last_rebalance = l1x_sdk::env::block_timestamp();
+        self.archived_proposals.push(proposal.clone());
+        
+        Ok(proposal)
+    }
+    
+    /// Scores the last recommendations, and those of the pending proposal, by asset
+    pub fn score_recommendations(&mut self, confidence: impl Fn(&str) -> u8) {
+        let pending = self.pending_proposal.iter_mut()
+            .flat_map(|proposal| proposal.recommendations.iter_mut());
+            
+        for recommendation in self.last_recommendations.iter_mut().chain(pending) {
+            recommendation.confidence_score = confidence(&recommendation.asset_id);
+        }
+    }
+    
+    /// Last recommendations still valid at `now`, with the number that expired
+    pub fn fresh_recommendations(&self, now: u64) -> RecommendationsResponse {
+        let (recommendations, stale): (Vec<_>, Vec<_>) = self.last_recommendations.iter()
+            .cloned()
+            .partition(|recommendation| now < recommendation.expires_at);
+            
+        RecommendationsResponse {
+            recommendations,
+            stale_count: stale.len() as u32,
+        }
+    }
+    
+    /// Recommended trades to bring each allocation back to its target, made at `now`
+    fn build_recommendations(&self, now: u64) -> Vec<RebalanceRecommendation> {
         let mut recommendations = Vec::new();
         
         if self.status != VaultStatus::Active || self.estimated_value == 0 {
@@ -753,16 +1276,21 @@ impl NonCustodialVault {
                 target_percentage: allocation.target_percentage,
                 action,
                 amount_usd,
+                recommended_at: now,
+                expires_at: now.saturating_add(self.recommendation_ttl_seconds),
+                confidence_score: crate::rebalance::NEUTRAL_CONFIDENCE,
             });
         }
         
-        self.last_recommendations = recommendations.clone();
-        self.last_rebalance = l1x_sdk::env::block_timestamp();
-        
         recommendations
     }
 }
 
+/// Message the owner's wallet signs to confirm a rebalance proposal
+pub fn proposal_message(vault_id: &str, proposal_id: &str) -> Vec<u8> {
+    format!("confirm-rebalance:{}:{}", vault_id, proposal_id).into_bytes()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -819,4 +1347,170 @@ mod tests {
         assert_eq!(eth_rec.action, RebalanceAction::Buy);
         assert_eq!(eth_rec.amount_usd, 1000); // 40% - 30% = 10% of 10000 = 1000
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_user_vaults_pagination() {
+        let mut state = NonCustodialVaultContract {
+            vaults: std::collections::HashMap::new(),
+            user_vaults: std::collections::HashMap::new(),
+        };
+        
+        for i in 0..5 {
+            let vault_id = format!("vault-{}", i);
+            state.vaults.insert(vault_id.clone(), NonCustodialVault::new(vault_id.clone(), "owner-1".to_string(), 300));
+            state.user_vaults.entry("owner-1".to_string()).or_insert_with(Vec::new).push(vault_id);
+        }
+        
+        let first = state.user_vaults_page("owner-1", 0, 3);
+        assert_eq!(first.total, 5);
+        assert_eq!(first.vaults.len(), 3);
+        assert_eq!(first.vaults[0].id, "vault-0");
+        assert_eq!(first.vaults[2].id, "vault-2");
+        
+        let second = state.user_vaults_page("owner-1", 3, 3);
+        assert_eq!(second.total, 5);
+        assert_eq!(second.offset, 3);
+        assert_eq!(second.vaults.len(), 2);
+        assert_eq!(second.vaults[0].id, "vault-3");
+        assert_eq!(second.vaults[1].id, "vault-4");
+        
+        // Past the end yields an empty page
+        assert!(state.user_vaults_page("owner-1", 5, 3).vaults.is_empty());
+    }
+    
+    #[test]
+    fn test_proposal_leaves_allocations_until_confirmed() {
+        let mut vault = NonCustodialVault::new(
+            "vault-1".to_string(),
+            "owner-1".to_string(),
+            300,
+        );
+        
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(7000);
+        vault.allocations.allocations[1].update_current_percentage(3000);
+        vault.update_estimated_value(10000);
+        
+        let proposal = vault.propose_rebalance("proposal-1".to_string());
+        assert_eq!(proposal.status, ProposalStatus::Proposed);
+        assert_eq!(proposal.recommendations.len(), 2);
+        
+        // Without confirmation nothing is marked as rebalanced
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 7000);
+        assert_eq!(vault.allocations.allocations[1].current_percentage, 3000);
+        assert_eq!(vault.last_rebalance, 0);
+        
+        assert!(vault.confirm_proposal("proposal-2").is_err());
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 7000);
+        
+        let confirmed = vault.confirm_proposal("proposal-1").unwrap();
+        assert_eq!(confirmed.status, ProposalStatus::Confirmed);
+        assert_eq!(vault.allocations.allocations[0].current_percentage, 6000);
+        assert_eq!(vault.allocations.allocations[1].current_percentage, 4000);
+        assert!(vault.pending_proposal.is_none());
+        assert_eq!(vault.archived_proposals.len(), 1);
+        
+        // A confirmed proposal cannot be confirmed again
+        assert!(vault.confirm_proposal("proposal-1").is_err());
+    }
+    
+    #[test]
+    fn test_recommendations_expire_after_ttl() {
+        let mut vault = NonCustodialVault::new(
+            "vault-1".to_string(),
+            "owner-1".to_string(),
+            300,
+        );
+        
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.update_estimated_value(10000);
+        vault.recommendation_ttl_seconds = 60;
+        
+        let recommendations = vault.generate_rebalance_recommendations();
+        let recommended_at = recommendations[0].recommended_at;
+        assert_eq!(recommendations[0].expires_at, recommended_at + 60);
+        
+        let fresh = vault.fresh_recommendations(recommended_at + 59);
+        assert_eq!(fresh.recommendations.len(), 2);
+        assert_eq!(fresh.stale_count, 0);
+        
+        let later = vault.fresh_recommendations(recommended_at + 61);
+        assert!(later.recommendations.is_empty());
+        assert_eq!(later.stale_count, 2);
+    }
+    
+    #[test]
+    fn test_drift_history_records_each_check() {
+        let mut vault = NonCustodialVault::new(
+            "vault-1".to_string(),
+            "owner-1".to_string(),
+            300,
+        );
+        
+        vault.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        vault.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        vault.allocations.allocations[0].update_current_percentage(6500);
+        vault.allocations.allocations[1].update_current_percentage(3500);
+        
+        for i in 0..5 {
+            vault.needs_rebalancing_at(1000 + i * 60);
+        }
+        
+        assert_eq!(vault.drift_history.len(), 5);
+        assert!(vault.drift_history.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+        assert_eq!(vault.drift_history[0].max_drift, 500);
+        assert_eq!(vault.drift_history[0].per_asset_drift, vec![("BTC".to_string(), 500), ("ETH".to_string(), 500)]);
+        
+        // The history is capped, dropping the oldest snapshots
+        for i in 5..40 {
+            vault.needs_rebalancing_at(1000 + i * 60);
+        }
+        assert_eq!(vault.drift_history.len(), MAX_DRIFT_HISTORY);
+        assert_eq!(vault.drift_history[0].timestamp, 1000 + 10 * 60);
+    }
+    
+    #[test]
+    fn test_migration_round_trip() {
+        use crate::custodial_vault::CustodialVault;
+        
+        let mut custodial = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        custodial.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        custodial.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        custodial.deposit(1000).unwrap();
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        strategy.set_baseline(1000);
+        custodial.take_profit = Some(strategy);
+        
+        let payload = custodial.begin_migration(500).unwrap();
+        assert_eq!(custodial.status, VaultStatus::Migrating);
+        assert_eq!(payload.signature, payload.digest());
+        assert!(custodial.begin_migration(600).is_err());
+        
+        // The payload survives the trip through JSON
+        let json = serde_json::to_string(&payload).unwrap();
+        let received: MigrationPayload = serde_json::from_str(&json).unwrap();
+        
+        // A tampered payload is rejected and leaves the vault migrating
+        let mut tampered = received.clone();
+        tampered.allocations.allocations[0].target_percentage = 10000;
+        assert!(custodial.finish_migration(&tampered).is_err());
+        assert_eq!(custodial.status, VaultStatus::Migrating);
+        
+        custodial.finish_migration(&received).unwrap();
+        assert_eq!(custodial.status, VaultStatus::Closed);
+        assert!(custodial.finish_migration(&received).is_err());
+        
+        let vault = NonCustodialVault::from_migration(&received, "nc-vault-1".to_string(), 700);
+        assert_eq!(vault.id, "nc-vault-1");
+        assert_eq!(vault.owner, "owner-1");
+        assert_eq!(vault.status, VaultStatus::Active);
+        assert_eq!(vault.estimated_value, 1000);
+        assert_eq!(vault.created_at, 700);
+        assert_eq!(vault.allocations.drift_threshold_bp, 300);
+        assert_eq!(vault.allocations.allocations.len(), 2);
+        assert_eq!(vault.allocations.get_allocation("BTC").unwrap().target_percentage, 6000);
+        assert_eq!(vault.take_profit.as_ref().unwrap().baseline_value, 1000);
+    }
+}
@@ -0,0 +1,75 @@
+//! Portfolio performance metrics for One Capital Auto-Investing
+//!
+//! This module derives risk-adjusted performance metrics such as the Sharpe
+//! ratio from the snapshot history kept in `snapshot_store`.
+
+use super::{snapshot_store, Portfolio, PortfolioSnapshot};
+
+/// Computes a vault's Sharpe ratio over returns sampled every `period_seconds`
+pub fn compute_vault_sharpe_ratio(vault_id: String, period_seconds: u64, risk_free_bps: i64) -> f64 {
+    let history = snapshot_store::vault_snapshots(&vault_id);
+    if history.is_empty() {
+        panic!("No snapshots recorded for vault {}", vault_id);
+    }
+
+    let returns = period_returns(&history, period_seconds);
+
+    Portfolio::sharpe_ratio(&returns, risk_free_bps)
+}
+
+/// Samples snapshots at least `period_seconds` apart and returns the gain
+/// between consecutive samples in basis points
+pub fn period_returns(snapshots: &[PortfolioSnapshot], period_seconds: u64) -> Vec<i64> {
+    let mut sorted: Vec<&PortfolioSnapshot> = snapshots.iter().collect();
+    sorted.sort_by_key(|s| s.timestamp);
+
+    let mut returns = Vec::new();
+    let mut previous: Option<&PortfolioSnapshot> = None;
+
+    for snapshot in sorted {
+        match previous {
+            None => previous = Some(snapshot),
+            Some(prev) if snapshot.timestamp >= prev.timestamp.saturating_add(period_seconds) => {
+                if prev.total_value > 0 {
+                    returns.push(Portfolio::calculate_gain_percentage_since(snapshot, prev) as i64);
+                }
+                previous = Some(snapshot);
+            },
+            Some(_) => {},
+        }
+    }
+
+    returns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(timestamp: u64, total_value: u128) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            timestamp,
+            total_value,
+            asset_values: Vec::new(),
+            asset_allocations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_period_returns() {
+        let day = 86_400;
+        let snapshots = vec![
+            snapshot(0, 10_000),
+            snapshot(day / 2, 10_500), // Intra-period, skipped
+            snapshot(day, 10_100),
+            snapshot(2 * day, 10_302),
+            snapshot(3 * day, 10_250),
+        ];
+
+        let returns = period_returns(&snapshots, day);
+        assert_eq!(returns, vec![100, 200, -50]);
+
+        let ratio = Portfolio::sharpe_ratio(&returns, 0);
+        assert!(ratio > 0.0);
+    }
+}
@@ -3,8 +3,13 @@
 //! This module provides higher-level portfolio management functions that
 //! integrate allocation, rebalancing, and take-profit strategies.
 
+pub mod metrics;
+pub mod snapshot_store;
+
 use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
+use std::collections::HashMap;
 
 use crate::allocation::{AllocationSet, AssetAllocation};
 use crate::custodial_vault::CustodialVault;
@@ -12,7 +17,7 @@ use crate::non_custodial_vault::NonCustodialVault;
 use crate::take_profit::{TakeProfitStrategy, TakeProfitType};
 
 /// Represents a portfolio performance snapshot
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct PortfolioSnapshot {
     /// Timestamp when the snapshot was taken
     pub timestamp: u64,
@@ -27,6 +32,65 @@ pub struct PortfolioSnapshot {
     pub asset_allocations: Vec<(String, u32)>,
 }
 
+/// Combined view of several vaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregatedPortfolioView {
+    /// Total value across the vaults
+    pub total_value: u128,
+    
+    /// Value held in each asset across the vaults
+    pub asset_values: HashMap<String, u128>,
+    
+    /// Share of the total held in each asset (in basis points)
+    pub weighted_allocations: HashMap<String, u32>,
+    
+    /// Number of vaults aggregated
+    pub vault_count: u32,
+}
+
+/// One asset's share of a portfolio's return
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetAttribution {
+    /// Asset identifier
+    pub asset_id: String,
+    
+    /// Contribution to the total return (in basis points of the starting value)
+    pub contribution_bps: i32,
+    
+    /// Change in the asset's value over the window
+    pub value_change: i128,
+}
+
+/// Fixed-point scale of the weights and returns used in performance attribution
+const ATTRIBUTION_SCALE: i128 = 100_000_000;
+
+/// Health score points for allocations on target, lost linearly up to `HEALTH_MAX_DRIFT_BP`
+pub const HEALTH_DRIFT_WEIGHT: u32 = 40;
+
+/// Worst-asset drift (in basis points) at which the drift component reaches zero
+pub const HEALTH_MAX_DRIFT_BP: u32 = 2000;
+
+/// Health score points for diversification, earned up to `HEALTH_TARGET_HOLDINGS` effective holdings
+pub const HEALTH_DIVERSITY_WEIGHT: u32 = 20;
+
+/// Effective number of holdings (inverse Herfindahl index) that earns the full diversity score
+pub const HEALTH_TARGET_HOLDINGS: u32 = 5;
+
+/// Health score points for drawdown, lost linearly up to `HEALTH_MAX_DRAWDOWN_PCT`
+pub const HEALTH_DRAWDOWN_WEIGHT: u32 = 25;
+
+/// Maximum drawdown (in percent) at which the drawdown component reaches zero
+pub const HEALTH_MAX_DRAWDOWN_PCT: f64 = 50.0;
+
+/// Health score points for a recent rebalance
+pub const HEALTH_RECENCY_WEIGHT: u32 = 15;
+
+/// Rebalances within this many seconds earn the full recency score
+pub const HEALTH_RECENT_SECONDS: u64 = 30 * 86400;
+
+/// Rebalances older than this many seconds (or none at all) earn no recency score
+pub const HEALTH_STALE_SECONDS: u64 = 90 * 86400;
+
 /// Portfolio management functions
 pub struct Portfolio;
 
@@ -80,6 +144,114 @@ impl Portfolio {
         ((gain as f64) / (previous.total_value as f64) * 10000.0) as i32
     }
     
+    /// Calculates the Sharpe ratio of a series of period returns (in basis points)
+    ///
+    /// Uses the sample standard deviation; returns 0 when there are fewer than
+    /// two returns or the returns have no variance.
+    pub fn sharpe_ratio(returns: &[i64], risk_free_rate_bps: i64) -> f64 {
+        if returns.len() < 2 {
+            return 0.0;
+        }
+        
+        let n = returns.len() as f64;
+        let mean = returns.iter().map(|r| *r as f64).sum::<f64>() / n;
+        
+        let variance = returns.iter()
+            .map(|r| {
+                let deviation = *r as f64 - mean;
+                deviation * deviation
+            })
+            .sum::<f64>() / (n - 1.0);
+            
+        let std_dev = variance.sqrt();
+        
+        if std_dev == 0.0 {
+            return 0.0;
+        }
+        
+        (mean - risk_free_rate_bps as f64) / std_dev
+    }
+    
+    /// Calculates the largest peak-to-trough drop in total value, as a percentage
+    ///
+    /// Snapshots are walked in timestamp order; returns 0 for a series that
+    /// never falls below its running peak.
+    pub fn max_drawdown(snapshots: &[PortfolioSnapshot]) -> f64 {
+        let mut sorted: Vec<&PortfolioSnapshot> = snapshots.iter().collect();
+        sorted.sort_by_key(|s| s.timestamp);
+        
+        let mut peak: u128 = 0;
+        let mut max_drawdown = 0.0;
+        
+        for snapshot in sorted {
+            if snapshot.total_value > peak {
+                peak = snapshot.total_value;
+                continue;
+            }
+            
+            if peak > 0 {
+                let drawdown = (peak - snapshot.total_value) as f64 / peak as f64 * 100.0;
+                if drawdown > max_drawdown {
+                    max_drawdown = drawdown;
+                }
+            }
+        }
+        
+        max_drawdown
+    }
+    
+    /// Calculates a 0-100 composite health score for a portfolio
+    ///
+    /// Weighted by the `HEALTH_*` constants: drift (40), diversification (20),
+    /// max drawdown (25) and rebalance recency (15).
+    pub fn health_score(allocations: &AllocationSet, snapshots: &[PortfolioSnapshot]) -> u32 {
+        Self::health_score_at(allocations, snapshots, l1x_sdk::env::block_timestamp())
+    }
+    
+    /// Calculates the health score as of `now`
+    pub fn health_score_at(allocations: &AllocationSet, snapshots: &[PortfolioSnapshot], now: u64) -> u32 {
+        // Drift: the worst-drifted asset drives the penalty
+        let max_drift = allocations.allocations.iter()
+            .map(|a| a.drift())
+            .max()
+            .unwrap_or(0)
+            .min(HEALTH_MAX_DRIFT_BP);
+        let drift_score = HEALTH_DRIFT_WEIGHT * (HEALTH_MAX_DRIFT_BP - max_drift) / HEALTH_MAX_DRIFT_BP;
+        
+        // Diversification: effective number of holdings from current weights
+        let concentration: u64 = allocations.allocations.iter()
+            .map(|a| (a.current_percentage as u64) * (a.current_percentage as u64))
+            .sum();
+        let diversity_score = if concentration == 0 {
+            0
+        } else {
+            let effective_holdings = 100_000_000.0 / concentration as f64;
+            let ratio = (effective_holdings / HEALTH_TARGET_HOLDINGS as f64).min(1.0);
+            (HEALTH_DIVERSITY_WEIGHT as f64 * ratio) as u32
+        };
+        
+        // Drawdown: deeper peak-to-trough drops cost more
+        let drawdown = Self::max_drawdown(snapshots).min(HEALTH_MAX_DRAWDOWN_PCT);
+        let drawdown_score = (HEALTH_DRAWDOWN_WEIGHT as f64 * (1.0 - drawdown / HEALTH_MAX_DRAWDOWN_PCT)) as u32;
+        
+        // Recency: full credit for a recent rebalance, fading to zero once stale
+        let recency_score = if allocations.last_rebalance == 0 {
+            0
+        } else {
+            let age = now.saturating_sub(allocations.last_rebalance);
+            if age <= HEALTH_RECENT_SECONDS {
+                HEALTH_RECENCY_WEIGHT
+            } else if age >= HEALTH_STALE_SECONDS {
+                0
+            } else {
+                let remaining = HEALTH_STALE_SECONDS - age;
+                (HEALTH_RECENCY_WEIGHT as u64 * remaining / (HEALTH_STALE_SECONDS - HEALTH_RECENT_SECONDS)) as u32
+            }
+        };
+        
+        (drift_score + diversity_score + drawdown_score + recency_score).min(100)
+    }
+    
     /// Checks if portfolio needs rebalancing based on allocation drift
     pub fn needs_rebalancing(
         current_values: &[(String, u128)],
@@ -116,12 +288,120 @@ impl Portfolio {
         false
     }
     
+    /// Aggregates the vaults listed in `vault_ids` into one portfolio view
+    ///
+    /// Each vault's value is split by its current percentages. Vaults not
+    /// listed in `vault_ids` are skipped and duplicates are counted once.
+    pub fn aggregate_vaults(vault_ids: Vec<String>, vaults: &[&CustodialVault]) -> AggregatedPortfolioView {
+        let mut view = AggregatedPortfolioView::default();
+        let mut seen = Vec::new();
+        
+        for vault in vaults {
+            if !vault_ids.contains(&vault.id) || seen.contains(&vault.id) {
+                continue;
+            }
+            seen.push(vault.id.clone());
+            
+            view.vault_count += 1;
+            view.total_value += vault.total_value;
+            
+            for allocation in &vault.allocations.allocations {
+                let value = vault.total_value * (allocation.current_percentage as u128) / 10000;
+                *view.asset_values.entry(allocation.asset_id.clone()).or_insert(0) += value;
+            }
+        }
+        
+        if view.total_value > 0 {
+            view.weighted_allocations = view.asset_values.iter()
+                .map(|(asset, value)| (asset.clone(), (value * 10000 / view.total_value) as u32))
+                .collect();
+        }
+        
+        view
+    }
+    
+    /// Attributes the return between the first and last snapshot to each asset
+    ///
+    /// Over each period between consecutive snapshots an asset contributes its
+    /// weight at the start of the period times its return over the period.
+    /// Holdings are assumed unchanged between snapshots, so an asset's value
+    /// return is its price return. Each period's contribution is scaled by the
+    /// portfolio's growth up to that period so that it is expressed against the
+    /// starting value, and the contributions add up to the total return. An
+    /// asset absent at the start of a period has no weight in it and
+    /// contributes nothing. Results are sorted by asset.
+    pub fn compute_attribution(snapshots: &[PortfolioSnapshot]) -> Vec<AssetAttribution> {
+        let mut sorted: Vec<&PortfolioSnapshot> = snapshots.iter().collect();
+        sorted.sort_by_key(|snapshot| snapshot.timestamp);
+        
+        let starting_total = match sorted.first() {
+            Some(first) if first.total_value > 0 => first.total_value as i128,
+            _ => return Vec::new(),
+        };
+        
+        let value_of = |snapshot: &PortfolioSnapshot, asset_id: &str| -> i128 {
+            snapshot.asset_values.iter()
+                .find(|(id, _)| id == asset_id)
+                .map(|(_, value)| *value as i128)
+                .unwrap_or(0)
+        };
+        
+        // Asset ID -> (contribution scaled by ATTRIBUTION_SCALE, value change)
+        let mut totals: HashMap<String, (i128, i128)> = HashMap::new();
+        for snapshot in &sorted {
+            for (asset_id, _) in &snapshot.asset_values {
+                totals.entry(asset_id.clone()).or_insert((0, 0));
+            }
+        }
+        
+        for pair in sorted.windows(2) {
+            let period_total = pair[0].total_value as i128;
+            if period_total == 0 {
+                continue;
+            }
+            let growth = period_total * ATTRIBUTION_SCALE / starting_total;
+            
+            for (asset_id, (contribution, value_change)) in totals.iter_mut() {
+                let start = value_of(pair[0], asset_id);
+                let end = value_of(pair[1], asset_id);
+                *value_change += end - start;
+                
+                if start == 0 {
+                    continue;
+                }
+                
+                let weight = start * ATTRIBUTION_SCALE / period_total;
+                let asset_return = (end - start) * ATTRIBUTION_SCALE / start;
+                *contribution += weight * asset_return / ATTRIBUTION_SCALE * growth / ATTRIBUTION_SCALE;
+            }
+        }
+        
+        let mut attribution: Vec<AssetAttribution> = totals.into_iter()
+            .map(|(asset_id, (contribution, value_change))| {
+                // Rounded to the nearest basis point
+                let scaled_bps = contribution * 10000;
+                AssetAttribution {
+                    asset_id,
+                    contribution_bps: ((scaled_bps + scaled_bps.signum() * ATTRIBUTION_SCALE / 2) / ATTRIBUTION_SCALE) as i32,
+                    value_change,
+                }
+            })
+            .collect();
+        attribution.sort_by(|a, b| a.asset_id.cmp(&b.asset_id));
+        
+        attribution
+    }
+    
     /// Checks if take profit conditions are met
     pub fn should_take_profit(
         strategy: &TakeProfitStrategy,
         current_snapshot: &PortfolioSnapshot,
         baseline_snapshot: &PortfolioSnapshot,
     ) -> bool {
+        if strategy.paused {
+            return false;
+        }
+        
         match strategy.strategy_type {
             TakeProfitType::Manual => false, // Manual requires explicit trigger
             
@@ -134,6 +414,10 @@ impl Portfolio {
                 let elapsed = current_snapshot.timestamp.saturating_sub(strategy.last_execution);
                 elapsed >= interval_seconds
             },
+            
+            TakeProfitType::TrailingStop { .. } | TakeProfitType::Ladder { .. } | TakeProfitType::Composite { .. } => {
+                strategy.should_execute(current_snapshot.total_value)
+            },
         }
     }
 }
@@ -239,6 +523,16 @@ mod tests {
             strategy_type: TakeProfitType::Percentage { percentage: 1000 }, // 10%
             last_execution: 0,
             baseline_value: 1000,
+            high_water_mark: 1000,
+            fired_tiers: Vec::new(),
+            ladder_realized: 0,
+            profit_portion_bp: 10000,
+            paused: false,
+            baseline_policy: crate::take_profit::BaselinePolicy::ResetToCurrent,
+            profit_destination: crate::take_profit::ProfitDestination::HoldInVault,
+            max_executions_per_period: None,
+            period_seconds: 0,
+            recent_executions: Vec::new(),
         };
         
         // Should take profit since gain (15%) exceeds threshold (10%)
@@ -249,6 +543,16 @@ mod tests {
             strategy_type: TakeProfitType::Percentage { percentage: 2000 }, // 20%
             last_execution: 0,
             baseline_value: 1000,
+            high_water_mark: 1000,
+            fired_tiers: Vec::new(),
+            ladder_realized: 0,
+            profit_portion_bp: 10000,
+            paused: false,
+            baseline_policy: crate::take_profit::BaselinePolicy::ResetToCurrent,
+            profit_destination: crate::take_profit::ProfitDestination::HoldInVault,
+            max_executions_per_period: None,
+            period_seconds: 0,
+            recent_executions: Vec::new(),
         };
         
         // Should not take profit since gain (15%) is below threshold (20%)
@@ -259,9 +563,179 @@ mod tests {
             strategy_type: TakeProfitType::Time { interval_seconds: 3600 }, // 1 hour
             last_execution: 1000, // Same as baseline timestamp
             baseline_value: 1000,
+            high_water_mark: 1000,
+            fired_tiers: Vec::new(),
+            ladder_realized: 0,
+            profit_portion_bp: 10000,
+            paused: false,
+            baseline_policy: crate::take_profit::BaselinePolicy::ResetToCurrent,
+            profit_destination: crate::take_profit::ProfitDestination::HoldInVault,
+            max_executions_per_period: None,
+            period_seconds: 0,
+            recent_executions: Vec::new(),
         };
         
         // Should not take profit since only 1000 seconds have passed (< 3600)
         assert!(!Portfolio::should_take_profit(&time_strategy, &current, &baseline));
     }
+    
+    #[test]
+    fn test_sharpe_ratio() {
+        let returns = [100, 200, -50, 150, 100];
+        
+        let ratio = Portfolio::sharpe_ratio(&returns, 20);
+        assert_eq!((ratio * 100.0).round() / 100.0, 0.86);
+        
+        // No variance or too few periods yields 0
+        assert_eq!(Portfolio::sharpe_ratio(&[50, 50, 50], 0), 0.0);
+        assert_eq!(Portfolio::sharpe_ratio(&[50], 0), 0.0);
+    }
+    
+    #[test]
+    fn test_max_drawdown() {
+        let series = |values: &[u128]| -> Vec<PortfolioSnapshot> {
+            values.iter().enumerate().map(|(i, value)| PortfolioSnapshot {
+                timestamp: i as u64,
+                total_value: *value,
+                asset_values: Vec::new(),
+                asset_allocations: Vec::new(),
+            }).collect()
+        };
+        
+        // Dips 10% (1000 -> 900) and later 25% (1200 -> 900)
+        let snapshots = series(&[1000, 900, 1100, 1200, 1000, 900, 1150]);
+        assert_eq!(Portfolio::max_drawdown(&snapshots), 25.0);
+        
+        // Monotonically increasing series has no drawdown
+        let snapshots = series(&[100, 200, 300, 400]);
+        assert_eq!(Portfolio::max_drawdown(&snapshots), 0.0);
+    }
+    
+    #[test]
+    fn test_health_score() {
+        let day = 86_400;
+        let now = 200 * day;
+        let series = |values: &[u128]| -> Vec<PortfolioSnapshot> {
+            values.iter().enumerate().map(|(i, value)| PortfolioSnapshot {
+                timestamp: i as u64 * day,
+                total_value: *value,
+                asset_values: Vec::new(),
+                asset_allocations: Vec::new(),
+            }).collect()
+        };
+        
+        // Balanced, diversified and rebalanced yesterday
+        let mut balanced = AllocationSet::new(300);
+        for asset in ["BTC", "ETH", "SOL", "USDC"] {
+            balanced.add_allocation(AssetAllocation::new(asset.to_string(), 2500)).unwrap();
+        }
+        balanced.last_rebalance = now - day;
+        
+        let score = Portfolio::health_score_at(&balanced, &series(&[1000, 1050, 1100, 1080]), now);
+        assert!(score >= 85, "balanced score was {}", score);
+        
+        // Paused: everything drifted into one asset, deep drawdown, no rebalance for months
+        let mut neglected = AllocationSet::new(300);
+        neglected.add_allocation(AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        neglected.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        neglected.allocations[0].update_current_percentage(10000);
+        neglected.allocations[1].update_current_percentage(0);
+        neglected.last_rebalance = now - 120 * day;
+        
+        let score = Portfolio::health_score_at(&neglected, &series(&[1000, 1200, 700, 750]), now);
+        assert!(score <= 30, "neglected score was {}", score);
+    }
+    
+    #[test]
+    fn test_aggregate_vaults() {
+        let mut first = CustodialVault::new("vault-1".to_string(), "owner-1".to_string(), 300);
+        first.allocations.add_allocation(AssetAllocation::new("BTC".to_string(), 6000)).unwrap();
+        first.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 4000)).unwrap();
+        first.allocations.allocations[0].update_current_percentage(6000);
+        first.allocations.allocations[1].update_current_percentage(4000);
+        first.total_value = 1000;
+        
+        let mut second = CustodialVault::new("vault-2".to_string(), "owner-1".to_string(), 300);
+        second.allocations.add_allocation(AssetAllocation::new("ETH".to_string(), 5000)).unwrap();
+        second.allocations.add_allocation(AssetAllocation::new("USDC".to_string(), 5000)).unwrap();
+        second.allocations.allocations[0].update_current_percentage(5000);
+        second.allocations.allocations[1].update_current_percentage(5000);
+        second.total_value = 3000;
+        
+        let view = Portfolio::aggregate_vaults(
+            vec!["vault-1".to_string(), "vault-2".to_string()],
+            &[&first, &second],
+        );
+        
+        assert_eq!(view.vault_count, 2);
+        assert_eq!(view.total_value, 4000);
+        assert_eq!(view.asset_values["BTC"], 600);
+        assert_eq!(view.asset_values["ETH"], 1900); // 400 + 1500
+        assert_eq!(view.asset_values["USDC"], 1500);
+        assert_eq!(view.weighted_allocations["BTC"], 1500);
+        assert_eq!(view.weighted_allocations["ETH"], 4750);
+        assert_eq!(view.weighted_allocations["USDC"], 3750);
+        
+        // Unlisted vaults are skipped
+        let view = Portfolio::aggregate_vaults(vec!["vault-2".to_string()], &[&first, &second]);
+        assert_eq!(view.vault_count, 1);
+        assert!(!view.asset_values.contains_key("BTC"));
+    }
+    
+    #[test]
+    fn test_compute_attribution() {
+        let snapshot = |timestamp: u64, values: [u128; 3]| PortfolioSnapshot {
+            timestamp,
+            total_value: values.iter().sum(),
+            asset_values: vec![
+                ("BTC".to_string(), values[0]),
+                ("ETH".to_string(), values[1]),
+                ("SOL".to_string(), values[2]),
+            ],
+            asset_allocations: Vec::new(),
+        };
+        
+        // 1000 -> 1200 (+20%), out of order to check sorting
+        let snapshots = vec![
+            snapshot(300, [620, 360, 220]),
+            snapshot(100, [500, 300, 200]),
+            snapshot(200, [560, 340, 180]),
+        ];
+        
+        // Period 1 (1000 -> 1080), weight x return:
+        //   BTC 50% x +12%     = +600 bps
+        //   ETH 30% x +13.33%  = +400 bps
+        //   SOL 20% x -10%     = -200 bps
+        // Period 2 (1080 -> 1200), weight x return x growth so far (1.08):
+        //   BTC 51.85% x +10.71% x 1.08 = +600 bps
+        //   ETH 31.48% x +5.88%  x 1.08 = +200 bps
+        //   SOL 16.67% x +22.22% x 1.08 = +400 bps
+        let attribution = Portfolio::compute_attribution(&snapshots);
+        
+        assert_eq!(attribution, vec![
+            AssetAttribution { asset_id: "BTC".to_string(), contribution_bps: 1200, value_change: 120 },
+            AssetAttribution { asset_id: "ETH".to_string(), contribution_bps: 600, value_change: 60 },
+            AssetAttribution { asset_id: "SOL".to_string(), contribution_bps: 200, value_change: 20 },
+        ]);
+        
+        let total: i32 = attribution.iter().map(|a| a.contribution_bps).sum();
+        assert_eq!(total, 2000);
+        
+        assert!(Portfolio::compute_attribution(&[]).is_empty());
+        
+        // An asset bought mid-window has no weight before it is held
+        let snapshots = vec![
+            snapshot(100, [800, 200, 0]),
+            snapshot(200, [600, 200, 300]),
+            snapshot(300, [600, 200, 330]),
+        ];
+        let attribution = Portfolio::compute_attribution(&snapshots);
+        
+        // Period 1: BTC 80% x -25% = -2000 bps, ETH 20% x 0% = 0, SOL no weight
+        // Period 2: SOL 27.27% x +10% x 1.1 = +300 bps
+        assert_eq!(attribution[0].contribution_bps, -2000);
+        assert_eq!(attribution[1].contribution_bps, 0);
+        assert_eq!(attribution[2].contribution_bps, 300);
+        assert_eq!(attribution[2].value_change, 330);
+    }
 }
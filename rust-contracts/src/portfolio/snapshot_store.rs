@@ -0,0 +1,166 @@
+//! Persistent portfolio snapshot storage for One Capital Auto-Investing
+//!
+//! Snapshots are kept per vault in timestamp order so historical ranges can be
+//! read without scanning a vault's whole history.
+
+use borsh::{BorshSerialize, BorshDeserialize};
+use std::collections::{BTreeMap, HashMap};
+use l1x_sdk::prelude::*;
+
+use super::PortfolioSnapshot;
+
+/// Maximum number of snapshots stored per vault; the oldest are evicted first
+pub const MAX_STORED_SNAPSHOTS_PER_VAULT: usize = 1000;
+
+/// Snapshot store contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"SNAPSHOT_STORE";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SnapshotStoreContract {
+    snapshots: HashMap<String, BTreeMap<u64, PortfolioSnapshot>>, // Vault ID -> Timestamp -> Snapshot
+}
+
+#[l1x_sdk::contract]
+impl SnapshotStoreContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            snapshots: HashMap::new(),
+        };
+
+        state.save()
+    }
+
+    /// Stores a snapshot under `(vault_id, snapshot.timestamp)`, replacing any
+    /// snapshot already taken at that timestamp
+    fn insert(&mut self, vault_id: &str, snapshot: PortfolioSnapshot) {
+        let history = self.snapshots.entry(vault_id.to_string()).or_insert_with(BTreeMap::new);
+        history.insert(snapshot.timestamp, snapshot);
+
+        while history.len() > MAX_STORED_SNAPSHOTS_PER_VAULT {
+            history.pop_first();
+        }
+    }
+
+    /// Snapshots with `from_ts <= timestamp <= to_ts`, oldest first, at most `limit`
+    fn snapshots_in_range(&self, vault_id: &str, from_ts: u64, to_ts: u64, limit: u32) -> Vec<PortfolioSnapshot> {
+        if from_ts > to_ts {
+            return Vec::new();
+        }
+
+        self.snapshots.get(vault_id)
+            .map(|history| {
+                history.range(from_ts..=to_ts)
+                    .take(limit as usize)
+                    .map(|(_, snapshot)| snapshot.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Records a performance snapshot for a vault
+    ///
+    /// Only the vault owner can record one; the vault contract stores its own
+    /// snapshots through `store_snapshot`.
+    pub fn record_snapshot(vault_id: String, snapshot_json: String) -> String {
+        let owner = crate::custodial_vault::vault_owner(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+        if owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can record snapshots");
+        }
+
+        let mut state = Self::load();
+
+        let snapshot: PortfolioSnapshot = serde_json::from_str(&snapshot_json)
+            .unwrap_or_else(|_| panic!("Failed to parse snapshot"));
+
+        state.insert(&vault_id, snapshot);
+        state.save();
+
+        format!("Snapshot recorded for vault {}", vault_id)
+    }
+
+    /// Gets a vault's snapshots taken between `from_ts` and `to_ts` (inclusive)
+    pub fn get_snapshots_in_range(vault_id: String, from_ts: u64, to_ts: u64, limit: u32) -> String {
+        let state = Self::load();
+
+        let snapshots = state.snapshots_in_range(&vault_id, from_ts, to_ts, limit);
+
+        serde_json::to_string(&snapshots)
+            .unwrap_or_else(|_| "Failed to serialize snapshots".to_string())
+    }
+}
+
+/// Persists a portfolio snapshot for a vault
+pub fn store_snapshot(vault_id: &str, snapshot: PortfolioSnapshot) {
+    let mut state = SnapshotStoreContract::load();
+
+    state.insert(vault_id, snapshot);
+    state.save();
+}
+
+/// Gets the stored snapshot history for a vault (oldest first)
+pub fn vault_snapshots(vault_id: &str) -> Vec<PortfolioSnapshot> {
+    let state = SnapshotStoreContract::load();
+
+    state.snapshots.get(vault_id)
+        .map(|history| history.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(timestamp: u64, total_value: u128) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            timestamp,
+            total_value,
+            asset_values: vec![("BTC".to_string(), total_value)],
+            asset_allocations: vec![("BTC".to_string(), 10000)],
+        }
+    }
+
+    #[test]
+    fn test_snapshots_in_range() {
+        let mut store = SnapshotStoreContract {
+            snapshots: HashMap::new(),
+        };
+
+        // Ten snapshots an hour apart, inserted out of order
+        for i in (0..10u64).rev() {
+            store.insert("vault-1", snapshot(i * 3600, 1000 + i as u128));
+        }
+        store.insert("vault-2", snapshot(7200, 5000));
+
+        let range = store.snapshots_in_range("vault-1", 3 * 3600, 6 * 3600, 10);
+        let timestamps: Vec<u64> = range.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![3 * 3600, 4 * 3600, 5 * 3600, 6 * 3600]);
+        assert_eq!(range[0].total_value, 1003);
+
+        // The limit keeps the oldest matches
+        let limited = store.snapshots_in_range("vault-1", 0, u64::MAX, 3);
+        let timestamps: Vec<u64> = limited.iter().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 3600, 7200]);
+
+        // Bounds between snapshots, inverted ranges and other vaults
+        assert_eq!(store.snapshots_in_range("vault-1", 3601, 7199, 10).len(), 0);
+        assert!(store.snapshots_in_range("vault-1", 10, 5, 10).is_empty());
+        assert_eq!(store.snapshots_in_range("vault-2", 0, u64::MAX, 10).len(), 1);
+
+        // Re-storing a timestamp replaces the snapshot
+        store.insert("vault-1", snapshot(0, 42));
+        assert_eq!(store.snapshots_in_range("vault-1", 0, 0, 10)[0].total_value, 42);
+        assert_eq!(store.snapshots["vault-1"].len(), 10);
+    }
+}
@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
+use crate::validation::{validate_address, validate_amount_nonzero, validate_non_empty_string};
+
 /// Price data for a single asset
 #[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct PriceData {
@@ -56,6 +58,127 @@ pub struct PriceHistoryRecord {
     pub timestamp: u64,
 }
 
+/// Price with its freshness, as returned by `get_price_checked`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckedPrice {
+    /// Asset symbol
+    pub symbol: String,
+    
+    /// Price in USD (scaled by 1e8)
+    pub price: u128,
+    
+    /// Last update timestamp
+    pub updated_at: u64,
+    
+    /// Seconds since the last update
+    pub age_seconds: u64,
+    
+    /// Whether the price is older than the asset's maximum age
+    pub stale: bool,
+}
+
+/// Price of one symbol in a `get_prices` response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceQuote {
+    /// Price in USD (scaled by 1e8)
+    pub price: u128,
+    
+    /// Last update timestamp
+    pub updated_at: u64,
+    
+    /// Whether the price is older than the asset's maximum age
+    pub stale: bool,
+}
+
+/// Prices of a list of symbols, as returned by `get_prices`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PricesResponse {
+    /// Price of each requested symbol that has one
+    pub prices: std::collections::BTreeMap<String, PriceQuote>,
+    
+    /// Requested symbols without price data
+    pub missing: Vec<String>,
+}
+
+/// Time-weighted average price of an asset, as returned by `get_twap`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TwapResult {
+    /// Asset symbol
+    pub symbol: String,
+    
+    /// Time-weighted average price in USD (scaled by 1e8, like `PriceData::price`)
+    pub twap: u128,
+    
+    /// Length of the averaging window
+    pub period_seconds: u64,
+    
+    /// Number of history records in the window
+    pub records_used: usize,
+}
+
+/// Price update held back for exceeding the deviation limit, awaiting a second authority
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct PendingPrice {
+    /// Quarantined price (scaled by 1e8)
+    pub price: u128,
+    
+    /// Authority that submitted it
+    pub provider: String,
+    
+    /// Timestamp of the submission
+    pub proposed_at: u64,
+}
+
+/// Outcome of screening a price update against the deviation limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceScreening {
+    /// Within the limit, or confirmed by a second authority
+    Accepted,
+    
+    /// Held in `pending_prices`
+    Quarantined {
+        previous_price: u128,
+        deviation_bp: u32,
+    },
+}
+
+/// Token details of an asset, used to convert between USD values and token units
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AssetMetadata {
+    /// Asset symbol, as used for its price
+    pub symbol: String,
+    
+    /// Number of decimal places of the token's smallest unit
+    pub decimals: u8,
+    
+    /// Human-readable name
+    pub display_name: String,
+    
+    /// Canonical token contract address by chain
+    pub addresses: std::collections::HashMap<String, String>,
+    
+    /// Whether the asset is still supported
+    pub active: bool,
+    
+    /// Timestamp of the registration
+    pub registered_at: u64,
+}
+
+/// Most decimals an asset may have (10^38 is the largest power of ten in a u128)
+pub const MAX_ASSET_DECIMALS: u8 = 38;
+
+/// Largest move from the stored price an update may make unless configured otherwise (20%)
+pub const DEFAULT_MAX_DEVIATION_BP: u32 = 2000;
+
+/// Maximum age of a price unless configured otherwise (1 hour)
+pub const DEFAULT_MAX_PRICE_AGE_SECONDS: u64 = 3600;
+
+/// Provider recorded on prices aggregated from the external oracles
+pub const AGGREGATED_PROVIDER: &str = "aggregated";
+
+/// How long an aggregated price takes precedence over single-provider updates
+pub const AGGREGATED_PRICE_TTL_SECONDS: u64 = 3600;
+
 /// Price feed contract storage
 const STORAGE_CONTRACT_KEY: &[u8] = b"PRICE_FEED";
 
@@ -75,6 +198,33 @@ pub struct PriceFeedContract {
     
     /// Admin address (can add/remove authorities)
     admin: String,
+    
+    /// Oracle contract addresses aggregated by `fetch_and_aggregate_price`
+    external_oracles: Vec<String>,
+    
+    /// Maximum age of a price before it is stale
+    max_age_seconds: u64,
+    
+    /// Per-asset maximum ages overriding `max_age_seconds`
+    asset_max_age_seconds: std::collections::HashMap<String, u64>,
+    
+    /// Emergency override letting stale prices through the checked reads
+    allow_stale_prices: bool,
+    
+    /// Largest move from the stored price an update may make (in basis points)
+    max_deviation_bp: u32,
+    
+    /// Per-asset deviation limits overriding `max_deviation_bp`
+    asset_max_deviation_bp: std::collections::HashMap<String, u32>,
+    
+    /// Updates quarantined for exceeding the deviation limit
+    pending_prices: std::collections::HashMap<String, PendingPrice>,
+    
+    /// Registered assets by symbol
+    assets: std::collections::HashMap<String, AssetMetadata>,
+    
+    /// Whether price updates are refused for symbols without an active registration
+    strict_asset_registry: bool,
 }
 
 #[l1x_sdk::contract]
@@ -91,21 +241,7 @@ impl PriceFeedContract {
     }
 
     pub fn new(admin: String) {
-        let mut state = Self {
-            prices: std::collections::HashMap::new(),
-            authorities: std::collections::HashMap::new(),
-            history: std::collections::HashMap::new(),
-            max_history_records: 24, // Keep 24 hours of hourly data by default
-            admin,
-        };
-        
-        // Add admin as the first authority
-        state.authorities.insert(admin.clone(), PriceFeedAuthority {
-            address: admin.clone(),
-            name: "Admin".to_string(),
-            active: true,
-            added_at: l1x_sdk::env::block_timestamp(),
-        });
+        let mut state = Self::with_admin(admin);
         
         state.save()
     }
@@ -135,6 +271,11 @@ impl PriceFeedContract {
     
     /// Adds a new price feed authority
     pub fn add_authority(address: String, name: String) -> String {
+        validate_address(&address)
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_non_empty_string(&name, "name")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
         if !Self::is_admin() {
             panic!("Only admin can add authorities");
         }
@@ -147,7 +288,7 @@ impl PriceFeedContract {
         
         let authority = PriceFeedAuthority {
             address: address.clone(),
-            name,
+            name: name.clone(),
             active: true,
             added_at: l1x_sdk::env::block_timestamp(),
         };
@@ -155,7 +296,16 @@ impl PriceFeedContract {
         state.authorities.insert(address.clone(), authority);
         state.save();
         
-        format!("Authority {} added", address)
+        let result = format!("Authority {} added", address);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "add_authority",
+            &serde_json::json!({ "address": address, "name": name }),
+            &result,
+        );
+        
+        result
     }
     
     /// Removes a price feed authority
@@ -177,7 +327,16 @@ impl PriceFeedContract {
         state.authorities.remove(&address);
         state.save();
         
-        format!("Authority {} removed", address)
+        let result = format!("Authority {} removed", address);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "remove_authority",
+            &serde_json::json!({ "address": address }),
+            &result,
+        );
+        
+        result
     }
     
     /// Disables a price feed authority
@@ -218,6 +377,63 @@ impl PriceFeedContract {
         format!("Authority {} enabled", address)
     }
     
+    /// Adds an oracle contract to the aggregated price sources
+    pub fn add_external_oracle(address: String) -> String {
+        validate_address(&address)
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        if !Self::is_admin() {
+            panic!("Only admin can add external oracles");
+        }
+        
+        let mut state = Self::load();
+        
+        if state.external_oracles.contains(&address) {
+            panic!("External oracle already exists");
+        }
+        
+        state.external_oracles.push(address.clone());
+        state.save();
+        
+        format!("External oracle {} added", address)
+    }
+    
+    /// Removes an oracle contract from the aggregated price sources
+    pub fn remove_external_oracle(address: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can remove external oracles");
+        }
+        
+        let mut state = Self::load();
+        
+        let index = state.external_oracles.iter()
+            .position(|oracle| *oracle == address)
+            .unwrap_or_else(|| panic!("External oracle not found: {}", address));
+            
+        state.external_oracles.remove(index);
+        state.save();
+        
+        format!("External oracle {} removed", address)
+    }
+    
+    /// Reads `symbol` from every external oracle and stores the median as its price
+    ///
+    /// The aggregated price takes precedence over single-provider updates for
+    /// `AGGREGATED_PRICE_TTL_SECONDS`.
+    pub fn fetch_and_aggregate_price(symbol: String) -> u128 {
+        validate_non_empty_string(&symbol, "symbol")
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        let mut state = Self::load();
+        let now = l1x_sdk::env::block_timestamp();
+        
+        let price = state.aggregate_price(&symbol, now, query_oracle_price)
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+        
+        price
+    }
+    
     /// Sets the maximum number of history records per asset
     pub fn set_max_history_records(max_records: usize) -> String {
         if !Self::is_admin() {
@@ -233,6 +449,11 @@ impl PriceFeedContract {
     
     /// Updates the price for a single asset
     pub fn update_price(symbol: String, price: u128, signature: Option<String>) -> String {
+        validate_non_empty_string(&symbol, "symbol")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_amount_nonzero(price, "price")
+            .unwrap_or_else(|err| panic!("{}", err));
+        
         if !Self::is_authority() {
             panic!("Only authorized price providers can update prices");
         }
@@ -241,6 +462,26 @@ impl PriceFeedContract {
         let caller = l1x_sdk::env::caller();
         let now = l1x_sdk::env::block_timestamp();
         
+        state.check_registered(&symbol)
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        if state.is_superseded(&symbol, now) {
+            panic!("Price for {} is set by oracle aggregation", symbol);
+        }
+        
+        // Large moves wait for a second authority
+        if let PriceScreening::Quarantined { previous_price, deviation_bp } = state.screen_update(&symbol, price, &caller, now) {
+            let max_deviation_bp = state.max_deviation_for(&symbol);
+            state.save();
+            
+            crate::events::emit_price_deviation_rejected_event(&symbol, previous_price, price, deviation_bp, max_deviation_bp, &caller);
+            
+            return format!(
+                "Price update for {} quarantined: {} deviates {} bp from {}, awaiting confirmation",
+                symbol, price, deviation_bp, previous_price
+            );
+        }
+        
         // Create new price data
         let price_data = PriceData {
             symbol: symbol.clone(),
@@ -251,21 +492,11 @@ impl PriceFeedContract {
         };
         
         // Add to history before updating current price
-        let history_record = PriceHistoryRecord {
+        state.record_history(PriceHistoryRecord {
             symbol: symbol.clone(),
             price,
             timestamp: now,
-        };
-        
-        let history = state.history.entry(symbol.clone())
-            .or_insert_with(Vec::new);
-            
-        history.push(history_record);
-        
-        // Trim history if needed
-        if history.len() > state.max_history_records {
-            *history = history[history.len() - state.max_history_records..].to_vec();
-        }
+        });
         
         // Update current price
         state.prices.insert(symbol.clone(), price_data);
@@ -288,7 +519,33 @@ impl PriceFeedContract {
         let caller = l1x_sdk::env::caller();
         let now = l1x_sdk::env::block_timestamp();
         
+        for (symbol, price) in &price_updates {
+            validate_non_empty_string(symbol, "symbol")
+                .unwrap_or_else(|err| panic!("{}", err));
+            validate_amount_nonzero(*price, "price")
+                .unwrap_or_else(|err| panic!("{}", err));
+            state.check_registered(symbol)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        
+        // Symbols with a fresh aggregated price keep it, and large moves wait for a second authority
+        let mut accepted_updates = Vec::new();
         for (symbol, price) in price_updates {
+            if state.is_superseded(&symbol, now) {
+                continue;
+            }
+            
+            match state.screen_update(&symbol, price, &caller, now) {
+                PriceScreening::Accepted => accepted_updates.push((symbol, price)),
+                PriceScreening::Quarantined { previous_price, deviation_bp } => {
+                    let max_deviation_bp = state.max_deviation_for(&symbol);
+                    crate::events::emit_price_deviation_rejected_event(&symbol, previous_price, price, deviation_bp, max_deviation_bp, &caller);
+                },
+            }
+        }
+        let price_updates = accepted_updates;
+            
+        for (symbol, price) in price_updates.iter().cloned() {
             // Create new price data
             let price_data = PriceData {
                 symbol: symbol.clone(),
@@ -299,21 +556,11 @@ impl PriceFeedContract {
             };
             
             // Add to history
-            let history_record = PriceHistoryRecord {
+            state.record_history(PriceHistoryRecord {
                 symbol: symbol.clone(),
                 price,
                 timestamp: now,
-            };
-            
-            let history = state.history.entry(symbol.clone())
-                .or_insert_with(Vec::new);
-                
-            history.push(history_record);
-            
-            // Trim history if needed
-            if history.len() > state.max_history_records {
-                *history = history[history.len() - state.max_history_records..].to_vec();
-            }
+            });
             
             // Update current price
             state.prices.insert(symbol.clone(), price_data);
@@ -349,6 +596,21 @@ impl PriceFeedContract {
             .unwrap_or_else(|_| "Failed to serialize prices".to_string())
     }
     
+    /// Gets the prices of the symbols in `symbols_json` (e.g. `["BTC","ETH","USDC"]`)
+    ///
+    /// Each price carries its staleness; symbols without data are listed
+    /// under `missing` so callers can fail fast.
+    pub fn get_prices(symbols_json: String) -> String {
+        let symbols: Vec<String> = serde_json::from_str(&symbols_json)
+            .unwrap_or_else(|e| panic!("Failed to parse symbols: {}", e));
+            
+        let state = Self::load();
+        let response = state.price_quotes(&symbols, l1x_sdk::env::block_timestamp());
+        
+        serde_json::to_string(&response)
+            .unwrap_or_else(|_| "Failed to serialize prices".to_string())
+    }
+    
     /// Gets the price history for a single asset
     pub fn get_price_history(symbol: String) -> String {
         let state = Self::load();
@@ -361,102 +623,1186 @@ impl PriceFeedContract {
         }
     }
     
-    /// Gets the time-weighted average price (TWAP) for an asset
-    pub fn get_twap(symbol: String, period_seconds: u64) -> String {
-        let state = Self::load();
+    /// Gets an asset's price history between `from_ts` and `to_ts` (inclusive),
+    /// downsampled to at most `max_points` evenly spaced records
+    pub fn get_price_history_range(symbol: String, from_ts: u64, to_ts: u64, max_points: Option<u32>) -> String {
+        if from_ts > to_ts {
+            panic!("from_ts must not be after to_ts");
+        }
         
-        let history = match state.history.get(&symbol) {
-            Some(h) => h,
-            None => return format!("No price history for {}", symbol),
-        };
+        let state = Self::load();
         
-        if history.is_empty() {
+        if !state.history.contains_key(&symbol) {
             return format!("No price history for {}", symbol);
         }
         
-        let now = l1x_sdk::env::block_timestamp();
-        let start_time = now.saturating_sub(period_seconds);
+        let records = state.history_range(&symbol, from_ts, to_ts, max_points.map(|points| points as usize));
         
-        // Filter records within the time window
-        let relevant_records: Vec<&PriceHistoryRecord> = history
-            .iter()
-            .filter(|record| record.timestamp >= start_time)
-            .collect();
+        serde_json::to_string(&records)
+            .unwrap_or_else(|_| "Failed to serialize price history".to_string())
+    }
+    
+    /// Gets the latest recorded price of an asset at or before `timestamp`
+    pub fn get_price_at(symbol: String, timestamp: u64) -> String {
+        let state = Self::load();
+        
+        match state.price_at(&symbol, timestamp) {
+            Some(record) => serde_json::to_string(record)
+                .unwrap_or_else(|_| "Failed to serialize price record".to_string()),
+                
+            None => format!("No price recorded for {} at or before {}", symbol, timestamp),
+        }
+    }
+    
+    /// Sets a price regardless of its deviation, for legitimate large moves
+    pub fn force_update_price(symbol: String, price: u128) -> String {
+        validate_non_empty_string(&symbol, "symbol")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_amount_nonzero(price, "price")
+            .unwrap_or_else(|err| panic!("{}", err));
             
-        if relevant_records.is_empty() {
-            return format!("No price data for {} in the last {} seconds", symbol, period_seconds);
+        if !Self::is_admin() {
+            panic!("Only admin can force price updates");
         }
         
-        // Calculate TWAP
-        let mut sum_price_time = 0.0;
-        let mut total_time = 0.0;
+        let mut state = Self::load();
+        let caller = l1x_sdk::env::caller();
+        let now = l1x_sdk::env::block_timestamp();
         
-        for i in 0..relevant_records.len() - 1 {
-            let current = relevant_records[i];
-            let next = relevant_records[i + 1];
+        state.check_registered(&symbol)
+            .unwrap_or_else(|err| panic!("{}", err));
             
-            let time_diff = (next.timestamp - current.timestamp) as f64;
-            sum_price_time += (current.price as f64) * time_diff;
-            total_time += time_diff;
+        state.pending_prices.remove(&symbol);
+        state.record_history(PriceHistoryRecord {
+            symbol: symbol.clone(),
+            price,
+            timestamp: now,
+        });
+        state.prices.insert(symbol.clone(), PriceData {
+            symbol: symbol.clone(),
+            price,
+            updated_at: now,
+            provider: caller.clone(),
+            signature: None,
+        });
+        state.save();
+        
+        let result = format!("Price forced for {}: {}", symbol, price);
+        crate::audit::AuditLogContract::record(
+            &caller,
+            "PriceFeedContract",
+            "force_update_price",
+            &serde_json::json!({ "symbol": symbol, "price": price.to_string() }),
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Sets the deviation limit, for one asset when `symbol` is given or globally otherwise
+    pub fn set_max_deviation_bp(symbol: Option<String>, max_deviation_bp: u32) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can change the deviation limit");
         }
         
-        // Add the last record, using time until now
-        let last = relevant_records.last().unwrap();
-        let time_diff = (now - last.timestamp) as f64;
-        sum_price_time += (last.price as f64) * time_diff;
-        total_time += time_diff;
+        if max_deviation_bp == 0 {
+            panic!("Deviation limit must be greater than zero");
+        }
         
-        let twap = if total_time > 0.0 {
-            sum_price_time / total_time
-        } else {
-            // If there's only one record or records at the same time
-            last.price as f64
-        };
+        let mut state = Self::load();
         
-        // Return the TWAP as a JSON
-        let result = serde_json::json!({
-            "symbol": symbol,
-            "twap": twap,
-            "period_seconds": period_seconds,
-            "records_used": relevant_records.len(),
-        });
+        let result = match symbol {
+            Some(symbol) => {
+                state.asset_max_deviation_bp.insert(symbol.clone(), max_deviation_bp);
+                format!("Deviation limit for {} set to {} bp", symbol, max_deviation_bp)
+            },
+            None => {
+                state.max_deviation_bp = max_deviation_bp;
+                format!("Deviation limit set to {} bp", max_deviation_bp)
+            },
+        };
+        state.save();
         
-        serde_json::to_string(&result)
-            .unwrap_or_else(|_| "Failed to serialize TWAP result".to_string())
+        result
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
     
-    #[test]
-    fn test_price_update() {
-        let symbol = "BTC".to_string();
-        let price = 50000_00000000; // $50,000 with 8 decimal precision
+    /// Registers an asset's token details
+    ///
+    /// Registering a deactivated symbol again replaces its record and reactivates it.
+    pub fn register_asset(
+        symbol: String,
+        decimals: u8,
+        display_name: String,
+        addresses: std::collections::HashMap<String, String>,
+    ) -> String {
+        validate_non_empty_string(&symbol, "symbol")
+            .unwrap_or_else(|err| panic!("{}", err));
+        validate_non_empty_string(&display_name, "display_name")
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        if !Self::is_admin() {
+            panic!("Only admin can register assets");
+        }
         
-        let price_data = PriceData {
+        let mut state = Self::load();
+        state.register_asset_metadata(AssetMetadata {
             symbol: symbol.clone(),
-            price,
-            updated_at: 0,
-            provider: "test_provider".to_string(),
-            signature: None,
-        };
+            decimals,
+            display_name,
+            addresses,
+            active: true,
+            registered_at: l1x_sdk::env::block_timestamp(),
+        }).unwrap_or_else(|err| panic!("{}", err));
+        state.save();
         
-        assert_eq!(price_data.symbol, symbol);
-        assert_eq!(price_data.price, price);
+        let result = format!("Asset {} registered with {} decimals", symbol, decimals);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "register_asset",
+            &serde_json::json!({ "symbol": symbol, "decimals": decimals }),
+            &result,
+        );
+        
+        result
     }
     
-    #[test]
-    fn test_history_record() {
-        let record = PriceHistoryRecord {
-            symbol: "ETH".to_string(),
-            price: 3000_00000000, // $3,000 with 8 decimal precision
-            timestamp: 1234567890,
-        };
+    /// Updates the given fields of a registered asset, leaving the others unchanged
+    pub fn update_asset(
+        symbol: String,
+        decimals: Option<u8>,
+        display_name: Option<String>,
+        addresses: Option<std::collections::HashMap<String, String>>,
+    ) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can update assets");
+        }
         
-        assert_eq!(record.symbol, "ETH");
-        assert_eq!(record.price, 3000_00000000);
-        assert_eq!(record.timestamp, 1234567890);
+        let mut state = Self::load();
+        let metadata = state.update_asset_metadata(&symbol, decimals, display_name, addresses)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .clone();
+        state.save();
+        
+        let result = format!("Asset {} updated", symbol);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "update_asset",
+            &serde_json::json!({ "symbol": symbol, "decimals": metadata.decimals, "display_name": metadata.display_name }),
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Marks an asset as no longer supported, keeping its record
+    pub fn deactivate_asset(symbol: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can deactivate assets");
+        }
+        
+        let mut state = Self::load();
+        state.deactivate_asset_metadata(&symbol)
+            .unwrap_or_else(|err| panic!("{}", err));
+        state.save();
+        
+        let result = format!("Asset {} deactivated", symbol);
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "deactivate_asset",
+            &serde_json::json!({ "symbol": symbol }),
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Refuses price updates for unregistered or deactivated symbols while enabled
+    pub fn set_strict_asset_registry(enabled: bool) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can change the asset registry mode");
+        }
+        
+        let mut state = Self::load();
+        state.strict_asset_registry = enabled;
+        state.save();
+        
+        format!("Strict asset registry {}", if enabled { "enabled" } else { "disabled" })
+    }
+    
+    /// Gets the registered token details of an asset
+    pub fn get_asset_metadata(symbol: String) -> String {
+        let state = Self::load();
+        
+        match state.assets.get(&symbol) {
+            Some(metadata) => serde_json::to_string(metadata)
+                .unwrap_or_else(|_| "Failed to serialize asset metadata".to_string()),
+                
+            None => format!("Asset not registered: {}", symbol),
+        }
+    }
+    
+    /// Lists every registered asset, active or not, by symbol
+    pub fn list_assets() -> String {
+        let state = Self::load();
+        
+        serde_json::to_string(&state.list_asset_metadata())
+            .unwrap_or_else(|_| "Failed to serialize assets".to_string())
+    }
+    
+    /// Gets the price updates quarantined for exceeding the deviation limit
+    pub fn get_pending_prices() -> String {
+        let state = Self::load();
+        
+        serde_json::to_string(&state.pending_prices)
+            .unwrap_or_else(|_| "Failed to serialize pending prices".to_string())
+    }
+    
+    /// Gets the current price for a single asset with its staleness
+    ///
+    /// Panics when the price is older than the asset's maximum age, unless
+    /// the stale price override is on, in which case it is returned flagged.
+    pub fn get_price_checked(symbol: String) -> String {
+        let state = Self::load();
+        
+        let checked = state.checked_price(&symbol, l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("{}", err));
+            
+        serde_json::to_string(&checked)
+            .unwrap_or_else(|_| "Failed to serialize price".to_string())
+    }
+    
+    /// Sets the maximum price age, for one asset when `symbol` is given or globally otherwise
+    pub fn set_max_age_seconds(symbol: Option<String>, max_age_seconds: u64) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can change the maximum price age");
+        }
+        
+        if max_age_seconds == 0 {
+            panic!("Maximum price age must be greater than zero");
+        }
+        
+        let mut state = Self::load();
+        
+        let result = match symbol {
+            Some(symbol) => {
+                state.asset_max_age_seconds.insert(symbol.clone(), max_age_seconds);
+                format!("Maximum price age for {} set to {} seconds", symbol, max_age_seconds)
+            },
+            None => {
+                state.max_age_seconds = max_age_seconds;
+                format!("Maximum price age set to {} seconds", max_age_seconds)
+            },
+        };
+        state.save();
+        
+        result
+    }
+    
+    /// Lets stale prices through the checked reads (emergency use only)
+    pub fn set_stale_price_override(enabled: bool) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can override price staleness");
+        }
+        
+        let mut state = Self::load();
+        state.allow_stale_prices = enabled;
+        state.save();
+        
+        let result = format!("Stale price override {}", if enabled { "enabled" } else { "disabled" });
+        crate::audit::AuditLogContract::record(
+            &l1x_sdk::env::caller(),
+            "PriceFeedContract",
+            "set_stale_price_override",
+            &serde_json::json!({ "enabled": enabled }),
+            &result,
+        );
+        
+        result
+    }
+    
+    /// Gets the time-weighted average price (TWAP) for an asset
+    pub fn get_twap(symbol: String, period_seconds: u64) -> String {
+        let state = Self::load();
+        
+        let history = match state.history.get(&symbol) {
+            Some(h) => h,
+            None => return format!("No price history for {}", symbol),
+        };
+        
+        if history.is_empty() {
+            return format!("No price history for {}", symbol);
+        }
+        
+        let now = l1x_sdk::env::block_timestamp();
+        let start_time = now.saturating_sub(period_seconds);
+        
+        let twap = match twap(history, start_time, now) {
+            Some(twap) => twap,
+            None => return format!("No price data for {} in the last {} seconds", symbol, period_seconds),
+        };
+        
+        let result = TwapResult {
+            symbol,
+            twap,
+            period_seconds,
+            records_used: history.len() - history.partition_point(|record| record.timestamp < start_time),
+        };
+        
+        serde_json::to_string(&result)
+            .unwrap_or_else(|_| "Failed to serialize TWAP result".to_string())
+    }
+    
+    /// Gets an asset's price change over the last 24 hours
+    pub fn get_price_change_24h(symbol: String) -> String {
+        let state = Self::load();
+        
+        let result = serde_json::json!({
+            "symbol": symbol,
+            "change_bp": state.price_change_24h_bp(&symbol, l1x_sdk::env::block_timestamp()),
+        });
+        
+        serde_json::to_string(&result)
+            .unwrap_or_else(|_| "Failed to serialize price change".to_string())
+    }
+    
+    /// Gets an asset's volatility over its last `lookback` price records
+    pub fn get_volatility(symbol: String, lookback: u32) -> String {
+        let state = Self::load();
+        
+        let history = state.history.get(&symbol)
+            .map(|h| h.as_slice())
+            .unwrap_or(&[]);
+            
+        let result = serde_json::json!({
+            "symbol": symbol,
+            "volatility_bp": volatility_bp(history, lookback as usize),
+            "lookback": lookback,
+        });
+        
+        serde_json::to_string(&result)
+            .unwrap_or_else(|_| "Failed to serialize volatility".to_string())
+    }
+}
+
+impl PriceFeedContract {
+    /// Creates a price feed with `admin` as its first authority
+    pub fn with_admin(admin: String) -> Self {
+        let mut state = Self {
+            prices: std::collections::HashMap::new(),
+            authorities: std::collections::HashMap::new(),
+            history: std::collections::HashMap::new(),
+            max_history_records: 24, // Keep 24 hours of hourly data by default
+            admin: admin.clone(),
+            external_oracles: Vec::new(),
+            max_age_seconds: DEFAULT_MAX_PRICE_AGE_SECONDS,
+            asset_max_age_seconds: std::collections::HashMap::new(),
+            allow_stale_prices: false,
+            max_deviation_bp: DEFAULT_MAX_DEVIATION_BP,
+            asset_max_deviation_bp: std::collections::HashMap::new(),
+            pending_prices: std::collections::HashMap::new(),
+            assets: std::collections::HashMap::new(),
+            strict_asset_registry: false,
+        };
+        
+        // Add admin as the first authority
+        state.authorities.insert(admin.clone(), PriceFeedAuthority {
+            address: admin,
+            name: "Admin".to_string(),
+            active: true,
+            added_at: l1x_sdk::env::block_timestamp(),
+        });
+        
+        state
+    }
+    
+    /// Inserts a record into its asset's history, trimming the oldest beyond `max_history_records`
+    ///
+    /// History is kept sorted by timestamp; a record sharing a timestamp with
+    /// existing ones goes after them.
+    pub fn record_history(&mut self, record: PriceHistoryRecord) {
+        let history = self.history.entry(record.symbol.clone())
+            .or_insert_with(Vec::new);
+            
+        let index = history.partition_point(|existing| existing.timestamp <= record.timestamp);
+        history.insert(index, record);
+        
+        // Trim history if needed
+        if history.len() > self.max_history_records {
+            *history = history[history.len() - self.max_history_records..].to_vec();
+        }
+    }
+    
+    /// Records of `symbol` with timestamps in `from..=to`, downsampled to at most `max_points`
+    pub fn history_range(&self, symbol: &str, from: u64, to: u64, max_points: Option<usize>) -> Vec<PriceHistoryRecord> {
+        let history = match self.history.get(symbol) {
+            Some(history) => history,
+            None => return Vec::new(),
+        };
+        
+        let start = history.partition_point(|record| record.timestamp < from);
+        let end = history.partition_point(|record| record.timestamp <= to);
+        let window = &history[start..end.max(start)];
+        
+        match max_points {
+            Some(max_points) => downsample(window, max_points),
+            None => window.to_vec(),
+        }
+    }
+    
+    /// Latest record of `symbol` at or before `timestamp`
+    pub fn price_at(&self, symbol: &str, timestamp: u64) -> Option<&PriceHistoryRecord> {
+        let history = self.history.get(symbol)?;
+        let index = history.partition_point(|record| record.timestamp <= timestamp);
+        
+        index.checked_sub(1).map(|index| &history[index])
+    }
+    
+    /// Adds `metadata` to the registry, replacing a deactivated record of the same symbol
+    pub fn register_asset_metadata(&mut self, metadata: AssetMetadata) -> Result<(), &'static str> {
+        if metadata.decimals > MAX_ASSET_DECIMALS {
+            return Err("Asset decimals exceed the maximum");
+        }
+        
+        if self.assets.get(&metadata.symbol).is_some_and(|existing| existing.active) {
+            return Err("Asset already registered");
+        }
+        
+        self.assets.insert(metadata.symbol.clone(), metadata);
+        Ok(())
+    }
+    
+    /// Overwrites the given fields of a registered asset
+    pub fn update_asset_metadata(
+        &mut self,
+        symbol: &str,
+        decimals: Option<u8>,
+        display_name: Option<String>,
+        addresses: Option<std::collections::HashMap<String, String>>,
+    ) -> Result<&AssetMetadata, &'static str> {
+        if decimals.is_some_and(|decimals| decimals > MAX_ASSET_DECIMALS) {
+            return Err("Asset decimals exceed the maximum");
+        }
+        
+        if display_name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+            return Err("Display name cannot be empty");
+        }
+        
+        let metadata = self.assets.get_mut(symbol).ok_or("Asset not registered")?;
+        
+        if let Some(decimals) = decimals {
+            metadata.decimals = decimals;
+        }
+        if let Some(display_name) = display_name {
+            metadata.display_name = display_name;
+        }
+        if let Some(addresses) = addresses {
+            metadata.addresses = addresses;
+        }
+        
+        Ok(metadata)
+    }
+    
+    /// Marks a registered asset inactive
+    pub fn deactivate_asset_metadata(&mut self, symbol: &str) -> Result<(), &'static str> {
+        let metadata = self.assets.get_mut(symbol).ok_or("Asset not registered")?;
+        
+        if !metadata.active {
+            return Err("Asset already deactivated");
+        }
+        
+        metadata.active = false;
+        Ok(())
+    }
+    
+    /// Every registered asset, sorted by symbol
+    pub fn list_asset_metadata(&self) -> Vec<&AssetMetadata> {
+        let mut assets: Vec<&AssetMetadata> = self.assets.values().collect();
+        assets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        assets
+    }
+    
+    /// Decimals of every active asset, as consumed by `RebalanceEngine::create_from_value_deltas`
+    pub fn asset_decimals(&self) -> std::collections::HashMap<String, u8> {
+        self.assets.values()
+            .filter(|metadata| metadata.active)
+            .map(|metadata| (metadata.symbol.clone(), metadata.decimals))
+            .collect()
+    }
+    
+    /// Fails in strict mode when `symbol` has no active registration
+    pub fn check_registered(&self, symbol: &str) -> Result<(), String> {
+        if !self.strict_asset_registry {
+            return Ok(());
+        }
+        
+        match self.assets.get(symbol) {
+            Some(metadata) if metadata.active => Ok(()),
+            Some(_) => Err(format!("Asset is deactivated: {}", symbol)),
+            None => Err(format!("Asset not registered: {}", symbol)),
+        }
+    }
+    
+    /// Deviation limit of `symbol` (in basis points)
+    pub fn max_deviation_for(&self, symbol: &str) -> u32 {
+        self.asset_max_deviation_bp.get(symbol).copied().unwrap_or(self.max_deviation_bp)
+    }
+    
+    /// Screens an update of `symbol` to `price` by `provider` against the deviation limit
+    ///
+    /// An update beyond the limit is quarantined; it is accepted once a
+    /// different authority submits a price within the limit of the quarantined one.
+    pub fn screen_update(&mut self, symbol: &str, price: u128, provider: &str, now: u64) -> PriceScreening {
+        let max_deviation_bp = self.max_deviation_for(symbol);
+        
+        let previous_price = match self.prices.get(symbol) {
+            Some(data) => data.price,
+            None => return PriceScreening::Accepted,
+        };
+        
+        let deviation = deviation_bp(previous_price, price);
+        
+        let confirmed = self.pending_prices.get(symbol).is_some_and(|pending| {
+            pending.provider != provider && deviation_bp(pending.price, price) <= max_deviation_bp
+        });
+        
+        if deviation <= max_deviation_bp || confirmed {
+            self.pending_prices.remove(symbol);
+            return PriceScreening::Accepted;
+        }
+        
+        self.pending_prices.insert(symbol.to_string(), PendingPrice {
+            price,
+            provider: provider.to_string(),
+            proposed_at: now,
+        });
+        
+        PriceScreening::Quarantined { previous_price, deviation_bp: deviation }
+    }
+    
+    /// Maximum age of `symbol`'s price
+    pub fn max_age_for(&self, symbol: &str) -> u64 {
+        self.asset_max_age_seconds.get(symbol).copied().unwrap_or(self.max_age_seconds)
+    }
+    
+    /// `symbol`'s price with its staleness at `now`
+    ///
+    /// Fails when there is no price, or when it is stale and the override is off.
+    pub fn checked_price(&self, symbol: &str, now: u64) -> Result<CheckedPrice, String> {
+        let data = self.prices.get(symbol)
+            .ok_or_else(|| format!("No price data for {}", symbol))?;
+            
+        let age_seconds = now.saturating_sub(data.updated_at);
+        let stale = age_seconds > self.max_age_for(symbol);
+        
+        if stale && !self.allow_stale_prices {
+            return Err(format!("Price for {} is stale: updated {} seconds ago", symbol, age_seconds));
+        }
+        
+        Ok(CheckedPrice {
+            symbol: symbol.to_string(),
+            price: data.price,
+            updated_at: data.updated_at,
+            age_seconds,
+            stale,
+        })
+    }
+    
+    /// Prices of `symbols` with their staleness at `now`, listing those without data as missing
+    pub fn price_quotes(&self, symbols: &[String], now: u64) -> PricesResponse {
+        let mut response = PricesResponse {
+            prices: std::collections::BTreeMap::new(),
+            missing: Vec::new(),
+        };
+        
+        for symbol in symbols {
+            match self.prices.get(symbol) {
+                Some(data) => {
+                    response.prices.insert(symbol.clone(), PriceQuote {
+                        price: data.price,
+                        updated_at: data.updated_at,
+                        stale: now.saturating_sub(data.updated_at) > self.max_age_for(symbol),
+                    });
+                },
+                None if !response.missing.contains(symbol) => response.missing.push(symbol.clone()),
+                None => {},
+            }
+        }
+        
+        response
+    }
+    
+    /// Whether `symbol` has an aggregated price that single-provider updates must not overwrite at `now`
+    fn is_superseded(&self, symbol: &str, now: u64) -> bool {
+        self.prices.get(symbol).is_some_and(|data| {
+            data.provider == AGGREGATED_PROVIDER
+                && now.saturating_sub(data.updated_at) < AGGREGATED_PRICE_TTL_SECONDS
+        })
+    }
+    
+    /// Stores the median of the external oracles' prices for `symbol`, read with `fetch`
+    ///
+    /// Oracles without a price are skipped; fails when none has one.
+    pub fn aggregate_price(
+        &mut self,
+        symbol: &str,
+        now: u64,
+        fetch: impl Fn(&str, &str) -> Option<u128>,
+    ) -> Result<u128, String> {
+        self.check_registered(symbol)?;
+        
+        if self.external_oracles.is_empty() {
+            return Err("No external oracles configured".to_string());
+        }
+        
+        let prices: Vec<u128> = self.external_oracles.iter()
+            .filter_map(|oracle| fetch(oracle, symbol))
+            .filter(|price| *price > 0)
+            .collect();
+        let price = median_price(prices).ok_or_else(|| "No external oracle returned a price".to_string())?;
+        
+        self.record_history(PriceHistoryRecord {
+            symbol: symbol.to_string(),
+            price,
+            timestamp: now,
+        });
+        self.prices.insert(symbol.to_string(), PriceData {
+            symbol: symbol.to_string(),
+            price,
+            updated_at: now,
+            provider: AGGREGATED_PROVIDER.to_string(),
+            signature: None,
+        });
+        
+        Ok(price)
+    }
+    
+    /// Change from the first price recorded in the 24 hours before `now` to the latest (in basis points)
+    ///
+    /// Returns `None` when fewer than two prices were recorded in that window.
+    pub fn price_change_24h_bp(&self, symbol: &str, now: u64) -> Option<i32> {
+        let since = now.saturating_sub(24 * 3600);
+        let history = self.history.get(symbol)?;
+        let window = &history[history.partition_point(|record| record.timestamp < since)..];
+            
+        let (first, last) = match (window.first(), window.last()) {
+            (Some(first), Some(last)) if window.len() >= 2 && first.price > 0 => (first, last),
+            _ => return None,
+        };
+        
+        let change = div_round((last.price as i128 - first.price as i128) * 10000, first.price as i128);
+        Some(change.clamp(i32::MIN as i128, i32::MAX as i128) as i32)
+    }
+}
+
+/// At most `max_points` records of `records`, evenly spaced and always keeping
+/// the first and last
+pub fn downsample(records: &[PriceHistoryRecord], max_points: usize) -> Vec<PriceHistoryRecord> {
+    if records.len() <= max_points {
+        return records.to_vec();
+    }
+    
+    match max_points {
+        0 => Vec::new(),
+        1 => records[records.len() - 1..].to_vec(),
+        _ => (0..max_points)
+            .map(|i| records[i * (records.len() - 1) / (max_points - 1)].clone())
+            .collect(),
+    }
+}
+
+/// Relative move from `previous` to `price` (in basis points, saturating)
+pub fn deviation_bp(previous: u128, price: u128) -> u32 {
+    if previous == 0 {
+        return u32::MAX;
+    }
+    
+    let bp = previous.abs_diff(price).saturating_mul(10000) / previous;
+    bp.min(u32::MAX as u128) as u32
+}
+
+/// Median of `prices`, averaging the middle two of an even count
+pub fn median_price(mut prices: Vec<u128>) -> Option<u128> {
+    if prices.is_empty() {
+        return None;
+    }
+    
+    prices.sort_unstable();
+    let mid = prices.len() / 2;
+    
+    if prices.len() % 2 == 0 {
+        Some(prices[mid - 1] / 2 + prices[mid] / 2 + (prices[mid - 1] % 2 + prices[mid] % 2) / 2)
+    } else {
+        Some(prices[mid])
+    }
+}
+
+/// Reads `symbol`'s current price from the oracle contract at `oracle_address`
+///
+/// Stub for the cross-contract call: no oracle contract interface is wired up
+/// yet, so every oracle reports no price.
+fn query_oracle_price(_oracle_address: &str, _symbol: &str) -> Option<u128> {
+    None
+}
+
+/// Scale of the returns `volatility_bp` works in (1e8 per unit, i.e. 1e4 per basis point)
+const RETURN_SCALE: i128 = 100_000_000;
+
+/// Time-weighted average of the records from `start_time` to `now` (scaled by 1e8, like the prices)
+///
+/// Each price is weighted by the seconds until the next record, and the last
+/// one by the seconds until `now`; the average is rounded to the nearest unit.
+/// A window covering no time (a single record at `now`, or records at the
+/// same instant) averages to the last price. Returns `None` when no record
+/// falls in the window.
+pub fn twap(history: &[PriceHistoryRecord], start_time: u64, now: u64) -> Option<u128> {
+    let records = &history[history.partition_point(|record| record.timestamp < start_time)..];
+    let last = records.last()?;
+    
+    let mut sum_price_time: u128 = 0;
+    let mut total_time: u128 = 0;
+    
+    for (i, record) in records.iter().enumerate() {
+        let until = records.get(i + 1).map_or(now, |next| next.timestamp);
+        let time_diff = until.saturating_sub(record.timestamp) as u128;
+        
+        sum_price_time = sum_price_time.saturating_add(record.price.saturating_mul(time_diff));
+        total_time += time_diff;
+    }
+    
+    if total_time == 0 {
+        return Some(last.price);
+    }
+    
+    Some((sum_price_time + total_time / 2) / total_time)
+}
+
+/// Standard deviation of the returns between the last `lookback` records (in basis points)
+///
+/// Returns are computed at `RETURN_SCALE` and the result rounded to the
+/// nearest basis point. Returns `None` when fewer than two usable returns
+/// are available.
+pub fn volatility_bp(history: &[PriceHistoryRecord], lookback: usize) -> Option<u32> {
+    let start = history.len().saturating_sub(lookback);
+    let returns: Vec<i128> = history[start..]
+        .windows(2)
+        .filter(|pair| pair[0].price > 0)
+        .map(|pair| div_round((pair[1].price as i128 - pair[0].price as i128) * RETURN_SCALE, pair[0].price as i128))
+        .collect();
+        
+    if returns.len() < 2 {
+        return None;
+    }
+    
+    let count = returns.len() as i128;
+    let mean = div_round(returns.iter().sum::<i128>(), count);
+    let variance = returns.iter()
+        .map(|r| ((r - mean) * (r - mean)) as u128)
+        .sum::<u128>() / count as u128;
+        
+    let std_dev_bp = div_round(isqrt(variance) as i128, RETURN_SCALE / 10000);
+    Some(std_dev_bp.min(u32::MAX as i128) as u32)
+}
+
+/// `numerator / denominator` rounded to the nearest integer, halves away from zero (`denominator` > 0)
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let rounded = (numerator.abs() + denominator / 2) / denominator;
+    
+    if numerator < 0 {
+        -rounded
+    } else {
+        rounded
+    }
+}
+
+/// Integer square root (the largest `r` with `r * r <= n`)
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    
+    // Newton's method from an estimate above the root
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Reads an asset's volatility from the price feed's history
+pub fn asset_volatility_bp(symbol: &str, lookback: usize) -> Option<u32> {
+    let state = PriceFeedContract::load();
+    
+    state.history.get(symbol)
+        .and_then(|history| volatility_bp(history, lookback))
+}
+
+/// Loads the price feed for reading several assets at once
+pub fn load_price_feed() -> PriceFeedContract {
+    PriceFeedContract::load()
+}
+
+/// Latest stored price for each of `symbols`, skipping symbols with no price
+pub fn latest_prices(symbols: &[String]) -> Vec<(String, u128)> {
+    let state = PriceFeedContract::load();
+    
+    symbols.iter()
+        .filter_map(|symbol| state.prices.get(symbol).map(|data| (symbol.clone(), data.price)))
+        .collect()
+}
+
+/// Latest price for each of `symbols`, failing if any is missing or stale
+pub fn fresh_prices(symbols: &[String]) -> Result<Vec<(String, u128)>, String> {
+    let state = PriceFeedContract::load();
+    let now = l1x_sdk::env::block_timestamp();
+    
+    symbols.iter()
+        .map(|symbol| state.checked_price(symbol, now).map(|checked| (symbol.clone(), checked.price)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_price_update() {
+        let symbol = "BTC".to_string();
+        let price = 50000_00000000; // $50,000 with 8 decimal precision
+        
+        let price_data = PriceData {
+            symbol: symbol.clone(),
+            price,
+            updated_at: 0,
+            provider: "test_provider".to_string(),
+            signature: None,
+        };
+        
+        assert_eq!(price_data.symbol, symbol);
+        assert_eq!(price_data.price, price);
+    }
+    
+    #[test]
+    fn test_history_record() {
+        let record = PriceHistoryRecord {
+            symbol: "ETH".to_string(),
+            price: 3000_00000000, // $3,000 with 8 decimal precision
+            timestamp: 1234567890,
+        };
+        
+        assert_eq!(record.symbol, "ETH");
+        assert_eq!(record.price, 3000_00000000);
+        assert_eq!(record.timestamp, 1234567890);
+    }
+    
+    #[test]
+    fn test_volatility_bp() {
+        let records = |prices: &[u128]| -> Vec<PriceHistoryRecord> {
+            prices.iter().enumerate()
+                .map(|(i, price)| PriceHistoryRecord { symbol: "BTC".to_string(), price: *price, timestamp: i as u64 * 3600 })
+                .collect()
+        };
+        
+        // Flat prices have no volatility; +1%/-1% swings do
+        assert_eq!(volatility_bp(&records(&[100, 100, 100, 100]), 24), Some(0));
+        let calm = volatility_bp(&records(&[10000, 10100, 10000, 10100, 10000]), 24).unwrap();
+        let wild = volatility_bp(&records(&[10000, 11000, 10000, 11000, 10000]), 24).unwrap();
+        assert!(calm > 0);
+        assert!(wild > calm * 5);
+        
+        // Only the last `lookback` records are used
+        let mixed = records(&[10000, 15000, 10000, 10000, 10000, 10000]);
+        assert_eq!(volatility_bp(&mixed, 4), Some(0));
+        assert!(volatility_bp(&mixed, 6).unwrap() > 0);
+        
+        // Too little history
+        assert_eq!(volatility_bp(&records(&[100, 101]), 24), None);
+        assert_eq!(volatility_bp(&[], 24), None);
+    }
+    
+    #[test]
+    fn test_aggregate_price_uses_median() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        assert!(feed.aggregate_price("BTC", 1000, |_, _| Some(1)).is_err());
+        
+        feed.external_oracles = vec!["oracle-a".to_string(), "oracle-b".to_string(), "oracle-c".to_string()];
+        
+        // One oracle reporting an outlier does not move the price
+        let mock = |oracle: &str, _: &str| match oracle {
+            "oracle-a" => Some(50_100_00000000),
+            "oracle-b" => Some(49_900_00000000),
+            _ => Some(90_000_00000000),
+        };
+        assert_eq!(feed.aggregate_price("BTC", 1000, mock), Ok(50_100_00000000));
+        assert_eq!(feed.prices["BTC"].provider, AGGREGATED_PROVIDER);
+        assert_eq!(feed.history["BTC"].len(), 1);
+        
+        // Single-provider updates are superseded until the aggregated price ages out
+        assert!(feed.is_superseded("BTC", 1000 + AGGREGATED_PRICE_TTL_SECONDS - 1));
+        assert!(!feed.is_superseded("BTC", 1000 + AGGREGATED_PRICE_TTL_SECONDS));
+        assert!(!feed.is_superseded("ETH", 1000));
+        
+        // Silent oracles are skipped
+        let partial = |oracle: &str, _: &str| if oracle == "oracle-c" { Some(3_000_00000000) } else { None };
+        assert_eq!(feed.aggregate_price("ETH", 1000, partial), Ok(3_000_00000000));
+        assert!(feed.aggregate_price("SOL", 1000, |_, _| None).is_err());
+        
+        assert_eq!(median_price(vec![3, 1, 4, 2]), Some(2));
+        assert_eq!(median_price(vec![]), None);
+    }
+    
+    #[test]
+    fn test_stale_prices_rejected() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        let now = l1x_sdk::env::block_timestamp();
+        feed.prices.insert("BTC".to_string(), PriceData {
+            symbol: "BTC".to_string(),
+            price: 50000_00000000,
+            updated_at: now,
+            provider: "admin".to_string(),
+            signature: None,
+        });
+        
+        let checked = feed.checked_price("BTC", now).unwrap();
+        assert!(!checked.stale);
+        assert!(feed.checked_price("ETH", now).is_err());
+        
+        // Advance past the window
+        l1x_sdk::env::set_block_timestamp(now + DEFAULT_MAX_PRICE_AGE_SECONDS + 1);
+        let later = l1x_sdk::env::block_timestamp();
+        assert!(feed.checked_price("BTC", later).is_err());
+        
+        // A longer per-asset window keeps the price fresh
+        feed.asset_max_age_seconds.insert("BTC".to_string(), 2 * DEFAULT_MAX_PRICE_AGE_SECONDS);
+        assert!(!feed.checked_price("BTC", later).unwrap().stale);
+        feed.asset_max_age_seconds.clear();
+        
+        // The emergency override returns the stale price, flagged
+        feed.allow_stale_prices = true;
+        let checked = feed.checked_price("BTC", later).unwrap();
+        assert!(checked.stale);
+        assert_eq!(checked.age_seconds, DEFAULT_MAX_PRICE_AGE_SECONDS + 1);
+    }
+    
+    #[test]
+    fn test_deviation_circuit_breaker() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        feed.prices.insert("BTC".to_string(), PriceData {
+            symbol: "BTC".to_string(),
+            price: 50000_00000000,
+            updated_at: 0,
+            provider: "admin".to_string(),
+            signature: None,
+        });
+        
+        // Ordinary moves and first prices pass
+        assert_eq!(feed.screen_update("BTC", 55000_00000000, "oracle-a", 10), PriceScreening::Accepted);
+        assert_eq!(feed.screen_update("ETH", 3000_00000000, "oracle-a", 10), PriceScreening::Accepted);
+        
+        // A fat-fingered $5 is quarantined, even when resubmitted by the same authority
+        let fat_finger = PriceScreening::Quarantined { previous_price: 50000_00000000, deviation_bp: 9999 };
+        assert_eq!(feed.screen_update("BTC", 5_00000000, "oracle-a", 10), fat_finger);
+        assert_eq!(feed.screen_update("BTC", 5_00000000, "oracle-a", 20), fat_finger);
+        assert_eq!(feed.pending_prices["BTC"].price, 5_00000000);
+        
+        // A second authority confirming a nearby price releases it
+        assert_eq!(feed.screen_update("BTC", 5_10000000, "oracle-b", 30), PriceScreening::Accepted);
+        assert!(feed.pending_prices.is_empty());
+        
+        // Volatile assets get a wider limit
+        feed.asset_max_deviation_bp.insert("BTC".to_string(), 9999);
+        assert_eq!(feed.screen_update("BTC", 5_00000000, "oracle-a", 40), PriceScreening::Accepted);
+        
+        assert_eq!(deviation_bp(100, 120), 2000);
+        assert_eq!(deviation_bp(100, 80), 2000);
+        assert_eq!(deviation_bp(0, 80), u32::MAX);
+    }
+    
+    #[test]
+    fn test_integer_math_matches_float() {
+        let records = |points: &[(u128, u64)]| -> Vec<PriceHistoryRecord> {
+            points.iter()
+                .map(|(price, timestamp)| PriceHistoryRecord { symbol: "BTC".to_string(), price: *price, timestamp: *timestamp })
+                .collect()
+        };
+        
+        // The float implementations these replaced
+        let float_twap = |history: &[PriceHistoryRecord], now: u64| {
+            let mut sum_price_time = 0.0;
+            let mut total_time = 0.0;
+            for (i, record) in history.iter().enumerate() {
+                let until = history.get(i + 1).map_or(now, |next| next.timestamp);
+                sum_price_time += record.price as f64 * (until - record.timestamp) as f64;
+                total_time += (until - record.timestamp) as f64;
+            }
+            sum_price_time / total_time
+        };
+        let float_volatility = |history: &[PriceHistoryRecord]| {
+            let returns: Vec<f64> = history.windows(2)
+                .map(|pair| (pair[1].price as f64 - pair[0].price as f64) / pair[0].price as f64 * 10000.0)
+                .collect();
+            let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+            let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64;
+            variance.sqrt().round()
+        };
+        
+        let series = records(&[
+            (65_012_34567890, 0),
+            (65_437_00000001, 3600),
+            (64_998_76543210, 5400),
+            (66_120_00000000, 9000),
+            (65_555_55555555, 12600),
+        ]);
+        
+        let twap_value = twap(&series, 0, 14400).unwrap();
+        assert!((twap_value as f64 - float_twap(&series, 14400)).abs() <= 1.0);
+        
+        let volatility = volatility_bp(&series, 24).unwrap();
+        assert!((volatility as f64 - float_volatility(&series)).abs() <= 1.0);
+        
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        for record in series.iter().cloned() {
+            feed.record_history(record);
+        }
+        let change = feed.price_change_24h_bp("BTC", 12600).unwrap();
+        let float_change = ((65_555_55555555f64 - 65_012_34567890f64) / 65_012_34567890f64 * 10000.0).round();
+        assert_eq!(change as f64, float_change);
+        
+        // Windows covering no time average to the last price; empty windows have none
+        assert_eq!(twap(&series, 12600, 12600), Some(65_555_55555555));
+        assert_eq!(twap(&records(&[(100, 50), (300, 50)]), 0, 50), Some(300));
+        assert_eq!(twap(&series, 20000, 20000), None);
+        assert_eq!(twap(&[], 0, 100), None);
+        
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(div_round(-15, 10), -2);
+        assert_eq!(div_round(14, 10), 1);
+    }
+    
+    #[test]
+    fn test_price_quotes_for_symbol_list() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        for (symbol, price, updated_at) in [("BTC", 65000_00000000, 10_000), ("ETH", 3500_00000000, 1_000), ("SOL", 140_00000000, 10_000)] {
+            feed.prices.insert(symbol.to_string(), PriceData {
+                symbol: symbol.to_string(),
+                price,
+                updated_at,
+                provider: "admin".to_string(),
+                signature: None,
+            });
+        }
+        
+        let symbols: Vec<String> = ["BTC", "ETH", "USDC", "USDC"].iter().map(|s| s.to_string()).collect();
+        let response = feed.price_quotes(&symbols, 10_000 + 60);
+        
+        // Only the requested symbols, with ETH past the default maximum age
+        assert_eq!(response.prices.len(), 2);
+        assert_eq!(response.prices["BTC"], PriceQuote { price: 65000_00000000, updated_at: 10_000, stale: false });
+        assert!(response.prices["ETH"].stale);
+        assert_eq!(response.missing, vec!["USDC".to_string()]);
+    }
+    
+    #[test]
+    fn test_history_range_and_price_at() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        let record = |price: u128, timestamp: u64| PriceHistoryRecord {
+            symbol: "BTC".to_string(),
+            price,
+            timestamp,
+        };
+        
+        // Recorded out of order, stored sorted
+        for (price, timestamp) in [(300, 3000), (100, 1000), (500, 5000), (200, 2000), (400, 4000)] {
+            feed.record_history(record(price, timestamp));
+        }
+        let timestamps: Vec<u64> = feed.history["BTC"].iter().map(|r| r.timestamp).collect();
+        assert_eq!(timestamps, vec![1000, 2000, 3000, 4000, 5000]);
+        
+        let prices = |records: Vec<PriceHistoryRecord>| -> Vec<u128> {
+            records.iter().map(|r| r.price).collect()
+        };
+        assert_eq!(prices(feed.history_range("BTC", 2000, 4000, None)), vec![200, 300, 400]);
+        assert_eq!(prices(feed.history_range("BTC", 1500, 4500, Some(2))), vec![200, 400]);
+        assert_eq!(prices(feed.history_range("BTC", 0, u64::MAX, Some(3))), vec![100, 300, 500]);
+        assert!(feed.history_range("BTC", 6000, 7000, None).is_empty());
+        assert!(feed.history_range("ETH", 0, u64::MAX, None).is_empty());
+        
+        assert_eq!(feed.price_at("BTC", 3500).map(|r| r.price), Some(300));
+        assert_eq!(feed.price_at("BTC", 3000).map(|r| r.price), Some(300));
+        assert_eq!(feed.price_at("BTC", 9999).map(|r| r.price), Some(500));
+        assert!(feed.price_at("BTC", 999).is_none());
+    }
+    
+    #[test]
+    fn test_asset_registry() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        let metadata = |symbol: &str, decimals: u8| AssetMetadata {
+            symbol: symbol.to_string(),
+            decimals,
+            display_name: symbol.to_string(),
+            addresses: std::collections::HashMap::new(),
+            active: true,
+            registered_at: 0,
+        };
+        
+        // Not enforced until strict mode is on
+        assert!(feed.check_registered("BTC").is_ok());
+        feed.strict_asset_registry = true;
+        assert_eq!(feed.check_registered("BTC"), Err("Asset not registered: BTC".to_string()));
+        
+        feed.register_asset_metadata(metadata("BTC", 8)).unwrap();
+        feed.register_asset_metadata(metadata("ETH", 18)).unwrap();
+        assert!(feed.check_registered("BTC").is_ok());
+        assert_eq!(feed.register_asset_metadata(metadata("BTC", 8)), Err("Asset already registered"));
+        assert_eq!(feed.register_asset_metadata(metadata("XYZ", 39)), Err("Asset decimals exceed the maximum"));
+        
+        let addresses: std::collections::HashMap<String, String> =
+            [("ethereum".to_string(), "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599".to_string())].into_iter().collect();
+        let updated = feed.update_asset_metadata("BTC", None, Some("Bitcoin".to_string()), Some(addresses)).unwrap();
+        assert_eq!(updated.display_name, "Bitcoin");
+        assert_eq!(updated.decimals, 8);
+        assert_eq!(updated.addresses.len(), 1);
+        
+        // Deactivated assets are refused in strict mode and drop out of the decimals map
+        feed.deactivate_asset_metadata("ETH").unwrap();
+        assert_eq!(feed.check_registered("ETH"), Err("Asset is deactivated: ETH".to_string()));
+        assert_eq!(feed.asset_decimals().get("ETH"), None);
+        assert_eq!(feed.asset_decimals().get("BTC"), Some(&8));
+        
+        let symbols: Vec<&str> = feed.list_asset_metadata().iter().map(|m| m.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["BTC", "ETH"]);
+        
+        // Registering again reactivates it
+        feed.register_asset_metadata(metadata("ETH", 18)).unwrap();
+        assert!(feed.check_registered("ETH").is_ok());
+    }
+    
+    #[test]
+    fn test_strict_registry_blocks_aggregation() {
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        feed.external_oracles = vec!["oracle-1".to_string()];
+        feed.strict_asset_registry = true;
+        
+        let mock = |_: &str, _: &str| Some(50_000_00000000);
+        assert_eq!(feed.aggregate_price("BTC", 1000, mock), Err("Asset not registered: BTC".to_string()));
+        assert!(!feed.prices.contains_key("BTC"));
+        
+        feed.register_asset_metadata(AssetMetadata {
+            symbol: "BTC".to_string(),
+            decimals: 8,
+            display_name: "Bitcoin".to_string(),
+            addresses: std::collections::HashMap::new(),
+            active: true,
+            registered_at: 0,
+        }).unwrap();
+        assert_eq!(feed.aggregate_price("BTC", 1000, mock), Ok(50_000_00000000));
     }
 }
\ No newline at end of file
@@ -0,0 +1,132 @@
+//! Gradual rebalancing for One Capital Auto-Investing
+//!
+//! This module queues the slices of a rebalance spread over time (see
+//! `RebalanceEngine::create_gradual_operation`) until the scheduler job
+//! executes them, one slice per vault per interval.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::collections::HashMap;
+use l1x_sdk::prelude::*;
+
+use super::RebalanceOperation;
+
+/// Gradual rebalance contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"GRADUAL_REBALANCE";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GradualRebalanceContract {
+    queued: HashMap<String, Vec<RebalanceOperation>>, // Vault ID -> Slices not yet executed, in order
+}
+
+#[l1x_sdk::contract]
+impl GradualRebalanceContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            queued: HashMap::new(),
+        };
+
+        state.save()
+    }
+
+    /// Next slice of each vault whose scheduled time has come at `now`, sorted by vault
+    fn due_slices(&self, now: u64) -> Vec<RebalanceOperation> {
+        let mut due: Vec<RebalanceOperation> = self.queued.values()
+            .filter_map(|slices| slices.first())
+            .filter(|slice| slice.scheduled_at <= now)
+            .cloned()
+            .collect();
+        due.sort_by(|a, b| a.vault_id.cmp(&b.vault_id));
+        due
+    }
+
+    /// Drops an executed slice from the front of its vault's queue
+    fn pop_slice(&mut self, vault_id: &str, slice_id: &str) {
+        if let Some(slices) = self.queued.get_mut(vault_id) {
+            if slices.first().map(|slice| slice.id.as_str()) == Some(slice_id) {
+                slices.remove(0);
+            }
+            if slices.is_empty() {
+                self.queued.remove(vault_id);
+            }
+        }
+    }
+
+    /// Gets the slices of a vault's gradual rebalance that have not executed yet
+    pub fn get_queued_slices(vault_id: String) -> String {
+        let state = Self::load();
+
+        let slices = state.queued.get(&vault_id).cloned().unwrap_or_default();
+
+        serde_json::to_string(&slices)
+            .unwrap_or_else(|_| "Failed to serialize queued slices".to_string())
+    }
+}
+
+/// Queues the slices of a vault's gradual rebalance, replacing any still pending
+pub fn queue_slices(vault_id: &str, slices: Vec<RebalanceOperation>) {
+    let mut state = GradualRebalanceContract::load();
+
+    state.queued.insert(vault_id.to_string(), slices);
+    state.save();
+}
+
+/// Next due slice of each vault with a gradual rebalance queued
+pub fn due_slices(now: u64) -> Vec<RebalanceOperation> {
+    GradualRebalanceContract::load().due_slices(now)
+}
+
+/// Marks a slice as executed
+pub fn complete_slice(vault_id: &str, slice_id: &str) {
+    let mut state = GradualRebalanceContract::load();
+
+    state.pop_slice(vault_id, slice_id);
+    state.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rebalance::RebalanceEngine;
+
+    #[test]
+    fn test_one_slice_per_interval() {
+        let slices: Vec<RebalanceOperation> = RebalanceEngine::create_gradual_operation(
+            "gradual-1".to_string(),
+            vec![("BTC".to_string(), "USDC".to_string(), 1000)],
+            3 * 3600,
+            3,
+        ).into_iter().map(|slice| slice.with_vault_id("vault-1".to_string())).collect();
+        let start = slices[0].scheduled_at;
+
+        let mut state = GradualRebalanceContract { queued: HashMap::new() };
+        state.queued.insert("vault-1".to_string(), slices);
+
+        // Only the first slice is due, even once later ones have come due too
+        let due = state.due_slices(start + 2 * 3600);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, "gradual-1-slice-0");
+
+        state.pop_slice("vault-1", "gradual-1-slice-0");
+        assert_eq!(state.due_slices(start + 3599).len(), 0);
+        assert_eq!(state.due_slices(start + 3600)[0].id, "gradual-1-slice-1");
+
+        // Popping anything but the front slice is ignored
+        state.pop_slice("vault-1", "gradual-1-slice-2");
+        assert_eq!(state.queued["vault-1"].len(), 2);
+
+        state.pop_slice("vault-1", "gradual-1-slice-1");
+        state.pop_slice("vault-1", "gradual-1-slice-2");
+        assert!(state.queued.is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+//! Rebalance history for One Capital Auto-Investing
+//!
+//! This module persists executed rebalance operations (their transactions,
+//! statuses, gas costs and trigger strategy) so they can be queried after the
+//! rebalance has finished.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::collections::HashMap;
+use l1x_sdk::prelude::*;
+
+use super::{RebalanceOperation, RebalanceStatus, RetryOutcome};
+
+/// Default number of operations kept per vault
+pub const DEFAULT_MAX_HISTORY_PER_VAULT: usize = 50;
+
+/// Rebalance history contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"REBALANCE_HISTORY";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct RebalanceHistoryContract {
+    operations: HashMap<String, RebalanceOperation>, // Rebalance ID -> Operation
+    vault_operations: HashMap<String, Vec<String>>, // Vault ID -> Rebalance IDs (oldest first)
+    max_per_vault: usize,
+    admin: String,
+    swap_executors: Vec<String>, // Accounts allowed to report swap results (swap executor, XTalk relayer)
+}
+
+#[l1x_sdk::contract]
+impl RebalanceHistoryContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(admin: String) {
+        let mut state = Self::with_capacity(DEFAULT_MAX_HISTORY_PER_VAULT);
+        state.admin = admin;
+
+        state.save()
+    }
+
+    /// Creates an empty history keeping at most `max_per_vault` operations per vault
+    fn with_capacity(max_per_vault: usize) -> Self {
+        Self {
+            operations: HashMap::new(),
+            vault_operations: HashMap::new(),
+            max_per_vault,
+            admin: String::new(),
+            swap_executors: Vec::new(),
+        }
+    }
+
+    /// Stores an operation under its vault, evicting the oldest beyond the cap
+    ///
+    /// Fails rather than overwrite another vault's operation with the same ID.
+    fn insert(&mut self, operation: RebalanceOperation) -> Result<(), &'static str> {
+        let vault_id = operation.vault_id.clone().unwrap_or_default();
+        if self.operations.get(&operation.id).is_some_and(|existing| existing.vault_id.as_deref().unwrap_or_default() != vault_id) {
+            return Err("Rebalance ID already recorded for another vault");
+        }
+
+        let ids = self.vault_operations.entry(vault_id).or_insert_with(Vec::new);
+
+        // Re-recording an operation replaces it in place
+        if !ids.contains(&operation.id) {
+            ids.push(operation.id.clone());
+        }
+
+        if ids.len() > self.max_per_vault {
+            let excess = ids.len() - self.max_per_vault;
+            for evicted in ids.drain(..excess) {
+                self.operations.remove(&evicted);
+            }
+        }
+
+        self.operations.insert(operation.id.clone(), operation);
+        Ok(())
+    }
+
+    /// Collects a vault's operations, newest first, in `[offset, offset + limit)`
+    fn history_page(&self, vault_id: &str, offset: u32, limit: u32) -> Vec<&RebalanceOperation> {
+        self.vault_operations.get(vault_id)
+            .map(|ids| {
+                ids.iter()
+                    .rev()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .filter_map(|id| self.operations.get(id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets a page of a vault's rebalance operations, newest first
+    pub fn get_rebalance_history(vault_id: String, offset: u32, limit: u32) -> String {
+        let state = Self::load();
+
+        let history = state.history_page(&vault_id, offset, limit);
+
+        serde_json::to_string(&history)
+            .unwrap_or_else(|_| "Failed to serialize rebalance history".to_string())
+    }
+
+    /// Registers an account allowed to report swap results
+    pub fn add_swap_executor(executor: String) -> String {
+        let mut state = Self::load();
+        state.assert_admin();
+
+        if !state.swap_executors.contains(&executor) {
+            state.swap_executors.push(executor.clone());
+        }
+        state.save();
+
+        format!("Swap executor {} added", executor)
+    }
+
+    /// Removes an account from the swap executors
+    pub fn remove_swap_executor(executor: String) -> String {
+        let mut state = Self::load();
+        state.assert_admin();
+
+        state.swap_executors.retain(|existing| *existing != executor);
+        state.save();
+
+        format!("Swap executor {} removed", executor)
+    }
+
+    /// Re-submits the failed transactions of a stored operation that still have retries left
+    ///
+    /// Only the vault owner may retry. The vault is locked again until the
+    /// resubmitted swaps report back through `handle_swap_callback`.
+    pub fn retry_failed(rebalance_id: String) -> String {
+        let mut state = Self::load();
+
+        let operation = state.operations.get_mut(&rebalance_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", rebalance_id));
+        let vault_id = operation.vault_id.clone().unwrap_or_default();
+
+        if crate::custodial_vault::vault_owner(&vault_id).as_deref() != Some(l1x_sdk::env::caller().as_str()) {
+            panic!("Only the vault owner can retry a rebalance");
+        }
+
+        let outcomes = operation.retry_failed();
+        let operation = operation.clone();
+
+        if !operation.is_resolved() {
+            crate::custodial_vault::acquire_rebalance_lock(&vault_id, &rebalance_id)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+
+        state.save();
+
+        for (index, outcome) in &outcomes {
+            if *outcome == RetryOutcome::Exhausted {
+                let transaction = &operation.transactions[*index];
+                crate::events::emit_transaction_failed_permanently_event(
+                    &vault_id, &rebalance_id, *index, transaction.error.as_deref().unwrap_or_default(),
+                );
+            }
+        }
+
+        serde_json::to_string(&operation)
+            .unwrap_or_else(|_| "Failed to serialize rebalance operation".to_string())
+    }
+
+    /// Finalizes a submitted rebalance transaction once its swap has settled
+    ///
+    /// When the last in-flight transaction resolves, the operation's status is
+    /// settled and the completed or failed event is emitted.
+    pub fn handle_swap_callback(rebalance_id: String, tx_index: u32, success: bool, received_amount: u128) -> String {
+        let mut state = Self::load();
+
+        if !state.swap_executors.contains(&l1x_sdk::env::caller()) {
+            panic!("Only a registered swap executor can report swap results");
+        }
+
+        let operation = state.operations.get_mut(&rebalance_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", rebalance_id));
+
+        let resolved = operation.complete_transaction(tx_index as usize, success, received_amount)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let operation = operation.clone();
+
+        state.save();
+
+        let vault_id = operation.vault_id.clone().unwrap_or_default();
+        let transaction = &operation.transactions[tx_index as usize];
+        if success && transaction.retry_count > 0 {
+            crate::events::emit_transaction_retry_succeeded_event(
+                &vault_id, &rebalance_id, tx_index as usize, transaction.retry_count,
+            );
+        }
+
+        if resolved {
+            crate::custodial_vault::release_rebalance_lock(&vault_id, &operation.id);
+
+            match operation.status {
+                RebalanceStatus::Failed => crate::events::emit_rebalance_failed_event(
+                    &vault_id, "All rebalance swaps failed",
+                ),
+                // The cancellation was reported when it was requested
+                RebalanceStatus::Cancelled => {},
+                _ => crate::events::emit_rebalance_completed_event(
+                    &vault_id, operation.transactions.len(), operation.total_cost, operation.truncated_by_cap,
+                ),
+            }
+        }
+
+        serde_json::to_string(&operation)
+            .unwrap_or_else(|_| "Failed to serialize rebalance operation".to_string())
+    }
+
+    /// Cancels the legs of a rebalance that have not been submitted yet
+    ///
+    /// Only the vault owner may cancel. Completed legs stay completed and the
+    /// vault's drift state is rolled back for the cancelled ones. Legs already
+    /// in flight keep the vault locked until their swap callbacks settle them.
+    pub fn cancel_rebalance(rebalance_id: String) -> String {
+        let mut state = Self::load();
+
+        let operation = state.operations.get_mut(&rebalance_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", rebalance_id));
+        let vault_id = operation.vault_id.clone()
+            .unwrap_or_else(|| panic!("Rebalance operation {} has no vault", rebalance_id));
+
+        let caller = l1x_sdk::env::caller();
+        if crate::custodial_vault::vault_owner(&vault_id).as_deref() != Some(caller.as_str()) {
+            panic!("Only the vault owner can cancel a rebalance");
+        }
+
+        let cancelled = operation.cancel()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let resolved = operation.is_resolved();
+        state.save();
+
+        crate::custodial_vault::revert_cancelled_rebalance(&vault_id, &rebalance_id, &cancelled, resolved);
+        crate::events::emit_rebalance_cancelled_event(&vault_id, &rebalance_id, cancelled.len(), &caller);
+
+        format!("Cancelled {} transactions of rebalance {}", cancelled.len(), rebalance_id)
+    }
+
+    /// Gets the before/after allocations of a rebalance and the drift it removed
+    ///
+    /// Drift is measured against the vault's current targets.
+    pub fn get_rebalance_report(rebalance_id: String) -> String {
+        let state = Self::load();
+
+        let operation = state.operations.get(&rebalance_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", rebalance_id));
+        let vault_id = operation.vault_id.clone().unwrap_or_default();
+        let targets = crate::custodial_vault::vault_target_allocations(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+
+        serde_json::to_string(&operation.report(&targets))
+            .unwrap_or_else(|_| "Failed to serialize rebalance report".to_string())
+    }
+
+    /// Gets how many of a rebalance operation's transactions have completed, failed or are pending
+    pub fn get_rebalance_progress(operation_id: String) -> String {
+        let state = Self::load();
+
+        let operation = state.operations.get(&operation_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", operation_id));
+
+        serde_json::to_string(&operation.get_progress())
+            .unwrap_or_else(|_| "Failed to serialize rebalance progress".to_string())
+    }
+
+    /// Gets a single rebalance operation by ID
+    pub fn get_rebalance_operation(rebalance_id: String) -> String {
+        let state = Self::load();
+
+        let operation = state.operations.get(&rebalance_id)
+            .unwrap_or_else(|| panic!("Rebalance operation not found: {}", rebalance_id));
+
+        serde_json::to_string(operation)
+            .unwrap_or_else(|_| "Failed to serialize rebalance operation".to_string())
+    }
+}
+
+impl RebalanceHistoryContract {
+    /// Panics unless the caller is the contract admin
+    fn assert_admin(&self) {
+        if self.admin != l1x_sdk::env::caller() {
+            panic!("Only admin can manage swap executors");
+        }
+    }
+}
+
+/// Persists a completed or failed rebalance operation
+pub fn record_operation(operation: RebalanceOperation) {
+    let mut state = RebalanceHistoryContract::load();
+
+    let rebalance_id = operation.id.clone();
+    if let Err(err) = state.insert(operation) {
+        l1x_sdk::env::log(&format!("Rebalance {} not recorded: {}", rebalance_id, err));
+        return;
+    }
+    state.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rebalance::{RebalanceEngine, RebalanceStrategy};
+
+    fn operation(id: &str, vault_id: &str) -> RebalanceOperation {
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            id.to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("BTC".to_string(), "ETH".to_string(), 100)],
+        ).with_vault_id(vault_id.to_string());
+        operation.execute().unwrap();
+        operation
+    }
+
+    #[test]
+    fn test_history_pagination_and_eviction() {
+        let mut history = RebalanceHistoryContract::with_capacity(3);
+
+        for i in 0..5 {
+            history.insert(operation(&format!("rebalance-{}", i), "vault-1")).unwrap();
+        }
+        history.insert(operation("rebalance-other", "vault-2")).unwrap();
+
+        // Only the newest three are kept, newest first
+        let page: Vec<&str> = history.history_page("vault-1", 0, 10).iter().map(|op| op.id.as_str()).collect();
+        assert_eq!(page, vec!["rebalance-4", "rebalance-3", "rebalance-2"]);
+        assert!(!history.operations.contains_key("rebalance-0"));
+
+        let page: Vec<&str> = history.history_page("vault-1", 1, 1).iter().map(|op| op.id.as_str()).collect();
+        assert_eq!(page, vec!["rebalance-3"]);
+
+        // Stored operations keep their transactions, statuses and costs
+        let stored = &history.operations["rebalance-4"];
+        assert_eq!(stored.status, RebalanceStatus::Completed);
+        assert_eq!(stored.strategy, RebalanceStrategy::Threshold);
+        assert_eq!(stored.transactions.len(), 1);
+        assert!(stored.transactions[0].gas_cost.is_some());
+        assert!(stored.total_cost.is_some());
+
+        assert_eq!(history.history_page("vault-2", 0, 10).len(), 1);
+        assert!(history.history_page("vault-3", 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_history_keeps_vaults_apart() {
+        let mut history = RebalanceHistoryContract::with_capacity(3);
+        history.insert(operation("rebalance-1", "vault-1")).unwrap();
+
+        // Re-recording the vault's own operation replaces it; another vault can't take the ID
+        history.insert(operation("rebalance-1", "vault-1")).unwrap();
+        assert!(history.insert(operation("rebalance-1", "vault-2")).is_err());
+
+        assert_eq!(history.operations["rebalance-1"].vault_id.as_deref(), Some("vault-1"));
+        assert_eq!(history.history_page("vault-1", 0, 10).len(), 1);
+        assert!(history.history_page("vault-2", 0, 10).is_empty());
+    }
+}
@@ -5,6 +5,8 @@
 //! scheduled rebalancing, optimal transaction planning, and execution.
 
 pub mod scheduled;
+pub mod history;
+pub mod gradual;
 
 use serde::{Deserialize, Serialize};
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -23,8 +25,49 @@ pub enum RebalanceStatus {
     /// Operation was completed successfully
     Completed,
     
+    /// Some transactions completed while others remain failed
+    PartiallyCompleted,
+    
     /// Operation failed
     Failed,
+    
+    /// Cancelled before it finished; legs that had already executed stay completed
+    Cancelled,
+}
+
+/// Default number of times a failed rebalance transaction may be retried
+pub const DEFAULT_MAX_RETRIES: u8 = 3;
+
+fn default_max_retries() -> u8 {
+    DEFAULT_MAX_RETRIES
+}
+
+/// Maximum slippage allowed on rebalance swaps (in basis points)
+pub const DEFAULT_SWAP_SLIPPAGE_BPS: u32 = 50;
+
+/// Base gas cost of a rebalance operation
+pub const BASE_GAS_COST: u128 = 1_000_000;
+
+/// Gas cost of each rebalance transaction
+pub const GAS_COST_PER_TX: u128 = 2_500_000;
+
+/// Suggested maximum cost of a trade relative to its value (in basis points)
+pub const DEFAULT_MAX_TRADE_COST_BP: u32 = 2500;
+
+/// Largest single swap allowed relative to the vault's value (in basis points)
+pub const MAX_SWAP_SHARE_BP: u32 = 3000;
+
+/// Result of retrying one failed transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetryOutcome {
+    /// The swap was submitted again and awaits its callback
+    Resubmitted,
+    
+    /// The retry failed but further retries are allowed
+    Failed,
+    
+    /// The retry failed and no retries are left
+    Exhausted,
 }
 
 /// Rebalance strategy
@@ -38,6 +81,26 @@ pub enum RebalanceStrategy {
     
     /// Manual rebalancing (user-initiated)
     Manual,
+    
+    /// Threshold-based rebalancing with the threshold scaled by recent volatility
+    Adaptive,
+    
+    /// One slice of a rebalance spread evenly over `total_duration_seconds`
+    Gradual {
+        total_duration_seconds: u64,
+        slices: u32,
+    },
+}
+
+/// Whether a rebalance moves funds or only rehearses its swaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum RebalanceMode {
+    /// Swaps are executed
+    #[default]
+    Live,
+    
+    /// Swaps are logged but never executed, at zero cost
+    Simulate,
 }
 
 /// Rebalance transaction
@@ -63,6 +126,72 @@ pub struct RebalanceTransaction {
     
     /// Gas cost of the transaction
     pub gas_cost: Option<u128>,
+    
+    /// Maximum number of retries after the first failed attempt
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u8,
+    
+    /// Number of retries attempted so far
+    #[serde(default)]
+    pub retry_count: u8,
+    
+    /// Amount of the target asset received, reported by the swap callback
+    #[serde(default)]
+    pub received_amount: Option<u128>,
+    
+    /// Planned trade this transaction is a slice of, when a large trade was split
+    #[serde(default)]
+    pub parent_id: Option<String>,
+}
+
+impl RebalanceTransaction {
+    /// Whether the transaction failed and may still be retried
+    pub fn can_retry(&self) -> bool {
+        self.status == RebalanceStatus::Failed && self.retry_count < self.max_retries
+    }
+}
+
+/// A planned trade dropped because it costs too much relative to its value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct SuppressedTrade {
+    /// Source asset ID
+    pub source_asset: String,
+    
+    /// Target asset ID
+    pub target_asset: String,
+    
+    /// Amount that would have been swapped
+    pub amount: u128,
+    
+    /// Estimated swap fee plus gas
+    pub estimated_cost: u128,
+    
+    /// Estimated cost relative to the amount (in basis points)
+    pub cost_ratio_bp: u32,
+}
+
+/// Problem found when checking a rebalance operation before it executes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionValidationError {
+    /// The operation sells more of an asset than is available
+    InsufficientLiquidity {
+        asset: String,
+        required: u128,
+        available: u128,
+    },
+    
+    /// A single swap moves more than `MAX_SWAP_SHARE_BP` of the vault's value
+    SwapTooLarge {
+        index: usize,
+        amount: u128,
+        max_amount: u128,
+    },
+    
+    /// The same pair is traded by more than one trade (children of one split trade excepted)
+    DuplicatePair {
+        source_asset: String,
+        target_asset: String,
+    },
 }
 
 /// Rebalance operation that manages a set of transactions
@@ -88,19 +217,105 @@ pub struct RebalanceOperation {
     
     /// Total cost of all transactions
     pub total_cost: Option<u128>,
+    
+    /// Whether a rebalance budget cap cut the planned transactions short
+    #[serde(default)]
+    pub truncated_by_cap: bool,
+    
+    /// Planned trades dropped because their cost outweighed the drift they corrected
+    #[serde(default)]
+    pub suppressed_trades: Vec<SuppressedTrade>,
+    
+    /// Timestamp the operation should be executed at
+    #[serde(default)]
+    pub scheduled_at: u64,
+    
+    /// Current allocations (in basis points) before the operation executed
+    #[serde(default)]
+    pub pre_allocations: Vec<(String, u32)>,
+    
+    /// Current allocations (in basis points) after the operation executed
+    #[serde(default)]
+    pub post_allocations: Vec<(String, u32)>,
+    
+    /// Whether the operation only rehearsed its swaps (`RebalanceMode::Simulate`)
+    #[serde(default)]
+    pub simulated: bool,
+    
+    /// `AllocationSet::canonical_hash` of the allocation targets the operation was planned against
+    #[serde(default)]
+    pub allocation_config_hash: [u8; 32],
+    
+    /// Protocol fee taken out of the vault's value for the operation's trades
+    #[serde(default)]
+    pub protocol_fee: u128,
+}
+
+/// Completion status of a rebalance operation's transactions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RebalanceProgress {
+    /// Number of transactions
+    pub total: u32,
+    
+    /// Transactions that completed
+    pub completed: u32,
+    
+    /// Transactions that failed
+    pub failed: u32,
+    
+    /// Transactions not executed or still awaiting their swap callback
+    pub pending: u32,
+    
+    /// Gas cost of the transactions executed so far
+    pub total_cost_so_far: u128,
+}
+
+/// Before/after view of a rebalance operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebalanceReport {
+    /// Rebalance operation ID
+    pub rebalance_id: String,
+    
+    /// Status of the operation
+    pub status: RebalanceStatus,
+    
+    /// Current allocations before the operation
+    pub pre_allocations: Vec<(String, u32)>,
+    
+    /// Current allocations after the operation
+    pub post_allocations: Vec<(String, u32)>,
+    
+    /// Sum of every asset's drift from target before the operation (in basis points)
+    pub drift_before_bp: u32,
+    
+    /// Sum of every asset's drift from target after the operation (in basis points)
+    pub drift_after_bp: u32,
+    
+    /// Drift removed by the operation (in basis points, negative if it grew)
+    pub drift_reduction_bp: i64,
 }
 
 impl RebalanceOperation {
     /// Creates a new rebalance operation
     pub fn new(id: String, strategy: RebalanceStrategy) -> Self {
+        let created_at = l1x_sdk::env::block_timestamp();
+        
         Self {
             id,
             vault_id: None,
             strategy,
-            created_at: l1x_sdk::env::block_timestamp(),
+            created_at,
             transactions: Vec::new(),
             status: RebalanceStatus::Pending,
             total_cost: None,
+            truncated_by_cap: false,
+            suppressed_trades: Vec::new(),
+            scheduled_at: created_at,
+            pre_allocations: Vec::new(),
+            post_allocations: Vec::new(),
+            simulated: false,
+            allocation_config_hash: [0; 32],
+            protocol_fee: 0,
         }
     }
     
@@ -110,6 +325,18 @@ impl RebalanceOperation {
         self
     }
     
+    /// Sets the hash of the allocation configuration the operation was planned against
+    pub fn with_allocation_config_hash(mut self, hash: [u8; 32]) -> Self {
+        self.allocation_config_hash = hash;
+        self
+    }
+    
+    /// Sets whether the operation executes or only simulates its swaps
+    pub fn with_mode(mut self, mode: RebalanceMode) -> Self {
+        self.simulated = mode == RebalanceMode::Simulate;
+        self
+    }
+    
     /// Adds a transaction to the operation
     pub fn add_transaction(&mut self, source: String, target: String, amount: u128) {
         let transaction = RebalanceTransaction {
@@ -120,30 +347,50 @@ impl RebalanceOperation {
             tx_hash: None,
             error: None,
             gas_cost: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_count: 0,
+            received_amount: None,
+            parent_id: None,
         };
         
         self.transactions.push(transaction);
     }
     
     /// Executes all transactions in the operation
+    ///
+    /// Simulated operations log each swap and complete it at zero cost.
     pub fn execute(&mut self) -> Result<(), String> {
+        if self.simulated {
+            return self.execute_with(Self::simulate_transaction);
+        }
+        
+        self.execute_with(Self::execute_transaction)
+    }
+    
+    /// Executes all transactions using `swap` to perform each one
+    pub fn execute_with<F>(&mut self, mut swap: F) -> Result<(), String>
+    where
+        F: FnMut(&str, &RebalanceTransaction) -> Result<u128, String>,
+    {
         if self.transactions.is_empty() {
             return Ok(());
         }
         
         self.status = RebalanceStatus::InProgress;
-        let mut total_cost: u128 = 0;
         
-        for transaction in &mut self.transactions {
-            match self.execute_transaction(transaction) {
+        let vault_id = self.vault_id.clone().unwrap_or_default();
+        
+        for (index, transaction) in self.transactions.iter_mut().enumerate() {
+            match swap(&self.id, transaction) {
                 Ok(cost) => {
                     transaction.status = RebalanceStatus::Completed;
                     transaction.gas_cost = Some(cost);
-                    total_cost = total_cost.saturating_add(cost);
+                    crate::events::emit_transaction_progress_event(&vault_id, &self.id, index, transaction);
                 },
                 Err(e) => {
                     transaction.status = RebalanceStatus::Failed;
                     transaction.error = Some(e.clone());
+                    crate::events::emit_transaction_progress_event(&vault_id, &self.id, index, transaction);
                     
                     // Roll back or continue based on strategy
                     if self.strategy == RebalanceStrategy::Manual {
@@ -157,26 +404,245 @@ impl RebalanceOperation {
             }
         }
         
-        // Set overall status based on transaction results
+        self.update_status();
+        Ok(())
+    }
+    
+    /// Submits all transactions to the swap layer
+    ///
+    /// Submitted transactions stay InProgress until `complete_transaction` is
+    /// called for them from the swap callback.
+    pub fn submit(&mut self) -> Result<(), String> {
+        // Nothing is sent to the swap layer, so simulated swaps settle at once
+        if self.simulated {
+            return self.execute();
+        }
+        
+        self.submit_with(Self::submit_transaction)
+    }
+    
+    /// Submits all transactions using `submit` to obtain each swap's message or tx ID
+    pub fn submit_with<F>(&mut self, mut submit: F) -> Result<(), String>
+    where
+        F: FnMut(&str, &RebalanceTransaction) -> Result<String, String>,
+    {
+        if self.transactions.is_empty() {
+            return Ok(());
+        }
+        
+        self.status = RebalanceStatus::InProgress;
+        
+        let vault_id = self.vault_id.clone().unwrap_or_default();
+        
+        for (index, transaction) in self.transactions.iter_mut().enumerate() {
+            match submit(&self.id, transaction) {
+                Ok(tx_hash) => {
+                    transaction.status = RebalanceStatus::InProgress;
+                    transaction.tx_hash = Some(tx_hash);
+                    crate::events::emit_transaction_progress_event(&vault_id, &self.id, index, transaction);
+                },
+                Err(e) => {
+                    transaction.status = RebalanceStatus::Failed;
+                    transaction.error = Some(e.clone());
+                    crate::events::emit_transaction_progress_event(&vault_id, &self.id, index, transaction);
+                    
+                    if self.strategy == RebalanceStrategy::Manual {
+                        self.status = RebalanceStatus::Failed;
+                        return Err(format!("Transaction failed: {}", e));
+                    }
+                    
+                    l1x_sdk::env::log(&format!("Rebalance transaction failed but continuing: {}", e));
+                }
+            }
+        }
+        
+        if self.is_resolved() {
+            self.update_status();
+        }
+        
+        Ok(())
+    }
+    
+    /// Finalizes a submitted transaction from its swap callback
+    ///
+    /// Returns true once every transaction has resolved and the operation
+    /// status has been settled.
+    pub fn complete_transaction(&mut self, index: usize, success: bool, received_amount: u128) -> Result<bool, &'static str> {
+        let transaction = self.transactions.get_mut(index)
+            .ok_or("Transaction index out of range")?;
+            
+        if transaction.status != RebalanceStatus::InProgress {
+            return Err("Transaction is not awaiting a swap callback");
+        }
+        
+        if success {
+            transaction.status = RebalanceStatus::Completed;
+            transaction.received_amount = Some(received_amount);
+            transaction.error = None;
+        } else {
+            transaction.status = RebalanceStatus::Failed;
+            transaction.error = Some("Swap failed on the swap layer".to_string());
+        }
+        
+        crate::events::emit_transaction_progress_event(
+            self.vault_id.as_deref().unwrap_or_default(),
+            &self.id,
+            index,
+            &self.transactions[index],
+        );
+        
+        if !self.is_resolved() {
+            return Ok(false);
+        }
+        
+        self.update_status();
+        Ok(true)
+    }
+    
+    /// Counts the operation's transactions by status
+    pub fn get_progress(&self) -> RebalanceProgress {
+        let mut progress = RebalanceProgress {
+            total: self.transactions.len() as u32,
+            ..RebalanceProgress::default()
+        };
+        
+        for transaction in &self.transactions {
+            match transaction.status {
+                RebalanceStatus::Completed => progress.completed += 1,
+                RebalanceStatus::Failed => progress.failed += 1,
+                RebalanceStatus::Pending | RebalanceStatus::InProgress => progress.pending += 1,
+                _ => {},
+            }
+            
+            progress.total_cost_so_far = progress.total_cost_so_far.saturating_add(transaction.gas_cost.unwrap_or(0));
+        }
+        
+        progress
+    }
+    
+    /// Builds the before/after report of the operation against `targets`
+    pub fn report(&self, targets: &[(String, u32)]) -> RebalanceReport {
+        let drift_before_bp = aggregate_drift(&self.pre_allocations, targets);
+        let drift_after_bp = aggregate_drift(&self.post_allocations, targets);
+        
+        RebalanceReport {
+            rebalance_id: self.id.clone(),
+            status: self.status,
+            pre_allocations: self.pre_allocations.clone(),
+            post_allocations: self.post_allocations.clone(),
+            drift_before_bp,
+            drift_after_bp,
+            drift_reduction_bp: drift_before_bp as i64 - drift_after_bp as i64,
+        }
+    }
+    
+    /// Cancels every transaction that has not been submitted yet
+    ///
+    /// Only Pending legs are cancelled. Legs already submitted stay
+    /// InProgress until their swap callback settles them, and the operation
+    /// only becomes Cancelled once they have (see `is_resolved`). Returns
+    /// the `(source, target, amount)` of each cancelled leg.
+    pub fn cancel(&mut self) -> Result<Vec<(String, String, u128)>, &'static str> {
+        let mut cancelled = Vec::new();
+        for transaction in &mut self.transactions {
+            if transaction.status == RebalanceStatus::Pending {
+                transaction.status = RebalanceStatus::Cancelled;
+                cancelled.push((transaction.source_asset.clone(), transaction.target_asset.clone(), transaction.amount));
+            }
+        }
+        
+        if cancelled.is_empty() {
+            return Err("Rebalance operation can no longer be cancelled");
+        }
+        
+        if self.is_resolved() {
+            self.update_status();
+        }
+        
+        Ok(cancelled)
+    }
+    
+    /// Whether no transaction is still waiting for a swap callback
+    pub fn is_resolved(&self) -> bool {
+        self.transactions.iter().all(|t| t.status != RebalanceStatus::InProgress)
+    }
+    
+    /// Re-submits only the failed transactions that still have retries left
+    ///
+    /// Resubmitted transactions stay InProgress until `complete_transaction`
+    /// is called for them from the swap callback. Returns the index and
+    /// outcome of every transaction retried.
+    pub fn retry_failed(&mut self) -> Vec<(usize, RetryOutcome)> {
+        self.retry_failed_with(Self::submit_transaction)
+    }
+    
+    /// Re-submits failed transactions using `submit` to obtain each swap's message or tx ID
+    pub fn retry_failed_with<F>(&mut self, mut submit: F) -> Vec<(usize, RetryOutcome)>
+    where
+        F: FnMut(&str, &RebalanceTransaction) -> Result<String, String>,
+    {
+        let mut outcomes = Vec::new();
+        
+        for (index, transaction) in self.transactions.iter_mut().enumerate() {
+            if !transaction.can_retry() {
+                continue;
+            }
+            
+            transaction.retry_count += 1;
+            
+            let outcome = match submit(&self.id, transaction) {
+                Ok(tx_hash) => {
+                    transaction.status = RebalanceStatus::InProgress;
+                    transaction.tx_hash = Some(tx_hash);
+                    transaction.error = None;
+                    RetryOutcome::Resubmitted
+                },
+                Err(e) => {
+                    transaction.error = Some(e);
+                    if transaction.retry_count >= transaction.max_retries {
+                        RetryOutcome::Exhausted
+                    } else {
+                        RetryOutcome::Failed
+                    }
+                },
+            };
+            
+            outcomes.push((index, outcome));
+        }
+        
+        if !self.is_resolved() {
+            self.status = RebalanceStatus::InProgress;
+        } else if !outcomes.is_empty() {
+            self.update_status();
+        }
+        
+        outcomes
+    }
+    
+    /// Sets the overall status and total cost from the transaction results
+    fn update_status(&mut self) {
         let all_completed = self.transactions.iter().all(|t| t.status == RebalanceStatus::Completed);
         let any_completed = self.transactions.iter().any(|t| t.status == RebalanceStatus::Completed);
         
-        if all_completed {
+        if self.transactions.iter().any(|t| t.status == RebalanceStatus::Cancelled) {
+            self.status = RebalanceStatus::Cancelled;
+        } else if all_completed {
             self.status = RebalanceStatus::Completed;
         } else if any_completed {
-            // Partial success
-            self.status = RebalanceStatus::Completed;
+            self.status = RebalanceStatus::PartiallyCompleted;
             l1x_sdk::env::log("Rebalance operation partially completed");
         } else {
             self.status = RebalanceStatus::Failed;
         }
         
+        let total_cost = self.transactions.iter()
+            .filter_map(|t| t.gas_cost)
+            .fold(0u128, |total, cost| total.saturating_add(cost));
         self.total_cost = Some(total_cost);
-        Ok(())
     }
     
     /// Executes a single transaction
-    fn execute_transaction(&self, transaction: &RebalanceTransaction) -> Result<u128, String> {
+    fn execute_transaction(operation_id: &str, transaction: &RebalanceTransaction) -> Result<u128, String> {
         // In a real implementation, this would use a swap service or DEX
         // For now, we'll simulate success with a fixed gas cost
         
@@ -185,24 +651,163 @@ impl RebalanceOperation {
             transaction.amount, 
             transaction.source_asset, 
             transaction.target_asset,
-            self.id
+            operation_id
         ));
         
-        // Simulate transaction execution
-        let tx_hash = format!("tx-{}-{}", self.id, l1x_sdk::env::block_timestamp());
-        
         // Fixed gas cost for simulation
         let gas_cost = 2_500_000;
         
         Ok(gas_cost)
     }
+    
+    /// Logs a single transaction instead of executing it
+    fn simulate_transaction(operation_id: &str, transaction: &RebalanceTransaction) -> Result<u128, String> {
+        l1x_sdk::env::log(&format!(
+            "Simulated swap for {}: {} {} to {}",
+            operation_id,
+            transaction.amount,
+            transaction.source_asset,
+            transaction.target_asset
+        ));
+        
+        Ok(0)
+    }
+    
+    /// Submits a single transaction to the swap layer
+    ///
+    /// Same-chain swaps go through the cross-chain contract's swap requests;
+    /// legs between chains are sent as XTalk swap messages.
+    fn submit_transaction(operation_id: &str, transaction: &RebalanceTransaction) -> Result<String, String> {
+        use crate::cross_chain::Blockchain;
+        
+        let source_chain = Blockchain::native_for_asset(&transaction.source_asset);
+        let target_chain = Blockchain::native_for_asset(&transaction.target_asset);
+        let recipient = l1x_sdk::env::contract_instance_address().to_string();
+        
+        if source_chain == target_chain {
+            return crate::cross_chain::submit_swap_request(
+                operation_id,
+                source_chain,
+                &transaction.source_asset,
+                &transaction.target_asset,
+                transaction.amount,
+                DEFAULT_SWAP_SLIPPAGE_BPS,
+                &recipient,
+            );
+        }
+        
+        let swap_request = crate::xtalk::XTalkSwapRequest {
+            source_asset: transaction.source_asset.clone(),
+            target_asset: transaction.target_asset.clone(),
+            amount: transaction.amount,
+            slippage_bps: DEFAULT_SWAP_SLIPPAGE_BPS,
+            recipient,
+        };
+        
+        crate::xtalk::XTalkClient::execute_swap(&swap_request, target_chain.chain_id())
+            .map_err(|e| format!("XTalk swap failed: {:?}", e))
+    }
+}
+
+/// Sum over `targets` of each asset's distance from its target percentage
+///
+/// Assets missing from `allocations` count as held at 0%.
+pub fn aggregate_drift(allocations: &[(String, u32)], targets: &[(String, u32)]) -> u32 {
+    targets.iter()
+        .map(|(asset_id, target)| {
+            let current = allocations.iter()
+                .find(|(id, _)| id == asset_id)
+                .map(|(_, percentage)| *percentage)
+                .unwrap_or(0);
+            current.abs_diff(*target)
+        })
+        .sum()
+}
+
+/// Confidence given to a recommendation when the asset's price change is unknown
+pub const NEUTRAL_CONFIDENCE: u8 = 50;
+
+/// 24-hour price move at or beyond which a recommendation gets no confidence (in basis points)
+pub const ZERO_CONFIDENCE_CHANGE_BP: u32 = 1000;
+
+/// Confidence (0-100) that an asset's measured drift is worth acting on
+///
+/// Derived from the asset's 24-hour price change: a stable price makes the
+/// drift measurement reliable, while a large move may well reverse on its own.
+/// Confidence falls linearly from 100 to 0 as the move grows to
+/// `ZERO_CONFIDENCE_CHANGE_BP`.
+pub fn compute_confidence(asset_id: &str, price_feed_contract: &crate::price_feed::PriceFeedContract) -> u8 {
+    let change_bp = match price_feed_contract.price_change_24h_bp(asset_id, l1x_sdk::env::block_timestamp()) {
+        Some(change_bp) => change_bp.unsigned_abs().min(ZERO_CONFIDENCE_CHANGE_BP),
+        None => return NEUTRAL_CONFIDENCE,
+    };
+    
+    (100 - change_bp * 100 / ZERO_CONFIDENCE_CHANGE_BP) as u8
 }
 
 /// Rebalance engine for creating and executing rebalance operations
 pub struct RebalanceEngine;
 
 impl RebalanceEngine {
+    /// Copy of `allocations` with current percentages recomputed from `current_values`
+    ///
+    /// Assets missing from `current_values` are treated as holding nothing.
+    fn with_live_percentages(
+        allocations: &crate::allocation::AllocationSet,
+        current_values: &[(String, u128)],
+        total_value: u128,
+    ) -> crate::allocation::AllocationSet {
+        let values: HashMap<&str, u128> = current_values.iter()
+            .map(|(asset_id, value)| (asset_id.as_str(), *value))
+            .collect();
+            
+        let mut live = allocations.clone();
+        for allocation in &mut live.allocations {
+            let value = values.get(allocation.asset_id.as_str()).copied().unwrap_or(0);
+            let percentage = if total_value == 0 {
+                0
+            } else {
+                (value.saturating_mul(10000) / total_value).min(10000) as u32
+            };
+            allocation.update_current_percentage(percentage);
+        }
+        
+        live
+    }
+    
+    /// Checks if `allocations` needs rebalancing at the given live values
+    ///
+    /// The stored current percentages are ignored; drift, bounds and the cash
+    /// buffer are evaluated on percentages derived from `current_values`.
+    pub fn needs_rebalancing(
+        allocations: &crate::allocation::AllocationSet,
+        current_values: &[(String, u128)],
+        total_value: u128,
+    ) -> bool {
+        Self::with_live_percentages(allocations, current_values, total_value).needs_rebalancing()
+    }
+    
+    /// Plans the `(source, target, amount)` swaps that bring the live values back to target
+    ///
+    /// Returns no transactions if the plan cannot be computed (e.g. the targets
+    /// no longer sum to 100%).
+    pub fn generate_rebalance_transactions(
+        allocations: &crate::allocation::AllocationSet,
+        current_values: &[(String, u128)],
+        total_value: u128,
+    ) -> Vec<(String, String, u128)> {
+        Self::with_live_percentages(allocations, current_values, total_value)
+            .calculate_rebalance_transactions(current_values, total_value)
+            .unwrap_or_else(|e| {
+                l1x_sdk::env::log(&format!("Failed to plan rebalance transactions: {}", e));
+                Vec::new()
+            })
+    }
+    
     /// Creates a new rebalance operation from transactions
+    ///
+    /// Consecutive transactions between the same pair are the children of one
+    /// split trade (see `AllocationSet::split_for_impact`) and share a parent ID.
     pub fn create_rebalance_operation(
         id: String,
         strategy: RebalanceStrategy,
@@ -214,16 +819,340 @@ impl RebalanceEngine {
             operation.add_transaction(source, target, amount);
         }
         
+        let mut start = 0;
+        let mut parent_index = 0;
+        while start < operation.transactions.len() {
+            let mut end = start + 1;
+            while end < operation.transactions.len()
+                && operation.transactions[end].source_asset == operation.transactions[start].source_asset
+                && operation.transactions[end].target_asset == operation.transactions[start].target_asset
+            {
+                end += 1;
+            }
+            
+            if end - start > 1 {
+                let parent_id = format!("{}-trade-{}", operation.id, parent_index);
+                for transaction in &mut operation.transactions[start..end] {
+                    transaction.parent_id = Some(parent_id.clone());
+                }
+            }
+            
+            parent_index += 1;
+            start = end;
+        }
+        
         operation
     }
     
+    /// Splits a rebalance into `slices` operations executed over `total_duration_seconds`
+    ///
+    /// Each slice carries `1/slices` of every transaction (the last slice takes
+    /// any remainder) and is scheduled one interval after the previous one,
+    /// starting now. Slice IDs are `{id}-slice-{n}`.
+    pub fn create_gradual_operation(
+        id: String,
+        total_transactions: Vec<(String, String, u128)>,
+        total_duration_seconds: u64,
+        slices: u32,
+    ) -> Vec<RebalanceOperation> {
+        let slices = slices.max(1);
+        let interval = total_duration_seconds / slices as u64;
+        let strategy = RebalanceStrategy::Gradual { total_duration_seconds, slices };
+        
+        (0..slices).map(|slice| {
+            let transactions = total_transactions.iter()
+                .map(|(source, target, amount)| {
+                    let per_slice = amount / slices as u128;
+                    let slice_amount = if slice == slices - 1 {
+                        amount - per_slice * (slices as u128 - 1)
+                    } else {
+                        per_slice
+                    };
+                    (source.clone(), target.clone(), slice_amount)
+                })
+                .filter(|(_, _, amount)| *amount > 0)
+                .collect();
+                
+            let mut operation = Self::create_rebalance_operation(format!("{}-slice-{}", id, slice), strategy, transactions);
+            operation.scheduled_at = operation.created_at.saturating_add(interval * slice as u64);
+            operation
+        }).collect()
+    }
+    
+    /// Creates a rebalance operation from USD value deltas, converting each to source-asset units
+    ///
+    /// `transactions` are `(source, target, value)` tuples as returned by
+    /// `AllocationSet::calculate_rebalance_transactions`, with values and
+    /// `prices` both scaled by 1e8. `decimals` gives each asset's number of
+    /// decimal places, so a value converts to `value * 10^decimals / price`
+    /// smallest units. Fails if a source asset has no price or decimals.
+    pub fn create_from_value_deltas(
+        id: String,
+        strategy: RebalanceStrategy,
+        transactions: Vec<(String, String, u128)>,
+        prices: &HashMap<String, u128>,
+        decimals: &HashMap<String, u8>,
+    ) -> Result<RebalanceOperation, String> {
+        let mut unit_transactions = Vec::with_capacity(transactions.len());
+        
+        for (source, target, value) in transactions {
+            let price = match prices.get(&source) {
+                Some(price) if *price > 0 => *price,
+                _ => return Err(format!("Price not found for asset {}", source)),
+            };
+            let asset_decimals = *decimals.get(&source)
+                .ok_or_else(|| format!("Decimals not found for asset {}", source))?;
+                
+            let units = 10u128.checked_pow(asset_decimals as u32)
+                .and_then(|scale| value.checked_mul(scale))
+                .map(|scaled| scaled / price)
+                .ok_or_else(|| format!("Overflow converting {} value to units", source))?;
+                
+            unit_transactions.push((source, target, units));
+        }
+        
+        Ok(Self::create_rebalance_operation(id, strategy, unit_transactions))
+    }
+    
+    /// Creates a rebalance operation from USD value deltas, taking each asset's
+    /// decimals from the price feed's asset registry
+    ///
+    /// Fails like `create_from_value_deltas` for a source asset that has no
+    /// price or is not an active registered asset.
+    pub fn create_from_registered_assets(
+        id: String,
+        strategy: RebalanceStrategy,
+        transactions: Vec<(String, String, u128)>,
+        prices: &HashMap<String, u128>,
+        price_feed: &crate::price_feed::PriceFeedContract,
+    ) -> Result<RebalanceOperation, String> {
+        Self::create_from_value_deltas(id, strategy, transactions, prices, &price_feed.asset_decimals())
+    }
+    
+    /// Limits planned `(source, target, amount)` transactions to a total of `cap`
+    ///
+    /// Transactions touching the assets with the largest drift (in `drifts`,
+    /// basis points by asset ID) are kept first; the transaction that crosses
+    /// the cap is scaled down to the remaining budget and the rest are dropped.
+    /// Returns the capped plan and whether anything was cut.
+    pub fn apply_budget_cap(
+        transactions: Vec<(String, String, u128)>,
+        drifts: &HashMap<String, u32>,
+        cap: u128,
+    ) -> (Vec<(String, String, u128)>, bool) {
+        let drift_of = |asset_id: &str| drifts.get(asset_id).copied().unwrap_or(0);
+        
+        // Stable sort keeps the planner's order between equally drifted transactions
+        let mut prioritized = transactions;
+        prioritized.sort_by_key(|(source, target, _)| std::cmp::Reverse(drift_of(source).max(drift_of(target))));
+        
+        let mut remaining = cap;
+        let mut truncated = false;
+        let mut capped = Vec::with_capacity(prioritized.len());
+        
+        for (source, target, amount) in prioritized {
+            if remaining == 0 {
+                truncated = true;
+                continue;
+            }
+            
+            if amount > remaining {
+                capped.push((source, target, remaining));
+                remaining = 0;
+                truncated = true;
+            } else {
+                remaining -= amount;
+                capped.push((source, target, amount));
+            }
+        }
+        
+        (capped, truncated)
+    }
+    
+    /// Drops planned `(source, target, amount)` transactions that cost more than they are worth
+    ///
+    /// Each transaction's cost is its swap fee (`fee_bps_per_pair` for the
+    /// source/target pair) plus `gas_per_tx`. Transactions whose cost exceeds
+    /// `max_cost_bp` of their amount are returned separately as suppressed.
+    pub fn plan_with_costs<F>(
+        transactions: Vec<(String, String, u128)>,
+        fee_bps_per_pair: F,
+        gas_per_tx: u128,
+        max_cost_bp: u32,
+    ) -> (Vec<(String, String, u128)>, Vec<SuppressedTrade>)
+    where
+        F: Fn(&str, &str) -> u32,
+    {
+        let mut planned = Vec::with_capacity(transactions.len());
+        let mut suppressed = Vec::new();
+        
+        for (source, target, amount) in transactions {
+            let fee = amount.saturating_mul(fee_bps_per_pair(&source, &target) as u128) / 10000;
+            let estimated_cost = fee.saturating_add(gas_per_tx);
+            let cost_ratio_bp = if amount == 0 {
+                u32::MAX
+            } else {
+                (estimated_cost.saturating_mul(10000) / amount).min(u32::MAX as u128) as u32
+            };
+            
+            if cost_ratio_bp > max_cost_bp {
+                suppressed.push(SuppressedTrade {
+                    source_asset: source,
+                    target_asset: target,
+                    amount,
+                    estimated_cost,
+                    cost_ratio_bp,
+                });
+            } else {
+                planned.push((source, target, amount));
+            }
+        }
+        
+        (planned, suppressed)
+    }
+    
+    /// Matches sellers with buyers so the plan needs as few swaps as possible
+    ///
+    /// Every group of sellers and buyers whose amounts net to zero can be settled
+    /// with one swap fewer than it has members, so the heuristic settles exact
+    /// pairs first, then one-to-two groups, and hands whatever is left to the
+    /// largest-first matcher. Amounts are never moved, only regrouped.
+    pub fn optimal_transactions(
+        sellers: Vec<(String, u128)>,
+        buyers: Vec<(String, u128)>,
+    ) -> Vec<(String, String, u128)> {
+        let mut sellers: Vec<(String, u128)> = sellers.into_iter().filter(|(_, amount)| *amount > 0).collect();
+        let mut buyers: Vec<(String, u128)> = buyers.into_iter().filter(|(_, amount)| *amount > 0).collect();
+        let mut transactions = Vec::new();
+        
+        // Pairs of equal amounts settle both legs in a single swap
+        let mut i = 0;
+        while i < sellers.len() {
+            match buyers.iter().position(|(_, amount)| *amount == sellers[i].1) {
+                Some(j) => {
+                    let (sell_asset, amount) = sellers.remove(i);
+                    let (buy_asset, _) = buyers.remove(j);
+                    transactions.push((sell_asset, buy_asset, amount));
+                },
+                None => i += 1,
+            }
+        }
+        
+        // One seller funding exactly two buyers
+        let mut i = 0;
+        while i < sellers.len() {
+            match Self::find_pair_summing_to(&buyers, sellers[i].1) {
+                Some((a, b)) => {
+                    let (sell_asset, _) = sellers.remove(i);
+                    let (second, second_amount) = buyers.remove(b);
+                    let (first, first_amount) = buyers.remove(a);
+                    transactions.push((sell_asset.clone(), first, first_amount));
+                    transactions.push((sell_asset, second, second_amount));
+                },
+                None => i += 1,
+            }
+        }
+        
+        // Two sellers funding exactly one buyer
+        let mut j = 0;
+        while j < buyers.len() {
+            match Self::find_pair_summing_to(&sellers, buyers[j].1) {
+                Some((a, b)) => {
+                    let (buy_asset, _) = buyers.remove(j);
+                    let (second, second_amount) = sellers.remove(b);
+                    let (first, first_amount) = sellers.remove(a);
+                    transactions.push((first, buy_asset.clone(), first_amount));
+                    transactions.push((second, buy_asset, second_amount));
+                },
+                None => j += 1,
+            }
+        }
+        
+        transactions.extend(crate::allocation::AllocationSet::match_largest_first(sellers, buyers));
+        transactions
+    }
+    
+    /// Indices `(a, b)` with `a < b` of two legs whose amounts add up to `target`
+    fn find_pair_summing_to(legs: &[(String, u128)], target: u128) -> Option<(usize, usize)> {
+        for a in 0..legs.len() {
+            for b in (a + 1)..legs.len() {
+                if legs[a].1.checked_add(legs[b].1) == Some(target) {
+                    return Some((a, b));
+                }
+            }
+        }
+        None
+    }
+    
+    /// Checks `operation` against the available `liquidity` and `vault_total_value`
+    ///
+    /// Every problem is reported, not just the first: liquidity is checked per
+    /// source asset across all of its swaps, each swap is capped at
+    /// `MAX_SWAP_SHARE_BP` of the vault's value, and each pair may be traded
+    /// once (the children of a split trade share a parent ID and count as one).
+    pub fn validate_transactions(
+        operation: &RebalanceOperation,
+        liquidity: &HashMap<String, u128>,
+        vault_total_value: u128,
+    ) -> Vec<TransactionValidationError> {
+        let mut errors = Vec::new();
+        
+        let mut required: Vec<(String, u128)> = Vec::new();
+        for transaction in &operation.transactions {
+            match required.iter_mut().find(|(asset, _)| *asset == transaction.source_asset) {
+                Some((_, amount)) => *amount = amount.saturating_add(transaction.amount),
+                None => required.push((transaction.source_asset.clone(), transaction.amount)),
+            }
+        }
+        
+        for (asset, required) in required {
+            let available = liquidity.get(&asset).copied().unwrap_or(0);
+            if required > available {
+                errors.push(TransactionValidationError::InsufficientLiquidity { asset, required, available });
+            }
+        }
+        
+        let max_amount = vault_total_value.saturating_mul(MAX_SWAP_SHARE_BP as u128) / 10000;
+        for (index, transaction) in operation.transactions.iter().enumerate() {
+            if transaction.amount > max_amount {
+                errors.push(TransactionValidationError::SwapTooLarge {
+                    index,
+                    amount: transaction.amount,
+                    max_amount,
+                });
+            }
+        }
+        
+        let mut seen: HashMap<(&str, &str), Option<&str>> = HashMap::new();
+        let mut reported: Vec<(&str, &str)> = Vec::new();
+        for transaction in &operation.transactions {
+            let pair = (transaction.source_asset.as_str(), transaction.target_asset.as_str());
+            let parent_id = transaction.parent_id.as_deref();
+            
+            match seen.get(&pair) {
+                Some(first_parent) if first_parent.is_none() || *first_parent != parent_id => {
+                    if !reported.contains(&pair) {
+                        reported.push(pair);
+                        errors.push(TransactionValidationError::DuplicatePair {
+                            source_asset: pair.0.to_string(),
+                            target_asset: pair.1.to_string(),
+                        });
+                    }
+                },
+                Some(_) => {},
+                None => {
+                    seen.insert(pair, parent_id);
+                },
+            }
+        }
+        
+        errors
+    }
+    
     /// Simulates gas costs for a rebalance operation
     pub fn estimate_gas_costs(operation: &RebalanceOperation) -> u128 {
-        const BASE_COST: u128 = 1_000_000;
-        const PER_TX_COST: u128 = 2_500_000;
-        
         let tx_count = operation.transactions.len() as u128;
-        BASE_COST + (tx_count * PER_TX_COST)
+        BASE_GAS_COST + (tx_count * GAS_COST_PER_TX)
     }
 }
 
@@ -302,4 +1231,546 @@ mod tests {
         // Base cost + (3 * per_tx_cost)
         assert_eq!(estimated_cost, 8_500_000);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_retry_failed_transactions() {
+        let transactions = vec![
+            ("BTC".to_string(), "ETH".to_string(), 100),
+            ("BTC".to_string(), "SOL".to_string(), 50),
+            ("ETH".to_string(), "AVAX".to_string(), 200),
+        ];
+        
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            "test-op-4".to_string(),
+            RebalanceStrategy::Threshold,
+            transactions,
+        );
+        
+        // SOL and AVAX swaps fail on the first attempt
+        operation.execute_with(|_, tx| {
+            if tx.source_asset == "BTC" && tx.target_asset == "ETH" { Ok(1_000) } else { Err("DEX unavailable".to_string()) }
+        }).unwrap();
+        assert_eq!(operation.status, RebalanceStatus::PartiallyCompleted);
+        assert_eq!(operation.total_cost, Some(1_000));
+        
+        // SOL is resubmitted on retry; AVAX keeps failing until its retries run out
+        let mut outcomes = Vec::new();
+        for _ in 0..DEFAULT_MAX_RETRIES + 1 {
+            outcomes.extend(operation.retry_failed_with(|_, tx| {
+                if tx.target_asset == "SOL" { Ok("swap-sol".to_string()) } else { Err("DEX unavailable".to_string()) }
+            }));
+        }
+        
+        assert_eq!(outcomes, vec![
+            (1, RetryOutcome::Resubmitted),
+            (2, RetryOutcome::Failed),
+            (2, RetryOutcome::Failed),
+            (2, RetryOutcome::Exhausted),
+        ]);
+        
+        // The resubmitted swap only completes from its callback
+        assert_eq!(operation.transactions[1].status, RebalanceStatus::InProgress);
+        assert_eq!(operation.transactions[1].tx_hash.as_deref(), Some("swap-sol"));
+        assert_eq!(operation.status, RebalanceStatus::InProgress);
+        assert_eq!(operation.complete_transaction(1, true, 48), Ok(true));
+        
+        // Only the failed transactions were retried
+        assert_eq!(operation.transactions[0].retry_count, 0);
+        assert_eq!(operation.transactions[1].retry_count, 1);
+        assert_eq!(operation.transactions[2].retry_count, DEFAULT_MAX_RETRIES);
+        assert!(!operation.transactions[2].can_retry());
+        
+        assert_eq!(operation.status, RebalanceStatus::PartiallyCompleted);
+        assert_eq!(operation.total_cost, Some(1_000));
+    }
+    
+    #[test]
+    fn test_swap_callbacks_finalize_operation() {
+        let transactions = vec![
+            ("BTC".to_string(), "USDC".to_string(), 100),
+            ("BTC".to_string(), "ETH".to_string(), 50),
+        ];
+        
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            "test-op-5".to_string(),
+            RebalanceStrategy::Threshold,
+            transactions,
+        );
+        
+        operation.submit_with(|_, tx| Ok(format!("msg-{}", tx.target_asset))).unwrap();
+        
+        // Both legs wait for their callbacks
+        assert_eq!(operation.status, RebalanceStatus::InProgress);
+        assert!(operation.transactions.iter().all(|t| t.status == RebalanceStatus::InProgress));
+        assert_eq!(operation.transactions[1].tx_hash.as_deref(), Some("msg-ETH"));
+        assert!(!operation.is_resolved());
+        
+        assert_eq!(operation.complete_transaction(0, true, 99), Ok(false));
+        assert_eq!(operation.status, RebalanceStatus::InProgress);
+        assert_eq!(operation.transactions[0].received_amount, Some(99));
+        
+        // A settled transaction can't be finalized twice
+        assert_eq!(operation.complete_transaction(0, true, 99), Err("Transaction is not awaiting a swap callback"));
+        assert_eq!(operation.complete_transaction(5, true, 0), Err("Transaction index out of range"));
+        
+        assert_eq!(operation.complete_transaction(1, false, 0), Ok(true));
+        assert_eq!(operation.status, RebalanceStatus::PartiallyCompleted);
+        assert!(operation.transactions[1].can_retry());
+    }
+    
+    #[test]
+    fn test_cancel_keeps_executed_legs() {
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            "test-op-6".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("BTC".to_string(), "USDC".to_string(), 100),
+                ("BTC".to_string(), "ETH".to_string(), 50),
+                ("SOL".to_string(), "ETH".to_string(), 25),
+            ],
+        );
+        
+        // A pending operation can be cancelled outright
+        let mut pending = operation.clone();
+        assert_eq!(pending.cancel().unwrap().len(), 3);
+        assert_eq!(pending.status, RebalanceStatus::Cancelled);
+        assert_eq!(pending.cancel(), Err("Rebalance operation can no longer be cancelled"));
+        
+        // Submitted legs are in flight: only the one not yet submitted is cancelled
+        operation.transactions[0].status = RebalanceStatus::Completed;
+        operation.transactions[1].status = RebalanceStatus::InProgress;
+        operation.status = RebalanceStatus::InProgress;
+        
+        let cancelled = operation.cancel().unwrap();
+        assert_eq!(cancelled, vec![("SOL".to_string(), "ETH".to_string(), 25)]);
+        assert_eq!(operation.transactions[0].status, RebalanceStatus::Completed);
+        assert_eq!(operation.transactions[1].status, RebalanceStatus::InProgress);
+        assert_eq!(operation.transactions[2].status, RebalanceStatus::Cancelled);
+        
+        // The operation stays open until the in-flight leg's callback settles it
+        assert!(!operation.is_resolved());
+        assert_eq!(operation.status, RebalanceStatus::InProgress);
+        assert_eq!(operation.complete_transaction(1, true, 50), Ok(true));
+        assert_eq!(operation.transactions[1].status, RebalanceStatus::Completed);
+        assert_eq!(operation.status, RebalanceStatus::Cancelled);
+        
+        // Nothing is left to cancel once every leg was submitted
+        let mut submitted = RebalanceEngine::create_rebalance_operation(
+            "test-op-7".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("BTC".to_string(), "USDC".to_string(), 100)],
+        );
+        submitted.submit_with(|_, tx| Ok(format!("msg-{}", tx.target_asset))).unwrap();
+        assert_eq!(submitted.cancel(), Err("Rebalance operation can no longer be cancelled"));
+        assert_eq!(submitted.transactions[0].status, RebalanceStatus::InProgress);
+    }
+    
+    #[test]
+    fn test_create_gradual_operation() {
+        let slices = RebalanceEngine::create_gradual_operation(
+            "gradual-1".to_string(),
+            vec![
+                ("BTC".to_string(), "USDC".to_string(), 1000),
+                ("ETH".to_string(), "SOL".to_string(), 10),
+            ],
+            4 * 3600,
+            4,
+        );
+        
+        assert_eq!(slices.len(), 4);
+        for (i, slice) in slices.iter().enumerate() {
+            assert_eq!(slice.id, format!("gradual-1-slice-{}", i));
+            assert_eq!(slice.strategy, RebalanceStrategy::Gradual { total_duration_seconds: 4 * 3600, slices: 4 });
+            assert_eq!(slice.transactions[0].amount, 250);
+            assert_eq!(slice.scheduled_at, slices[0].created_at + i as u64 * 3600);
+        }
+        
+        // The last slice takes the remainder
+        let small: Vec<u128> = slices.iter().map(|slice| slice.transactions[1].amount).collect();
+        assert_eq!(small, vec![2, 2, 2, 4]);
+    }
+    
+    #[test]
+    fn test_simulated_operation() {
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            "test-op-sim".to_string(),
+            RebalanceStrategy::Manual,
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 100),
+                ("SOL".to_string(), "USDC".to_string(), 50),
+            ],
+        ).with_mode(RebalanceMode::Simulate);
+        assert!(operation.simulated);
+        
+        // Submitting never reaches the swap layer
+        operation.submit().unwrap();
+        
+        assert_eq!(operation.status, RebalanceStatus::Completed);
+        assert_eq!(operation.total_cost, Some(0));
+        assert!(operation.transactions.iter().all(|t| t.gas_cost == Some(0) && t.tx_hash.is_none()));
+        
+        let live = RebalanceEngine::create_rebalance_operation(
+            "test-op-live".to_string(),
+            RebalanceStrategy::Manual,
+            Vec::new(),
+        ).with_mode(RebalanceMode::Live);
+        assert!(!live.simulated);
+    }
+    
+    #[test]
+    fn test_progress_mid_execution() {
+        let mut operation = RebalanceEngine::create_rebalance_operation(
+            "test-op-progress".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 100),
+                ("BTC".to_string(), "SOL".to_string(), 50),
+                ("ETH".to_string(), "USDC".to_string(), 25),
+                ("SOL".to_string(), "USDC".to_string(), 10),
+            ],
+        );
+        
+        assert_eq!(operation.get_progress(), RebalanceProgress {
+            total: 4,
+            completed: 0,
+            failed: 0,
+            pending: 4,
+            total_cost_so_far: 0,
+        });
+        
+        // One leg settled, one failed, two still in flight
+        operation.submit_with(|_, tx| Ok(format!("msg-{}", tx.target_asset))).unwrap();
+        operation.transactions[0].status = RebalanceStatus::Completed;
+        operation.transactions[0].gas_cost = Some(1000);
+        operation.transactions[1].status = RebalanceStatus::Failed;
+        
+        assert_eq!(operation.get_progress(), RebalanceProgress {
+            total: 4,
+            completed: 1,
+            failed: 1,
+            pending: 2,
+            total_cost_so_far: 1000,
+        });
+    }
+    
+    #[test]
+    fn test_swap_routing_by_chain() {
+        use crate::cross_chain::Blockchain;
+        
+        // BTC and USDC are both held on L1X; ETH legs cross to Ethereum
+        assert_eq!(Blockchain::native_for_asset("BTC"), Blockchain::native_for_asset("USDC"));
+        assert_eq!(Blockchain::native_for_asset("ETH"), Blockchain::Ethereum);
+        assert_eq!(Blockchain::native_for_asset("SOL").chain_id(), 1399811);
+    }
+    
+    #[test]
+    fn test_apply_budget_cap() {
+        let transactions = vec![
+            ("BTC".to_string(), "USDC".to_string(), 400),
+            ("ETH".to_string(), "SOL".to_string(), 300),
+            ("BTC".to_string(), "SOL".to_string(), 200),
+        ];
+        let drifts: HashMap<String, u32> = vec![
+            ("BTC".to_string(), 500),
+            ("ETH".to_string(), 1200),
+            ("SOL".to_string(), 800),
+            ("USDC".to_string(), 100),
+        ].into_iter().collect();
+        
+        // A cap above the plan leaves it untouched
+        let (capped, truncated) = RebalanceEngine::apply_budget_cap(transactions.clone(), &drifts, 900);
+        assert!(!truncated);
+        assert_eq!(capped.iter().map(|t| t.2).sum::<u128>(), 900);
+        
+        // The ETH leg (largest drift) goes first, then BTC->SOL, then the partial BTC->USDC
+        let (capped, truncated) = RebalanceEngine::apply_budget_cap(transactions.clone(), &drifts, 600);
+        assert!(truncated);
+        assert_eq!(capped, vec![
+            ("ETH".to_string(), "SOL".to_string(), 300),
+            ("BTC".to_string(), "SOL".to_string(), 200),
+            ("BTC".to_string(), "USDC".to_string(), 100),
+        ]);
+        
+        // Once the budget is spent the remaining legs are dropped
+        let (capped, truncated) = RebalanceEngine::apply_budget_cap(transactions, &drifts, 300);
+        assert!(truncated);
+        assert_eq!(capped, vec![("ETH".to_string(), "SOL".to_string(), 300)]);
+    }
+    
+    #[test]
+    fn test_plan_with_costs_skips_tiny_trades() {
+        let transactions = vec![
+            ("BTC".to_string(), "ETH".to_string(), 100_000),
+            ("BTC".to_string(), "USDC".to_string(), 300), // Tiny drift correction
+            ("ETH".to_string(), "SOL".to_string(), 1000),
+        ];
+        
+        let (planned, suppressed) = RebalanceEngine::plan_with_costs(
+            transactions,
+            crate::cross_chain::swap_fee_bps,
+            100,
+            DEFAULT_MAX_TRADE_COST_BP,
+        );
+        
+        assert_eq!(planned, vec![
+            ("BTC".to_string(), "ETH".to_string(), 100_000), // 500 fee + 100 gas = 0.6%
+            ("ETH".to_string(), "SOL".to_string(), 1000),    // 5 fee + 100 gas = 10.5%
+        ]);
+        
+        // Gas alone is a third of the tiny trade
+        assert_eq!(suppressed, vec![SuppressedTrade {
+            source_asset: "BTC".to_string(),
+            target_asset: "USDC".to_string(),
+            amount: 300,
+            estimated_cost: 100,
+            cost_ratio_bp: 3333,
+        }]);
+        
+        // A stricter limit also drops the mid-sized trade
+        let (planned, suppressed) = RebalanceEngine::plan_with_costs(
+            vec![("ETH".to_string(), "SOL".to_string(), 1000), ("BTC".to_string(), "ETH".to_string(), 0)],
+            |_, _| 50,
+            100,
+            1000,
+        );
+        assert!(planned.is_empty());
+        assert_eq!(suppressed[0].cost_ratio_bp, 1050);
+        assert_eq!(suppressed[1].cost_ratio_bp, u32::MAX);
+    }
+    
+    #[test]
+    fn test_needs_rebalancing_from_live_values() {
+        let mut allocations = crate::allocation::AllocationSet::new(300);
+        allocations.add_allocation(crate::allocation::AssetAllocation::new("BTC".to_string(), 5000)).unwrap();
+        allocations.add_allocation(crate::allocation::AssetAllocation::new("ETH".to_string(), 3000)).unwrap();
+        allocations.add_allocation(crate::allocation::AssetAllocation::new("USDC".to_string(), 2000)).unwrap();
+        
+        // Stored percentages still sit on target; only the live values have drifted
+        assert!(!allocations.needs_rebalancing());
+        
+        let drifted = vec![
+            ("BTC".to_string(), 6000),
+            ("ETH".to_string(), 2000),
+            ("USDC".to_string(), 2000),
+        ];
+        assert!(RebalanceEngine::needs_rebalancing(&allocations, &drifted, 10000));
+        
+        let transactions = RebalanceEngine::generate_rebalance_transactions(&allocations, &drifted, 10000);
+        assert_eq!(transactions, vec![("BTC".to_string(), "ETH".to_string(), 1000)]);
+        
+        let balanced = vec![
+            ("BTC".to_string(), 5000),
+            ("ETH".to_string(), 3000),
+            ("USDC".to_string(), 2000),
+        ];
+        assert!(!RebalanceEngine::needs_rebalancing(&allocations, &balanced, 10000));
+        assert!(RebalanceEngine::generate_rebalance_transactions(&allocations, &balanced, 10000).is_empty());
+        
+        // A missing asset counts as holding nothing
+        let missing_usdc = vec![("BTC".to_string(), 5000), ("ETH".to_string(), 3000)];
+        assert!(RebalanceEngine::needs_rebalancing(&allocations, &missing_usdc, 10000));
+    }
+    
+    #[test]
+    fn test_split_trades_share_parent() {
+        let operation = RebalanceEngine::create_rebalance_operation(
+            "op-1".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 7_500),
+                ("BTC".to_string(), "ETH".to_string(), 7_500),
+                ("BTC".to_string(), "SOL".to_string(), 1_000),
+            ],
+        );
+        
+        assert_eq!(operation.transactions[0].parent_id.as_deref(), Some("op-1-trade-0"));
+        assert_eq!(operation.transactions[1].parent_id.as_deref(), Some("op-1-trade-0"));
+        assert_eq!(operation.transactions[2].parent_id, None);
+    }
+    
+    #[test]
+    fn test_create_from_value_deltas() {
+        let prices: HashMap<String, u128> = vec![
+            ("BTC".to_string(), 50_000_00000000), // $50,000
+            ("ETH".to_string(), 3_000_00000000),  // $3,000
+        ].into_iter().collect();
+        let decimals: HashMap<String, u8> = vec![
+            ("BTC".to_string(), 8),
+            ("ETH".to_string(), 18),
+        ].into_iter().collect();
+        
+        // $1,000 of BTC into ETH, then $600 of ETH back into BTC
+        let operation = RebalanceEngine::create_from_value_deltas(
+            "op-1".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("BTC".to_string(), "ETH".to_string(), 1_000_00000000),
+                ("ETH".to_string(), "BTC".to_string(), 600_00000000),
+            ],
+            &prices,
+            &decimals,
+        ).unwrap();
+        
+        assert_eq!(operation.transactions[0].amount, 2_000_000); // 0.02 BTC in satoshi
+        assert_eq!(operation.transactions[1].amount, 200_000_000_000_000_000); // 0.2 ETH in wei
+        
+        // A missing price rejects the whole operation
+        let err = RebalanceEngine::create_from_value_deltas(
+            "op-2".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("SOL".to_string(), "ETH".to_string(), 100_00000000)],
+            &prices,
+            &decimals,
+        ).unwrap_err();
+        assert_eq!(err, "Price not found for asset SOL");
+    }
+    
+    #[test]
+    fn test_confidence_from_price_volatility() {
+        use crate::price_feed::{PriceFeedContract, PriceHistoryRecord};
+        
+        let now = l1x_sdk::env::block_timestamp();
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        let record = |symbol: &str, price: u128, timestamp: u64| PriceHistoryRecord {
+            symbol: symbol.to_string(),
+            price,
+            timestamp,
+        };
+        
+        // USDC barely moves; SOL is up 8% over the day
+        feed.record_history(record("USDC", 100_000_000, now.saturating_sub(3600)));
+        feed.record_history(record("USDC", 100_100_000, now));
+        feed.record_history(record("SOL", 140_00000000, now.saturating_sub(3600)));
+        feed.record_history(record("SOL", 151_20000000, now));
+        
+        assert!(compute_confidence("USDC", &feed) >= 80);
+        assert!(compute_confidence("SOL", &feed) <= 40);
+        
+        // No history: neither confident nor doubtful
+        assert_eq!(compute_confidence("BTC", &feed), NEUTRAL_CONFIDENCE);
+    }
+    
+    #[test]
+    fn test_optimal_transactions_minimizes_swap_count() {
+        use crate::allocation::AllocationSet;
+        
+        let legs = |amounts: &[(&str, u128)]| -> Vec<(String, u128)> {
+            amounts.iter().map(|(asset, amount)| (asset.to_string(), *amount)).collect()
+        };
+        let sellers = legs(&[("BTC", 10), ("ETH", 20), ("SOL", 30), ("AVAX", 40), ("DOT", 50)]);
+        let buyers = legs(&[("USDC", 20), ("USDT", 10), ("DAI", 40), ("LINK", 30), ("UNI", 50)]);
+        
+        let greedy = AllocationSet::match_in_order(sellers.clone(), buyers.clone());
+        assert_eq!(greedy.len(), 7);
+        
+        let optimal = RebalanceEngine::optimal_transactions(sellers.clone(), buyers.clone());
+        assert_eq!(optimal.len(), 5);
+        
+        // Every leg is still settled in full
+        for (asset, amount) in &sellers {
+            let sold: u128 = optimal.iter().filter(|(source, _, _)| source == asset).map(|(_, _, a)| a).sum();
+            assert_eq!(sold, *amount);
+        }
+        for (asset, amount) in &buyers {
+            let bought: u128 = optimal.iter().filter(|(_, target, _)| target == asset).map(|(_, _, a)| a).sum();
+            assert_eq!(bought, *amount);
+        }
+        
+        // A seller covering two buyers exactly needs two swaps, not three
+        let split = RebalanceEngine::optimal_transactions(
+            legs(&[("BTC", 70), ("ETH", 25)]),
+            legs(&[("SOL", 45), ("USDC", 25), ("DAI", 25)]),
+        );
+        assert_eq!(split.len(), 3);
+        assert!(split.contains(&("BTC".to_string(), "SOL".to_string(), 45)));
+        assert!(split.contains(&("BTC".to_string(), "DAI".to_string(), 25)));
+    }
+    
+    #[test]
+    fn test_validate_transactions() {
+        let liquidity: HashMap<String, u128> = [
+            ("BTC".to_string(), 5_000),
+            ("ETH".to_string(), 1_000),
+        ].into_iter().collect();
+        
+        // A 4,000 swap out of a 10,000 vault is past the 30% guard
+        let operation = RebalanceEngine::create_rebalance_operation(
+            "op-1".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("BTC".to_string(), "USDC".to_string(), 4_000)],
+        );
+        assert_eq!(
+            RebalanceEngine::validate_transactions(&operation, &liquidity, 10_000),
+            vec![TransactionValidationError::SwapTooLarge { index: 0, amount: 4_000, max_amount: 3_000 }],
+        );
+        
+        // Every problem is reported: ETH oversold, a repeated pair, and the missing SOL
+        let operation = RebalanceEngine::create_rebalance_operation(
+            "op-2".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("ETH".to_string(), "USDC".to_string(), 800),
+                ("BTC".to_string(), "ETH".to_string(), 500),
+                ("ETH".to_string(), "USDC".to_string(), 400),
+                ("SOL".to_string(), "BTC".to_string(), 100),
+            ],
+        );
+        let errors = RebalanceEngine::validate_transactions(&operation, &liquidity, 10_000);
+        assert_eq!(errors, vec![
+            TransactionValidationError::InsufficientLiquidity { asset: "ETH".to_string(), required: 1_200, available: 1_000 },
+            TransactionValidationError::InsufficientLiquidity { asset: "SOL".to_string(), required: 100, available: 0 },
+            TransactionValidationError::DuplicatePair { source_asset: "ETH".to_string(), target_asset: "USDC".to_string() },
+        ]);
+        
+        // The children of one split trade are not duplicates
+        let operation = RebalanceEngine::create_rebalance_operation(
+            "op-3".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![
+                ("BTC".to_string(), "USDC".to_string(), 2_500),
+                ("BTC".to_string(), "USDC".to_string(), 2_500),
+            ],
+        );
+        assert!(RebalanceEngine::validate_transactions(&operation, &liquidity, 10_000).is_empty());
+    }
+    
+    #[test]
+    fn test_create_from_registered_assets() {
+        use crate::price_feed::{AssetMetadata, PriceFeedContract};
+        
+        let mut feed = PriceFeedContract::with_admin("admin".to_string());
+        feed.register_asset_metadata(AssetMetadata {
+            symbol: "BTC".to_string(),
+            decimals: 8,
+            display_name: "Bitcoin".to_string(),
+            addresses: HashMap::new(),
+            active: true,
+            registered_at: 0,
+        }).unwrap();
+        
+        let prices: HashMap<String, u128> = [
+            ("BTC".to_string(), 50_000_00000000),
+            ("ETH".to_string(), 2_500_00000000),
+        ].into_iter().collect();
+        
+        // $1,000 of BTC at $50,000 is 0.02 BTC
+        let operation = RebalanceEngine::create_from_registered_assets(
+            "op-1".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("BTC".to_string(), "ETH".to_string(), 1_000_00000000)],
+            &prices,
+            &feed,
+        ).unwrap();
+        assert_eq!(operation.transactions[0].amount, 2_000_000);
+        
+        // ETH has a price but was never registered
+        let err = RebalanceEngine::create_from_registered_assets(
+            "op-2".to_string(),
+            RebalanceStrategy::Threshold,
+            vec![("ETH".to_string(), "BTC".to_string(), 1_000_00000000)],
+            &prices,
+            &feed,
+        ).unwrap_err();
+        assert_eq!(err, "Decimals not found for asset ETH");
+    }
+}
@@ -7,9 +7,12 @@ use crate::custodial_vault::CustodialVault;
 use crate::non_custodial_vault::NonCustodialVault;
 use crate::events;
 use l1x_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use std::collections::HashMap;
 
 /// Frequency for scheduled rebalancing
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub enum RebalanceFrequency {
     /// Daily rebalancing
     Daily,
@@ -44,6 +47,182 @@ impl RebalanceFrequency {
     }
 }
 
+/// A vault enrolled in scheduled rebalancing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct ScheduleEntry {
+    /// Vault to rebalance
+    pub vault_id: String,
+    
+    /// How often the vault is rebalanced
+    pub frequency: RebalanceFrequency,
+    
+    /// Timestamp the schedule is aligned to
+    pub anchor_timestamp: u64,
+    
+    /// Timestamp of the next scheduled run
+    pub next_run: u64,
+}
+
+impl ScheduleEntry {
+    /// Creates an entry whose first run is the first anchor-aligned time at or after `now`
+    pub fn new(vault_id: String, frequency: RebalanceFrequency, anchor_timestamp: u64, now: u64) -> Result<Self, &'static str> {
+        if frequency.to_seconds() == 0 {
+            return Err("Schedule interval must be greater than zero");
+        }
+        
+        let mut entry = Self {
+            vault_id,
+            frequency,
+            anchor_timestamp,
+            next_run: anchor_timestamp,
+        };
+        if now > anchor_timestamp {
+            entry.next_run = entry.run_after(now - 1);
+        }
+        
+        Ok(entry)
+    }
+    
+    /// Whether the scheduled run has come due at `now`
+    pub fn is_due(&self, now: u64) -> bool {
+        now >= self.next_run
+    }
+    
+    /// First anchor-aligned run time strictly after `time`
+    fn run_after(&self, time: u64) -> u64 {
+        let interval = self.frequency.to_seconds();
+        if time < self.anchor_timestamp {
+            return self.anchor_timestamp;
+        }
+        
+        let runs = (time - self.anchor_timestamp) / interval + 1;
+        self.anchor_timestamp.saturating_add(runs.saturating_mul(interval))
+    }
+    
+    /// Moves `next_run` to the first anchor + N * interval after `now`
+    ///
+    /// Runs missed while the job was not called are skipped rather than
+    /// replayed, and late runs don't shift the schedule.
+    pub fn advance(&mut self, now: u64) {
+        self.next_run = self.run_after(now);
+    }
+}
+
+/// Rebalance schedule contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"REBALANCE_SCHEDULES";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct RebalanceScheduleContract {
+    schedules: HashMap<String, ScheduleEntry>, // Vault ID -> Schedule
+}
+
+#[l1x_sdk::contract]
+impl RebalanceScheduleContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+    
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+    
+    pub fn new() {
+        let mut state = Self {
+            schedules: HashMap::new(),
+        };
+        
+        state.save()
+    }
+    
+    /// Vault IDs whose next run has come due at `now`, sorted
+    fn due_vaults(&self, now: u64) -> Vec<String> {
+        let mut due: Vec<String> = self.schedules.values()
+            .filter(|entry| entry.is_due(now))
+            .map(|entry| entry.vault_id.clone())
+            .collect();
+        due.sort();
+        due
+    }
+    
+    /// Enrolls one of the caller's vaults in scheduled rebalancing, replacing any existing schedule
+    pub fn register_schedule(vault_id: String, frequency_json: String, anchor_timestamp: u64) -> String {
+        let owner = crate::custodial_vault::vault_owner(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+        if owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can schedule rebalancing");
+        }
+        
+        let frequency: RebalanceFrequency = serde_json::from_str(&frequency_json)
+            .unwrap_or_else(|e| panic!("Failed to parse frequency: {}", e));
+        let entry = ScheduleEntry::new(vault_id.clone(), frequency, anchor_timestamp, l1x_sdk::env::block_timestamp())
+            .unwrap_or_else(|err| panic!("{}", err));
+        let next_run = entry.next_run;
+        
+        let mut state = Self::load();
+        state.schedules.insert(vault_id.clone(), entry);
+        state.save();
+        
+        format!("Vault {} scheduled for rebalancing, next run at {}", vault_id, next_run)
+    }
+    
+    /// Removes a vault from scheduled rebalancing
+    pub fn unregister_schedule(vault_id: String) -> String {
+        let owner = crate::custodial_vault::vault_owner(&vault_id)
+            .unwrap_or_else(|| panic!("Vault not found: {}", vault_id));
+        if owner != l1x_sdk::env::caller() {
+            panic!("Only the vault owner can unschedule rebalancing");
+        }
+        
+        let mut state = Self::load();
+        if state.schedules.remove(&vault_id).is_none() {
+            panic!("Vault {} has no rebalance schedule", vault_id);
+        }
+        state.save();
+        
+        format!("Vault {} removed from scheduled rebalancing", vault_id)
+    }
+    
+    /// Gets a vault's rebalance schedule
+    pub fn get_schedule(vault_id: String) -> String {
+        let state = Self::load();
+        
+        let entry = state.schedules.get(&vault_id)
+            .unwrap_or_else(|| panic!("Vault {} has no rebalance schedule", vault_id));
+            
+        serde_json::to_string(entry)
+            .unwrap_or_else(|_| "Failed to serialize schedule".to_string())
+    }
+    
+    /// Gets the vaults whose scheduled rebalance has come due at `now`
+    pub fn get_due_vaults(now: u64) -> String {
+        let state = Self::load();
+        
+        serde_json::to_string(&state.due_vaults(now))
+            .unwrap_or_else(|_| "Failed to serialize due vaults".to_string())
+    }
+}
+
+/// Vault IDs whose scheduled rebalance has come due at `now`
+pub fn due_vaults(now: u64) -> Vec<String> {
+    RebalanceScheduleContract::load().due_vaults(now)
+}
+
+/// Advances the schedules of `vault_ids` past `now`
+pub fn advance_schedules(vault_ids: &[String], now: u64) {
+    let mut state = RebalanceScheduleContract::load();
+    
+    for vault_id in vault_ids {
+        if let Some(entry) = state.schedules.get_mut(vault_id) {
+            entry.advance(now);
+        }
+    }
+    
+    state.save();
+}
+
 /// Scheduled rebalancer that processes vaults based on time schedule
 pub struct ScheduledRebalancer;
 
@@ -161,4 +340,35 @@ mod tests {
         assert!(!custom_freq.is_due(one_minute_ago));
         assert!(custom_freq.is_due(two_hours_ago));
     }
+    
+    #[test]
+    fn test_schedule_registry() {
+        let day = 86400;
+        let anchor = 1_000 * day;
+        
+        let mut state = RebalanceScheduleContract { schedules: HashMap::new() };
+        let daily = ScheduleEntry::new("vault-1".to_string(), RebalanceFrequency::Daily, anchor, anchor - 10).unwrap();
+        assert_eq!(daily.next_run, anchor);
+        
+        // Registered after the anchor: first run is the next aligned time
+        let weekly = ScheduleEntry::new("vault-2".to_string(), RebalanceFrequency::Weekly, anchor, anchor + 3 * day).unwrap();
+        assert_eq!(weekly.next_run, anchor + 7 * day);
+        
+        assert!(ScheduleEntry::new("vault-3".to_string(), RebalanceFrequency::Custom(0), anchor, anchor).is_err());
+        
+        state.schedules.insert("vault-1".to_string(), daily);
+        state.schedules.insert("vault-2".to_string(), weekly);
+        
+        assert!(state.due_vaults(anchor - 1).is_empty());
+        assert_eq!(state.due_vaults(anchor + 7 * day), vec!["vault-1".to_string(), "vault-2".to_string()]);
+        
+        // A late run stays aligned to the anchor and skips missed runs
+        let entry = state.schedules.get_mut("vault-1").unwrap();
+        entry.advance(anchor + 2 * day + 3600);
+        assert_eq!(entry.next_run, anchor + 3 * day);
+        entry.advance(anchor + 3 * day);
+        assert_eq!(entry.next_run, anchor + 4 * day);
+        
+        assert_eq!(state.due_vaults(anchor + 3 * day + 1), Vec::<String>::new());
+    }
 }
\ No newline at end of file
@@ -8,9 +8,17 @@ use crate::price_feed::PriceFeedOracle;
 use crate::events;
 use l1x_sdk::prelude::*;
 
+/// Panics unless the caller is a registered keeper
+fn assert_keeper() {
+    if !crate::custodial_vault::is_keeper(&l1x_sdk::env::caller()) {
+        panic!("Only registered keepers can run scheduled jobs");
+    }
+}
+
 // Main entry point for scheduled rebalancing
 #[no_mangle]
 extern "C" fn scheduled_rebalance() {
+    assert_keeper();
     l1x_sdk::env::log("Starting scheduled rebalancing job");
     
     // Get latest prices for assets
@@ -23,15 +31,49 @@ extern "C" fn scheduled_rebalance() {
         }
     };
     
-    // Run the scheduled rebalancer
-    let result = ScheduledRebalancer::run_scheduled_rebalancing(&prices_json);
+    // Rebalance every vault whose schedule has come due
+    let now = l1x_sdk::env::block_timestamp();
+    let due = crate::rebalance::scheduled::due_vaults(now);
+    
+    for vault_id in &due {
+        let result = crate::custodial_vault::CustodialVaultContract::auto_rebalance(vault_id.clone(), prices_json.clone());
+        l1x_sdk::env::log(&format!("Scheduled: {}: {}", vault_id, result));
+    }
+    
+    crate::rebalance::scheduled::advance_schedules(&due, now);
+    
+    l1x_sdk::env::log(&format!("Scheduled rebalancing complete: {} vaults processed", due.len()));
+}
+
+/// Scheduled job executing the next due slice of each gradual rebalance
+#[no_mangle]
+extern "C" fn process_gradual_rebalances() {
+    assert_keeper();
+    l1x_sdk::env::log("Processing gradual rebalance slices");
+    
+    let slices = crate::rebalance::gradual::due_slices(l1x_sdk::env::block_timestamp());
+    let count = slices.len();
+    
+    for slice in slices {
+        let vault_id = slice.vault_id.clone().unwrap_or_default();
+        let slice_id = slice.id.clone();
+        
+        match crate::custodial_vault::execute_rebalance_slice(slice) {
+            Ok(result) => {
+                crate::rebalance::gradual::complete_slice(&vault_id, &slice_id);
+                l1x_sdk::env::log(&format!("Gradual: {}", result));
+            },
+            Err(e) => l1x_sdk::env::log(&format!("Gradual: slice {} deferred: {}", slice_id, e)),
+        }
+    }
     
-    l1x_sdk::env::log(&format!("Scheduled rebalancing complete: {}", result));
+    l1x_sdk::env::log(&format!("Gradual rebalancing complete: {} slices due", count));
 }
 
 // Manual trigger for scheduled rebalancing (for testing)
 #[no_mangle]
 extern "C" fn manual_trigger_rebalance(prices_json_ptr: u64) {
+    assert_keeper();
     let prices_json = unsafe { l1x_sdk::env::read_input(prices_json_ptr) };
     let prices_json = String::from_utf8(prices_json).unwrap();
     
@@ -119,6 +161,7 @@ fn check_non_custodial_drifts() -> Vec<String> {
 /// Scheduled job for taking profits based on price movements
 #[no_mangle]
 extern "C" fn scheduled_take_profit(prices_json_ptr: u64) {
+    assert_keeper();
     let prices_json = unsafe { l1x_sdk::env::read_input(prices_json_ptr) };
     let prices_json = String::from_utf8(prices_json).unwrap();
     
@@ -4,9 +4,23 @@
 //! investment portfolios to realize gains according to different triggers.
 
 use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
+/// One rung of a take profit ladder
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct LadderTier {
+    /// Gain over the baseline that fires the tier, in basis points
+    pub trigger_bp: u32,
+    
+    /// Portion of the gain realized when the tier fires, in basis points
+    pub portion_bp: u32,
+}
+
 /// Types of take profit strategies
+///
+/// Borsh is implemented by hand: the derive cannot resolve the recursive
+/// `Vec<TakeProfitType>` of composite conditions.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TakeProfitType {
     /// Manual trigger (user must explicitly execute)
@@ -23,10 +37,240 @@ pub enum TakeProfitType {
         /// Interval in seconds between executions
         interval_seconds: u64,
     },
+    
+    /// Trailing stop (execute when value falls back from its peak while still in profit)
+    TrailingStop {
+        /// Drop from the peak value that triggers execution, in basis points
+        trail_bp: u32,
+    },
+    
+    /// Laddered trigger (realize part of the gain at each of several thresholds)
+    Ladder {
+        /// Tiers in ascending order of `trigger_bp`
+        tiers: Vec<LadderTier>,
+    },
+    
+    /// Combination of percentage and time triggers
+    Composite {
+        /// Conditions to combine (Percentage, Time, or a nested Composite)
+        conditions: Vec<TakeProfitType>,
+        
+        /// Whether all or any of the conditions must hold
+        mode: CompositeMode,
+    },
 }
 
-/// Take profit strategy for a portfolio
+/// How the conditions of a composite take profit trigger are combined
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum CompositeMode {
+    /// Every condition must hold
+    All,
+    
+    /// At least one condition must hold
+    Any,
+}
+
+/// How the baseline moves after a take profit execution
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum BaselinePolicy {
+    /// Reset to the value at execution, even after a loss
+    #[default]
+    ResetToCurrent,
+    
+    /// Only ever raise the baseline, so gains lost in a dip are not taken again on recovery
+    HighWaterMark,
+    
+    /// Keep the baseline where it was configured
+    Fixed,
+}
+
+/// Where the stable-asset proceeds of a take profit execution go
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum ProfitDestination {
+    /// Deposit the proceeds back into the vault's allocations
+    ReinvestAsAllocation,
+    
+    /// Accumulate the proceeds in the vault's realized profits
+    #[default]
+    HoldInVault,
+    
+    /// Queue the proceeds for the owner to claim
+    QueueWithdrawal,
+}
+
+/// Deepest nesting of composite conditions allowed (a composite inside a composite)
+pub const MAX_COMPOSITE_DEPTH: usize = 2;
+
+impl TakeProfitType {
+    /// Short name of the strategy type
+    pub fn name(&self) -> &'static str {
+        match self {
+            TakeProfitType::Manual => "manual",
+            TakeProfitType::Percentage { .. } => "percentage",
+            TakeProfitType::Time { .. } => "time",
+            TakeProfitType::TrailingStop { .. } => "trailing_stop",
+            TakeProfitType::Ladder { .. } => "ladder",
+            TakeProfitType::Composite { .. } => "composite",
+        }
+    }
+    
+    /// Checks that ladders and composite conditions are well-formed
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            TakeProfitType::Ladder { tiers } => validate_ladder(tiers),
+            TakeProfitType::Composite { .. } => validate_composite(self, 1),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl BorshSerialize for TakeProfitType {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        match self {
+            TakeProfitType::Manual => BorshSerialize::serialize(&0u8, writer),
+            TakeProfitType::Percentage { percentage } => {
+                BorshSerialize::serialize(&1u8, writer)?;
+                BorshSerialize::serialize(percentage, writer)
+            },
+            TakeProfitType::Time { interval_seconds } => {
+                BorshSerialize::serialize(&2u8, writer)?;
+                BorshSerialize::serialize(interval_seconds, writer)
+            },
+            TakeProfitType::TrailingStop { trail_bp } => {
+                BorshSerialize::serialize(&3u8, writer)?;
+                BorshSerialize::serialize(trail_bp, writer)
+            },
+            TakeProfitType::Ladder { tiers } => {
+                BorshSerialize::serialize(&4u8, writer)?;
+                BorshSerialize::serialize(tiers, writer)
+            },
+            TakeProfitType::Composite { conditions, mode } => {
+                BorshSerialize::serialize(&5u8, writer)?;
+                BorshSerialize::serialize(&(conditions.len() as u32), writer)?;
+                for condition in conditions {
+                    BorshSerialize::serialize(condition, writer)?;
+                }
+                BorshSerialize::serialize(mode, writer)
+            },
+        }
+    }
+}
+
+impl BorshDeserialize for TakeProfitType {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let variant: u8 = BorshDeserialize::deserialize(buf)?;
+        match variant {
+            0 => Ok(TakeProfitType::Manual),
+            1 => Ok(TakeProfitType::Percentage { percentage: BorshDeserialize::deserialize(buf)? }),
+            2 => Ok(TakeProfitType::Time { interval_seconds: BorshDeserialize::deserialize(buf)? }),
+            3 => Ok(TakeProfitType::TrailingStop { trail_bp: BorshDeserialize::deserialize(buf)? }),
+            4 => Ok(TakeProfitType::Ladder { tiers: BorshDeserialize::deserialize(buf)? }),
+            5 => {
+                let len: u32 = BorshDeserialize::deserialize(buf)?;
+                let mut conditions = Vec::new();
+                for _ in 0..len {
+                    conditions.push(BorshDeserialize::deserialize(buf)?);
+                }
+                Ok(TakeProfitType::Composite { conditions, mode: BorshDeserialize::deserialize(buf)? })
+            },
+            variant => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unexpected variant index: {}", variant),
+            )),
+        }
+    }
+}
+
+/// Checks a composite at nesting `depth`: non-empty, only percentage, time
+/// and composite conditions, nested at most `MAX_COMPOSITE_DEPTH` deep
+fn validate_composite(strategy_type: &TakeProfitType, depth: usize) -> Result<(), &'static str> {
+    let conditions = match strategy_type {
+        TakeProfitType::Composite { conditions, .. } => conditions,
+        TakeProfitType::Percentage { .. } | TakeProfitType::Time { .. } => return Ok(()),
+        _ => return Err("Composite conditions can only be percentage, time or composite triggers"),
+    };
+    
+    if depth > MAX_COMPOSITE_DEPTH {
+        return Err("Composite conditions can be nested at most 2 levels deep");
+    }
+    
+    if conditions.is_empty() {
+        return Err("A composite needs at least one condition");
+    }
+    
+    conditions.iter().try_for_each(|condition| validate_composite(condition, depth + 1))
+}
+
+/// JSON configuration of a take profit strategy
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TakeProfitConfig {
+    /// Trigger of the strategy
+    pub strategy_type: TakeProfitType,
+    
+    /// Share of the gain realized per execution (the whole gain when omitted)
+    #[serde(default)]
+    pub profit_portion_bp: Option<u32>,
+    
+    /// How the baseline moves after each execution
+    #[serde(default)]
+    pub baseline_policy: BaselinePolicy,
+    
+    /// Where the proceeds of each execution go
+    #[serde(default)]
+    pub profit_destination: ProfitDestination,
+    
+    /// Maximum executions within any `period_seconds` window (uncapped when omitted)
+    #[serde(default)]
+    pub max_executions_per_period: Option<u32>,
+    
+    /// Length of the rolling window of the execution cap
+    #[serde(default)]
+    pub period_seconds: u64,
+}
+
+impl TakeProfitConfig {
+    /// Validates the configuration and builds the strategy it describes
+    pub fn into_strategy(self) -> Result<TakeProfitStrategy, &'static str> {
+        self.strategy_type.validate()?;
+        
+        let mut strategy = TakeProfitStrategy::new(self.strategy_type);
+        if let Some(portion) = self.profit_portion_bp {
+            strategy.set_profit_portion(portion)?;
+        }
+        strategy.baseline_policy = self.baseline_policy;
+        strategy.profit_destination = self.profit_destination;
+        strategy.set_execution_cap(self.max_executions_per_period, self.period_seconds)?;
+        
+        Ok(strategy)
+    }
+}
+
+/// Share of the gain realized per execution unless configured otherwise (in basis points)
+pub const FULL_PROFIT_PORTION_BP: u32 = 10000;
+
+fn default_profit_portion_bp() -> u32 {
+    FULL_PROFIT_PORTION_BP
+}
+
+/// Checks that ladder tiers are non-empty, strictly ascending and realize a valid portion
+pub fn validate_ladder(tiers: &[LadderTier]) -> Result<(), &'static str> {
+    if tiers.is_empty() {
+        return Err("A ladder needs at least one tier");
+    }
+    
+    if tiers.iter().any(|tier| tier.trigger_bp == 0 || tier.portion_bp == 0 || tier.portion_bp > 10000) {
+        return Err("Ladder tiers need a positive trigger and a portion between 1 and 10000 basis points");
+    }
+    
+    if tiers.windows(2).any(|pair| pair[0].trigger_bp >= pair[1].trigger_bp) {
+        return Err("Ladder tiers must be in strictly ascending order of trigger");
+    }
+    
+    Ok(())
+}
+
+/// Take profit strategy for a portfolio
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct TakeProfitStrategy {
     /// Type of take profit strategy
     pub strategy_type: TakeProfitType,
@@ -36,6 +280,46 @@ pub struct TakeProfitStrategy {
     
     /// Baseline value for percentage-based strategies
     pub baseline_value: u128,
+    
+    /// Highest portfolio value seen since the baseline was set
+    #[serde(default)]
+    pub high_water_mark: u128,
+    
+    /// Indices of the ladder tiers fired since the baseline was set
+    #[serde(default)]
+    pub fired_tiers: Vec<u32>,
+    
+    /// Gain realized by the ladder tiers fired since the baseline was set
+    #[serde(default)]
+    pub ladder_realized: u128,
+    
+    /// Share of the gain realized per execution (in basis points; ladders use their tier portions)
+    #[serde(default = "default_profit_portion_bp")]
+    pub profit_portion_bp: u32,
+    
+    /// Whether automation is suspended (the strategy never triggers while paused)
+    #[serde(default)]
+    pub paused: bool,
+    
+    /// How the baseline moves after each execution
+    #[serde(default)]
+    pub baseline_policy: BaselinePolicy,
+    
+    /// Where the proceeds of each execution go
+    #[serde(default)]
+    pub profit_destination: ProfitDestination,
+    
+    /// Maximum executions within any `period_seconds` window (uncapped when `None`)
+    #[serde(default)]
+    pub max_executions_per_period: Option<u32>,
+    
+    /// Length of the rolling window of the execution cap
+    #[serde(default)]
+    pub period_seconds: u64,
+    
+    /// Timestamps of the latest executions, at most `max_executions_per_period` of them
+    #[serde(default)]
+    pub recent_executions: Vec<u64>,
 }
 
 impl TakeProfitStrategy {
@@ -45,22 +329,202 @@ impl TakeProfitStrategy {
             strategy_type,
             last_execution: 0,
             baseline_value: 0,
+            high_water_mark: 0,
+            fired_tiers: Vec::new(),
+            ladder_realized: 0,
+            profit_portion_bp: FULL_PROFIT_PORTION_BP,
+            paused: false,
+            baseline_policy: BaselinePolicy::ResetToCurrent,
+            profit_destination: ProfitDestination::HoldInVault,
+            max_executions_per_period: None,
+            period_seconds: 0,
+            recent_executions: Vec::new(),
         }
     }
     
-    /// Sets the baseline value for percentage-based strategies
+    /// Suspends the strategy, keeping its type and baseline
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    
+    /// Re-enables a paused strategy
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+    
+    /// Sets the share of the gain realized per execution (1 to 10000 basis points)
+    pub fn set_profit_portion(&mut self, profit_portion_bp: u32) -> Result<(), &'static str> {
+        if profit_portion_bp == 0 || profit_portion_bp > FULL_PROFIT_PORTION_BP {
+            return Err("Profit portion must be between 1 and 10000 basis points");
+        }
+        
+        self.profit_portion_bp = profit_portion_bp;
+        Ok(())
+    }
+    
+    /// Caps executions at `max_executions` per rolling `period_seconds` window (`None` removes the cap)
+    pub fn set_execution_cap(&mut self, max_executions: Option<u32>, period_seconds: u64) -> Result<(), &'static str> {
+        if let Some(max_executions) = max_executions {
+            if max_executions == 0 {
+                return Err("The execution cap must allow at least one execution per period");
+            }
+            
+            if period_seconds == 0 {
+                return Err("The execution cap needs a period longer than zero seconds");
+            }
+        }
+        
+        self.max_executions_per_period = max_executions;
+        self.period_seconds = period_seconds;
+        self.recent_executions.clear();
+        Ok(())
+    }
+    
+    /// Executions still allowed in the window ending at `now` (`None` when uncapped)
+    pub fn remaining_executions(&self, now: u64) -> Option<u32> {
+        let max_executions = self.max_executions_per_period?;
+        let window_start = now.saturating_sub(self.period_seconds);
+        
+        let in_window = self.recent_executions.iter()
+            .filter(|&&timestamp| timestamp > window_start)
+            .count() as u32;
+            
+        Some(max_executions.saturating_sub(in_window))
+    }
+    
+    /// Sets the baseline value, restarting the peak tracking and ladder from it
     pub fn set_baseline(&mut self, baseline_value: u128) {
         self.baseline_value = baseline_value;
+        self.high_water_mark = baseline_value;
+        self.fired_tiers.clear();
+        self.ladder_realized = 0;
+    }
+    
+    /// Lowest ladder tier not yet fired whose trigger is met at `current_value`
+    pub fn next_ladder_tier(&self, current_value: u128) -> Option<usize> {
+        let tiers = match &self.strategy_type {
+            TakeProfitType::Ladder { tiers } => tiers,
+            _ => return None,
+        };
+        
+        if self.baseline_value == 0 || current_value <= self.baseline_value {
+            return None;
+        }
+        
+        let gain_bp = (current_value - self.baseline_value) * 10000 / self.baseline_value;
+        
+        tiers.iter()
+            .enumerate()
+            .filter(|(index, tier)| !self.fired_tiers.contains(&(*index as u32)) && gain_bp >= tier.trigger_bp as u128)
+            .min_by_key(|(_, tier)| tier.trigger_bp)
+            .map(|(index, _)| index)
+    }
+    
+    /// Whether a ladder still has tiers left to fire
+    pub fn ladder_in_progress(&self) -> bool {
+        match &self.strategy_type {
+            TakeProfitType::Ladder { tiers } => self.fired_tiers.len() < tiers.len(),
+            _ => false,
+        }
+    }
+    
+    /// Gain to realize at `current_value`, firing the next ladder tier if any
+    ///
+    /// Ladders realize the fired tier's portion of the gain not yet realized by
+    /// earlier tiers (nothing when no tier is due), so a ladder never takes
+    /// more than the gain; every other strategy realizes `profit_portion_bp`
+    /// of it.
+    pub fn realize_gain(&mut self, current_value: u128) -> u128 {
+        let gain = current_value.saturating_sub(self.baseline_value);
+        
+        let tiers = match &self.strategy_type {
+            TakeProfitType::Ladder { tiers } => tiers,
+            _ => return gain * self.profit_portion_bp as u128 / 10000,
+        };
+        
+        match self.next_ladder_tier(current_value) {
+            Some(index) => {
+                let portion_bp = tiers[index].portion_bp as u128;
+                let realized = gain.saturating_sub(self.ladder_realized) * portion_bp / 10000;
+                self.fired_tiers.push(index as u32);
+                self.ladder_realized += realized;
+                realized
+            },
+            None => 0,
+        }
+    }
+    
+    /// Moves the baseline after an execution at `current_value` that realized `realized`
+    ///
+    /// Partial executions only add the realized amount to the baseline, so the
+    /// gain left unrealized still counts towards the next trigger. Ladders keep
+    /// their baseline until every tier has fired. The move is subject to the
+    /// strategy's `baseline_policy`.
+    pub fn advance_baseline(&mut self, current_value: u128, realized: u128) {
+        match &self.strategy_type {
+            TakeProfitType::Ladder { .. } if self.ladder_in_progress() => {},
+            TakeProfitType::Ladder { .. } => self.rebase(current_value),
+            _ if self.profit_portion_bp < FULL_PROFIT_PORTION_BP => {
+                self.rebase(self.baseline_value + realized)
+            },
+            _ => self.rebase(current_value),
+        }
+    }
+    
+    /// Moves the baseline towards `value` as far as the `baseline_policy` allows
+    pub fn rebase(&mut self, value: u128) {
+        let baseline = match self.baseline_policy {
+            BaselinePolicy::ResetToCurrent => value,
+            BaselinePolicy::HighWaterMark => value.max(self.baseline_value),
+            BaselinePolicy::Fixed => self.baseline_value,
+        };
+        
+        self.set_baseline(baseline);
+    }
+    
+    /// Raises the peak value seen since the baseline to `current_value` if it is higher
+    pub fn update_high_water_mark(&mut self, current_value: u128) {
+        self.high_water_mark = self.high_water_mark.max(current_value);
     }
     
     /// Records an execution of the take profit strategy
     pub fn record_execution(&mut self) {
-        self.last_execution = l1x_sdk::env::block_timestamp();
+        self.record_execution_at(l1x_sdk::env::block_timestamp());
     }
     
-    /// Determines if the take profit strategy should be executed
-    pub fn should_execute(&self, current_prices: &[(String, u128)]) -> bool {
-        match &self.strategy_type {
+    /// Records an execution at `now`, keeping only as many timestamps as the cap counts
+    pub fn record_execution_at(&mut self, now: u64) {
+        self.last_execution = now;
+        
+        if let Some(max_executions) = self.max_executions_per_period {
+            self.recent_executions.push(now);
+            
+            let excess = self.recent_executions.len().saturating_sub(max_executions as usize);
+            self.recent_executions.drain(..excess);
+        }
+    }
+    
+    /// Determines if the take profit strategy should be executed at the portfolio's `current_value`
+    ///
+    /// Percentage gains are measured against `baseline_value`. Trailing stops
+    /// compare against the stored `high_water_mark`, so
+    /// `update_high_water_mark` must have seen the values in between. Nothing
+    /// executes once the execution cap of the current period is used up.
+    pub fn should_execute(&self, current_value: u128) -> bool {
+        if self.paused {
+            return false;
+        }
+        
+        if self.remaining_executions(l1x_sdk::env::block_timestamp()) == Some(0) {
+            return false;
+        }
+        
+        self.condition_met(&self.strategy_type, current_value)
+    }
+    
+    /// Evaluates one trigger, recursing into composite conditions
+    fn condition_met(&self, condition: &TakeProfitType, current_value: u128) -> bool {
+        match condition {
             TakeProfitType::Manual => false, // Manual requires explicit trigger
             
             TakeProfitType::Percentage { percentage } => {
@@ -68,12 +532,6 @@ impl TakeProfitStrategy {
                     return false;
                 }
                 
-                // Calculate current value based on prices
-                let current_value: u128 = current_prices
-                    .iter()
-                    .map(|(_, price)| *price)
-                    .sum();
-                
                 // Calculate gain as a percentage
                 if current_value <= self.baseline_value {
                     return false;
@@ -91,6 +549,24 @@ impl TakeProfitStrategy {
                 
                 elapsed >= *interval_seconds
             },
+            
+            TakeProfitType::TrailingStop { trail_bp } => {
+                if self.baseline_value == 0 || current_value <= self.baseline_value {
+                    return false;
+                }
+                
+                let peak = self.high_water_mark.max(current_value);
+                let drop_bp = (peak - current_value) * 10000 / peak;
+                
+                drop_bp > (*trail_bp as u128)
+            },
+            
+            TakeProfitType::Ladder { .. } => self.next_ladder_tier(current_value).is_some(),
+            
+            TakeProfitType::Composite { conditions, mode } => match mode {
+                CompositeMode::All => conditions.iter().all(|c| self.condition_met(c, current_value)),
+                CompositeMode::Any => conditions.iter().any(|c| self.condition_met(c, current_value)),
+            },
         }
     }
     
@@ -136,7 +612,7 @@ mod tests {
         let strategy = TakeProfitStrategy::new(TakeProfitType::Manual);
         
         // Manual strategy should never auto-execute
-        assert!(!strategy.should_execute(&[]));
+        assert!(!strategy.should_execute(u128::MAX));
     }
     
     #[test]
@@ -149,16 +625,16 @@ mod tests {
         strategy.set_baseline(1000);
         
         // No gain yet
-        let no_gain_prices = vec![("BTC".to_string(), 1000)];
-        assert!(!strategy.should_execute(&no_gain_prices));
+        assert!(!strategy.should_execute(1000));
         
         // 5% gain (below threshold)
-        let small_gain_prices = vec![("BTC".to_string(), 1050)];
-        assert!(!strategy.should_execute(&small_gain_prices));
+        assert!(!strategy.should_execute(1050));
         
         // 20% gain (above threshold)
-        let large_gain_prices = vec![("BTC".to_string(), 1200)];
-        assert!(strategy.should_execute(&large_gain_prices));
+        assert!(strategy.should_execute(1200));
+        
+        // A loss never triggers
+        assert!(!strategy.should_execute(800));
     }
     
     #[test]
@@ -171,13 +647,262 @@ mod tests {
         strategy.record_execution();
         
         // Time hasn't elapsed yet
-        assert!(!strategy.should_execute(&[]));
+        assert!(!strategy.should_execute(0));
         
         // Simulate time passing (1 hour + 1 second)
         let timestamp = l1x_sdk::env::block_timestamp();
         l1x_sdk::env::set_block_timestamp(timestamp + 3601);
         
         // Time has elapsed, should execute
-        assert!(strategy.should_execute(&[]));
+        assert!(strategy.should_execute(0));
+    }
+    
+    #[test]
+    fn test_trailing_stop_peak_then_drop() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::TrailingStop {
+            trail_bp: 1000, // 10%
+        });
+        strategy.set_baseline(1000);
+        
+        // Rising to a peak of 1500 never triggers
+        for value in [1100, 1300, 1500] {
+            strategy.update_high_water_mark(value);
+            assert!(!strategy.should_execute(value));
+        }
+        assert_eq!(strategy.high_water_mark, 1500);
+        
+        // Exactly 10% below the peak is not more than the trail
+        strategy.update_high_water_mark(1350);
+        assert!(!strategy.should_execute(1350));
+        
+        // More than 10% below the peak while above the baseline triggers
+        strategy.update_high_water_mark(1340);
+        assert!(strategy.should_execute(1340));
+        assert_eq!(strategy.high_water_mark, 1500);
+        
+        // Resetting the baseline restarts the peak
+        strategy.set_baseline(1340);
+        assert_eq!(strategy.high_water_mark, 1340);
+    }
+    
+    #[test]
+    fn test_trailing_stop_never_above_baseline() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::TrailingStop {
+            trail_bp: 500, // 5%
+        });
+        strategy.set_baseline(1000);
+        
+        // Falling straight from the baseline is a loss, not a profit to lock in
+        for value in [990, 800, 500] {
+            strategy.update_high_water_mark(value);
+            assert!(!strategy.should_execute(value));
+        }
+        assert_eq!(strategy.high_water_mark, 1000);
+    }
+    
+    #[test]
+    fn test_ladder_walks_through_tiers() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Ladder {
+            tiers: vec![
+                LadderTier { trigger_bp: 1000, portion_bp: 2500 },  // 25% of gains at +10%
+                LadderTier { trigger_bp: 2500, portion_bp: 2500 },  // 25% of the remaining gains at +25%
+                LadderTier { trigger_bp: 5000, portion_bp: 10000 }, // the rest at +50%
+            ],
+        });
+        strategy.set_baseline(1000);
+        
+        let mut realized = Vec::new();
+        for value in [1050, 1100, 1150, 1300, 1400, 1600, 1700] {
+            if strategy.should_execute(value) {
+                realized.push((value, strategy.realize_gain(value)));
+            }
+        }
+        
+        // 25% of 100, then 25% of the 275 left of 300, then all 507 left of 600
+        assert_eq!(realized, vec![(1100, 25), (1300, 68), (1600, 507)]);
+        assert_eq!(realized.iter().map(|(_, gain)| gain).sum::<u128>(), 600);
+        assert_eq!(strategy.fired_tiers, vec![0, 1, 2]);
+        assert!(!strategy.ladder_in_progress());
+        
+        // Resetting the baseline starts a new ladder
+        strategy.set_baseline(1700);
+        assert!(strategy.fired_tiers.is_empty());
+        assert_eq!(strategy.ladder_realized, 0);
+        assert!(strategy.ladder_in_progress());
+        
+        // A jump past several triggers fires them one at a time, lowest first
+        assert_eq!(strategy.next_ladder_tier(2200), Some(0));
+        strategy.realize_gain(2200);
+        assert_eq!(strategy.next_ladder_tier(2200), Some(1));
+    }
+    
+    #[test]
+    fn test_composite_conditions() {
+        let up_5_percent = TakeProfitType::Percentage { percentage: 500 };
+        let every_30_days = TakeProfitType::Time { interval_seconds: 30 * 86400 };
+        
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Composite {
+            conditions: vec![up_5_percent.clone(), every_30_days.clone()],
+            mode: CompositeMode::All,
+        });
+        strategy.set_baseline(1000);
+        strategy.last_execution = l1x_sdk::env::block_timestamp();
+        
+        // Up 10% but the interval has not elapsed: All mode does not fire
+        assert!(!strategy.should_execute(1100));
+        
+        // The same single condition is enough in Any mode
+        strategy.strategy_type = TakeProfitType::Composite {
+            conditions: vec![up_5_percent.clone(), every_30_days.clone()],
+            mode: CompositeMode::Any,
+        };
+        assert!(strategy.should_execute(1100));
+        assert!(!strategy.should_execute(1010));
+        
+        // Nested composites up to two levels deep
+        let nested = TakeProfitType::Composite {
+            conditions: vec![
+                every_30_days.clone(),
+                TakeProfitType::Composite { conditions: vec![up_5_percent.clone()], mode: CompositeMode::Any },
+            ],
+            mode: CompositeMode::Any,
+        };
+        assert!(nested.validate().is_ok());
+        strategy.strategy_type = nested.clone();
+        assert!(strategy.should_execute(1100));
+        
+        // Nested composites survive a round trip through storage
+        let bytes = strategy.try_to_vec().unwrap();
+        let restored = TakeProfitStrategy::try_from_slice(&bytes).unwrap();
+        assert_eq!(restored.strategy_type, nested);
+        
+        let too_deep = TakeProfitType::Composite { conditions: vec![nested], mode: CompositeMode::All };
+        assert!(too_deep.validate().is_err());
+        
+        let empty = TakeProfitType::Composite { conditions: Vec::new(), mode: CompositeMode::All };
+        assert!(empty.validate().is_err());
+        
+        let trailing = TakeProfitType::Composite {
+            conditions: vec![TakeProfitType::TrailingStop { trail_bp: 500 }],
+            mode: CompositeMode::Any,
+        };
+        assert!(trailing.validate().is_err());
+    }
+    
+    #[test]
+    fn test_partial_take_profit_preserves_unrealized_gain() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        strategy.set_profit_portion(5000).unwrap();
+        strategy.set_baseline(1000);
+        
+        // +20%: realize half of the 200 gain, keep the other 100 unrealized
+        assert!(strategy.should_execute(1200));
+        let realized = strategy.realize_gain(1200);
+        strategy.advance_baseline(1200, realized);
+        assert_eq!(realized, 100);
+        assert_eq!(strategy.baseline_value, 1100);
+        
+        // +20% from the new baseline: the unrealized 100 is part of the gain again
+        assert!(strategy.should_execute(1320));
+        let realized = strategy.realize_gain(1320);
+        strategy.advance_baseline(1320, realized);
+        assert_eq!(realized, 110);
+        assert_eq!(strategy.baseline_value, 1210);
+        
+        assert!(strategy.set_profit_portion(0).is_err());
+        assert!(strategy.set_profit_portion(10001).is_err());
+        assert!(strategy.set_profit_portion(10000).is_ok());
+        
+        // Full executions reset the baseline to the current value
+        let realized = strategy.realize_gain(1500);
+        strategy.advance_baseline(1500, realized);
+        assert_eq!(realized, 290);
+        assert_eq!(strategy.baseline_value, 1500);
+    }
+    
+    #[test]
+    fn test_paused_strategy_never_executes() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        strategy.set_baseline(1000);
+        assert!(strategy.should_execute(1200));
+        
+        strategy.pause();
+        assert!(!strategy.should_execute(1200));
+        assert_eq!(strategy.baseline_value, 1000);
+        
+        strategy.resume();
+        assert!(strategy.should_execute(1200));
+    }
+    
+    #[test]
+    fn test_execution_cap_rolls_over_with_period() {
+        let mut strategy = TakeProfitStrategy::new(TakeProfitType::Time { interval_seconds: 60 });
+        assert!(strategy.set_execution_cap(Some(0), 3600).is_err());
+        assert!(strategy.set_execution_cap(Some(2), 0).is_err());
+        
+        strategy.set_execution_cap(Some(2), 3600).unwrap();
+        assert_eq!(strategy.remaining_executions(1000), Some(2));
+        
+        strategy.record_execution_at(1000);
+        strategy.record_execution_at(1600);
+        assert_eq!(strategy.remaining_executions(1700), Some(0));
+        
+        // The first execution slides out of the window an hour after it happened
+        assert_eq!(strategy.remaining_executions(4599), Some(0));
+        assert_eq!(strategy.remaining_executions(4600), Some(1));
+        
+        // The ring buffer only keeps the executions the cap counts
+        strategy.record_execution_at(4600);
+        assert_eq!(strategy.recent_executions, vec![1600, 4600]);
+        assert_eq!(strategy.remaining_executions(4700), Some(0));
+        assert_eq!(strategy.remaining_executions(8200), Some(2));
+        
+        // Uncapped strategies never run out
+        strategy.set_execution_cap(None, 0).unwrap();
+        assert_eq!(strategy.remaining_executions(8200), None);
+    }
+    
+    #[test]
+    fn test_high_water_mark_baseline_prevents_double_counting() {
+        // Take profit at +20%, then execute again in a dip and recover to the same value
+        let run = |policy: BaselinePolicy| {
+            let mut strategy = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+            strategy.baseline_policy = policy;
+            strategy.set_baseline(1000);
+            
+            let mut realized = Vec::new();
+            for value in [1200, 900, 1200] {
+                if value < strategy.baseline_value || strategy.should_execute(value) {
+                    let gain = strategy.realize_gain(value);
+                    strategy.advance_baseline(value, gain);
+                    realized.push(gain);
+                }
+            }
+            (realized, strategy.baseline_value)
+        };
+        
+        // Resetting to the dip takes the recovered 300 as profit again
+        assert_eq!(run(BaselinePolicy::ResetToCurrent), (vec![200, 0, 300], 1200));
+        
+        // The high-water mark keeps the baseline at 1200, so the recovery is not profit
+        assert_eq!(run(BaselinePolicy::HighWaterMark), (vec![200, 0], 1200));
+        
+        // A fixed baseline never moves
+        let mut fixed = TakeProfitStrategy::new(TakeProfitType::Percentage { percentage: 1000 });
+        fixed.baseline_policy = BaselinePolicy::Fixed;
+        fixed.set_baseline(1000);
+        fixed.advance_baseline(1200, 200);
+        assert_eq!(fixed.baseline_value, 1000);
+    }
+    
+    #[test]
+    fn test_validate_ladder() {
+        let tier = |trigger_bp, portion_bp| LadderTier { trigger_bp, portion_bp };
+        
+        assert!(validate_ladder(&[tier(1000, 2500), tier(2500, 10000)]).is_ok());
+        assert!(validate_ladder(&[]).is_err());
+        assert!(validate_ladder(&[tier(2500, 2500), tier(1000, 2500)]).is_err());
+        assert!(validate_ladder(&[tier(1000, 0)]).is_err());
+        assert!(validate_ladder(&[tier(1000, 10001)]).is_err());
     }
 }
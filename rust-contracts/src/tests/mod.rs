@@ -1,13 +1,20 @@
 //! Integration tests for One Capital Auto-Investing smart contracts
 
-use crate::allocation::{AllocationSet, AssetAllocation};
-use crate::custodial_vault::{CustodialVault, VaultStatus};
-use crate::non_custodial_vault::NonCustodialVault;
+use crate::allocation::AssetAllocation;
+use crate::custodial_vault::CustodialVault;
+use crate::non_custodial_vault::{NonCustodialVault, RebalanceAction};
 use crate::portfolio::Portfolio;
 use crate::rebalance::{RebalanceEngine, RebalanceStrategy, RebalanceStatus};
-use crate::take_profit::{TakeProfitStrategy, TakeProfitType};
-use crate::wallet::{Wallet, WalletType, AccessLevel};
-use crate::xtalk::{XTalkClient, XTalkSwapRequest};
+use crate::take_profit::TakeProfitType;
+use crate::wallet::Wallet;
+use crate::xtalk::{XTalkClient, XTalkMessageStatus, XTalkSwapRequest};
+
+mod rebalance_tests;
+
+#[test]
+fn version_check() {
+    assert_eq!(crate::VERSION, env!("CARGO_PKG_VERSION"));
+}
 
 #[test]
 fn test_full_portfolio_lifecycle() {
@@ -72,11 +79,13 @@ fn test_full_portfolio_lifecycle() {
     assert_eq!(transactions[0].2, 1000);  // Amount to swap ($1,000)
     
     // Create rebalance operation
-    let mut operation = RebalanceEngine::create_rebalance_operation(
+    let operation = RebalanceEngine::create_rebalance_operation(
         "rebalance-1".to_string(),
         RebalanceStrategy::Threshold,
         transactions,
     );
+    assert_eq!(operation.status, RebalanceStatus::Pending);
+    assert_eq!(operation.transactions.len(), 1);
     
     // Simulate rebalance execution (normally would happen on-chain)
     vault.allocations.allocations[0].update_current_percentage(5000); // BTC now 50%
@@ -100,11 +109,11 @@ fn test_full_portfolio_lifecycle() {
     let snapshot = Portfolio::create_snapshot(balanced_values, &vault.allocations);
     assert_eq!(snapshot.total_value, 10000);
     
-    // Simulate market movements (20% gain)
+    // Simulate market movements (20% gain, led by BTC)
     let new_values = vec![
-        ("BTC".to_string(), 6000),
-        ("ETH".to_string(), 3600),
-        ("USDC".to_string(), 2400),
+        ("BTC".to_string(), 6600),
+        ("ETH".to_string(), 3300),
+        ("USDC".to_string(), 2100),
     ];
     
     let new_total = 12000;
@@ -145,7 +154,6 @@ fn test_non_custodial_workflow() {
     let mut vault = NonCustodialVault::new(
         "vault-2".to_string(),
         "0xuser2".to_string(),
-        "0xcontract2".to_string(),
         300, // 3% drift threshold
     );
     
@@ -156,32 +164,26 @@ fn test_non_custodial_workflow() {
     let eth_allocation = AssetAllocation::new("ETH".to_string(), 5000); // 50%
     vault.allocations.add_allocation(eth_allocation).unwrap();
     
-    // Current values (60/40 split)
-    let current_values = vec![
-        ("BTC".to_string(), 6000),
-        ("ETH".to_string(), 4000),
-    ];
-    
-    // Generate rebalance suggestions
-    let suggestions = vault.generate_rebalance_suggestions(&current_values, 10000);
-    
-    // Verify suggestions
-    assert_eq!(suggestions.len(), 1);
-    assert_eq!(suggestions[0].source_asset, "BTC");
-    assert_eq!(suggestions[0].target_asset, "ETH");
-    assert_eq!(suggestions[0].amount, 1000);
+    // Current holdings (60/40 split of $10,000)
+    vault.update_estimated_value(10000);
+    vault.allocations.allocations[0].update_current_percentage(6000);
+    vault.allocations.allocations[1].update_current_percentage(4000);
     
-    // Nonce should have been incremented
-    assert_eq!(vault.rebalance_nonce, 1);
+    // Generate rebalance recommendations
+    let recommendations = vault.generate_rebalance_recommendations();
     
-    // Simulate user approving and executing the swap
-    // Then update the allocations to reflect the new balance
-    let new_values = vec![
-        ("BTC".to_string(), 5000),
-        ("ETH".to_string(), 5000),
-    ];
+    // Verify recommendations
+    assert_eq!(recommendations.len(), 2);
+    assert_eq!(recommendations[0].asset_id, "BTC");
+    assert_eq!(recommendations[0].action, RebalanceAction::Sell);
+    assert_eq!(recommendations[0].amount_usd, 1000);
+    assert_eq!(recommendations[1].asset_id, "ETH");
+    assert_eq!(recommendations[1].action, RebalanceAction::Buy);
+    assert_eq!(recommendations[1].amount_usd, 1000);
     
-    vault.update_allocations_after_rebalance(&new_values, 10000);
+    // Simulate user approving and executing the swaps
+    vault.propose_rebalance("proposal-1".to_string());
+    vault.confirm_proposal("proposal-1").unwrap();
     
     // Verify allocations are updated
     assert_eq!(vault.allocations.allocations[0].current_percentage, 5000);
@@ -193,43 +195,21 @@ fn test_non_custodial_workflow() {
 
 #[test]
 fn test_xtalk_integration() {
-    // Create a price quote
-    let quote = XTalkClient::get_price_quote("BTC", "ETH", 1_00000000).unwrap();
-    
-    // Verify quote details
-    assert_eq!(quote.source_asset, "BTC");
-    assert_eq!(quote.target_asset, "ETH");
-    assert!(quote.expires_at > 0);
-    
     // Create and execute a swap
     let swap_request = XTalkSwapRequest {
         source_asset: "BTC".to_string(),
         target_asset: "ETH".to_string(),
         amount: 1_00000000,
         slippage_bps: 50,
+        recipient: "0xuser1".to_string(),
     };
     
-    let result = XTalkClient::execute_swap(&swap_request).unwrap();
-    
-    // Verify swap result
-    assert_eq!(result.source_asset, "BTC");
-    assert_eq!(result.target_asset, "ETH");
-    assert_eq!(result.source_amount, 1_00000000);
-    assert!(result.fee > 0);
-    
-    // Get asset prices
-    let btc_price = XTalkClient::get_asset_price("BTC").unwrap();
-    let eth_price = XTalkClient::get_asset_price("ETH").unwrap();
-    
-    // Verify prices
-    assert!(btc_price > 0);
-    assert!(eth_price > 0);
+    let message_id = XTalkClient::execute_swap(&swap_request, 1).unwrap();
     
-    // Get liquidity
-    let btc_liquidity = XTalkClient::get_liquidity("BTC").unwrap();
-    let eth_liquidity = XTalkClient::get_liquidity("ETH").unwrap();
+    // The swap is sent to the token swap contract on the destination chain
+    assert!(message_id.contains("chain 1"));
+    assert!(message_id.contains("TokenSwapContract.executeSwap"));
     
-    // Verify liquidity
-    assert!(btc_liquidity > 0);
-    assert!(eth_liquidity > 0);
+    // Verify message status
+    assert_eq!(XTalkClient::check_message_status(&message_id), XTalkMessageStatus::Broadcasted);
 }
@@ -5,7 +5,6 @@
 
 use crate::allocation::{AllocationSet, AssetAllocation};
 use crate::rebalance::{RebalanceEngine, RebalanceStrategy};
-use crate::events::DriftResult;
 
 #[test]
 fn test_drift_calculation() {
@@ -80,7 +79,7 @@ fn test_calculate_rebalance_transactions() {
     let transactions = allocation_set.calculate_rebalance_transactions(
         &current_values, 
         total_value
-    );
+    ).unwrap();
     
     // Should have 1 transaction: sell BTC, buy ETH
     assert_eq!(transactions.len(), 1);
@@ -122,8 +121,9 @@ fn test_allocation_set_needs_rebalancing() {
     assert!(allocation_set.needs_rebalancing());
     
     // Reset allocations to target
-    btc_allocation.update_current_percentage(5000);
-    eth_allocation.update_current_percentage(5000);
+    for allocation in allocation_set.allocations.iter_mut() {
+        allocation.update_current_percentage(5000);
+    }
     
     // Set up time-based rebalancing (daily)
     allocation_set.set_rebalance_frequency(86400);
@@ -171,21 +171,21 @@ fn test_multi_asset_rebalancing() {
     let transactions = allocation_set.calculate_rebalance_transactions(
         &current_values, 
         total_value
-    );
+    ).unwrap();
     
     // Should generate transactions to correct imbalances
     assert_eq!(transactions.len(), 2);
     
-    // Sort transactions for consistent testing
-    let mut sorted_transactions = transactions.clone();
-    sorted_transactions.sort_by(|a, b| a.0.cmp(&b.0));
-    
-    // First transaction should involve SOL as source
-    let (source1, target1, amount1) = &sorted_transactions[0];
-    assert_eq!(source1, "SOL");
+    // SOL is the only source, covering both under-allocated assets
+    for (source, target, amount) in &transactions {
+        assert_eq!(source, "SOL");
+        assert!(target == "BTC" || target == "ETH");
+        assert_eq!(*amount, 500);
+    }
     
     // SOL needs to give up 10% (1000 units)
-    assert_eq!(*amount1, 1000);
+    let sold: u128 = transactions.iter().map(|(_, _, amount)| *amount).sum();
+    assert_eq!(sold, 1000);
 }
 
 // Test rebalance execution with simulated swap
@@ -291,10 +291,10 @@ fn test_complex_rebalancing_scenario() {
     let transactions = allocation_set.calculate_rebalance_transactions(
         &current_values,
         total_value
-    );
+    ).unwrap();
     
     // Should generate transactions to correct the imbalances
-    assert!(transactions.len() > 0);
+    assert!(!transactions.is_empty());
     
     // There should be at least one transaction selling BTC (over-allocated)
     assert!(transactions.iter().any(|(source, _, _)| source == "BTC"));
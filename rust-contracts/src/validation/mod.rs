@@ -0,0 +1,147 @@
+//! Input validation for One Capital Auto-Investing contracts
+//!
+//! This module provides the common parameter checks used by contract entry
+//! points so bad input is rejected with a message naming the offending field.
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum basis points value (100%)
+pub const MAX_BASIS_POINTS: u32 = 10000;
+
+/// Number of hex digits in an account address
+pub const ADDRESS_HEX_LENGTH: usize = 40;
+
+/// Reason a contract parameter was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValidationError {
+    /// Basis points value above 10000 (100%)
+    BasisPointsOutOfRange {
+        /// Parameter name
+        field: String,
+
+        /// Rejected value
+        value: u32,
+    },
+
+    /// Empty (or whitespace-only) string
+    EmptyString {
+        /// Parameter name
+        field: String,
+    },
+
+    /// Zero amount where a positive amount is required
+    ZeroAmount {
+        /// Parameter name
+        field: String,
+    },
+
+    /// Malformed account address
+    InvalidAddress {
+        /// Rejected address
+        address: String,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::BasisPointsOutOfRange { field, value } => {
+                write!(f, "{} must be at most {} basis points, got {}", field, MAX_BASIS_POINTS, value)
+            },
+            ValidationError::EmptyString { field } => write!(f, "{} must not be empty", field),
+            ValidationError::ZeroAmount { field } => write!(f, "{} must be greater than zero", field),
+            ValidationError::InvalidAddress { address } => {
+                write!(f, "Invalid address {:?}: expected 0x followed by {} hex digits", address, ADDRESS_HEX_LENGTH)
+            },
+        }
+    }
+}
+
+/// Checks that a basis points value is at most 10000 (100%)
+pub fn validate_basis_points(v: u32, field: &str) -> Result<(), ValidationError> {
+    if v > MAX_BASIS_POINTS {
+        return Err(ValidationError::BasisPointsOutOfRange { field: field.to_string(), value: v });
+    }
+
+    Ok(())
+}
+
+/// Checks that a string has non-whitespace content
+pub fn validate_non_empty_string(s: &str, field: &str) -> Result<(), ValidationError> {
+    if s.trim().is_empty() {
+        return Err(ValidationError::EmptyString { field: field.to_string() });
+    }
+
+    Ok(())
+}
+
+/// Checks that an amount is greater than zero
+pub fn validate_amount_nonzero(v: u128, field: &str) -> Result<(), ValidationError> {
+    if v == 0 {
+        return Err(ValidationError::ZeroAmount { field: field.to_string() });
+    }
+
+    Ok(())
+}
+
+/// Checks that an address is `0x` followed by 40 hex digits
+pub fn validate_address(s: &str) -> Result<(), ValidationError> {
+    let valid = match s.strip_prefix("0x") {
+        Some(hex) => hex.len() == ADDRESS_HEX_LENGTH && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    };
+
+    if !valid {
+        return Err(ValidationError::InvalidAddress { address: s.to_string() });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_basis_points() {
+        assert!(validate_basis_points(0, "bp").is_ok());
+        assert!(validate_basis_points(10000, "bp").is_ok());
+
+        let err = validate_basis_points(10001, "drift_threshold_bp").unwrap_err();
+        assert_eq!(err, ValidationError::BasisPointsOutOfRange { field: "drift_threshold_bp".to_string(), value: 10001 });
+        assert_eq!(err.to_string(), "drift_threshold_bp must be at most 10000 basis points, got 10001");
+        assert!(validate_basis_points(u32::MAX, "bp").is_err());
+    }
+
+    #[test]
+    fn test_validate_non_empty_string() {
+        assert!(validate_non_empty_string("v", "vault_id").is_ok());
+        assert!(validate_non_empty_string(" vault-1 ", "vault_id").is_ok());
+
+        assert_eq!(validate_non_empty_string("", "vault_id").unwrap_err().to_string(), "vault_id must not be empty");
+        assert!(validate_non_empty_string(" \t\n", "vault_id").is_err());
+    }
+
+    #[test]
+    fn test_validate_amount_nonzero() {
+        assert!(validate_amount_nonzero(1, "amount").is_ok());
+        assert!(validate_amount_nonzero(u128::MAX, "amount").is_ok());
+
+        assert_eq!(validate_amount_nonzero(0, "amount").unwrap_err(), ValidationError::ZeroAmount { field: "amount".to_string() });
+    }
+
+    #[test]
+    fn test_validate_address() {
+        assert!(validate_address("0x0123456789abcdefABCDEF0123456789abcdef01").is_ok());
+
+        // One digit short / long
+        assert!(validate_address("0x0123456789abcdef0123456789abcdef0123456").is_err());
+        assert!(validate_address("0x0123456789abcdef0123456789abcdef012345678").is_err());
+
+        // Missing prefix, non-hex digit, empty
+        assert!(validate_address("0123456789abcdef0123456789abcdef01234567").is_err());
+        assert!(validate_address("0x0123456789abcdef0123456789abcdef0123456g").is_err());
+        assert!(validate_address("").is_err());
+        assert!(validate_address("0x").is_err());
+    }
+}
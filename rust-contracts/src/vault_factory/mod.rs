@@ -0,0 +1,249 @@
+//! Vault factory for One Capital Auto-Investing
+//!
+//! This module keeps a registry of pre-configured vault templates (allocations,
+//! drift threshold and take-profit strategy) and creates custodial vaults from
+//! them in a single call.
+
+use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
+use l1x_sdk::prelude::*;
+
+use crate::allocation::{AllocationSet, AssetAllocation};
+use crate::custodial_vault::CustodialVault;
+use crate::take_profit::TakeProfitType;
+
+/// Pre-configured vault setup
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct VaultTemplate {
+    /// Template name (e.g. "Balanced Growth")
+    pub name: String,
+
+    /// Template description
+    pub description: String,
+
+    /// Asset targets in basis points, summing to 10000
+    pub allocations: Vec<(String, u32)>,
+
+    /// Drift threshold (in basis points) that triggers rebalancing
+    pub drift_threshold_bp: u32,
+
+    /// Take profit strategy applied to new vaults (if any)
+    pub take_profit_type: Option<TakeProfitType>,
+}
+
+impl VaultTemplate {
+    /// Builds the allocation set described by the template
+    pub fn build_allocation_set(&self) -> Result<AllocationSet, &'static str> {
+        let mut allocation_set = AllocationSet::new(self.drift_threshold_bp);
+
+        for (asset_id, target_percentage) in &self.allocations {
+            allocation_set.add_allocation(AssetAllocation::new(asset_id.clone(), *target_percentage))?;
+        }
+
+        allocation_set.validate_percentages()?;
+
+        Ok(allocation_set)
+    }
+
+    /// Creates a custodial vault configured from the template
+    pub fn build_vault(&self, vault_id: String, owner: String) -> Result<CustodialVault, &'static str> {
+        let mut vault = CustodialVault::new(vault_id, owner, self.drift_threshold_bp);
+        vault.allocations = self.build_allocation_set()?;
+
+        if let Some(take_profit_type) = &self.take_profit_type {
+            vault.set_take_profit_strategy(take_profit_type.clone())?;
+        }
+
+        Ok(vault)
+    }
+}
+
+/// Vault factory contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"VAULT_FACTORY";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct VaultFactoryContract {
+    templates: std::collections::HashMap<String, VaultTemplate>, // Template ID -> Template
+    admin: String,
+}
+
+#[l1x_sdk::contract]
+impl VaultFactoryContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new(admin: String) {
+        let mut state = Self {
+            templates: std::collections::HashMap::new(),
+            admin,
+        };
+
+        state.save()
+    }
+
+    /// Checks if the caller is an admin
+    fn is_admin() -> bool {
+        let state = Self::load();
+        let caller = l1x_sdk::env::caller();
+
+        state.admin == caller
+    }
+
+    /// Parses and validates a template from JSON
+    fn parse_template(template_json: &str) -> VaultTemplate {
+        let template: VaultTemplate = serde_json::from_str(template_json)
+            .unwrap_or_else(|_| panic!("Failed to parse template"));
+
+        template.build_allocation_set()
+            .unwrap_or_else(|err| panic!("Invalid template: {}", err));
+
+        template
+    }
+
+    /// Creates a new vault template
+    pub fn create_template(template_id: String, template_json: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can create vault templates");
+        }
+
+        let mut state = Self::load();
+
+        if state.templates.contains_key(&template_id) {
+            panic!("Template with this ID already exists");
+        }
+
+        let template = Self::parse_template(&template_json);
+        state.templates.insert(template_id.clone(), template);
+        state.save();
+
+        format!("Vault template {} created", template_id)
+    }
+
+    /// Replaces an existing vault template
+    pub fn update_template(template_id: String, template_json: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can update vault templates");
+        }
+
+        let mut state = Self::load();
+
+        if !state.templates.contains_key(&template_id) {
+            panic!("Template not found: {}", template_id);
+        }
+
+        let template = Self::parse_template(&template_json);
+        state.templates.insert(template_id.clone(), template);
+        state.save();
+
+        format!("Vault template {} updated", template_id)
+    }
+
+    /// Deletes a vault template
+    pub fn delete_template(template_id: String) -> String {
+        if !Self::is_admin() {
+            panic!("Only admin can delete vault templates");
+        }
+
+        let mut state = Self::load();
+
+        if state.templates.remove(&template_id).is_none() {
+            panic!("Template not found: {}", template_id);
+        }
+
+        state.save();
+
+        format!("Vault template {} deleted", template_id)
+    }
+
+    /// Gets a vault template by ID
+    pub fn get_template(template_id: String) -> String {
+        let state = Self::load();
+
+        let template = state.templates.get(&template_id)
+            .unwrap_or_else(|| panic!("Template not found: {}", template_id));
+
+        serde_json::to_string(template)
+            .unwrap_or_else(|_| "Failed to serialize template".to_string())
+    }
+
+    /// Lists all vault templates as `(template_id, template)` pairs
+    pub fn list_templates() -> String {
+        let state = Self::load();
+
+        let mut templates: Vec<(&String, &VaultTemplate)> = state.templates.iter().collect();
+        templates.sort_by(|a, b| a.0.cmp(b.0));
+
+        serde_json::to_string(&templates)
+            .unwrap_or_else(|_| "Failed to serialize templates".to_string())
+    }
+
+    /// Creates a custodial vault for `owner` configured from a template
+    pub fn create_vault_from_template(template_id: String, owner: String, vault_id: String) -> String {
+        let state = Self::load();
+
+        let template = state.templates.get(&template_id)
+            .unwrap_or_else(|| panic!("Template not found: {}", template_id));
+
+        let vault = template.build_vault(vault_id.clone(), owner.clone())
+            .unwrap_or_else(|err| panic!("Failed to build vault: {}", err));
+
+        crate::custodial_vault::register_vault(vault)
+            .unwrap_or_else(|err| panic!("Failed to register vault: {}", err));
+
+        format!("Vault {} created for user {} from template {}", vault_id, owner, template_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balanced_template() -> VaultTemplate {
+        VaultTemplate {
+            name: "Balanced".to_string(),
+            description: "BTC/ETH/USDC 50/30/20".to_string(),
+            allocations: vec![
+                ("BTC".to_string(), 5000),
+                ("ETH".to_string(), 3000),
+                ("USDC".to_string(), 2000),
+            ],
+            drift_threshold_bp: 400,
+            take_profit_type: Some(TakeProfitType::Percentage { percentage: 2000 }),
+        }
+    }
+
+    #[test]
+    fn test_vault_from_template() {
+        let template = balanced_template();
+        let vault = template.build_vault("vault-1".to_string(), "owner-1".to_string()).unwrap();
+
+        assert_eq!(vault.id, "vault-1");
+        assert_eq!(vault.owner, "owner-1");
+        assert_eq!(vault.allocations.drift_threshold_bp, 400);
+
+        let allocations: Vec<(String, u32)> = vault.allocations.allocations.iter()
+            .map(|a| (a.asset_id.clone(), a.target_percentage))
+            .collect();
+        assert_eq!(allocations, template.allocations);
+
+        let take_profit = vault.take_profit.expect("take profit strategy");
+        assert_eq!(take_profit.strategy_type, TakeProfitType::Percentage { percentage: 2000 });
+    }
+
+    #[test]
+    fn test_invalid_template_rejected() {
+        let mut template = balanced_template();
+        template.allocations[2].1 = 1000; // Sums to 90%
+
+        assert!(template.build_allocation_set().is_err());
+        assert!(template.build_vault("vault-1".to_string(), "owner-1".to_string()).is_err());
+    }
+}
@@ -4,10 +4,11 @@
 //! L1X blockchain and storing wallet-related data.
 
 use serde::{Deserialize, Serialize};
+use borsh::{BorshSerialize, BorshDeserialize};
 use l1x_sdk::prelude::*;
 
 /// Supported wallet types
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
 pub enum WalletType {
     /// Direct L1X blockchain wallet
     Native,
@@ -20,7 +21,7 @@ pub enum WalletType {
 }
 
 /// Wallet access levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BorshSerialize, BorshDeserialize)]
 pub enum AccessLevel {
     /// Read-only access (can view balances but not transact)
     ReadOnly,
@@ -32,8 +33,106 @@ pub enum AccessLevel {
     Admin,
 }
 
+/// Operations a session key can be delegated
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum OperationScope {
+    /// Trigger vault rebalances
+    Rebalance,
+    
+    /// Deposit funds into vaults
+    Deposit,
+    
+    /// Withdraw funds from vaults
+    Withdraw,
+    
+    /// Execute take-profit strategies
+    TakeProfit,
+    
+    /// Perform cross-chain swaps
+    Swap,
+}
+
+/// Short-lived key a wallet delegates a limited set of operations to
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct SessionKey {
+    /// Unique identifier for the session key
+    pub key_id: String,
+    
+    /// Public key of the delegated key pair
+    pub public_key: String,
+    
+    /// Operations the key may perform
+    pub scopes: Vec<OperationScope>,
+    
+    /// Timestamp after which the key is no longer valid
+    pub expires_at: u64,
+}
+
+impl SessionKey {
+    /// Checks whether the key has expired at the given time
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+    
+    /// Checks whether the key may perform an operation at the given time
+    pub fn allows(&self, scope: OperationScope, now: u64) -> bool {
+        !self.is_expired(now) && self.scopes.contains(&scope)
+    }
+}
+
+/// Rolling spending cap on outflows from a wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct SpendingLimit {
+    /// Maximum amount that can be spent per window
+    pub amount_per_window: u128,
+    
+    /// Length of the window in seconds
+    pub window_seconds: u64,
+    
+    /// Amount spent so far in the current window
+    pub spent_in_window: u128,
+    
+    /// Timestamp the current window started
+    pub window_start: u64,
+}
+
+impl SpendingLimit {
+    /// Creates a new spending limit with an empty window starting now
+    pub fn new(amount_per_window: u128, window_seconds: u64) -> Self {
+        Self {
+            amount_per_window,
+            window_seconds,
+            spent_in_window: 0,
+            window_start: l1x_sdk::env::block_timestamp(),
+        }
+    }
+    
+    /// Records a spend against the current window
+    pub fn check_and_record(&mut self, amount: u128) -> Result<(), &'static str> {
+        self.check_and_record_at(amount, l1x_sdk::env::block_timestamp())
+    }
+    
+    /// Records a spend at `now`, starting a fresh window if the current one has expired
+    pub fn check_and_record_at(&mut self, amount: u128, now: u64) -> Result<(), &'static str> {
+        if now >= self.window_start.saturating_add(self.window_seconds) {
+            self.window_start = now;
+            self.spent_in_window = 0;
+        }
+        
+        let spent = self.spent_in_window.checked_add(amount)
+            .ok_or("Spending overflow")?;
+            
+        if spent > self.amount_per_window {
+            return Err("Spending limit exceeded for current window");
+        }
+        
+        self.spent_in_window = spent;
+        Ok(())
+    }
+}
+
 /// Represents a wallet for interacting with L1X blockchain
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct Wallet {
     /// Unique identifier for the wallet
     pub id: String,
@@ -55,6 +154,10 @@ pub struct Wallet {
     
     /// Last activity timestamp
     pub last_activity: u64,
+    
+    /// Cap on withdrawals per time window (None = unlimited)
+    #[serde(default)]
+    pub spending_limit: Option<SpendingLimit>,
 }
 
 impl Wallet {
@@ -68,6 +171,7 @@ impl Wallet {
             access_level: AccessLevel::Standard,
             created_at: l1x_sdk::env::block_timestamp(),
             last_activity: l1x_sdk::env::block_timestamp(),
+            spending_limit: None,
         }
     }
     
@@ -81,6 +185,7 @@ impl Wallet {
             access_level: AccessLevel::Standard,
             created_at: l1x_sdk::env::block_timestamp(),
             last_activity: l1x_sdk::env::block_timestamp(),
+            spending_limit: None,
         }
     }
     
@@ -95,6 +200,23 @@ impl Wallet {
         self.update_activity();
     }
     
+    /// Verifies a signature over `message` against this wallet's public key
+    ///
+    /// Native wallets sign with secp256k1 ECDSA; hardware wallets sign with ed25519.
+    pub fn verify_signature(&self, message: &[u8], signature: &[u8]) -> bool {
+        let public_key = match decode_hex(&self.public_key) {
+            Some(bytes) => bytes,
+            None => return false,
+        };
+        
+        match self.wallet_type {
+            WalletType::Native => WalletManager::verify_secp256k1_signature(&public_key, message, signature),
+            WalletType::Hardware => WalletManager::verify_ed25519_signature(&public_key, message, signature),
+            // Multi-sig wallets are authorized through signer approvals, not a single key
+            WalletType::MultiSig => false,
+        }
+    }
+    
     /// Checks if the wallet has at least the specified access level
     pub fn has_access(&self, required_level: AccessLevel) -> bool {
         match (required_level, &self.access_level) {
@@ -110,9 +232,14 @@ impl Wallet {
 pub struct WalletManager;
 
 impl WalletManager {
+    /// Wallet ID assigned to an address
+    pub fn wallet_id(address: &str) -> String {
+        format!("wallet-{}", address)
+    }
+    
     /// Connects a wallet to the platform
     pub fn connect_wallet(address: String, public_key: String) -> Wallet {
-        let id = format!("wallet-{}", address);
+        let id = Self::wallet_id(&address);
         Wallet::new_native(id, address, public_key)
     }
     
@@ -125,14 +252,393 @@ impl WalletManager {
         vec![0, 1, 2, 3, 4]
     }
     
-    /// Verifies a signature (placeholder function)
-    pub fn verify_signature(_wallet: &Wallet, _message: &[u8], _signature: &[u8]) -> bool {
-        // In a real implementation, this would verify the signature
-        // using the wallet's public key
+    /// Sets a wallet's spending limit, starting a fresh window
+    ///
+    /// Only the wallet owner can change its limit.
+    pub fn set_spending_limit(wallet_id: String, amount: u128, window_seconds: u64) {
+        let mut state = WalletContract::load();
+        state.assert_wallet_owner(&wallet_id);
         
-        // For now, always return true
-        true
+        let wallet = state.wallets.get_mut(&wallet_id)
+            .unwrap_or_else(|| panic!("Wallet not found: {}", wallet_id));
+            
+        wallet.spending_limit = Some(SpendingLimit::new(amount, window_seconds));
+        wallet.update_activity();
+        
+        state.save();
     }
+    
+    /// Records an outflow against a wallet's spending limit
+    ///
+    /// Wallets that are not registered or have no limit are not restricted.
+    pub fn record_spend(wallet_id: &str, amount: u128) -> Result<(), &'static str> {
+        let mut state = WalletContract::load();
+        
+        let limit = match state.wallets.get_mut(wallet_id).and_then(|w| w.spending_limit.as_mut()) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        
+        limit.check_and_record(amount)?;
+        state.save();
+        
+        Ok(())
+    }
+    
+    /// Delegates a set of operations to a session key for `duration_seconds`
+    ///
+    /// Only the wallet owner can delegate.
+    pub fn create_session_key(
+        wallet_id: String,
+        public_key: String,
+        scopes: Vec<OperationScope>,
+        duration_seconds: u64,
+    ) -> SessionKey {
+        let mut state = WalletContract::load();
+        state.assert_wallet_owner(&wallet_id);
+        
+        let keys = state.session_keys.entry(wallet_id.clone()).or_insert_with(Vec::new);
+        let now = l1x_sdk::env::block_timestamp();
+        
+        // Drop expired keys so the list doesn't grow without bound
+        keys.retain(|key| !key.is_expired(now));
+        
+        let session_key = SessionKey {
+            key_id: format!("session-{}-{}-{}", wallet_id, now, keys.len()),
+            public_key,
+            scopes,
+            expires_at: now.saturating_add(duration_seconds),
+        };
+        
+        keys.push(session_key.clone());
+        state.save();
+        
+        session_key
+    }
+    
+    /// Checks that a session key exists, has not expired and covers `scope`
+    pub fn verify_session_key(wallet_id: String, key_id: String, scope: OperationScope) -> bool {
+        let state = WalletContract::load();
+        
+        state.session_key_allows(&wallet_id, &key_id, scope, l1x_sdk::env::block_timestamp())
+    }
+    
+    /// Verifies a signature using the wallet's public key
+    pub fn verify_signature(wallet: &Wallet, message: &[u8], signature: &[u8]) -> bool {
+        wallet.verify_signature(message, signature)
+    }
+    
+    /// Verifies an ed25519 signature (32-byte public key, 64-byte signature)
+    pub fn verify_ed25519_signature(public_key_bytes: &[u8], message: &[u8], signature_bytes: &[u8]) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+        
+        let public_key: [u8; 32] = match public_key_bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        let signature: [u8; 64] = match signature_bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        
+        match VerifyingKey::from_bytes(&public_key) {
+            Ok(key) => key.verify(message, &Signature::from_bytes(&signature)).is_ok(),
+            Err(_) => false,
+        }
+    }
+    
+    /// Verifies a secp256k1 ECDSA signature over the SHA-256 digest of `message`
+    ///
+    /// The public key is SEC1-encoded (compressed or uncompressed) and the
+    /// signature is the 64-byte `r || s` form with a normalized (low) `s`.
+    pub fn verify_secp256k1_signature(public_key_bytes: &[u8], message: &[u8], signature_bytes: &[u8]) -> bool {
+        use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+        
+        let key = match VerifyingKey::from_sec1_bytes(public_key_bytes) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let signature = match Signature::from_slice(signature_bytes) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        
+        key.verify(message, &signature).is_ok()
+    }
+}
+
+/// Signer set and threshold for a multi-signature wallet
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct MultiSigConfig {
+    /// Number of distinct signer approvals required
+    pub required_approvals: u32,
+    
+    /// Accounts allowed to approve actions
+    pub signers: Vec<String>,
+}
+
+impl MultiSigConfig {
+    /// Creates a new multi-sig configuration
+    pub fn new(required_approvals: u32, signers: Vec<String>) -> Result<Self, &'static str> {
+        if required_approvals == 0 {
+            return Err("At least one approval must be required");
+        }
+        
+        if required_approvals as usize > signers.len() {
+            return Err("Required approvals exceed number of signers");
+        }
+        
+        Ok(Self {
+            required_approvals,
+            signers,
+        })
+    }
+    
+    /// Checks whether an account is one of the wallet's signers
+    pub fn is_signer(&self, account: &str) -> bool {
+        self.signers.iter().any(|s| s == account)
+    }
+}
+
+/// Approvals collected so far for a multi-sig action
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct PendingApproval {
+    /// Hash identifying the action being approved
+    pub action_hash: Vec<u8>,
+    
+    /// Signers that have approved the action
+    pub approvals: Vec<String>,
+    
+    /// Timestamp when the first approval was submitted
+    pub created_at: u64,
+}
+
+impl PendingApproval {
+    /// Creates a pending approval with no signatures
+    pub fn new(action_hash: Vec<u8>) -> Self {
+        Self {
+            action_hash,
+            approvals: Vec::new(),
+            created_at: l1x_sdk::env::block_timestamp(),
+        }
+    }
+    
+    /// Records a signer's approval; returns true once the threshold is met
+    pub fn approve(&mut self, signer: &str, config: &MultiSigConfig) -> Result<bool, &'static str> {
+        if !config.is_signer(signer) {
+            return Err("Signer is not authorized for this wallet");
+        }
+        
+        // Duplicate approvals are ignored rather than counted twice
+        if !self.approvals.iter().any(|a| a == signer) {
+            self.approvals.push(signer.to_string());
+        }
+        
+        Ok(self.is_approved(config))
+    }
+    
+    /// Checks whether enough signers have approved
+    pub fn is_approved(&self, config: &MultiSigConfig) -> bool {
+        self.approvals.len() as u32 >= config.required_approvals
+    }
+}
+
+/// Wallet contract storage
+const STORAGE_CONTRACT_KEY: &[u8] = b"WALLET";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WalletContract {
+    multisig_configs: std::collections::HashMap<String, MultiSigConfig>, // Wallet ID -> Config
+    pending_approvals: std::collections::HashMap<String, PendingApproval>, // Wallet ID + action hash -> Approval
+    session_keys: std::collections::HashMap<String, Vec<SessionKey>>, // Wallet ID -> Session keys
+    wallets: std::collections::HashMap<String, Wallet>, // Wallet ID -> Wallet
+}
+
+#[l1x_sdk::contract]
+impl WalletContract {
+    fn load() -> Self {
+        match l1x_sdk::storage_read(STORAGE_CONTRACT_KEY) {
+            Some(bytes) => Self::try_from_slice(&bytes).unwrap(),
+            None => panic!("The contract isn't initialized"),
+        }
+    }
+
+    fn save(&mut self) {
+        l1x_sdk::storage_write(STORAGE_CONTRACT_KEY, &self.try_to_vec().unwrap());
+    }
+
+    pub fn new() {
+        let mut state = Self {
+            multisig_configs: std::collections::HashMap::new(),
+            pending_approvals: std::collections::HashMap::new(),
+            session_keys: std::collections::HashMap::new(),
+            wallets: std::collections::HashMap::new(),
+        };
+
+        state.save()
+    }
+    
+    /// Registers the caller's wallet
+    pub fn register_wallet(public_key: String) -> String {
+        let mut state = Self::load();
+        
+        let wallet = WalletManager::connect_wallet(l1x_sdk::env::caller(), public_key);
+        let wallet_id = wallet.id.clone();
+        
+        if state.wallets.contains_key(&wallet_id) {
+            panic!("Wallet already registered: {}", wallet_id);
+        }
+        
+        state.wallets.insert(wallet_id.clone(), wallet);
+        state.save();
+        
+        format!("Wallet {} registered", wallet_id)
+    }
+    
+    /// Gets a registered wallet
+    pub fn get_wallet(wallet_id: String) -> String {
+        let state = Self::load();
+        
+        let wallet = state.wallets.get(&wallet_id)
+            .unwrap_or_else(|| panic!("Wallet not found: {}", wallet_id));
+            
+        serde_json::to_string(wallet)
+            .unwrap_or_else(|_| "Failed to serialize wallet".to_string())
+    }
+    
+    /// Registers the signer set for the caller's multi-sig wallet
+    pub fn register_multisig(wallet_id: String, required_approvals: u32, signers: Vec<String>) -> String {
+        let mut state = Self::load();
+        state.assert_wallet_owner(&wallet_id);
+        
+        let config = MultiSigConfig::new(required_approvals, signers)
+            .unwrap_or_else(|err| panic!("Invalid multi-sig config: {}", err));
+            
+        state.multisig_configs.insert(wallet_id.clone(), config);
+        state.save();
+        
+        format!("Multi-sig config registered for wallet {}", wallet_id)
+    }
+    
+    /// Records the caller's approval of an action as a signer; returns true when the threshold is met
+    pub fn submit_approval(wallet_id: String, action_hash: Vec<u8>) -> bool {
+        let mut state = Self::load();
+        let signer = l1x_sdk::env::caller();
+        
+        let config = state.multisig_configs.get(&wallet_id)
+            .cloned()
+            .unwrap_or_else(|| panic!("Multi-sig wallet not found: {}", wallet_id));
+            
+        let approval = state.pending_approvals
+            .entry(approval_key(&wallet_id, &action_hash))
+            .or_insert_with(|| PendingApproval::new(action_hash));
+            
+        let approved = approval.approve(&signer, &config)
+            .unwrap_or_else(|err| panic!("Failed to approve action: {}", err));
+            
+        state.save();
+        
+        approved
+    }
+    
+    /// Checks whether an action has reached its approval threshold
+    pub fn is_action_approved(wallet_id: String, action_hash: Vec<u8>) -> bool {
+        let state = Self::load();
+        
+        match (
+            state.multisig_configs.get(&wallet_id),
+            state.pending_approvals.get(&approval_key(&wallet_id, &action_hash)),
+        ) {
+            (Some(config), Some(approval)) => approval.is_approved(config),
+            _ => false,
+        }
+    }
+    
+    /// Consumes an approved action so it cannot be replayed; returns false if not approved
+    pub fn consume_approval(wallet_id: String, action_hash: Vec<u8>) -> bool {
+        let mut state = Self::load();
+        
+        let key = approval_key(&wallet_id, &action_hash);
+        let approved = match (state.multisig_configs.get(&wallet_id), state.pending_approvals.get(&key)) {
+            (Some(config), Some(approval)) => approval.is_approved(config),
+            _ => false,
+        };
+        
+        if approved {
+            state.pending_approvals.remove(&key);
+            state.save();
+        }
+        
+        approved
+    }
+    
+    /// Gets the pending approval for an action
+    pub fn get_pending_approval(wallet_id: String, action_hash: Vec<u8>) -> String {
+        let state = Self::load();
+        
+        let approval = state.pending_approvals.get(&approval_key(&wallet_id, &action_hash))
+            .unwrap_or_else(|| panic!("No pending approval for wallet {}", wallet_id));
+            
+        serde_json::to_string(approval)
+            .unwrap_or_else(|_| "Failed to serialize approval".to_string())
+    }
+}
+
+impl WalletContract {
+    /// Panics unless the caller registered `wallet_id`
+    fn assert_wallet_owner(&self, wallet_id: &str) {
+        let caller = l1x_sdk::env::caller();
+        
+        match self.wallets.get(wallet_id) {
+            Some(wallet) if wallet.address == caller => {},
+            Some(_) => panic!("Only the wallet owner can manage wallet {}", wallet_id),
+            None => panic!("Wallet not found: {}", wallet_id),
+        }
+    }
+    
+    /// Checks whether a wallet's session key may perform an operation at `now`
+    fn session_key_allows(&self, wallet_id: &str, key_id: &str, scope: OperationScope, now: u64) -> bool {
+        self.session_keys.get(wallet_id)
+            .and_then(|keys| keys.iter().find(|key| key.key_id == key_id))
+            .map_or(false, |key| key.allows(scope, now))
+    }
+}
+
+/// Verifies a hex-encoded signature over `message` against the registered wallet of `address`
+///
+/// Addresses without a registered wallet never verify.
+pub fn verify_wallet_signature(address: &str, message: &[u8], signature_hex: &str) -> bool {
+    let state = WalletContract::load();
+    
+    let wallet = match state.wallets.get(&WalletManager::wallet_id(address)) {
+        Some(wallet) => wallet,
+        None => return false,
+    };
+    
+    match decode_hex(signature_hex) {
+        Some(signature) => wallet.verify_signature(message, &signature),
+        None => false,
+    }
+}
+
+/// Storage key for a wallet's pending approval of an action
+fn approval_key(wallet_id: &str, action_hash: &[u8]) -> String {
+    let hash_hex: String = action_hash.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}:{}", wallet_id, hash_hex)
+}
+
+/// Decodes a hex string (with or without a `0x` prefix)
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
 #[cfg(test)]
@@ -192,4 +698,162 @@ mod tests {
         assert_eq!(wallet.address, "0xaddress");
         assert_eq!(wallet.public_key, "0xpubkey");
     }
+    
+    // RFC 8032 section 7.1, TEST 1 and TEST 2
+    const ED25519_PUBLIC_KEY_1: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    const ED25519_SIGNATURE_1: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+    const ED25519_PUBLIC_KEY_2: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+    const ED25519_SIGNATURE_2: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+    
+    // secp256k1 key/signature over SHA-256("One Capital rebalance"), low-s normalized
+    const SECP256K1_PUBLIC_KEY: &str = "02085fe2ca7a5758957ea811bd8e743d9cee6bc20072f1470a888c43a1091a8e8b";
+    const SECP256K1_SIGNATURE: &str = "4111f7fab1b95a8e1673ff94aa886dc2a39cd91fe5eb234f09be2e49e425617a6003042e97d47a6ff96d76eaa834ba947c45feb1b05aaed2a7f446975d692d5a";
+    const SECP256K1_MESSAGE: &[u8] = b"One Capital rebalance";
+    
+    #[test]
+    fn test_ed25519_signature_vectors() {
+        let public_key = decode_hex(ED25519_PUBLIC_KEY_1).unwrap();
+        let signature = decode_hex(ED25519_SIGNATURE_1).unwrap();
+        assert!(WalletManager::verify_ed25519_signature(&public_key, b"", &signature));
+        
+        let public_key = decode_hex(ED25519_PUBLIC_KEY_2).unwrap();
+        let signature = decode_hex(ED25519_SIGNATURE_2).unwrap();
+        assert!(WalletManager::verify_ed25519_signature(&public_key, &[0x72], &signature));
+        
+        // Wrong message, tampered signature and malformed inputs are rejected
+        assert!(!WalletManager::verify_ed25519_signature(&public_key, &[0x73], &signature));
+        let mut tampered = signature.clone();
+        tampered[0] ^= 0x01;
+        assert!(!WalletManager::verify_ed25519_signature(&public_key, &[0x72], &tampered));
+        assert!(!WalletManager::verify_ed25519_signature(&public_key[..31], &[0x72], &signature));
+        assert!(!WalletManager::verify_ed25519_signature(&public_key, &[0x72], &signature[..63]));
+    }
+    
+    #[test]
+    fn test_wallet_signature_dispatch() {
+        let mut hardware = Wallet::new_native(
+            "wallet-hw".to_string(),
+            "0xaddress".to_string(),
+            format!("0x{}", ED25519_PUBLIC_KEY_2),
+        );
+        hardware.wallet_type = WalletType::Hardware;
+        
+        let ed25519_signature = decode_hex(ED25519_SIGNATURE_2).unwrap();
+        assert!(hardware.verify_signature(&[0x72], &ed25519_signature));
+        assert!(WalletManager::verify_signature(&hardware, &[0x72], &ed25519_signature));
+        
+        let native = Wallet::new_native(
+            "wallet-native".to_string(),
+            "0xaddress".to_string(),
+            SECP256K1_PUBLIC_KEY.to_string(),
+        );
+        
+        let secp256k1_signature = decode_hex(SECP256K1_SIGNATURE).unwrap();
+        assert!(native.verify_signature(SECP256K1_MESSAGE, &secp256k1_signature));
+        assert!(!native.verify_signature(b"another message", &secp256k1_signature));
+        
+        // Each wallet type only accepts its own scheme
+        assert!(!native.verify_signature(&[0x72], &ed25519_signature));
+        assert!(!hardware.verify_signature(SECP256K1_MESSAGE, &secp256k1_signature));
+        
+        // Placeholder keys no longer verify anything
+        let placeholder = WalletManager::connect_wallet("0xaddress".to_string(), "0xpubkey".to_string());
+        assert!(!placeholder.verify_signature(SECP256K1_MESSAGE, &secp256k1_signature));
+    }
+    
+    #[test]
+    fn test_multisig_two_of_three() {
+        let config = MultiSigConfig::new(
+            2,
+            vec!["alice".to_string(), "bob".to_string(), "carol".to_string()],
+        ).unwrap();
+        
+        let mut approval = PendingApproval::new(b"rebalance:vault-1".to_vec());
+        
+        // First approval is not enough
+        assert!(!approval.approve("alice", &config).unwrap());
+        
+        // Repeating an approval does not count twice
+        assert!(!approval.approve("alice", &config).unwrap());
+        assert_eq!(approval.approvals.len(), 1);
+        
+        // Outsiders cannot approve
+        assert!(approval.approve("mallory", &config).is_err());
+        
+        // Second distinct signer meets the threshold
+        assert!(approval.approve("carol", &config).unwrap());
+        assert!(approval.is_approved(&config));
+        
+        // Thresholds must be satisfiable
+        assert!(MultiSigConfig::new(4, config.signers.clone()).is_err());
+        assert!(MultiSigConfig::new(0, config.signers.clone()).is_err());
+        
+        assert_ne!(approval_key("wallet-1", b"a"), approval_key("wallet-2", b"a"));
+    }
+    
+    #[test]
+    fn test_session_key_scope_and_expiry() {
+        let mut state = WalletContract {
+            multisig_configs: std::collections::HashMap::new(),
+            pending_approvals: std::collections::HashMap::new(),
+            session_keys: std::collections::HashMap::new(),
+            wallets: std::collections::HashMap::new(),
+        };
+        
+        state.session_keys.insert("wallet-1".to_string(), vec![
+            SessionKey {
+                key_id: "rebalancer".to_string(),
+                public_key: "0xsession1".to_string(),
+                scopes: vec![OperationScope::Rebalance],
+                expires_at: 1_000,
+            },
+            SessionKey {
+                key_id: "depositor".to_string(),
+                public_key: "0xsession2".to_string(),
+                scopes: vec![OperationScope::Deposit],
+                expires_at: 5_000,
+            },
+        ]);
+        
+        // In scope and before expiry
+        assert!(state.session_key_allows("wallet-1", "rebalancer", OperationScope::Rebalance, 999));
+        
+        // Expired keys fail even for their own scope
+        assert!(!state.session_key_allows("wallet-1", "rebalancer", OperationScope::Rebalance, 1_000));
+        
+        // A key without the Rebalance scope cannot rebalance
+        assert!(state.session_key_allows("wallet-1", "depositor", OperationScope::Deposit, 999));
+        assert!(!state.session_key_allows("wallet-1", "depositor", OperationScope::Rebalance, 999));
+        
+        // Unknown keys and wallets fail
+        assert!(!state.session_key_allows("wallet-1", "missing", OperationScope::Rebalance, 0));
+        assert!(!state.session_key_allows("wallet-2", "rebalancer", OperationScope::Rebalance, 0));
+    }
+    
+    #[test]
+    fn test_spending_limit_window() {
+        let mut limit = SpendingLimit {
+            amount_per_window: 1_000,
+            window_seconds: 3_600,
+            spent_in_window: 0,
+            window_start: 10_000,
+        };
+        
+        // Spends accumulate within the window
+        assert!(limit.check_and_record_at(600, 10_100).is_ok());
+        assert!(limit.check_and_record_at(400, 11_000).is_ok());
+        assert_eq!(limit.spent_in_window, 1_000);
+        
+        // Breaching the cap fails without recording the spend
+        assert!(limit.check_and_record_at(1, 13_599).is_err());
+        assert_eq!(limit.spent_in_window, 1_000);
+        
+        // Once the window expires it resets
+        assert!(limit.check_and_record_at(700, 13_600).is_ok());
+        assert_eq!(limit.window_start, 13_600);
+        assert_eq!(limit.spent_in_window, 700);
+        
+        // A single spend above the cap never fits
+        assert!(limit.check_and_record_at(1_001, 20_000).is_err());
+    }
 }
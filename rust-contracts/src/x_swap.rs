@@ -11,6 +11,7 @@
 use std::collections::HashMap;
 
 // Error types
+#[derive(Debug, PartialEq)]
 pub enum Error {
     Unauthorized,
     InvalidAmount,
@@ -21,6 +22,7 @@ pub enum Error {
 }
 
 // Swap status enum
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SwapStatus {
     Pending,
     Completed,
@@ -50,6 +52,14 @@ pub struct SwapCompleted {
     pub timestamp: u64,
 }
 
+// Parameters for a single swap within a batch
+pub struct SwapSpec {
+    pub from_asset: String,
+    pub to_asset: String,
+    pub amount: u128,
+    pub target_chain_id: u64,
+}
+
 // Type aliases
 pub type Address = [u8; 20];
 pub type Result<T> = std::result::Result<T, Error>;
@@ -85,12 +95,9 @@ pub struct XSwap {
 impl XSwap {
     /// Create a new X-Swap contract with the caller as the owner
     pub fn new(caller: Address) -> Self {
-        let mut admins = Vec::new();
-        admins.push(caller);
-        
         XSwap {
             owner: caller,
-            admins,
+            admins: vec![caller],
             next_swap_id: 1,
             swaps: HashMap::new(),
             balances: HashMap::new(),
@@ -130,19 +137,14 @@ impl XSwap {
     
     /// Update user balance
     fn update_balance(&mut self, user: &Address, asset: &str, amount: u128, is_addition: bool) {
-        let user_balances = self.balances.entry(*user).or_insert_with(HashMap::new);
+        let user_balances = self.balances.entry(*user).or_default();
         let current_balance = user_balances.get(asset).copied().unwrap_or(0);
         
         if is_addition {
             user_balances.insert(asset.to_string(), current_balance + amount);
         } else {
             // Ensure we don't underflow
-            let new_balance = if current_balance >= amount {
-                current_balance - amount
-            } else {
-                0
-            };
-            user_balances.insert(asset.to_string(), new_balance);
+            user_balances.insert(asset.to_string(), current_balance.saturating_sub(amount));
         }
     }
     
@@ -209,6 +211,62 @@ impl XSwap {
         })
     }
     
+    /// Initiate multiple cross-chain swaps atomically
+    ///
+    /// Every swap is validated (including the combined balance required per
+    /// asset) before any balance is touched, so a single failure leaves all
+    /// balances and swap records unchanged.
+    pub fn batch_swap(
+        &mut self,
+        caller: &Address,
+        swaps: Vec<SwapSpec>,
+        timestamp: u64,
+    ) -> Result<Vec<SwapRequested>> {
+        // Validate all swaps upfront
+        let mut required: HashMap<String, u128> = HashMap::new();
+        
+        for spec in &swaps {
+            if spec.amount == 0 {
+                return Err(Error::InvalidAmount);
+            }
+            
+            if spec.from_asset.is_empty() || spec.to_asset.is_empty() {
+                return Err(Error::InvalidAsset);
+            }
+            
+            if spec.target_chain_id == 0 {
+                return Err(Error::InvalidChain);
+            }
+            
+            let total = required.entry(spec.from_asset.clone()).or_insert(0);
+            *total = total.checked_add(spec.amount).ok_or(Error::InvalidAmount)?;
+        }
+        
+        // Check combined balances
+        for (asset, amount) in &required {
+            if self.get_balance(caller, asset) < *amount {
+                return Err(Error::InsufficientBalance);
+            }
+        }
+        
+        // All checks passed, execute every swap
+        let mut events = Vec::with_capacity(swaps.len());
+        
+        for spec in swaps {
+            let event = self.cross_chain_swap(
+                caller,
+                spec.from_asset,
+                spec.to_asset,
+                spec.amount,
+                spec.target_chain_id,
+                timestamp,
+            )?;
+            events.push(event);
+        }
+        
+        Ok(events)
+    }
+    
     /// Complete a swap (called by X-Talk relayer or admin)
     pub fn complete_swap(
         &mut self,
@@ -230,11 +288,7 @@ impl XSwap {
         swap.completed_at = Some(timestamp);
         swap.received_amount = Some(received_amount);
         
-        // Credit user with received asset
-        self.update_balance(&swap.initiator, &swap.to_asset, received_amount, true);
-        
-        // Return event data
-        Ok(SwapCompleted {
+        let event = SwapCompleted {
             id: swap_id,
             from_asset: swap.from_asset.clone(),
             to_asset: swap.to_asset.clone(),
@@ -243,7 +297,13 @@ impl XSwap {
             target_chain_id: swap.target_chain_id,
             initiator: swap.initiator,
             timestamp,
-        })
+        };
+        
+        // Credit user with received asset
+        self.update_balance(&event.initiator, &event.to_asset, received_amount, true);
+        
+        // Return event data
+        Ok(event)
     }
     
     /// Mark a swap as failed (called by X-Talk relayer or admin)
@@ -260,8 +320,10 @@ impl XSwap {
         swap.status = SwapStatus::Failed;
         swap.completed_at = Some(timestamp);
         
+        let (initiator, from_asset, amount) = (swap.initiator, swap.from_asset.clone(), swap.amount);
+        
         // Refund user with original asset
-        self.update_balance(&swap.initiator, &swap.from_asset, swap.amount, true);
+        self.update_balance(&initiator, &from_asset, amount, true);
         
         Ok(())
     }
@@ -287,36 +349,52 @@ pub mod l1x {
     
     // We'll assume these functions are provided by the L1X runtime
     extern "C" {
-        fn l1x_get_caller() -> [u8; 20];
+        fn l1x_get_caller(out_ptr: *mut u8);
         fn l1x_get_timestamp() -> u64;
-        fn l1x_emit_event(event_type: &str, data: &[u8]);
-        fn l1x_xtalk_send(chain_id: u64, target_contract: &[u8], message: &[u8]) -> i32;
+        fn l1x_emit_event(event_type_ptr: *const u8, event_type_len: usize, data_ptr: *const u8, data_len: usize);
+        fn l1x_xtalk_send(
+            chain_id: u64,
+            target_ptr: *const u8, target_len: usize,
+            message_ptr: *const u8, message_len: usize,
+        ) -> i32;
     }
     
     static mut XSWAP: Option<XSwap> = None;
     
+    // Helper to get the caller address
+    fn get_caller() -> Address {
+        let mut caller = [0u8; 20];
+        unsafe { l1x_get_caller(caller.as_mut_ptr()) };
+        caller
+    }
+    
+    // Helper to emit an event through the runtime
+    fn emit_event(event_type: &str, data: &str) {
+        unsafe { l1x_emit_event(event_type.as_ptr(), event_type.len(), data.as_ptr(), data.len()) };
+    }
+    
     // Helper to get singleton instance
     fn get_xswap() -> &'static mut XSwap {
-        unsafe {
-            if XSWAP.is_none() {
-                let caller = l1x_get_caller();
-                XSWAP = Some(XSwap::new(caller));
-            }
-            XSWAP.as_mut().unwrap()
-        }
+        // Contracts run single-threaded, so no other reference to XSWAP can exist
+        let xswap = unsafe { &mut *std::ptr::addr_of_mut!(XSWAP) };
+        xswap.get_or_insert_with(|| XSwap::new(get_caller()))
     }
     
     // Public contract API functions
     
     /// Initiate a cross-chain swap
+    ///
+    /// # Safety
+    ///
+    /// Both asset pointers must be valid for reads of their given lengths.
     #[no_mangle]
-    pub extern "C" fn cross_chain_swap(
+    pub unsafe extern "C" fn cross_chain_swap(
         from_asset_ptr: *const u8, from_asset_len: usize,
         to_asset_ptr: *const u8, to_asset_len: usize,
         amount: u128,
         target_chain_id: u64
     ) -> i32 {
-        let caller = unsafe { l1x_get_caller() };
+        let caller = get_caller();
         let timestamp = unsafe { l1x_get_timestamp() };
         
         // Convert asset names from pointers to strings
@@ -342,20 +420,24 @@ pub mod l1x {
                     event.id, event.from_asset, event.to_asset, event.amount, event.target_chain_id
                 );
                 
+                emit_event(event_type, &event_data);
+                
+                // In a real implementation, we would also send a message to the target chain
+                // using L1X's X-Talk protocol
+                let x_talk_message = format!(
+                    "{{\"action\":\"swap\",\"swapId\":{},\"fromAsset\":\"{}\",\"toAsset\":\"{}\",\"amount\":{}}}",
+                    event.id, event.from_asset, event.to_asset, event.amount
+                );
+                
+                // Target contract address would be known in advance for the specific chain
+                let target_contract = b"target_contract_address_on_chain";
+                
                 unsafe {
-                    l1x_emit_event(event_type, event_data.as_bytes());
-                    
-                    // In a real implementation, we would also send a message to the target chain
-                    // using L1X's X-Talk protocol
-                    let x_talk_message = format!(
-                        "{{\"action\":\"swap\",\"swapId\":{},\"fromAsset\":\"{}\",\"toAsset\":\"{}\",\"amount\":{}}}",
-                        event.id, event.from_asset, event.to_asset, event.amount
+                    l1x_xtalk_send(
+                        target_chain_id,
+                        target_contract.as_ptr(), target_contract.len(),
+                        x_talk_message.as_ptr(), x_talk_message.len(),
                     );
-                    
-                    // Target contract address would be known in advance for the specific chain
-                    let target_contract = b"target_contract_address_on_chain";
-                    
-                    l1x_xtalk_send(target_chain_id, target_contract, x_talk_message.as_bytes());
                 }
                 
                 event.id as i32 // Return swap ID as success
@@ -371,7 +453,7 @@ pub mod l1x {
     /// Complete a swap (called by X-Talk relayer)
     #[no_mangle]
     pub extern "C" fn complete_swap(swap_id: u64, received_amount: u128) -> i32 {
-        let caller = unsafe { l1x_get_caller() };
+        let caller = get_caller();
         let timestamp = unsafe { l1x_get_timestamp() };
         
         let xswap = get_xswap();
@@ -384,9 +466,7 @@ pub mod l1x {
                     event.id, event.from_asset, event.to_asset, event.sent_amount, event.received_amount
                 );
                 
-                unsafe {
-                    l1x_emit_event(event_type, event_data.as_bytes());
-                }
+                emit_event(event_type, &event_data);
                 
                 0 // Success
             },
@@ -399,7 +479,7 @@ pub mod l1x {
     /// Mark a swap as failed (called by X-Talk relayer)
     #[no_mangle]
     pub extern "C" fn fail_swap(swap_id: u64) -> i32 {
-        let caller = unsafe { l1x_get_caller() };
+        let caller = get_caller();
         let timestamp = unsafe { l1x_get_timestamp() };
         
         let xswap = get_xswap();
@@ -409,9 +489,7 @@ pub mod l1x {
                 let event_type = "SwapFailed";
                 let event_data = format!("{{\"id\":{}}}", swap_id);
                 
-                unsafe {
-                    l1x_emit_event(event_type, event_data.as_bytes());
-                }
+                emit_event(event_type, &event_data);
                 
                 0 // Success
             },
@@ -422,8 +500,13 @@ pub mod l1x {
     }
     
     /// Get user balance for an asset
+    ///
+    /// # Safety
+    ///
+    /// `user_ptr` must be valid for reads of 20 bytes and `asset_ptr` for
+    /// reads of `asset_len` bytes.
     #[no_mangle]
-    pub extern "C" fn get_balance(
+    pub unsafe extern "C" fn get_balance(
         user_ptr: *const u8,
         asset_ptr: *const u8, asset_len: usize
     ) -> u128 {
@@ -564,4 +647,58 @@ mod tests {
         let balance = xswap.get_balance(&owner, "ETH");
         assert_eq!(balance, 50);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_batch_swap_rolls_back_on_failure() {
+        let owner = create_address(1);
+        let mut xswap = XSwap::new(owner);
+        
+        xswap.update_balance(&owner, "ETH", 1000, true);
+        xswap.update_balance(&owner, "BTC", 10, true);
+        
+        // Second swap has insufficient balance
+        let swaps = vec![
+            SwapSpec {
+                from_asset: "ETH".to_string(),
+                to_asset: "BTC".to_string(),
+                amount: 500,
+                target_chain_id: 2,
+            },
+            SwapSpec {
+                from_asset: "BTC".to_string(),
+                to_asset: "ETH".to_string(),
+                amount: 100,
+                target_chain_id: 2,
+            },
+        ];
+        
+        let result = xswap.batch_swap(&owner, swaps, 12345);
+        assert!(matches!(result, Err(Error::InsufficientBalance)));
+        
+        // All balances unchanged and no swaps recorded
+        assert_eq!(xswap.get_balance(&owner, "ETH"), 1000);
+        assert_eq!(xswap.get_balance(&owner, "BTC"), 10);
+        assert!(xswap.get_user_swaps(&owner).is_empty());
+        
+        // A valid batch executes every swap
+        let swaps = vec![
+            SwapSpec {
+                from_asset: "ETH".to_string(),
+                to_asset: "BTC".to_string(),
+                amount: 500,
+                target_chain_id: 2,
+            },
+            SwapSpec {
+                from_asset: "ETH".to_string(),
+                to_asset: "SOL".to_string(),
+                amount: 500,
+                target_chain_id: 3,
+            },
+        ];
+        
+        let events = xswap.batch_swap(&owner, swaps, 12346).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(xswap.get_balance(&owner, "ETH"), 0);
+        assert_eq!(xswap.get_user_swaps(&owner).len(), 2);
+    }
+}
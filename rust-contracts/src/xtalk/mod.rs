@@ -34,6 +34,98 @@ pub enum XTalkMessageStatus {
     Failed,
 }
 
+/// Priority of an XTalk message, used to order validator work queues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub enum MessagePriority {
+    /// Low priority (processed last)
+    Low,
+    
+    /// Normal priority (default)
+    Normal,
+    
+    /// High priority (processed before normal messages)
+    High,
+    
+    /// Critical priority (processed first)
+    Critical,
+}
+
+impl Default for MessagePriority {
+    fn default() -> Self {
+        MessagePriority::Normal
+    }
+}
+
+impl MessagePriority {
+    /// All priorities, from most to least urgent
+    pub const PROCESSING_ORDER: [MessagePriority; 4] = [
+        MessagePriority::Critical,
+        MessagePriority::High,
+        MessagePriority::Normal,
+        MessagePriority::Low,
+    ];
+}
+
+/// Pending message IDs queued separately per priority level
+#[derive(Debug, Clone, Default, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct PriorityQueues {
+    /// Message IDs per priority, in arrival order
+    queues: std::collections::HashMap<MessagePriority, Vec<String>>,
+}
+
+impl PriorityQueues {
+    /// Creates empty queues
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Adds a message to the queue for its priority (no-op if already queued)
+    pub fn push(&mut self, priority: MessagePriority, message_id: String) {
+        if self.contains(&message_id) {
+            return;
+        }
+        
+        self.queues.entry(priority).or_insert_with(Vec::new).push(message_id);
+    }
+    
+    /// Removes a message from whichever queue holds it
+    pub fn remove(&mut self, message_id: &str) -> bool {
+        for queue in self.queues.values_mut() {
+            if let Some(pos) = queue.iter().position(|id| id == message_id) {
+                queue.remove(pos);
+                return true;
+            }
+        }
+        
+        false
+    }
+    
+    /// Checks if a message is queued
+    pub fn contains(&self, message_id: &str) -> bool {
+        self.queues.values().any(|queue| queue.iter().any(|id| id == message_id))
+    }
+    
+    /// Gets the queued message IDs for a priority
+    pub fn get(&self, priority: MessagePriority) -> Vec<String> {
+        self.queues.get(&priority).cloned().unwrap_or_default()
+    }
+    
+    /// Gets the next message to process: Critical/High before Normal before Low
+    pub fn next(&self) -> Option<String> {
+        MessagePriority::PROCESSING_ORDER.iter()
+            .filter_map(|priority| self.queues.get(priority).and_then(|q| q.first()))
+            .next()
+            .cloned()
+    }
+    
+    /// Gets all queued message IDs in processing order
+    pub fn ordered(&self) -> Vec<String> {
+        MessagePriority::PROCESSING_ORDER.iter()
+            .flat_map(|priority| self.get(*priority))
+            .collect()
+    }
+}
+
 /// XTalk Message structure
 #[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub struct XTalkMessage {
@@ -75,6 +167,10 @@ pub struct XTalkMessage {
     
     /// Sender address on source chain
     pub sender: String,
+    
+    /// Message priority (influences relay validator ordering)
+    #[serde(default)]
+    pub priority: MessagePriority,
 }
 
 /// XTalk message with validator signatures
@@ -107,7 +203,7 @@ pub struct ValidatorSignature {
 }
 
 /// Validator roles in the XTalk protocol
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub enum ValidatorRole {
     /// Detects new messages on source chains
     Listener,
@@ -237,15 +333,22 @@ impl SourceRegistry {
     pub fn register_flow_contract(chain_id: u32, flow_contract: String) -> String {
         let mut contract = Self::load();
         
+        let signer = l1x_sdk::env::signer_account_id();
+        let params = serde_json::json!({ "chain_id": chain_id, "flow_contract": flow_contract });
+        
         // Only owner can register flow contracts
-        if l1x_sdk::env::signer_account_id() != contract.owner {
+        if signer != contract.owner {
+            crate::audit::AuditLogContract::record(&signer, "SourceRegistry", "register_flow_contract", &params, "Unauthorized");
             return "Unauthorized".to_string();
         }
         
         contract.chain_to_flow_contract.insert(chain_id, flow_contract.clone());
         contract.save();
         
-        format!("Registered FlowContract {} for chain {}", flow_contract, chain_id)
+        let result = format!("Registered FlowContract {} for chain {}", flow_contract, chain_id);
+        crate::audit::AuditLogContract::record(&signer, "SourceRegistry", "register_flow_contract", &params, &result);
+        
+        result
     }
     
     /// Get the FlowContract address for a source chain
@@ -281,6 +384,12 @@ pub struct XTalkConsensusContract {
     /// Required number of validator signatures for each role
     threshold: std::collections::HashMap<ValidatorRole, u32>,
     
+    /// Messages awaiting listener consensus, queued by priority
+    listener_queue: PriorityQueues,
+    
+    /// Messages awaiting signer consensus, queued by priority
+    signer_queue: PriorityQueues,
+    
     /// Owner of the contract
     owner: String,
 }
@@ -308,6 +417,8 @@ impl XTalkConsensusContract {
             signer_finalized_messages: std::collections::HashMap::new(),
             validators: std::collections::HashMap::new(),
             threshold: std::collections::HashMap::new(),
+            listener_queue: PriorityQueues::new(),
+            signer_queue: PriorityQueues::new(),
             owner,
         };
         
@@ -323,19 +434,26 @@ impl XTalkConsensusContract {
     pub fn register_validator(validator_id: String, role: ValidatorRole) -> String {
         let mut contract = Self::load();
         
+        let signer = l1x_sdk::env::signer_account_id();
+        let params = serde_json::json!({ "validator_id": validator_id, "role": format!("{:?}", role) });
+        
         // Only owner can register validators
-        if l1x_sdk::env::signer_account_id() != contract.owner {
+        if signer != contract.owner {
+            crate::audit::AuditLogContract::record(&signer, "XTalkConsensusContract", "register_validator", &params, "Unauthorized");
             return "Unauthorized".to_string();
         }
         
         contract.validators.insert(validator_id.clone(), role);
         contract.save();
         
-        format!("Registered validator {} as {:?}", validator_id, role)
+        let result = format!("Registered validator {} as {:?}", validator_id, role);
+        crate::audit::AuditLogContract::record(&signer, "XTalkConsensusContract", "register_validator", &params, &result);
+        
+        result
     }
     
     /// Submit a listener vote for a message
-    pub fn submit_listener_vote(message_id: String, message_data: String, vote: bool) -> String {
+    pub fn submit_listener_vote(message_id: String, message_data: String, vote: bool, priority: MessagePriority) -> String {
         let mut contract = Self::load();
         
         let validator_id = l1x_sdk::env::signer_account_id();
@@ -350,6 +468,9 @@ impl XTalkConsensusContract {
             contract.listener_votes.insert(message_id.clone(), std::collections::HashMap::new());
         }
         
+        // Route the message to the listener queue for its priority
+        contract.listener_queue.push(priority, message_id.clone());
+        
         // Record the vote
         let votes = contract.listener_votes.get_mut(&message_id).unwrap();
         votes.insert(validator_id.clone(), vote);
@@ -365,6 +486,10 @@ impl XTalkConsensusContract {
                 
             contract.listener_finalized_messages.insert(message_id.clone(), message);
             
+            // Move the message on to the signers' work queue
+            contract.listener_queue.remove(&message_id);
+            contract.signer_queue.push(priority, message_id.clone());
+            
             // TODO: Actually notify the FlowContract about the finalized message
             // This would be an external call in a real implementation
             
@@ -378,7 +503,7 @@ impl XTalkConsensusContract {
     }
     
     /// Submit a signer signature for a message
-    pub fn submit_signature(message_id: String, signature: Vec<u8>) -> String {
+    pub fn submit_signature(message_id: String, signature: Vec<u8>, priority: MessagePriority) -> String {
         let mut contract = Self::load();
         
         let validator_id = l1x_sdk::env::signer_account_id();
@@ -393,6 +518,9 @@ impl XTalkConsensusContract {
             return format!("Message {} has not achieved listener consensus", message_id);
         }
         
+        // Route the message to the signer queue for its priority
+        contract.signer_queue.push(priority, message_id.clone());
+        
         // Initialize signatures map for this message if it doesn't exist
         if !contract.signer_signatures.contains_key(&message_id) {
             contract.signer_signatures.insert(message_id.clone(), std::collections::HashMap::new());
@@ -426,6 +554,7 @@ impl XTalkConsensusContract {
             };
             
             contract.signer_finalized_messages.insert(message_id.clone(), signed_message);
+            contract.signer_queue.remove(&message_id);
             
             // TODO: Actually notify the FlowContract about the finalized signatures
             // This would be an external call in a real implementation
@@ -439,6 +568,31 @@ impl XTalkConsensusContract {
         }
     }
     
+    /// Get the pending message IDs for a validator role and priority
+    pub fn get_pending_messages_by_priority(role: ValidatorRole, priority: MessagePriority) -> String {
+        let contract = Self::load();
+        
+        let pending = match role {
+            ValidatorRole::Listener => contract.listener_queue.get(priority),
+            ValidatorRole::Signer | ValidatorRole::Relayer => contract.signer_queue.get(priority),
+        };
+        
+        serde_json::to_string(&pending)
+            .unwrap_or_else(|_| "Error serializing pending messages".to_string())
+    }
+    
+    /// Get the next message a validator role should process (highest priority first)
+    pub fn get_next_pending_message(role: ValidatorRole) -> String {
+        let contract = Self::load();
+        
+        let next = match role {
+            ValidatorRole::Listener => contract.listener_queue.next(),
+            ValidatorRole::Signer | ValidatorRole::Relayer => contract.signer_queue.next(),
+        };
+        
+        next.unwrap_or_else(|| "No pending messages".to_string())
+    }
+    
     /// Get a message that has achieved listener consensus
     pub fn get_listener_finalized_message(message_id: String) -> String {
         let contract = Self::load();
@@ -480,6 +634,9 @@ pub struct FlowContract {
     
     /// Source chain ID
     source_chain_id: u32,
+    
+    /// Nonces of event data already stored, to reject replays
+    processed_nonces: std::collections::HashSet<u64>,
 }
 
 const FLOW_CONTRACT_KEY: &[u8] = b"FLOW_CONTRACT";
@@ -504,12 +661,27 @@ impl FlowContract {
             owner,
             consensus_contract,
             source_chain_id,
+            processed_nonces: std::collections::HashSet::new(),
         };
         contract.save();
     }
     
+    /// Marks a nonce as processed, rejecting one that was seen before
+    fn claim_nonce(&mut self, nonce: u64) -> Result<(), XTalkError> {
+        if !self.processed_nonces.insert(nonce) {
+            return Err(XTalkError::DuplicateMessage);
+        }
+        
+        Ok(())
+    }
+    
+    /// Nonce following the highest one processed so far
+    fn next_expected_nonce(&self) -> u64 {
+        self.processed_nonces.iter().max().map_or(0, |nonce| nonce + 1)
+    }
+    
     /// Store validated event data from source chain
-    pub fn store_event_data(message_id: String, data: Vec<u8>) -> String {
+    pub fn store_event_data(message_id: String, nonce: u64, data: Vec<u8>) -> String {
         let mut contract = Self::load();
         
         // Check if caller is the consensus contract
@@ -517,6 +689,11 @@ impl FlowContract {
             return "Unauthorized: only consensus contract can store event data".to_string();
         }
         
+        // Reject replayed event data
+        if let Err(e) = contract.claim_nonce(nonce) {
+            return format!("Rejected event data for message {} with nonce {}: {:?}", message_id, nonce, e);
+        }
+        
         // Store the event data
         contract.event_data.insert(message_id.clone(), data.clone());
         
@@ -531,6 +708,11 @@ impl FlowContract {
         format!("Event data stored for message {}", message_id)
     }
     
+    /// Get the nonce validators should use for the next event data
+    pub fn get_next_expected_nonce() -> u64 {
+        Self::load().next_expected_nonce()
+    }
+    
     /// Get the hash that Signer Validators need to sign
     pub fn get_message_hash(message_id: String) -> Vec<u8> {
         let contract = Self::load();
@@ -628,6 +810,43 @@ mod tests {
         
         assert_eq!(status, XTalkMessageStatus::Broadcasted);
     }
+    
+    #[test]
+    fn test_priority_queue_ordering() {
+        let mut queues = PriorityQueues::new();
+        
+        queues.push(MessagePriority::Normal, "normal-1".to_string());
+        queues.push(MessagePriority::Low, "low-1".to_string());
+        queues.push(MessagePriority::Critical, "critical-1".to_string());
+        
+        // Critical message inserted after the Normal one is processed first
+        assert_eq!(queues.next(), Some("critical-1".to_string()));
+        assert_eq!(queues.ordered(), vec!["critical-1", "normal-1", "low-1"]);
+        
+        // Duplicate pushes are ignored
+        queues.push(MessagePriority::High, "normal-1".to_string());
+        assert!(queues.get(MessagePriority::High).is_empty());
+        
+        assert!(queues.remove("critical-1"));
+        assert_eq!(queues.next(), Some("normal-1".to_string()));
+    }
+    
+    #[test]
+    fn test_flow_contract_rejects_replayed_nonce() {
+        let mut contract = FlowContract {
+            event_data: std::collections::HashMap::new(),
+            message_hashes: std::collections::HashMap::new(),
+            owner: "owner".to_string(),
+            consensus_contract: "consensus".to_string(),
+            source_chain_id: 1,
+            processed_nonces: std::collections::HashSet::new(),
+        };
+        
+        assert_eq!(contract.next_expected_nonce(), 0);
+        assert!(contract.claim_nonce(7).is_ok());
+        assert!(matches!(contract.claim_nonce(7), Err(XTalkError::DuplicateMessage)));
+        assert_eq!(contract.next_expected_nonce(), 8);
+    }
 }
 
 #[cfg(test)]